@@ -1,4 +1,13 @@
+use std::env;
+use std::path::PathBuf;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_prost_build::compile_protos("proto/hashservice.proto")?;
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    tonic_prost_build::configure()
+        .file_descriptor_set_path(out_dir.join("hashservice_descriptor.bin"))
+        .compile_protos(
+            &["proto/hashservice.proto", "proto/hashservice_v2.proto"],
+            &["proto"],
+        )?;
     Ok(())
 }
@@ -1,5 +1,7 @@
 const grpc = require('@grpc/grpc-js');
 const protoLoader = require('@grpc/proto-loader');
+const fs = require('fs');
+const os = require('os');
 const path = require('path');
 
 const PROTO_PATH = path.join(__dirname, '../proto/hashservice.proto');
@@ -12,8 +14,31 @@ const packageDefinition = protoLoader.loadSync(PROTO_PATH, {
 });
 const hashservice = grpc.loadPackageDefinition(packageDefinition).hashservice;
 
-function createClient(address = 'localhost:50051') {
-    return new hashservice.HashLoader(address, grpc.credentials.createInsecure());
+// Data dir the service writes endpoint.json to, as picked by the `directories` crate
+function dataDir() {
+    switch (process.platform) {
+        case 'win32':
+            return path.join(process.env.APPDATA || '', 'LeagueToolkit', 'ltk-hash-service', 'data');
+        case 'darwin':
+            return path.join(os.homedir(), 'Library', 'Application Support', 'io.LeagueToolkit.ltk-hash-service');
+        default:
+            return path.join(process.env.XDG_DATA_HOME || path.join(os.homedir(), '.local', 'share'), 'ltk-hash-service');
+    }
+}
+
+// Address and TLS of the running service from its discovery file, else the default endpoint
+function discoverEndpoint() {
+    try {
+        const discovery = JSON.parse(fs.readFileSync(path.join(dataDir(), 'endpoint.json'), 'utf8'));
+        return { address: discovery.endpoint.replace(/^https?:\/\//, ''), tls: discovery.tls };
+    } catch {
+        return { address: 'localhost:50051', tls: false };
+    }
+}
+
+function createClient({ address, tls } = discoverEndpoint()) {
+    const credentials = tls ? grpc.credentials.createSsl() : grpc.credentials.createInsecure();
+    return new hashservice.HashLoader(address, credentials);
 }
 
 function hexToUint64String(hex) {
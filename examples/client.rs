@@ -8,6 +8,17 @@ pub mod hash_service {
     tonic::include_proto!("hashservice");
 }
 
+const DEFAULT_ENDPOINT: &str = "http://[::1]:50051";
+
+/// Endpoint the running service wrote to its discovery file, else the default one
+fn discovered_endpoint() -> String {
+    directories_next::ProjectDirs::from("io", "LeagueToolkit", "ltk-hash-service")
+        .and_then(|dirs| std::fs::read_to_string(dirs.data_dir().join("endpoint.json")).ok())
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+        .and_then(|discovery| discovery["endpoint"].as_str().map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string())
+}
+
 fn hex_to_u64(hex: &str) -> Result<u64, String> {
     let s = hex.trim_start_matches("0x");
     u64::from_str_radix(s, 16).map_err(|e| format!("invalid hex '{}': {}", hex, e))
@@ -24,7 +35,7 @@ async fn rpc_get_string(
     hash_hex: &str,
     hashtable_type: &str,
 ) -> Result<GetStringResponse, tonic::Status> {
-    let hash = hex_to_u64(hash_hex).map_err(|e| tonic::Status::invalid_argument(e))?;
+    let hash = hex_to_u64(hash_hex).map_err(tonic::Status::invalid_argument)?;
     let req = Request::new(GetStringRequest {
         hash,
         hashtable_type: hashtable_type.to_string(),
@@ -41,11 +52,13 @@ async fn rpc_add_hash(
     let req = Request::new(AddHashRequest {
         string: string_value.to_string(),
         hashtable_type: hashtable_type.to_string(),
+        ..AddHashRequest::default()
     });
     let resp = client.add_hash(req).await?;
     Ok(resp.into_inner())
 }
 
+#[allow(dead_code)]
 async fn rpc_unload_hashes(
     client: &mut HashLoaderClient<Channel>,
 ) -> Result<hash_service::UnloadHashesResponse, tonic::Status> {
@@ -56,8 +69,8 @@ async fn rpc_unload_hashes(
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let addr = "http://[::1]:50051";
-    let mut client = create_client(addr).await?;
+    let addr = discovered_endpoint();
+    let mut client = create_client(&addr).await?;
 
     let example_hash = "a7cf5b14b9b659e0";
     let string_to_hash = "data/characters/sru_es_bannerplatform_order/skins/skin33.bin";
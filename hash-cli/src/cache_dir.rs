@@ -0,0 +1,40 @@
+//! `set-cache-dir`: moves the service's caches to a new root while it runs.
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::Admin;
+use crate::hash_service::SetCacheDirRequest;
+
+#[derive(Debug, Args)]
+pub struct SetCacheDirArgs {
+    /// New cache root, as seen by the service; omit for the default location
+    dir: Option<PathBuf>,
+    /// Leave the cached files where they are and sync the new root afresh
+    #[arg(long)]
+    no_migrate: bool,
+    /// Also save the new root to the service's config file
+    #[arg(long)]
+    persist: bool,
+}
+
+pub async fn set_cache_dir(client: &mut Admin, args: &SetCacheDirArgs) -> Result<(), String> {
+    let cache_dir = match &args.dir {
+        Some(dir) => std::path::absolute(dir)
+            .map_err(|e| format!("Invalid cache dir {:?}: {}", dir, e))?
+            .display()
+            .to_string(),
+        None => String::new(),
+    };
+    let response = client
+        .set_cache_dir(SetCacheDirRequest {
+            cache_dir,
+            migrate: !args.no_migrate,
+            persist: args.persist,
+        })
+        .await
+        .map_err(|e| format!("Failed to set cache dir: {}", e.message()))?
+        .into_inner();
+    eprintln!("Cache dir is now {}", response.cache_dir);
+    Ok(())
+}
@@ -0,0 +1,36 @@
+//! `clean-cache`: deletes cache leftovers on the service's machine and reports the space freed.
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::CleanCacheRequest;
+
+#[derive(Debug, Args)]
+pub struct CleanCacheArgs {
+    /// Only list what would be removed
+    #[arg(long)]
+    dry_run: bool,
+}
+
+pub async fn clean_cache(client: &mut Client, args: &CleanCacheArgs) -> Result<(), String> {
+    let response = client
+        .clean_cache(CleanCacheRequest {
+            dry_run: args.dry_run,
+        })
+        .await
+        .map_err(|e| format!("Failed to clean cache: {}", e.message()))?
+        .into_inner();
+    for path in &response.removed {
+        println!("{}", path);
+    }
+    eprintln!(
+        "{} {} paths, {} bytes",
+        if args.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        },
+        response.removed.len(),
+        response.bytes_freed
+    );
+    Ok(())
+}
@@ -0,0 +1,32 @@
+//! `clear-table`: empties one of the service's tables, or just its custom entries.
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::ClearTableRequest;
+use crate::lookup::Table;
+
+#[derive(Debug, Args)]
+pub struct ClearTableArgs {
+    #[arg(long, value_enum)]
+    table: Table,
+    /// Only remove entries added at runtime, restoring synced values they overrode
+    #[arg(long)]
+    custom_only: bool,
+}
+
+pub async fn clear_table(client: &mut Client, args: &ClearTableArgs) -> Result<(), String> {
+    let response = client
+        .clear_table(ClearTableRequest {
+            hashtable_type: args.table.as_str().to_string(),
+            custom_only: args.custom_only,
+        })
+        .await
+        .map_err(|e| format!("Failed to clear table: {}", e.message()))?
+        .into_inner();
+    eprintln!(
+        "Removed {} entries from the {} table",
+        response.removed,
+        args.table.as_str()
+    );
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! `clients`: lists the connections using the service, one per line with their open calls and
+//! how often they called each RPC.
+use clap::Args;
+
+use crate::Admin;
+use crate::hash_service::ListClientsRequest;
+
+#[derive(Debug, Args)]
+pub struct ClientsArgs {}
+
+pub async fn clients(client: &mut Admin, _args: &ClientsArgs) -> Result<(), String> {
+    let response = client
+        .list_clients(ListClientsRequest {})
+        .await
+        .map_err(|e| format!("Failed to list clients: {}", e.message()))?
+        .into_inner();
+
+    for connected in &response.clients {
+        let mut calls: Vec<_> = connected.calls.iter().collect();
+        calls.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let calls: Vec<String> = calls
+            .into_iter()
+            .map(|(method, count)| {
+                let name = method.rsplit('/').next().unwrap_or(method);
+                format!("{}={}", name, count)
+            })
+            .collect();
+        println!(
+            "{}\t{}\t{}\tconnected {}\tlast call {}\t{} open\t{}",
+            connected.peer,
+            if connected.user_agent.is_empty() {
+                "-"
+            } else {
+                &connected.user_agent
+            },
+            if connected.authenticated {
+                "authenticated"
+            } else {
+                "anonymous"
+            },
+            connected.connected_at,
+            connected.last_call_at,
+            connected.active_calls,
+            calls.join(",")
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! `diff`: prints what the last hashtable sync added, removed and renamed.
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::GetDiffRequest;
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// Only show this table: game or bin
+    #[arg(long, default_value = "")]
+    table: String,
+    /// Only show entries whose name starts with this
+    #[arg(long, default_value = "")]
+    prefix: String,
+    /// Max entries per list, 0 for all
+    #[arg(long, default_value_t = 0)]
+    limit: u32,
+}
+
+pub async fn diff(client: &mut Client, args: &DiffArgs) -> Result<(), String> {
+    let response = client
+        .get_diff(GetDiffRequest {
+            hashtable_type: args.table.clone(),
+            prefix: args.prefix.clone(),
+            limit: args.limit,
+        })
+        .await
+        .map_err(|e| format!("Failed to get diff: {}", e.message()))?
+        .into_inner();
+
+    for diff in &response.diffs {
+        for entry in &diff.added {
+            println!(
+                "{}\t+\t{:x}\t{}",
+                diff.hashtable_type, entry.hash, entry.value
+            );
+        }
+        for entry in &diff.removed {
+            println!(
+                "{}\t-\t{:x}\t{}",
+                diff.hashtable_type, entry.hash, entry.value
+            );
+        }
+        for entry in &diff.renamed {
+            println!(
+                "{}\t~\t{:x}\t{}\t{}",
+                diff.hashtable_type, entry.hash, entry.old_value, entry.new_value
+            );
+        }
+        if diff.truncated {
+            eprintln!("{}: more entries past --limit", diff.hashtable_type);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! `discover`: asks the service to scan local game files for strings resolving unknown hashes.
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::DiscoverHashesRequest;
+
+#[derive(Debug, Args)]
+pub struct DiscoverArgs {
+    /// WADs, bins or directories of them; the service reads them from disk itself
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+pub async fn discover(client: &mut Client, args: &DiscoverArgs) -> Result<(), String> {
+    // The service runs with its own working directory
+    let paths = args
+        .paths
+        .iter()
+        .map(|path| {
+            std::path::absolute(path)
+                .map(|path| path.to_string_lossy().into_owned())
+                .map_err(|e| format!("Invalid path {:?}: {}", path, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let response = client
+        .discover_hashes(DiscoverHashesRequest { paths })
+        .await
+        .map_err(|e| format!("Failed to discover hashes: {}", e.message()))?
+        .into_inner();
+
+    for discovered in &response.discovered {
+        println!(
+            "{} {:x} {}",
+            discovered.hashtable_type, discovered.hash, discovered.value
+        );
+    }
+    eprintln!(
+        "Scanned {} files ({} skipped): {} strings, {} unknown hashes, {} discovered",
+        response.files_scanned,
+        response.files_skipped,
+        response.candidates,
+        response.unknown,
+        response.discovered.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! `resolve-document`: sends a bin dump to the ResolveDocument RPC and prints the result.
+use std::io::Read;
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::ResolveDocumentRequest;
+
+#[derive(Debug, Args)]
+pub struct ResolveDocumentArgs {
+    /// ritobin text (.py) or JSON dump, or `-` for stdin
+    input: PathBuf,
+    /// Write the result here instead of stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+pub async fn resolve_document(
+    client: &mut Client,
+    args: &ResolveDocumentArgs,
+) -> Result<(), String> {
+    let document = if args.input.as_os_str() == "-" {
+        let mut document = String::new();
+        std::io::stdin()
+            .read_to_string(&mut document)
+            .map_err(|e| format!("Failed to read stdin: {}", e))?;
+        document
+    } else {
+        std::fs::read_to_string(&args.input)
+            .map_err(|e| format!("Failed to read {:?}: {}", args.input, e))?
+    };
+
+    let response = client
+        .resolve_document(ResolveDocumentRequest { document })
+        .await
+        .map_err(|e| format!("Failed to resolve document: {}", e.message()))?
+        .into_inner();
+    eprintln!(
+        "Resolved {} hashes, {} left unresolved",
+        response.resolved, response.unresolved
+    );
+
+    match &args.output {
+        Some(path) => std::fs::write(path, response.document)
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e)),
+        None => {
+            print!("{}", response.document);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! `export-unknown`: writes the recorded unknown hashes, and the ones named locally since,
+//! as CommunityDragon-style hashtable files.
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use crate::Client;
+use crate::hash_service::{EntryOrigin, ExportUnknownRequest};
+
+#[derive(Debug, Args)]
+pub struct ExportUnknownArgs {
+    /// Directory to write `unknown.<table>.txt`, `hashes.<table>.txt` and, when the service
+    /// tracks sources, `sources.<table>.txt` to
+    #[arg(long, short, default_value = ".")]
+    output: PathBuf,
+    /// Only export this table: game or bin
+    #[arg(long, default_value = "")]
+    table: String,
+    /// Origins of the named hashes to export: custom (added locally) and/or official
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "custom")]
+    origin: Vec<Origin>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Origin {
+    Official,
+    Custom,
+}
+
+pub async fn export_unknown(client: &mut Client, args: &ExportUnknownArgs) -> Result<(), String> {
+    let response = client
+        .export_unknown(ExportUnknownRequest {
+            hashtable_type: args.table.clone(),
+            origins: args
+                .origin
+                .iter()
+                .map(|origin| match origin {
+                    Origin::Official => EntryOrigin::Official as i32,
+                    Origin::Custom => EntryOrigin::Custom as i32,
+                })
+                .collect(),
+        })
+        .await
+        .map_err(|e| format!("Failed to export unknown hashes: {}", e.message()))?
+        .into_inner();
+
+    std::fs::create_dir_all(&args.output)
+        .map_err(|e| format!("Failed to create {:?}: {}", args.output, e))?;
+    for table in response.tables {
+        // Named like the upstream files the pairs belong in
+        let name = match table.hashtable_type.as_str() {
+            "bin" => "binentries",
+            other => other,
+        };
+        for (prefix, text) in [
+            ("unknown", &table.unresolved),
+            ("hashes", &table.resolved),
+            ("sources", &table.sources),
+        ] {
+            if text.is_empty() {
+                continue;
+            }
+            let path = args.output.join(format!("{}.{}.txt", prefix, name));
+            std::fs::write(&path, text)
+                .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+            eprintln!(
+                "Wrote {} entries to {}",
+                text.lines().count(),
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
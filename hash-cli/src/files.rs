@@ -0,0 +1,83 @@
+//! `files`: lists the hashtable files in the service's cache and downloads one again.
+use clap::{Args, Subcommand};
+
+use crate::Client;
+use crate::hash_service::{
+    CachedFile, FileLoadState, ForceRedownloadRequest, ListSourceFilesRequest,
+};
+
+#[derive(Debug, Args)]
+pub struct FilesArgs {
+    #[command(subcommand)]
+    command: FilesCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum FilesCommand {
+    /// List the cached files with their size, SHA, sync time and whether they are loaded
+    List,
+    /// Download one file again, changed upstream or not
+    Redownload {
+        /// File name as `files list` prints it, e.g. hashes.game.txt.0
+        file_name: String,
+    },
+}
+
+fn print_file(file: &CachedFile) {
+    let load_state = match file.load_state() {
+        FileLoadState::NotLoaded => "not loaded",
+        FileLoadState::Loaded => "loaded",
+        FileLoadState::Stale => "stale",
+    };
+    let commit = file
+        .upstream
+        .as_ref()
+        .map(|upstream| {
+            if upstream.url.is_empty() {
+                upstream.commit_sha.as_str()
+            } else {
+                upstream.url.as_str()
+            }
+        })
+        .unwrap_or_default();
+    println!(
+        "{}\t{}\t{} bytes\t{}\tsynced {}\t{}\t{}",
+        file.file_name,
+        file.hashtable_type,
+        file.size,
+        file.blob_sha,
+        file.synced_at,
+        load_state,
+        commit
+    );
+}
+
+pub async fn files(client: &mut Client, args: &FilesArgs) -> Result<(), String> {
+    match &args.command {
+        FilesCommand::List => {
+            let response = client
+                .list_source_files(ListSourceFilesRequest {})
+                .await
+                .map_err(|e| format!("Failed to list files: {}", e.message()))?
+                .into_inner();
+            eprintln!("{}", response.cache_dir);
+            for file in &response.files {
+                print_file(file);
+            }
+        }
+        FilesCommand::Redownload { file_name } => {
+            let response = client
+                .force_redownload(ForceRedownloadRequest {
+                    file_name: file_name.clone(),
+                })
+                .await
+                .map_err(|e| format!("Failed to download {}: {}", file_name, e.message()))?
+                .into_inner();
+            if let Some(file) = &response.file {
+                print_file(file);
+            }
+            eprintln!("{} files changed upstream", response.updated_files);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! `guess`: expands path templates on the service and reports which unknown hashes they crack.
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::{GuessHashesRequest, Wordlist};
+use crate::lookup::Table;
+
+#[derive(Debug, Args)]
+pub struct GuessArgs {
+    /// Templates such as `data/characters/{champion}/skins/skin{0..100}.bin`
+    #[arg(required = true)]
+    templates: Vec<String>,
+    #[arg(long, value_enum, default_value_t = Table::Game)]
+    table: Table,
+    /// `name=path` of a local wordlist for `{name}`; others are read from the service's
+    /// wordlist dir
+    #[arg(long = "wordlist", value_parser = parse_wordlist)]
+    wordlists: Vec<(String, PathBuf)>,
+    /// Hex hash to crack; defaults to the hashes the service's lookups have missed
+    #[arg(long = "target", value_parser = parse_hex)]
+    targets: Vec<u64>,
+    /// Refuse templates expanding to more candidates than this
+    #[arg(long)]
+    max_candidates: Option<u64>,
+}
+
+fn parse_wordlist(value: &str) -> Result<(String, PathBuf), String> {
+    let (name, path) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Expected name=path, got '{}'", value))?;
+    Ok((name.to_string(), PathBuf::from(path)))
+}
+
+fn parse_hex(value: &str) -> Result<u64, String> {
+    u64::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("Invalid hash '{}': {}", value, e))
+}
+
+pub async fn guess(client: &mut Client, args: &GuessArgs) -> Result<(), String> {
+    let mut wordlists = HashMap::new();
+    for (name, path) in &args.wordlists {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        let words = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+        wordlists.insert(name.clone(), Wordlist { words });
+    }
+
+    let response = client
+        .guess_hashes(GuessHashesRequest {
+            templates: args.templates.clone(),
+            hashtable_type: args.table.as_str().to_string(),
+            wordlists,
+            targets: args.targets.clone(),
+            max_candidates: args.max_candidates.unwrap_or(0),
+        })
+        .await
+        .map_err(|e| format!("Failed to guess hashes: {}", e.message()))?
+        .into_inner();
+
+    for entry in &response.cracked {
+        println!("{:x} {}", entry.hash, entry.value);
+    }
+    eprintln!(
+        "Tried {} candidates, cracked {} of {} unknown hashes",
+        response.candidates,
+        response.cracked.len(),
+        response.targets
+    );
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! `load`: loads the tables through the streaming RPC and prints each file as it is read.
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::LoadHashesRequest;
+use crate::hash_service::load_hashes_update::Update;
+use crate::status::print_failed_files;
+
+#[derive(Debug, Args)]
+pub struct LoadArgs {
+    /// Download every file again and reload from scratch, keeping runtime additions
+    #[arg(long)]
+    resync: bool,
+    /// Only refresh the files of this table ("game" or "bin"), reloading just that table
+    #[arg(long = "table", value_name = "TABLE")]
+    tables: Vec<String>,
+    /// Only refresh this synced file, as listed by `files list`
+    #[arg(long = "file", value_name = "FILE")]
+    files: Vec<String>,
+}
+
+pub async fn load(client: &mut Client, args: &LoadArgs) -> Result<(), String> {
+    let mut stream = client
+        .load_hashes_stream(LoadHashesRequest {
+            resync: args.resync,
+            files: args.files.clone(),
+            hashtable_types: args.tables.clone(),
+        })
+        .await
+        .map_err(|e| format!("Failed to load: {}", e.message()))?
+        .into_inner();
+
+    while let Some(update) = stream
+        .message()
+        .await
+        .map_err(|e| format!("Load failed: {}", e.message()))?
+    {
+        match update.update {
+            // Only finished files, the ones in progress would flood the output
+            Some(Update::File(file)) if file.done => println!(
+                "{}/{}\t{}\t{}\t{} bytes\t{} lines\t{} ms",
+                file.index + 1,
+                file.file_count,
+                file.hashtable_type,
+                file.file_name,
+                file.bytes_read,
+                file.lines,
+                file.elapsed_ms
+            ),
+            Some(Update::File(_)) | None => {}
+            Some(Update::Result(result)) if result.success => {
+                println!("{}", result.message);
+                print_failed_files(&result.failed_files);
+            }
+            Some(Update::Result(result)) => return Err(result.message),
+        }
+    }
+    Ok(())
+}
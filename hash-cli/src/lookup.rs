@@ -0,0 +1,186 @@
+//! `lookup-file`: resolves one hash per line and prints the results as JSON, CSV or TSV.
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+
+use crate::Client;
+use crate::MAX_BATCH_SIZE;
+use crate::hash_service::{EntryOrigin, GetStringsRequest};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    Tsv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Table {
+    Game,
+    Bin,
+}
+
+impl Table {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Table::Game => "game",
+            Table::Bin => "bin",
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct LookupFileArgs {
+    /// File with one hex hash per line, or `-` for stdin
+    input: PathBuf,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    format: OutputFormat,
+    /// Table to resolve in; by default hashes longer than 8 hex digits are game hashes
+    #[arg(long, value_enum)]
+    table: Option<Table>,
+}
+
+#[derive(Debug, Serialize)]
+struct LookupRow {
+    hash: String,
+    table: &'static str,
+    found: bool,
+    /// Empty when not found
+    value: String,
+    /// "official" (synced) or "custom" (added at runtime), empty when not found
+    origin: &'static str,
+    /// File the synced value was read from, empty unless the service tracks sources
+    source_file: String,
+}
+
+/// Parses `0x`-prefixed or bare hex, returning the digits as written and the value
+fn parse_hash(line: &str) -> Option<(&str, u64)> {
+    let digits = line
+        .strip_prefix("0x")
+        .or_else(|| line.strip_prefix("0X"))
+        .unwrap_or(line);
+    let hash = u64::from_str_radix(digits, 16).ok()?;
+    Some((digits, hash))
+}
+
+fn read_hashes(args: &LookupFileArgs) -> Result<Vec<(Table, u64)>, String> {
+    let reader: Box<dyn BufRead> = if args.input.as_os_str() == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        let file = std::fs::File::open(&args.input)
+            .map_err(|e| format!("Failed to open {:?}: {}", args.input, e))?;
+        Box::new(BufReader::new(file))
+    };
+
+    let mut hashes = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read {:?}: {}", args.input, e))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (digits, hash) = parse_hash(line)
+            .ok_or_else(|| format!("Invalid hash '{}' on line {}", line, index + 1))?;
+        let table = args.table.unwrap_or(if digits.len() > 8 {
+            Table::Game
+        } else {
+            Table::Bin
+        });
+        hashes.push((table, hash));
+    }
+    Ok(hashes)
+}
+
+async fn resolve(client: &mut Client, hashes: &[(Table, u64)]) -> Result<Vec<LookupRow>, String> {
+    let mut rows: Vec<LookupRow> = hashes
+        .iter()
+        .map(|(table, hash)| LookupRow {
+            hash: match table {
+                Table::Game => format!("{:016x}", hash),
+                Table::Bin => format!("{:08x}", hash),
+            },
+            table: table.as_str(),
+            found: false,
+            value: String::new(),
+            origin: "",
+            source_file: String::new(),
+        })
+        .collect();
+
+    // One batch per table, each keeping the indices of its rows so input order is preserved
+    for table in [Table::Game, Table::Bin] {
+        let indices: Vec<usize> = (0..hashes.len())
+            .filter(|&i| hashes[i].0 == table)
+            .collect();
+        for chunk in indices.chunks(MAX_BATCH_SIZE) {
+            let response = client
+                .get_strings(GetStringsRequest {
+                    hashes: chunk.iter().map(|&i| hashes[i].1).collect(),
+                    hashtable_type: table.as_str().to_string(),
+                })
+                .await
+                .map_err(|e| format!("Failed to resolve hashes: {}", e.message()))?
+                .into_inner();
+
+            for (&i, result) in chunk.iter().zip(response.results) {
+                rows[i].found = result.found;
+                rows[i].origin = match result.origin() {
+                    EntryOrigin::Official => "official",
+                    EntryOrigin::Custom => "custom",
+                    EntryOrigin::None => "",
+                };
+                rows[i].value = result.value;
+                rows[i].source_file = result.source_file;
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_rows(out: &mut impl Write, rows: &[LookupRow], format: OutputFormat) -> io::Result<()> {
+    let separator = match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, rows)?;
+            writeln!(out)?;
+            return out.flush();
+        }
+        OutputFormat::Csv => ',',
+        OutputFormat::Tsv => '\t',
+    };
+
+    writeln!(
+        out,
+        "hash{0}table{0}found{0}value{0}origin{0}source_file",
+        separator
+    )?;
+    for row in rows {
+        let (value, source_file) = match format {
+            OutputFormat::Csv => (csv_field(&row.value), csv_field(&row.source_file)),
+            _ => (row.value.clone(), row.source_file.clone()),
+        };
+        writeln!(
+            out,
+            "{1}{0}{2}{0}{3}{0}{4}{0}{5}{0}{6}",
+            separator, row.hash, row.table, row.found, value, row.origin, source_file
+        )?;
+    }
+    out.flush()
+}
+
+pub async fn lookup_file(client: &mut Client, args: &LookupFileArgs) -> Result<(), String> {
+    let hashes = read_hashes(args)?;
+    let rows = resolve(client, &hashes).await?;
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    write_rows(&mut out, &rows, args.format).map_err(|e| format!("Failed to write stdout: {}", e))
+}
@@ -0,0 +1,319 @@
+//! Command line client for the hash service. Connects to the endpoint given with
+//! `--endpoint`, or the one the running instance advertises in its discovery file.
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use directories_next::ProjectDirs;
+use serde::Deserialize;
+use tonic::codec::CompressionEncoding;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::Interceptor;
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint};
+use tonic::{Request, Status};
+
+use hash_service::admin_client::AdminClient;
+use hash_service::hash_loader_client::HashLoaderClient;
+
+mod cache_dir;
+mod clean;
+mod clear;
+mod clients;
+mod diff;
+mod discover;
+mod document;
+mod export;
+mod files;
+mod guess;
+mod load;
+mod lookup;
+mod rename;
+mod resolve;
+mod snapshot;
+mod source;
+mod status;
+mod sync;
+mod table;
+mod unknown;
+mod watch;
+
+pub mod hash_service {
+    tonic::include_proto!("hashservice");
+}
+
+const DEFAULT_ENDPOINT: &str = "http://[::1]:50051";
+const DISCOVERY_FILE_NAME: &str = "endpoint.json";
+/// Most hashes the server resolves per GetStrings call
+const MAX_BATCH_SIZE: usize = 10_000;
+/// Matches the server's default response limit, well above tonic's 4 MiB
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+/// Header the server picks the product's tables by
+const PRODUCT_HEADER: &str = "x-hash-product";
+/// Header the server picks the data branch's tables by
+const BRANCH_HEADER: &str = "x-hash-branch";
+/// Header the server tags its log lines for a call with
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// HashLoader client that adds the selected product (and the like) to every request
+pub type Client = HashLoaderClient<InterceptedService<Channel, RequestHeaders>>;
+pub type Admin = AdminClient<InterceptedService<Channel, RequestHeaders>>;
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Command line client for the League Toolkit hash service"
+)]
+struct Cli {
+    /// Server to connect to (or HASH_SERVICE_ENDPOINT); defaults to the running instance,
+    /// then http://[::1]:50051
+    #[arg(long)]
+    endpoint: Option<String>,
+    /// PEM CA to verify the server with when connecting over https
+    #[arg(long)]
+    ca: Option<PathBuf>,
+    /// Name the server certificate was issued for
+    #[arg(long, default_value = "localhost")]
+    domain_name: String,
+    /// Product whose tables to use, e.g. tft; defaults to the server's default product
+    #[arg(long)]
+    product: Option<String>,
+    /// Data branch whose tables to use, e.g. pbe; defaults to the server's default branch
+    #[arg(long)]
+    branch: Option<String>,
+    /// Seconds each call may take; the server stops working on calls that run out
+    #[arg(long)]
+    timeout: Option<f64>,
+    /// Id the server logs the calls with, to find them in its log
+    #[arg(long)]
+    request_id: Option<String>,
+    /// Compress requests with gzip or zstd
+    #[arg(long, value_enum)]
+    send_compression: Option<Compression>,
+    /// Encodings the server may compress responses with
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "gzip,zstd")]
+    accept_compression: Vec<Compression>,
+    /// Largest message to send or receive, in bytes
+    #[arg(long, default_value_t = DEFAULT_MAX_MESSAGE_SIZE)]
+    max_message_size: usize,
+    /// Seconds between keepalive pings, so long `watch` sessions notice dead connections; 0 disables
+    #[arg(long, default_value_t = 30)]
+    keepalive: u64,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Copy stdin to stdout with 8 (bin) and 16 (game) hex digit hashes replaced by their names
+    ResolveStream(resolve::ResolveStreamArgs),
+    /// Resolve one hash per line and print the results as JSON, CSV or TSV
+    LookupFile(lookup::LookupFileArgs),
+    /// Substitute known names for unresolved 0x... literals in a ritobin or JSON bin dump
+    ResolveDocument(document::ResolveDocumentArgs),
+    /// Move files named by chunk hash (as left by WAD extractors) to their real paths
+    RenameWad(rename::RenameWadArgs),
+    /// Have the service scan WADs and bins for strings that resolve unknown hashes
+    Discover(discover::DiscoverArgs),
+    /// Expand path templates against wordlists to crack unknown hashes
+    Guess(guess::GuessArgs),
+    /// List hashes the service's lookups failed to resolve, most missed first
+    ListUnknown(unknown::ListUnknownArgs),
+    /// Write unknown hashes and locally found names in CommunityDragon's format for submission
+    ExportUnknown(export::ExportUnknownArgs),
+    /// Show what the last hashtable sync added (+), removed (-) and renamed (~)
+    Diff(diff::DiffArgs),
+    /// Save the loaded tables under a name and roll back to them later
+    Snapshot(snapshot::SnapshotArgs),
+    /// Print table changes (loads, syncs, additions, ...) as they happen
+    Watch(watch::WatchArgs),
+    /// Empty one table, or remove only the entries added at runtime
+    ClearTable(clear::ClearTableArgs),
+    /// Delete orphaned and superseded cache files and unconfigured products' caches
+    CleanCache(clean::CleanCacheArgs),
+    /// List the cached hashtable files, or download one of them again
+    Files(files::FilesArgs),
+    /// Move the service's caches to another directory without restarting it
+    SetCacheDir(cache_dir::SetCacheDirArgs),
+    /// Manage custom tables for hash namespaces beyond game and bin
+    Table(table::TableArgs),
+    /// Register your own hashtable files and directories to read on every load
+    Source(source::SourceArgs),
+    /// Show whether the tables are loaded and which upstream commit and patch they are from
+    Status(status::StatusArgs),
+    /// Load (or re-sync) the tables, printing each file's progress as it is read
+    Load(load::LoadArgs),
+    /// Bring the cached files up to date without loading them, printing each download
+    Sync(sync::SyncArgs),
+    /// List connections using the service, with their open calls and call counts
+    Clients(clients::ClientsArgs),
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl From<Compression> for CompressionEncoding {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::Gzip => CompressionEncoding::Gzip,
+            Compression::Zstd => CompressionEncoding::Zstd,
+        }
+    }
+}
+
+/// The part of the server's discovery file the CLI needs
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    endpoint: String,
+}
+
+fn discovered_endpoint() -> Option<String> {
+    let dirs = ProjectDirs::from("io", "LeagueToolkit", "ltk-hash-service")?;
+    let text = std::fs::read_to_string(dirs.data_dir().join(DISCOVERY_FILE_NAME)).ok()?;
+    serde_json::from_str::<Discovery>(&text)
+        .ok()
+        .map(|discovery| discovery.endpoint)
+}
+
+/// Metadata sent with every call
+#[derive(Debug, Clone)]
+pub struct RequestHeaders {
+    product: Option<MetadataValue<Ascii>>,
+    branch: Option<MetadataValue<Ascii>>,
+    request_id: Option<MetadataValue<Ascii>>,
+    timeout: Option<Duration>,
+}
+
+impl Interceptor for RequestHeaders {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(product) = &self.product {
+            request
+                .metadata_mut()
+                .insert(PRODUCT_HEADER, product.clone());
+        }
+        if let Some(branch) = &self.branch {
+            request.metadata_mut().insert(BRANCH_HEADER, branch.clone());
+        }
+        if let Some(request_id) = &self.request_id {
+            request
+                .metadata_mut()
+                .insert(REQUEST_ID_HEADER, request_id.clone());
+        }
+        if let Some(timeout) = self.timeout {
+            request.set_timeout(timeout);
+        }
+        Ok(request)
+    }
+}
+
+fn header_value(kind: &str, value: Option<&str>) -> Result<Option<MetadataValue<Ascii>>, String> {
+    value
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("Invalid {} '{}'", kind, value))
+        })
+        .transpose()
+}
+
+impl Cli {
+    fn request_headers(&self) -> Result<RequestHeaders, String> {
+        Ok(RequestHeaders {
+            product: header_value("product", self.product.as_deref())?,
+            branch: header_value("branch", self.branch.as_deref())?,
+            request_id: header_value("request id", self.request_id.as_deref())?,
+            timeout: self
+                .timeout
+                .map(|secs| {
+                    Duration::try_from_secs_f64(secs)
+                        .map_err(|e| format!("Invalid timeout '{}': {}", secs, e))
+                })
+                .transpose()?,
+        })
+    }
+
+    async fn connect(&self) -> Result<Channel, String> {
+        let endpoint = self
+            .endpoint
+            .clone()
+            .or_else(|| std::env::var("HASH_SERVICE_ENDPOINT").ok())
+            .or_else(discovered_endpoint)
+            .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string());
+
+        let mut channel = Endpoint::from_shared(endpoint.clone())
+            .map_err(|e| format!("Invalid endpoint '{}': {}", endpoint, e))?;
+        if self.keepalive > 0 {
+            channel = channel
+                .http2_keep_alive_interval(Duration::from_secs(self.keepalive))
+                .keep_alive_while_idle(true);
+        }
+        if let Some(ca) = &self.ca {
+            let pem = std::fs::read(ca).map_err(|e| format!("Failed to read {:?}: {}", ca, e))?;
+            channel = channel
+                .tls_config(
+                    ClientTlsConfig::new()
+                        .ca_certificate(Certificate::from_pem(pem))
+                        .domain_name(self.domain_name.clone()),
+                )
+                .map_err(|e| format!("Invalid TLS config: {}", e))?;
+        }
+        channel
+            .connect()
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {}", endpoint, e))
+    }
+
+    fn client(&self, channel: Channel) -> Result<Client, String> {
+        let mut client = HashLoaderClient::with_interceptor(channel, self.request_headers()?)
+            .max_decoding_message_size(self.max_message_size)
+            .max_encoding_message_size(self.max_message_size);
+        if let Some(compression) = self.send_compression {
+            client = client.send_compressed(compression.into());
+        }
+        for &compression in &self.accept_compression {
+            client = client.accept_compressed(compression.into());
+        }
+        Ok(client)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    let channel = cli.connect().await?;
+    let mut client = cli.client(channel.clone())?;
+
+    match &cli.command {
+        Command::ResolveStream(args) => resolve::resolve_stream(&mut client, args).await?,
+        Command::LookupFile(args) => lookup::lookup_file(&mut client, args).await?,
+        Command::ResolveDocument(args) => document::resolve_document(&mut client, args).await?,
+        Command::RenameWad(args) => rename::rename_wad(&mut client, args).await?,
+        Command::Discover(args) => discover::discover(&mut client, args).await?,
+        Command::Guess(args) => guess::guess(&mut client, args).await?,
+        Command::ListUnknown(args) => unknown::list_unknown(&mut client, args).await?,
+        Command::ExportUnknown(args) => export::export_unknown(&mut client, args).await?,
+        Command::Diff(args) => diff::diff(&mut client, args).await?,
+        Command::Snapshot(args) => snapshot::snapshot(&mut client, args).await?,
+        Command::Watch(args) => watch::watch(&mut client, args).await?,
+        Command::ClearTable(args) => clear::clear_table(&mut client, args).await?,
+        Command::CleanCache(args) => clean::clean_cache(&mut client, args).await?,
+        Command::Files(args) => files::files(&mut client, args).await?,
+        Command::Table(args) => table::table(&mut client, args).await?,
+        Command::Source(args) => source::source(&mut client, args).await?,
+        Command::Status(args) => status::status(&mut client, args).await?,
+        Command::Load(args) => load::load(&mut client, args).await?,
+        Command::Sync(args) => sync::sync(&mut client, args).await?,
+        Command::Clients(args) => {
+            let mut admin = AdminClient::with_interceptor(channel, cli.request_headers()?);
+            clients::clients(&mut admin, args).await?
+        }
+        Command::SetCacheDir(args) => {
+            let mut admin = AdminClient::with_interceptor(channel, cli.request_headers()?);
+            cache_dir::set_cache_dir(&mut admin, args).await?
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+//! `rename-wad`: moves files named by chunk hash, as WAD extractors leave unknown chunks,
+//! to their real paths.
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use clap::Args;
+use walkdir::WalkDir;
+
+use crate::Client;
+use crate::MAX_BATCH_SIZE;
+use crate::hash_service::GetStringsRequest;
+
+#[derive(Debug, Args)]
+pub struct RenameWadArgs {
+    /// Extracted directory to rename files in
+    dir: PathBuf,
+    /// Print the moves without touching any file
+    #[arg(long)]
+    dry_run: bool,
+    /// Also write the hashes that could not be resolved to this file, one per line
+    #[arg(long)]
+    unresolved: Option<PathBuf>,
+}
+
+/// Chunk hash of a file named `0123456789abcdef` or `0123456789abcdef.ext`
+fn chunk_hash(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    if stem.len() != 16 {
+        return None;
+    }
+    u64::from_str_radix(stem, 16).ok()
+}
+
+/// Resolved paths come from the hashtables, so refuse anything that would escape `dir`
+fn target_path(dir: &Path, resolved: &str) -> Option<PathBuf> {
+    let relative = Path::new(resolved);
+    relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then(|| dir.join(relative))
+}
+
+pub async fn rename_wad(client: &mut Client, args: &RenameWadArgs) -> Result<(), String> {
+    let files: Vec<(PathBuf, u64)> = WalkDir::new(&args.dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| chunk_hash(entry.path()).map(|hash| (entry.into_path(), hash)))
+        .collect();
+
+    let mut names = HashMap::new();
+    for chunk in files.chunks(MAX_BATCH_SIZE) {
+        let response = client
+            .get_strings(GetStringsRequest {
+                hashes: chunk.iter().map(|(_, hash)| *hash).collect(),
+                hashtable_type: "game".to_string(),
+            })
+            .await
+            .map_err(|e| format!("Failed to resolve hashes: {}", e.message()))?
+            .into_inner();
+        names.extend(
+            response
+                .results
+                .into_iter()
+                .filter(|r| r.found)
+                .map(|r| (r.hash, r.value)),
+        );
+    }
+
+    let mut renamed = 0;
+    let mut unresolved = Vec::new();
+    for (path, hash) in &files {
+        let Some(name) = names.get(hash) else {
+            unresolved.push(*hash);
+            continue;
+        };
+        let Some(target) = target_path(&args.dir, name) else {
+            eprintln!("Skipping {:?}: unsafe path '{}'", path, name);
+            continue;
+        };
+        if target.exists() {
+            eprintln!("Skipping {:?}: {:?} already exists", path, target);
+            continue;
+        }
+
+        println!("{} -> {}", path.display(), target.display());
+        if !args.dry_run {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+            }
+            std::fs::rename(path, &target)
+                .map_err(|e| format!("Failed to move {:?} to {:?}: {}", path, target, e))?;
+        }
+        renamed += 1;
+    }
+
+    eprintln!(
+        "{} {} files, {} unresolved",
+        if args.dry_run {
+            "Would rename"
+        } else {
+            "Renamed"
+        },
+        renamed,
+        unresolved.len()
+    );
+    if let Some(unresolved_path) = &args.unresolved {
+        let text: String = unresolved
+            .iter()
+            .map(|hash| format!("{:016x}\n", hash))
+            .collect();
+        std::fs::write(unresolved_path, text)
+            .map_err(|e| format!("Failed to write {:?}: {}", unresolved_path, e))?;
+    }
+    Ok(())
+}
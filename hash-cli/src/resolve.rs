@@ -0,0 +1,226 @@
+//! `resolve-stream`: rewrites hashes in text piped through the CLI, e.g. ritobin dumps or logs.
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use clap::Args;
+use regex::bytes::{Captures, Regex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader, BufWriter};
+
+use crate::Client;
+use crate::MAX_BATCH_SIZE;
+use crate::hash_service::GetStringsRequest;
+
+/// Whole-word hex tokens, optionally `0x` prefixed: 16 digits are game hashes, 8 are bin hashes
+static HASH_TOKEN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i-u)\b(?:0x)?([0-9a-f]{16}|[0-9a-f]{8})\b").expect("valid hash token regex")
+});
+
+#[derive(Debug, Args)]
+pub struct ResolveStreamArgs {
+    /// Keep each hash and append its name in parentheses instead of replacing it
+    #[arg(long)]
+    annotate: bool,
+    /// Most lines to collect before resolving their hashes in one round trip
+    #[arg(long, default_value_t = 1024)]
+    batch_lines: usize,
+}
+
+/// Resolved names by table and hash; `None` caches a miss
+#[derive(Debug, Default)]
+struct NameCache {
+    game: HashMap<u64, Option<String>>,
+    bin: HashMap<u64, Option<String>>,
+}
+
+impl NameCache {
+    fn table(&mut self, hashtable_type: &str) -> &mut HashMap<u64, Option<String>> {
+        match hashtable_type {
+            "game" => &mut self.game,
+            _ => &mut self.bin,
+        }
+    }
+
+    fn get(&self, token: &[u8]) -> Option<&str> {
+        let (hashtable_type, hash) = parse_token(token)?;
+        let table = match hashtable_type {
+            "game" => &self.game,
+            _ => &self.bin,
+        };
+        table.get(&hash)?.as_deref()
+    }
+
+    /// Resolves every hash in `lines` that isn't cached yet
+    async fn fill(&mut self, client: &mut Client, lines: &[Vec<u8>]) -> Result<(), String> {
+        let mut missing: HashMap<&'static str, Vec<u64>> = HashMap::new();
+        for line in lines {
+            for captures in HASH_TOKEN.captures_iter(line) {
+                if let Some((hashtable_type, hash)) = parse_token(&captures[1])
+                    && !self.table(hashtable_type).contains_key(&hash)
+                {
+                    self.table(hashtable_type).insert(hash, None);
+                    missing.entry(hashtable_type).or_default().push(hash);
+                }
+            }
+        }
+
+        for (hashtable_type, hashes) in missing {
+            for chunk in hashes.chunks(MAX_BATCH_SIZE) {
+                let response = client
+                    .get_strings(GetStringsRequest {
+                        hashes: chunk.to_vec(),
+                        hashtable_type: hashtable_type.to_string(),
+                    })
+                    .await
+                    .map_err(|e| format!("Failed to resolve hashes: {}", e.message()))?
+                    .into_inner();
+
+                let table = self.table(hashtable_type);
+                for result in response.results.into_iter().filter(|r| r.found) {
+                    table.insert(result.hash, Some(result.value));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_token(digits: &[u8]) -> Option<(&'static str, u64)> {
+    let hashtable_type = match digits.len() {
+        16 => "game",
+        8 => "bin",
+        _ => return None,
+    };
+    let hash = u64::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    Some((hashtable_type, hash))
+}
+
+/// Reads up to `batch_lines` whole lines, however the input was split into reads, and whether
+/// the input ended. Returns early when no more input is buffered, so `tail -f` style input
+/// isn't held back.
+async fn read_batch(
+    reader: &mut BufReader<impl AsyncRead + Unpin>,
+    batch_lines: usize,
+) -> Result<(Vec<Vec<u8>>, bool), String> {
+    let mut lines = Vec::new();
+    while lines.len() < batch_lines {
+        let mut line = Vec::new();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .await
+            .map_err(|e| format!("Failed to read stdin: {}", e))?;
+        if read == 0 {
+            return Ok((lines, true));
+        }
+        lines.push(line);
+        if reader.buffer().is_empty() {
+            break;
+        }
+    }
+    Ok((lines, false))
+}
+
+/// `line` with each hash the cache resolves replaced by its name, or followed by it
+fn rewrite(line: &[u8], cache: &NameCache, annotate: bool) -> Vec<u8> {
+    HASH_TOKEN
+        .replace_all(line, |captures: &Captures| match cache.get(&captures[1]) {
+            Some(name) if annotate => [&captures[0], b" (", name.as_bytes(), b")"].concat(),
+            Some(name) => name.as_bytes().to_vec(),
+            None => captures[0].to_vec(),
+        })
+        .into_owned()
+}
+
+pub async fn resolve_stream(client: &mut Client, args: &ResolveStreamArgs) -> Result<(), String> {
+    let mut reader = BufReader::new(tokio::io::stdin());
+    let mut writer = BufWriter::new(tokio::io::stdout());
+    let mut cache = NameCache::default();
+    let batch_lines = args.batch_lines.max(1);
+
+    loop {
+        let (lines, eof) = read_batch(&mut reader, batch_lines).await?;
+        cache.fill(client, &lines).await?;
+        for line in &lines {
+            writer
+                .write_all(&rewrite(line, &cache, args.annotate))
+                .await
+                .map_err(|e| format!("Failed to write stdout: {}", e))?;
+        }
+        writer
+            .flush()
+            .await
+            .map_err(|e| format!("Failed to write stdout: {}", e))?;
+
+        if eof {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    fn cache() -> NameCache {
+        NameCache {
+            game: HashMap::from([(
+                0x0123456789abcdef,
+                Some("data/characters/aatrox/aatrox.bin".to_string()),
+            )]),
+            bin: HashMap::from([
+                (0xdeadbeef, Some("mSpellCalculations".to_string())),
+                (0x1234abcd, None),
+            ]),
+        }
+    }
+
+    #[tokio::test]
+    async fn joins_tokens_split_across_reads() {
+        // Each slice is a separate read, as pipe writes can arrive; both hashes are cut in two
+        let input = (&b"path: 0123456789"[..])
+            .chain(&b"abcdef\nname: 0xDEAD"[..])
+            .chain(&b"BEEF\n"[..]);
+        let mut reader = BufReader::new(input);
+
+        let mut lines = Vec::new();
+        loop {
+            let (batch, eof) = read_batch(&mut reader, 16).await.unwrap();
+            lines.extend(batch);
+            if eof {
+                break;
+            }
+        }
+        let cache = cache();
+        let output: Vec<u8> = lines
+            .iter()
+            .flat_map(|line| rewrite(line, &cache, false))
+            .collect();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "path: data/characters/aatrox/aatrox.bin\nname: mSpellCalculations\n"
+        );
+    }
+
+    #[test]
+    fn only_rewrites_whole_tokens() {
+        let cache = cache();
+        let rewrite = |line: &str, annotate| {
+            String::from_utf8(rewrite(line.as_bytes(), &cache, annotate)).unwrap()
+        };
+        // Punctuation separates tokens
+        assert_eq!(
+            rewrite("{deadbeef,0xdeadbeef}: \"0123456789ABCDEF\"", false),
+            "{mSpellCalculations,mSpellCalculations}: \"data/characters/aatrox/aatrox.bin\""
+        );
+        // Longer hex runs, word characters around a token and misses stay as they are
+        assert_eq!(
+            rewrite("deadbeef0 xdeadbeef deadbeef_ 1234abcd", false),
+            "deadbeef0 xdeadbeef deadbeef_ 1234abcd"
+        );
+        assert_eq!(
+            rewrite("type = deadbeef\n", true),
+            "type = deadbeef (mSpellCalculations)\n"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! `snapshot`: saves, lists, restores and deletes named copies of the service's tables.
+use clap::{Args, Subcommand};
+
+use crate::Client;
+use crate::hash_service::{
+    CreateSnapshotRequest, DeleteSnapshotRequest, ListSnapshotsRequest, RestoreSnapshotRequest,
+};
+
+#[derive(Debug, Args)]
+pub struct SnapshotArgs {
+    #[command(subcommand)]
+    command: SnapshotCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum SnapshotCommand {
+    /// Save the loaded tables, custom entries included, replacing a snapshot of the same name
+    Create {
+        name: String,
+    },
+    /// List snapshots, newest first
+    List,
+    /// Replace the loaded tables with a snapshot
+    Restore {
+        name: String,
+    },
+    Delete {
+        name: String,
+    },
+}
+
+pub async fn snapshot(client: &mut Client, args: &SnapshotArgs) -> Result<(), String> {
+    match &args.command {
+        SnapshotCommand::Create { name } => {
+            let snapshot = client
+                .create_snapshot(CreateSnapshotRequest { name: name.clone() })
+                .await
+                .map_err(|e| format!("Failed to create snapshot: {}", e.message()))?
+                .into_inner()
+                .snapshot
+                .unwrap_or_default();
+            eprintln!(
+                "Saved '{}': {} game and {} bin entries, {} bytes",
+                snapshot.name, snapshot.game_count, snapshot.bin_count, snapshot.size_bytes
+            );
+        }
+        SnapshotCommand::List => {
+            let response = client
+                .list_snapshots(ListSnapshotsRequest {})
+                .await
+                .map_err(|e| format!("Failed to list snapshots: {}", e.message()))?
+                .into_inner();
+            for snapshot in &response.snapshots {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    snapshot.name,
+                    snapshot.created_at,
+                    snapshot.game_count,
+                    snapshot.bin_count,
+                    snapshot.size_bytes
+                );
+            }
+        }
+        SnapshotCommand::Restore { name } => {
+            let response = client
+                .restore_snapshot(RestoreSnapshotRequest { name: name.clone() })
+                .await
+                .map_err(|e| format!("Failed to restore snapshot: {}", e.message()))?
+                .into_inner();
+            eprintln!(
+                "Restored '{}': {} game and {} bin entries",
+                name, response.game_count, response.bin_count
+            );
+        }
+        SnapshotCommand::Delete { name } => {
+            client
+                .delete_snapshot(DeleteSnapshotRequest { name: name.clone() })
+                .await
+                .map_err(|e| format!("Failed to delete snapshot: {}", e.message()))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+//! `source`: registers hashtable files and directories of the user's own with the service and
+//! turns them on or off. Paths are on the service's machine.
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Subcommand};
+
+use crate::Client;
+use crate::hash_service::{
+    AddSourceDirectoryRequest, AddSourceFileRequest, ListSourcesRequest, LocalSource,
+    RemoveSourceRequest, SetSourceEnabledRequest,
+};
+use crate::lookup::Table;
+
+#[derive(Debug, Args)]
+pub struct SourceArgs {
+    #[command(subcommand)]
+    command: SourceCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum SourceCommand {
+    /// Read every table file in a directory, named like the synced ones, on each load
+    AddDir { path: PathBuf },
+    /// Read one file of any name into a table on each load
+    AddFile {
+        path: PathBuf,
+        #[arg(long, value_enum)]
+        table: Table,
+    },
+    /// List sources in the order they are read
+    List,
+    /// Read a source again from the next load on
+    Enable { path: PathBuf },
+    /// Skip a source from the next load on, keeping it registered
+    Disable { path: PathBuf },
+    /// Unregister a source added with add-dir or add-file
+    Remove { path: PathBuf },
+}
+
+fn absolute(path: &Path) -> Result<String, String> {
+    std::path::absolute(path)
+        .map(|path| path.display().to_string())
+        .map_err(|e| format!("Invalid path {:?}: {}", path, e))
+}
+
+fn print_source(source: &LocalSource) {
+    let table = if source.directory {
+        "dir"
+    } else {
+        source.hashtable_type.as_str()
+    };
+    let mut flags = Vec::new();
+    if !source.enabled {
+        flags.push("disabled");
+    }
+    if !source.available {
+        flags.push("unavailable");
+    }
+    if source.from_config {
+        flags.push("config");
+    }
+    println!("{}\t{}\t{}", table, source.path, flags.join(","));
+}
+
+pub async fn source(client: &mut Client, args: &SourceArgs) -> Result<(), String> {
+    match &args.command {
+        SourceCommand::AddDir { path } => {
+            let response = client
+                .add_source_directory(AddSourceDirectoryRequest {
+                    path: absolute(path)?,
+                })
+                .await
+                .map_err(|e| format!("Failed to add source: {}", e.message()))?
+                .into_inner();
+            if let Some(source) = &response.source {
+                eprintln!("Added {}; it is read from the next load on", source.path);
+            }
+        }
+        SourceCommand::AddFile { path, table } => {
+            let response = client
+                .add_source_file(AddSourceFileRequest {
+                    path: absolute(path)?,
+                    hashtable_type: table.as_str().to_string(),
+                })
+                .await
+                .map_err(|e| format!("Failed to add source: {}", e.message()))?
+                .into_inner();
+            if let Some(source) = &response.source {
+                eprintln!("Added {}; it is read from the next load on", source.path);
+            }
+        }
+        SourceCommand::List => {
+            let response = client
+                .list_sources(ListSourcesRequest {})
+                .await
+                .map_err(|e| format!("Failed to list sources: {}", e.message()))?
+                .into_inner();
+            for source in &response.sources {
+                print_source(source);
+            }
+        }
+        SourceCommand::Enable { path } | SourceCommand::Disable { path } => {
+            let enabled = matches!(args.command, SourceCommand::Enable { .. });
+            let response = client
+                .set_source_enabled(SetSourceEnabledRequest {
+                    path: absolute(path)?,
+                    enabled,
+                })
+                .await
+                .map_err(|e| format!("Failed to update source: {}", e.message()))?
+                .into_inner();
+            if let Some(source) = &response.source {
+                print_source(source);
+            }
+        }
+        SourceCommand::Remove { path } => {
+            client
+                .remove_source(RemoveSourceRequest {
+                    path: absolute(path)?,
+                })
+                .await
+                .map_err(|e| format!("Failed to remove source: {}", e.message()))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! `status`: prints the service's loading state, the files the loaded tables went without,
+//! and which upstream commit and game patch each synced file is from.
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::{FailedFile, FailureStage, GetStatusRequest, LoadingState};
+
+#[derive(Debug, Args)]
+pub struct StatusArgs {}
+
+pub async fn status(client: &mut Client, _args: &StatusArgs) -> Result<(), String> {
+    let response = client
+        .get_status(GetStatusRequest {})
+        .await
+        .map_err(|e| format!("Failed to get status: {}", e.message()))?
+        .into_inner();
+
+    println!("state\t{}", state_name(response.loading_state()));
+    println!(
+        "tables\tgame {}\tbin {}",
+        state_name(response.game_state()),
+        state_name(response.bin_state())
+    );
+    if !response.last_error.is_empty() {
+        println!("error\t{}", response.last_error);
+    }
+    print_failed_files(&response.failed_files);
+    if let Some(progress) = &response.progress
+        && response.loading_state() == LoadingState::Loading
+    {
+        println!(
+            "progress\t{}/{} files\t{}/{} bytes\t{} entries\t{}",
+            progress.files_done,
+            progress.file_count,
+            progress.bytes_read,
+            progress.total_bytes,
+            progress.entries,
+            progress.current_file
+        );
+    }
+    if matches!(
+        response.loading_state(),
+        LoadingState::Loaded | LoadingState::LoadedWithWarnings
+    ) {
+        println!("game\t{}", response.game_count);
+        println!("bin\t{}", response.bin_count);
+        println!(
+            "memory\t{} bytes\tgame {}\tbin {}",
+            response.memory_bytes, response.game_memory_bytes, response.bin_memory_bytes
+        );
+    }
+    if let Some(allocator) = &response.allocator {
+        println!(
+            "allocator\t{}\tresident {} bytes\tcommitted {} bytes",
+            allocator.name, allocator.resident_bytes, allocator.committed_bytes
+        );
+    }
+    println!("product\t{}", response.product);
+    println!("branch\t{}", response.branch);
+    if !response.patch.is_empty() {
+        println!("patch\t{}", response.patch);
+    }
+    for file in &response.files {
+        if !file.url.is_empty() {
+            println!("{}\t{}", file.file_name, file.url);
+            continue;
+        }
+        println!(
+            "{}\t{}\t{}\t{}",
+            file.file_name, file.commit_sha, file.commit_date, file.patch
+        );
+    }
+    Ok(())
+}
+
+fn state_name(state: LoadingState) -> &'static str {
+    match state {
+        LoadingState::Unloaded => "unloaded",
+        LoadingState::Loading => "loading",
+        LoadingState::Loaded => "loaded",
+        LoadingState::LoadedWithWarnings => "loaded with warnings",
+    }
+}
+
+/// One line per file a load or sync went without: its table, name, stage and error
+pub fn print_failed_files(files: &[FailedFile]) {
+    for file in files {
+        let stage = match file.stage() {
+            FailureStage::Sync => "sync",
+            FailureStage::Read => "read",
+            FailureStage::Unspecified => "unknown",
+        };
+        println!(
+            "failed\t{}\t{}\t{}\t{}",
+            file.hashtable_type, file.file_name, stage, file.error
+        );
+    }
+}
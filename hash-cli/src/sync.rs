@@ -0,0 +1,71 @@
+//! `sync`: brings the service's cache up to date without loading it, printing each file's
+//! download as it finishes.
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::SyncHashtablesRequest;
+use crate::hash_service::sync_hashtables_update::Update;
+use crate::status::print_failed_files;
+
+#[derive(Debug, Args)]
+pub struct SyncArgs {
+    /// Download the files again even when they did not change upstream
+    #[arg(long)]
+    force: bool,
+    /// Only sync the files of this table ("game" or "bin")
+    #[arg(long = "table", value_name = "TABLE")]
+    tables: Vec<String>,
+    /// Only sync this file, as listed by `files list`
+    #[arg(long = "file", value_name = "FILE")]
+    files: Vec<String>,
+}
+
+pub async fn sync(client: &mut Client, args: &SyncArgs) -> Result<(), String> {
+    let mut stream = client
+        .sync_hashtables(SyncHashtablesRequest {
+            force: args.force,
+            files: args.files.clone(),
+            hashtable_types: args.tables.clone(),
+        })
+        .await
+        .map_err(|e| format!("Failed to sync: {}", e.message()))?
+        .into_inner();
+
+    while let Some(update) = stream
+        .message()
+        .await
+        .map_err(|e| format!("Sync failed: {}", e.message()))?
+    {
+        match update.update {
+            Some(Update::File(file)) if file.done && !file.error.is_empty() => println!(
+                "{}/{}\t{}\tfailed\t{}",
+                file.index + 1,
+                file.file_count,
+                file.file_name,
+                file.error
+            ),
+            Some(Update::File(file)) if file.done && file.up_to_date => println!(
+                "{}/{}\t{}\tup to date",
+                file.index + 1,
+                file.file_count,
+                file.file_name
+            ),
+            // Only finished files, the ones in progress would flood the output
+            Some(Update::File(file)) if file.done => println!(
+                "{}/{}\t{}\t{} bytes\t{} ms\t{} KiB/s",
+                file.index + 1,
+                file.file_count,
+                file.file_name,
+                file.bytes_downloaded,
+                file.elapsed_ms,
+                file.bytes_per_second / 1024
+            ),
+            Some(Update::File(_)) | None => {}
+            Some(Update::Result(result)) => {
+                println!("{} files changed upstream", result.updated_files);
+                print_failed_files(&result.failed_files);
+            }
+        }
+    }
+    Ok(())
+}
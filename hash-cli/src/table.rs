@@ -0,0 +1,159 @@
+//! `table`: creates, lists and drops custom tables, and adds to and looks up in them; shows
+//! which files any table was loaded from.
+use clap::{Args, Subcommand, ValueEnum};
+
+use crate::Client;
+use crate::hash_service::{
+    AddHashRequest, CreateTableRequest, DropTableRequest, GetStringRequest, GetTableInfoRequest,
+    HashAlgorithm, ListTablesRequest,
+};
+use crate::status::print_failed_files;
+
+#[derive(Debug, Args)]
+pub struct TableArgs {
+    #[command(subcommand)]
+    command: TableCommand,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Algorithm {
+    Xxh64,
+    Xxh3,
+    Fnv1a,
+    Elf,
+}
+
+#[derive(Debug, Subcommand)]
+enum TableCommand {
+    /// Create a table hashing strings with the given algorithm
+    Create {
+        name: String,
+        #[arg(long, value_enum)]
+        algorithm: Algorithm,
+    },
+    /// Delete a table and its entries
+    Drop { name: String },
+    /// List tables with their algorithm and entry count
+    List,
+    /// Show the files each table was last loaded from, with their SHAs and entry counts
+    Info {
+        /// "game", "bin" or a custom table; all of them when omitted
+        name: Option<String>,
+    },
+    /// Hash strings and add them to a table, printing each hash
+    Add {
+        name: String,
+        #[arg(required = true)]
+        values: Vec<String>,
+    },
+    /// Look up hex hashes in a table
+    Get {
+        name: String,
+        #[arg(required = true)]
+        hashes: Vec<String>,
+    },
+}
+
+pub async fn table(client: &mut Client, args: &TableArgs) -> Result<(), String> {
+    match &args.command {
+        TableCommand::Create { name, algorithm } => {
+            let algorithm = match algorithm {
+                Algorithm::Xxh64 => HashAlgorithm::Xxh64,
+                Algorithm::Xxh3 => HashAlgorithm::Xxh3,
+                Algorithm::Fnv1a => HashAlgorithm::Fnv1a,
+                Algorithm::Elf => HashAlgorithm::Elf,
+            };
+            client
+                .create_table(CreateTableRequest {
+                    name: name.clone(),
+                    algorithm: algorithm as i32,
+                })
+                .await
+                .map_err(|e| format!("Failed to create table: {}", e.message()))?;
+        }
+        TableCommand::Drop { name } => {
+            let response = client
+                .drop_table(DropTableRequest { name: name.clone() })
+                .await
+                .map_err(|e| format!("Failed to drop table: {}", e.message()))?
+                .into_inner();
+            eprintln!("Dropped '{}' with {} entries", name, response.removed);
+        }
+        TableCommand::List => {
+            let response = client
+                .list_tables(ListTablesRequest {})
+                .await
+                .map_err(|e| format!("Failed to list tables: {}", e.message()))?
+                .into_inner();
+            for table in &response.tables {
+                println!(
+                    "{}\t{}\t{}",
+                    table.name,
+                    table.algorithm().as_str_name(),
+                    table.count
+                );
+            }
+        }
+        TableCommand::Info { name } => {
+            let response = client
+                .get_table_info(GetTableInfoRequest {
+                    hashtable_type: name.clone().unwrap_or_default(),
+                })
+                .await
+                .map_err(|e| format!("Failed to get table info: {}", e.message()))?
+                .into_inner();
+            for table in &response.tables {
+                println!(
+                    "{}\t{}\t{} entries\t{} custom\tloaded at {} in {} ms",
+                    table.hashtable_type,
+                    table.state().as_str_name(),
+                    table.entries,
+                    table.custom_entries,
+                    table.loaded_at,
+                    table.load_duration_ms
+                );
+                for file in &table.files {
+                    println!(
+                        "\t{}\t{} entries\t{}\t{}\tmodified {}",
+                        file.path, file.entries, file.blob_sha, file.commit_sha, file.modified
+                    );
+                }
+                print_failed_files(&table.failed_files);
+            }
+        }
+        TableCommand::Add { name, values } => {
+            for value in values {
+                let response = client
+                    .add_hash(AddHashRequest {
+                        string: value.clone(),
+                        hashtable_type: name.clone(),
+                        ..AddHashRequest::default()
+                    })
+                    .await
+                    .map_err(|e| format!("Failed to add '{}': {}", value, e.message()))?
+                    .into_inner();
+                if !response.success {
+                    return Err(format!("Failed to add '{}': {}", value, response.message));
+                }
+                println!("{:x}\t{}", response.hash, value);
+            }
+        }
+        TableCommand::Get { name, hashes } => {
+            for hash in hashes {
+                let digits = hash.trim_start_matches("0x");
+                let parsed = u64::from_str_radix(digits, 16)
+                    .map_err(|_| format!("Invalid hash '{}', expected hex", hash))?;
+                let response = client
+                    .get_string(GetStringRequest {
+                        hash: parsed,
+                        hashtable_type: name.clone(),
+                    })
+                    .await
+                    .map_err(|e| format!("Failed to look up '{}': {}", hash, e.message()))?
+                    .into_inner();
+                println!("{}\t{}\t{}", digits, response.found, response.value);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+//! `list-unknown`: prints the hashes the service's lookups failed to resolve.
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::ListUnknownHashesRequest;
+
+/// Page size used to walk the whole list
+const PAGE_SIZE: u32 = 1000;
+
+#[derive(Debug, Args)]
+pub struct ListUnknownArgs {
+    /// Only list hashes missed in this table: game, bin or any
+    #[arg(long, default_value = "")]
+    table: String,
+    /// Also list hashes resolved since, with their names
+    #[arg(long)]
+    include_resolved: bool,
+}
+
+pub async fn list_unknown(client: &mut Client, args: &ListUnknownArgs) -> Result<(), String> {
+    let mut page_token = String::new();
+    loop {
+        let response = client
+            .list_unknown_hashes(ListUnknownHashesRequest {
+                hashtable_type: args.table.clone(),
+                limit: PAGE_SIZE,
+                page_token,
+                include_resolved: args.include_resolved,
+            })
+            .await
+            .map_err(|e| format!("Failed to list unknown hashes: {}", e.message()))?
+            .into_inner();
+
+        for unknown in &response.hashes {
+            println!(
+                "{}\t{:x}\t{}\t{}",
+                unknown.hashtable_type, unknown.hash, unknown.count, unknown.value
+            );
+        }
+        if response.next_page_token.is_empty() {
+            return Ok(());
+        }
+        page_token = response.next_page_token;
+    }
+}
@@ -0,0 +1,57 @@
+//! `watch`: prints the service's table change events as they happen.
+use clap::Args;
+
+use crate::Client;
+use crate::hash_service::{SubscribeRequest, TableEventKind};
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// Only print these events: loaded, unloaded, synced, added, cleared or restored
+    #[arg(long, value_delimiter = ',')]
+    kind: Vec<String>,
+}
+
+fn parse_kind(kind: &str) -> Result<TableEventKind, String> {
+    TableEventKind::from_str_name(&format!("TABLE_EVENT_KIND_{}", kind.to_ascii_uppercase()))
+        .ok_or_else(|| format!("Unknown event kind '{}'", kind))
+}
+
+pub async fn watch(client: &mut Client, args: &WatchArgs) -> Result<(), String> {
+    let kinds = args
+        .kind
+        .iter()
+        .map(|kind| parse_kind(kind).map(|kind| kind as i32))
+        .collect::<Result<_, _>>()?;
+    let mut stream = client
+        .subscribe(SubscribeRequest { kinds })
+        .await
+        .map_err(|e| format!("Failed to subscribe: {}", e.message()))?
+        .into_inner();
+
+    while let Some(event) = stream
+        .message()
+        .await
+        .map_err(|e| format!("Subscription failed: {}", e.message()))?
+    {
+        match event.kind() {
+            TableEventKind::Loaded => {
+                println!("loaded\t{}\t{}", event.game_count, event.bin_count)
+            }
+            TableEventKind::Unloaded => println!("unloaded"),
+            TableEventKind::Synced => println!("synced\t{}", event.updated_files),
+            TableEventKind::Added => println!(
+                "added\t{}\t{:x}\t{}",
+                event.hashtable_type, event.hash, event.value
+            ),
+            TableEventKind::Cleared => println!(
+                "cleared\t{}\t{}",
+                event.hashtable_type,
+                if event.custom_only { "custom" } else { "all" }
+            ),
+            TableEventKind::Restored => println!("restored\t{}", event.snapshot),
+            TableEventKind::Lagged => println!("lagged\t{}", event.missed),
+            TableEventKind::Unspecified => {}
+        }
+    }
+    Ok(())
+}
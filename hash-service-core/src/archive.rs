@@ -0,0 +1,111 @@
+//! Hashtable files inside the archives community hash bundles are shared as: `.zip`,
+//! `.tar.gz` and `.zst`. Members are decompressed while they are parsed, never extracted.
+use std::io::{Read, Seek};
+
+use flate2::read::GzDecoder;
+
+use crate::engine::HashtableType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    TarGz,
+    /// A single compressed file rather than an archive of several
+    Zstd,
+}
+
+impl ArchiveKind {
+    /// Archive a file is by its extension, `None` for a plain text file
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let file_name = file_name.to_ascii_lowercase();
+        if file_name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else if file_name.ends_with(".zst") {
+            Some(ArchiveKind::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Members of an archive a table is read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveMembers {
+    /// Every file in it, for an archive of a single table
+    All,
+    /// Files named like the synced ones of this table, e.g. `hashes.game.txt.0`
+    Of(HashtableType),
+}
+
+impl ArchiveMembers {
+    fn contains(&self, member_name: &str) -> bool {
+        let file_name = member_name
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(member_name);
+        if file_name.ends_with(".sha") {
+            return false;
+        }
+        match self {
+            ArchiveMembers::All => true,
+            ArchiveMembers::Of(hashtable_type) => {
+                HashtableType::from_file_name(file_name) == Some(*hashtable_type)
+            }
+        }
+    }
+}
+
+/// How one table is read out of an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableArchive {
+    pub kind: ArchiveKind,
+    pub members: ArchiveMembers,
+}
+
+/// Calls `read` with every text stream of the hashtable file `file_name`: `file` itself when
+/// `archive` is `None`, else each of its members the table is read from, decompressed
+pub(crate) fn read_streams<R: Read + Seek>(
+    file_name: &str,
+    mut file: R,
+    archive: Option<TableArchive>,
+    mut read: impl FnMut(&mut dyn Read) -> Result<(), String>,
+) -> Result<(), String> {
+    let Some(archive) = archive else {
+        return read(&mut file);
+    };
+    let archive_error = |e: &dyn std::fmt::Display| format!("Failed to read {}: {}", file_name, e);
+    match archive.kind {
+        ArchiveKind::Zstd => {
+            let mut decoder = zstd::Decoder::new(file).map_err(|e| archive_error(&e))?;
+            read(&mut decoder)
+        }
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(file).map_err(|e| archive_error(&e))?;
+            for index in 0..zip.len() {
+                let mut member = zip.by_index(index).map_err(|e| archive_error(&e))?;
+                let member_name = member.name().map_err(|e| archive_error(&e))?;
+                if member.is_file() && archive.members.contains(&member_name) {
+                    read(&mut member)?;
+                }
+            }
+            Ok(())
+        }
+        ArchiveKind::TarGz => {
+            let mut tar = tar::Archive::new(GzDecoder::new(file));
+            for member in tar.entries().map_err(|e| archive_error(&e))? {
+                let mut member = member.map_err(|e| archive_error(&e))?;
+                let member_name = member
+                    .path()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                if member.header().entry_type().is_file() && archive.members.contains(&member_name)
+                {
+                    read(&mut member)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
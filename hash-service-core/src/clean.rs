@@ -0,0 +1,190 @@
+//! Cache garbage collection: files a crash, an older build or a since removed product left
+//! behind in the cache. Removing them never loses anything a sync cannot download again;
+//! snapshots are always kept.
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+use crate::diff::DIFF_DIR_NAME;
+use crate::disk::{self, LOOKUP_DIR_NAME};
+use crate::engine::{HashtableType, list_table_files};
+use crate::lock::{CACHE_LOCK_FILE_NAME, CacheLock};
+use crate::snapshot::SNAPSHOT_DIR_NAME;
+use crate::store::{DB_FILE_NAME, StoreFile, StoreKind};
+use crate::sync::{HashSource, NEW_FILE_PREFIX, SOURCE_FILES_NAME};
+
+/// What a clean removed, or with `dry_run` would remove
+#[derive(Debug, Clone, Default)]
+pub struct CleanReport {
+    pub removed: Vec<PathBuf>,
+    pub bytes_freed: u64,
+}
+
+impl CleanReport {
+    pub fn merge(&mut self, other: CleanReport) {
+        self.removed.extend(other.removed);
+        self.bytes_freed += other.bytes_freed;
+    }
+
+    /// Removes the file or directory at `path` and counts its size. Failures are logged and
+    /// skipped, e.g. a file still open by another process on Windows.
+    fn remove(&mut self, path: &Path, dry_run: bool) {
+        let bytes: u64 = WalkDir::new(path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| entry.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        if !dry_run {
+            let result = if path.is_dir() {
+                std::fs::remove_dir_all(path)
+            } else {
+                std::fs::remove_file(path)
+            };
+            if let Err(e) = result {
+                warn!(?path, error = %e, "Failed to remove cache file");
+                return;
+            }
+            info!(?path, bytes, "Removed cache file");
+        }
+        self.removed.push(path.to_path_buf());
+        self.bytes_freed += bytes;
+    }
+}
+
+fn file_names(dir: &Path) -> Vec<(PathBuf, String)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            (entry.path(), file_name)
+        })
+        .collect()
+}
+
+/// Leftovers in the cache dir `dir`: `.sha` files without their table file, downloads a
+/// crash never moved into place and copies of the tables `store` does not use, or no longer
+/// uses with the current files, which `store_files` lists per table. The caller holds the
+/// cache lock.
+pub(crate) fn clean_dir(
+    dir: &Path,
+    store: Option<StoreKind>,
+    store_files: impl Fn(HashtableType) -> Vec<StoreFile>,
+    dry_run: bool,
+) -> CleanReport {
+    let mut report = CleanReport::default();
+    for (path, file_name) in file_names(dir) {
+        let orphaned_sha = file_name
+            .strip_suffix(".sha")
+            .is_some_and(|table_file| !dir.join(table_file).exists());
+        let download = file_name.starts_with(&format!("{}.", NEW_FILE_PREFIX));
+        if orphaned_sha || download {
+            report.remove(&path, dry_run);
+        }
+    }
+
+    let lookup_dir = dir.join(LOOKUP_DIR_NAME);
+    if store == Some(StoreKind::Disk) {
+        for hashtable_type in HashtableType::ALL {
+            let current = disk::current_generation(&store_files(hashtable_type));
+            for path in disk::stale_files(dir, hashtable_type, &current) {
+                report.remove(&path, dry_run);
+            }
+        }
+    } else if lookup_dir.exists() {
+        report.remove(&lookup_dir, dry_run);
+    }
+
+    let db_path = dir.join(DB_FILE_NAME);
+    if store != Some(StoreKind::Database) && db_path.exists() {
+        report.remove(&db_path, dry_run);
+    }
+    report
+}
+
+/// Whether `dir` holds a synced cache of its own
+fn is_cache_dir(dir: &Path) -> bool {
+    dir.join(SOURCE_FILES_NAME).exists()
+        || !list_table_files(dir, &HashtableType::ALL).is_empty()
+        || file_names(dir)
+            .iter()
+            .any(|(_, file_name)| file_name.ends_with(".sha"))
+}
+
+fn subdirs(dir: &Path) -> Vec<(PathBuf, String)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            (entry.path(), name)
+        })
+        .filter(|(_, name)| {
+            ![DIFF_DIR_NAME, SNAPSHOT_DIR_NAME, LOOKUP_DIR_NAME].contains(&name.as_str())
+        })
+        .collect()
+}
+
+/// Caches under `base` of products or branches `sources` no longer configure, following the
+/// layout of [`HashSource::cache_dir`]
+pub fn unconfigured_caches(base: &Path, sources: &[HashSource]) -> Vec<PathBuf> {
+    let configured: HashSet<PathBuf> = sources
+        .iter()
+        .map(|source| source.cache_dir(base))
+        .collect();
+    let mut candidates = vec![base.to_path_buf()];
+    for (path, name) in subdirs(base) {
+        if name.starts_with('@') {
+            candidates.push(path);
+        } else {
+            candidates.extend(
+                subdirs(&path)
+                    .into_iter()
+                    .filter(|(_, branch)| branch.starts_with('@'))
+                    .map(|(path, _)| path),
+            );
+            candidates.push(path);
+        }
+    }
+    candidates
+        .into_iter()
+        .filter(|dir| !configured.contains(dir) && is_cache_dir(dir))
+        .collect()
+}
+
+/// Removes the synced tables and everything derived from them from `dir`, keeping
+/// snapshots and the caches of other products or branches nested inside it. The dir itself
+/// goes too once nothing else is left in it.
+pub async fn clear_cache_dir(dir: &Path, dry_run: bool) -> Result<CleanReport, String> {
+    let mut report = CleanReport::default();
+    {
+        let _cache_lock = CacheLock::exclusive(dir).await?;
+        for (path, file_name) in file_names(dir) {
+            if file_name != CACHE_LOCK_FILE_NAME {
+                report.remove(&path, dry_run);
+            }
+        }
+        for name in [DIFF_DIR_NAME, LOOKUP_DIR_NAME] {
+            let path = dir.join(name);
+            if path.exists() {
+                report.remove(&path, dry_run);
+            }
+        }
+    }
+    if !dry_run {
+        // Only once the lock is released, as an open file cannot be removed on Windows
+        let _ = std::fs::remove_file(dir.join(CACHE_LOCK_FILE_NAME));
+        let _ = std::fs::remove_dir(dir);
+    }
+    Ok(report)
+}
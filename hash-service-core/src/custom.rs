@@ -0,0 +1,66 @@
+//! Tables clients create at runtime for hash namespaces beyond the game and bin tables,
+//! e.g. other Riot formats or other games. They only live in memory.
+use crate::hash::{bin_hash, elf_hash, game_hash, xxh3_hash};
+use crate::table::Hashtable;
+
+/// Longest custom table name
+pub const MAX_TABLE_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// XXH64, like the game table
+    Xxh64,
+    /// XXH3 (64-bit)
+    Xxh3,
+    /// 32-bit FNV-1a, like the bin table
+    Fnv1a,
+    /// 32-bit ELF hash
+    Elf,
+}
+
+impl HashAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Xxh64 => "xxh64",
+            HashAlgorithm::Xxh3 => "xxh3",
+            HashAlgorithm::Fnv1a => "fnv1a",
+            HashAlgorithm::Elf => "elf",
+        }
+    }
+
+    /// Hashes the lowercased value, like the game does for every algorithm
+    pub fn hash(&self, value: &str) -> u64 {
+        match self {
+            HashAlgorithm::Xxh64 => game_hash(value),
+            HashAlgorithm::Xxh3 => xxh3_hash(value),
+            HashAlgorithm::Fnv1a => bin_hash(value) as u64,
+            HashAlgorithm::Elf => elf_hash(value) as u64,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CustomTable {
+    pub algorithm: HashAlgorithm,
+    pub table: Hashtable,
+}
+
+/// Names are lowercase `[a-z0-9._-]` and can't shadow the built-in tables
+pub fn validate_table_name(name: &str) -> Result<(), String> {
+    if matches!(name, "game" | "bin" | "any") {
+        return Err(format!("'{}' is reserved for a built-in table", name));
+    }
+    let valid = !name.is_empty()
+        && name.len() <= MAX_TABLE_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid table name '{}', use up to {} lowercase letters, digits, '.', '_' and '-'",
+            name, MAX_TABLE_NAME_LEN
+        ))
+    }
+}
@@ -0,0 +1,214 @@
+//! On-disk copy of the synced game and bin tables in an embedded redb database, so point
+//! lookups work without holding the tables in memory. Only redb's page cache stays resident.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use redb::{Database, ReadableDatabase, ReadableTableMetadata, TableDefinition};
+use tracing::info;
+
+use crate::archive::read_streams;
+use crate::engine::HashtableType;
+use crate::parse::read_entries;
+use crate::store::{LookupStore, StoreFile, StoreKind, built_from, generation};
+
+pub use crate::store::DB_FILE_NAME;
+/// Page cache used when none is configured
+pub const DEFAULT_DB_CACHE_BYTES: usize = 32 * 1024 * 1024;
+/// Bumped with every change to how tables are stored, so tables of older builds are rebuilt
+pub const FORMAT_VERSION: u32 = 1;
+
+const GAME: TableDefinition<u64, &str> = TableDefinition::new("game");
+const BIN: TableDefinition<u64, &str> = TableDefinition::new("bin");
+/// Per table, the generation and the file names and blob SHAs it was last built from
+const BUILT_FROM: TableDefinition<&str, &str> = TableDefinition::new("built_from");
+
+fn definition(hashtable_type: HashtableType) -> TableDefinition<'static, u64, &'static str> {
+    match hashtable_type {
+        HashtableType::Game => GAME,
+        HashtableType::Bin => BIN,
+    }
+}
+
+#[derive(Debug)]
+pub struct HashDb {
+    db: Database,
+}
+
+impl HashDb {
+    /// Opens (or creates) the database in `dir` with a page cache of `cache_bytes`
+    pub fn open(dir: &Path, cache_bytes: usize) -> Result<Self, String> {
+        let path = dir.join(DB_FILE_NAME);
+        let db = Database::builder()
+            .set_cache_size(cache_bytes)
+            .create(&path)
+            .map_err(|e| format!("Failed to open database {:?}: {}", path, e))?;
+        Ok(HashDb { db })
+    }
+
+    /// Rebuilds the table from `files` unless it was last built from the same file versions.
+    /// Returns whether it was rebuilt. Duplicate hashes keep the last value, like a load with
+    /// the default conflict policy.
+    pub fn refresh(
+        &self,
+        hashtable_type: HashtableType,
+        files: &[StoreFile],
+    ) -> Result<bool, String> {
+        let versions = built_from(files);
+        let versions = format!("{};{}", generation(&versions, FORMAT_VERSION), versions);
+        if self.built_from(hashtable_type)?.as_deref() == Some(versions.as_str()) {
+            return Ok(false);
+        }
+
+        info!(
+            hashtable_type = hashtable_type.as_str(),
+            files = files.len(),
+            "Rebuilding database table"
+        );
+        let txn = self.db.begin_write().map_err(db_error)?;
+        {
+            txn.delete_table(definition(hashtable_type))
+                .map_err(db_error)?;
+            let mut table = txn
+                .open_table(definition(hashtable_type))
+                .map_err(db_error)?;
+            for file in files {
+                let reader = File::open(&file.path)
+                    .map_err(|e| format!("Failed to open {:?}: {}", file.path, e))?;
+                read_streams(&file.file_name, reader, file.archive, |stream| {
+                    for entry in read_entries(BufReader::new(stream)) {
+                        let (hash, value) = entry?;
+                        table.insert(hash, value.as_str()).map_err(db_error)?;
+                    }
+                    Ok(())
+                })?;
+            }
+            let mut built_from = txn.open_table(BUILT_FROM).map_err(db_error)?;
+            built_from
+                .insert(hashtable_type.as_str(), versions.as_str())
+                .map_err(db_error)?;
+        }
+        txn.commit().map_err(db_error)?;
+        Ok(true)
+    }
+
+    fn built_from(&self, hashtable_type: HashtableType) -> Result<Option<String>, String> {
+        let txn = self.db.begin_read().map_err(db_error)?;
+        let table = match txn.open_table(BUILT_FROM) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(db_error(e)),
+        };
+        Ok(table
+            .get(hashtable_type.as_str())
+            .map_err(db_error)?
+            .map(|value| value.value().to_string()))
+    }
+
+    /// Values of `hashes`, in order, all read in one transaction
+    pub fn get_many(
+        &self,
+        hashtable_type: HashtableType,
+        hashes: &[u64],
+    ) -> Result<Vec<Option<String>>, String> {
+        let txn = self.db.begin_read().map_err(db_error)?;
+        let table = match txn.open_table(definition(hashtable_type)) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(vec![None; hashes.len()]),
+            Err(e) => return Err(db_error(e)),
+        };
+        hashes
+            .iter()
+            .map(|&hash| {
+                Ok(table
+                    .get(hash)
+                    .map_err(db_error)?
+                    .map(|value| value.value().to_string()))
+            })
+            .collect()
+    }
+
+    /// Entries in the table
+    pub fn len(&self, hashtable_type: HashtableType) -> Result<u64, String> {
+        let txn = self.db.begin_read().map_err(db_error)?;
+        match txn.open_table(definition(hashtable_type)) {
+            Ok(table) => table.len().map_err(db_error),
+            Err(redb::TableError::TableDoesNotExist(_)) => Ok(0),
+            Err(e) => Err(db_error(e)),
+        }
+    }
+}
+
+fn db_error(e: impl std::fmt::Display) -> String {
+    format!("Database error: {}", e)
+}
+
+/// Opens the database on the first refresh, once the cache dir exists
+#[derive(Debug)]
+pub(crate) struct DbStore {
+    cache_bytes: usize,
+    db: RwLock<Option<Arc<HashDb>>>,
+}
+
+impl DbStore {
+    fn db(&self) -> Result<Option<Arc<HashDb>>, String> {
+        self.db
+            .read()
+            .map(|db| db.clone())
+            .map_err(|_| "Failed to lock database".to_string())
+    }
+}
+
+impl DbStore {
+    pub(crate) fn new(cache_bytes: usize) -> Self {
+        DbStore {
+            cache_bytes,
+            db: RwLock::new(None),
+        }
+    }
+}
+
+impl LookupStore for DbStore {
+    fn kind(&self) -> StoreKind {
+        StoreKind::Database
+    }
+    fn refresh(
+        &self,
+        dir: &Path,
+        hashtable_type: HashtableType,
+        files: &[StoreFile],
+    ) -> Result<bool, String> {
+        let db = match self.db()? {
+            Some(db) => db,
+            None => {
+                let db = Arc::new(HashDb::open(dir, self.cache_bytes)?);
+                *self
+                    .db
+                    .write()
+                    .map_err(|_| "Failed to lock database".to_string())? = Some(db.clone());
+                db
+            }
+        };
+        db.refresh(hashtable_type, files)
+    }
+
+    fn close(&self) -> Result<(), String> {
+        *self
+            .db
+            .write()
+            .map_err(|_| "Failed to lock database".to_string())? = None;
+        Ok(())
+    }
+
+    fn get_many(
+        &self,
+        hashtable_type: HashtableType,
+        hashes: &[u64],
+    ) -> Result<Vec<Option<String>>, String> {
+        match self.db()? {
+            Some(db) => db.get_many(hashtable_type, hashes),
+            None => Ok(vec![None; hashes.len()]),
+        }
+    }
+}
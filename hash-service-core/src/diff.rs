@@ -0,0 +1,224 @@
+//! What a sync changed in a table: entries added, removed or renamed (same hash, new value).
+//! The last diff of each table is kept in the `diffs` dir of the cache.
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh64::xxh64;
+
+use crate::engine::HashtableType;
+use crate::parse::read_entries;
+
+/// Subdirectory of the cache dir holding diffs; skipped when loading hashtables
+pub const DIFF_DIR_NAME: &str = "diffs";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TableDiff {
+    /// Unix seconds of the sync that produced the diff
+    pub synced_at: u64,
+    pub added: Vec<(u64, String)>,
+    pub removed: Vec<(u64, String)>,
+    /// `(hash, old value, new value)`
+    pub renamed: Vec<(u64, String, String)>,
+}
+
+impl TableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.renamed.is_empty()
+    }
+}
+
+fn read_file(path: &Path, entries: &mut impl FnMut(u64, String)) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    for entry in read_entries(BufReader::new(file)) {
+        let (hash, value) = entry?;
+        entries(hash, value);
+    }
+    Ok(())
+}
+
+/// Hashes of the entries in `paths` with a fingerprint of their values, sorted by hash. A hash
+/// listed more than once keeps its last value, as a load under `KeepLast` does. Missing files
+/// count as empty.
+fn fingerprints(paths: &[PathBuf]) -> Result<Vec<(u64, u64)>, String> {
+    let mut entries = Vec::new();
+    for path in paths.iter().filter(|path| path.exists()) {
+        read_file(path, &mut |hash, value| {
+            entries.push((hash, xxh64(value.as_bytes(), 0)));
+        })?;
+    }
+    // Stable, so duplicates stay in file order and the last one is kept
+    entries.sort_by_key(|&(hash, _)| hash);
+    entries.dedup_by(|later, kept| {
+        let same = later.0 == kept.0;
+        if same {
+            *kept = *later;
+        }
+        same
+    });
+    Ok(entries)
+}
+
+/// Last values in `paths` of the entries in `hashes`
+fn values_of(paths: &[PathBuf], hashes: &HashSet<u64>) -> Result<HashMap<u64, String>, String> {
+    let mut values = HashMap::new();
+    if hashes.is_empty() {
+        return Ok(values);
+    }
+    for path in paths.iter().filter(|path| path.exists()) {
+        read_file(path, &mut |hash, value| {
+            if hashes.contains(&hash) {
+                values.insert(hash, value);
+            }
+        })?;
+    }
+    Ok(values)
+}
+
+/// Diffs the old files of a table against its new ones. Both sides are the whole table, so an
+/// entry that moved between files is no change. Rather than holding either side's values,
+/// both are reduced to sorted hashes and value fingerprints, 16 bytes an entry, and walked
+/// side by side; only the values of the entries that changed are read back from the files.
+/// Missing old files count as empty.
+pub fn diff_files(old: &[PathBuf], new: &[PathBuf]) -> Result<TableDiff, String> {
+    let (added, removed, renamed) = {
+        let old_entries = fingerprints(old)?;
+        let new_entries = fingerprints(new)?;
+        let mut added = HashSet::new();
+        let mut removed = HashSet::new();
+        let mut renamed = HashSet::new();
+        let (mut old_iter, mut new_iter) =
+            (old_entries.iter().peekable(), new_entries.iter().peekable());
+        loop {
+            match (old_iter.peek(), new_iter.peek()) {
+                (None, None) => break,
+                (Some(&&(hash, _)), None) => {
+                    removed.insert(hash);
+                    old_iter.next();
+                }
+                (None, Some(&&(hash, _))) => {
+                    added.insert(hash);
+                    new_iter.next();
+                }
+                (Some(&&(old_hash, old_value)), Some(&&(new_hash, new_value))) => {
+                    if old_hash < new_hash {
+                        removed.insert(old_hash);
+                        old_iter.next();
+                    } else if new_hash < old_hash {
+                        added.insert(new_hash);
+                        new_iter.next();
+                    } else {
+                        if old_value != new_value {
+                            renamed.insert(old_hash);
+                        }
+                        old_iter.next();
+                        new_iter.next();
+                    }
+                }
+            }
+        }
+        (added, removed, renamed)
+    };
+
+    let mut old_values = values_of(old, &removed.union(&renamed).copied().collect())?;
+    let mut new_values = values_of(new, &added.union(&renamed).copied().collect())?;
+    let take = |values: &mut HashMap<u64, String>, hash| values.remove(&hash).unwrap_or_default();
+    let mut diff = TableDiff {
+        synced_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs()),
+        added: added
+            .into_iter()
+            .map(|hash| (hash, take(&mut new_values, hash)))
+            .collect(),
+        removed: removed
+            .into_iter()
+            .map(|hash| (hash, take(&mut old_values, hash)))
+            .collect(),
+        renamed: renamed
+            .into_iter()
+            .map(|hash| {
+                (
+                    hash,
+                    take(&mut old_values, hash),
+                    take(&mut new_values, hash),
+                )
+            })
+            .collect(),
+    };
+
+    diff.added.sort_unstable_by(|a, b| a.1.cmp(&b.1));
+    diff.removed.sort_unstable_by(|a, b| a.1.cmp(&b.1));
+    diff.renamed.sort_unstable_by(|a, b| a.2.cmp(&b.2));
+    Ok(diff)
+}
+
+fn diff_path(cache_dir: &Path, hashtable_type: HashtableType) -> PathBuf {
+    cache_dir
+        .join(DIFF_DIR_NAME)
+        .join(format!("{}.json", hashtable_type.as_str()))
+}
+
+pub fn save_diff(
+    cache_dir: &Path,
+    hashtable_type: HashtableType,
+    diff: &TableDiff,
+) -> Result<(), String> {
+    let path = diff_path(cache_dir, hashtable_type);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    let text =
+        serde_json::to_string(diff).map_err(|e| format!("Failed to serialize diff: {}", e))?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// The diff of the last sync that changed the table, if any
+pub fn load_diff(
+    cache_dir: &Path,
+    hashtable_type: HashtableType,
+) -> Result<Option<TableDiff>, String> {
+    let path = diff_path(cache_dir, hashtable_type);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse {:?}: {}", path, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {:?}: {}", path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_both_sides_by_their_last_values() {
+        let dir =
+            std::env::temp_dir().join(format!("hash-service-diff-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, text: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, text).unwrap();
+            path
+        };
+        let old = [
+            write("old.0", "1 a\n2 b\n3 c\n"),
+            // Listed twice, so `B` is what the table held
+            write("old.1", "2 B\n"),
+            dir.join("missing"),
+        ];
+        let new = [write("new.0", "1 a\n2 B\n4 d\n"), write("new.1", "4 D\n")];
+
+        let diff = diff_files(&old, &new);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let diff = diff.unwrap();
+        assert_eq!(diff.added, [(4, "D".to_string())]);
+        assert_eq!(diff.removed, [(3, "c".to_string())]);
+        assert!(diff.renamed.is_empty());
+    }
+}
@@ -0,0 +1,469 @@
+//! Sorted lookup files that answer lookups with a seek and a read, without ever building the
+//! tables in memory. One file per table and generation, `lookup/<table>.<generation>.lkp` in
+//! the cache dir, where the generation follows the source file SHAs and [`VERSION`]:
+//!
+//! ```text
+//! "HLKP" | version u32 | built from length u32 | built from UTF-8 | count u64
+//! then count records sorted by hash: hash u64 | value offset u64 | value length u32
+//! then the values, UTF-8, offsets relative to the end of the records
+//! ```
+//!
+//! Integers are little-endian. Every [`FENCE_INTERVAL`]th hash is kept in memory, so a lookup
+//! reads one block of records and then the value.
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+use tracing::{debug, info};
+
+use crate::archive::read_streams;
+use crate::engine::HashtableType;
+use crate::parse::read_entries;
+use crate::store::{LookupStore, StoreFile, StoreKind, built_from, generation};
+
+/// Subdirectory of the cache dir holding the lookup files; skipped when loading hashtables
+pub const LOOKUP_DIR_NAME: &str = "lookup";
+const LOOKUP_EXTENSION: &str = "lkp";
+const MAGIC: &[u8; 4] = b"HLKP";
+/// Bumped with every change to the format, so files of older builds are rebuilt
+pub const VERSION: u32 = 1;
+const RECORD_SIZE: usize = 8 + 8 + 4;
+/// Records per block; a lookup reads one block of this many records
+const FENCE_INTERVAL: usize = 256;
+
+fn lookup_path(dir: &Path, hashtable_type: HashtableType, generation: &str) -> PathBuf {
+    dir.join(LOOKUP_DIR_NAME).join(format!(
+        "{}.{}.{}",
+        hashtable_type.as_str(),
+        generation,
+        LOOKUP_EXTENSION
+    ))
+}
+
+/// The table's lookup files and build leftovers of every generation but `current`
+pub fn stale_files(dir: &Path, hashtable_type: HashtableType, current: &str) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir.join(LOOKUP_DIR_NAME)) else {
+        return Vec::new();
+    };
+    let table_prefix = format!("{}.", hashtable_type.as_str());
+    let current_prefix = format!("{}{}.", table_prefix, current);
+    entries
+        .flatten()
+        .filter(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            file_name.starts_with(&table_prefix) && !file_name.starts_with(&current_prefix)
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// The generation of lookup file `files` are built into
+pub fn current_generation(files: &[StoreFile]) -> String {
+    generation(&built_from(files), VERSION)
+}
+
+/// Deletes the table's lookup files of every generation but `current`. Files still open
+/// elsewhere, e.g. by another process on Windows, are left for next time.
+fn remove_stale_generations(dir: &Path, hashtable_type: HashtableType, current: &str) {
+    for path in stale_files(dir, hashtable_type, current) {
+        match std::fs::remove_file(&path) {
+            Ok(()) => info!(?path, "Removed stale lookup file"),
+            Err(e) => debug!(?path, error = %e, "Stale lookup file not removed yet"),
+        }
+    }
+}
+
+/// An open lookup file
+#[derive(Debug)]
+pub struct LookupFile {
+    file: Mutex<File>,
+    built_from: String,
+    count: u64,
+    records_offset: u64,
+    /// First hash of every block
+    fences: Vec<u64>,
+}
+
+impl LookupFile {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let read_error = |e: std::io::Error| format!("Failed to read {:?}: {}", path, e);
+        let truncated = || format!("Lookup file {:?} is truncated", path);
+        let mut file = File::open(path).map_err(read_error)?;
+        let file_len = file.metadata().map_err(read_error)?.len();
+
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic).map_err(read_error)?;
+        if &magic != MAGIC {
+            return Err(format!("{:?} is not a lookup file", path));
+        }
+        let version = read_u32(&mut file).map_err(read_error)?;
+        if version != VERSION {
+            return Err(format!(
+                "{:?} has unsupported lookup file version {}",
+                path, version
+            ));
+        }
+        let built_from_len = read_u32(&mut file).map_err(read_error)? as usize;
+        if built_from_len as u64 > file_len {
+            return Err(truncated());
+        }
+        let mut built_from = vec![0; built_from_len];
+        file.read_exact(&mut built_from).map_err(read_error)?;
+        let built_from = String::from_utf8(built_from)
+            .map_err(|e| format!("Invalid lookup file {:?}: {}", path, e))?;
+        let count = read_u64(&mut file).map_err(read_error)?;
+        let records_offset = file.stream_position().map_err(read_error)?;
+        // Checked before anything is sized by `count`, so a damaged file fails here
+        if count
+            .checked_mul(RECORD_SIZE as u64)
+            .and_then(|len| len.checked_add(records_offset))
+            .is_none_or(|end| end > file_len)
+        {
+            return Err(truncated());
+        }
+
+        let mut fences = Vec::with_capacity((count as usize).div_ceil(FENCE_INTERVAL));
+        for block in (0..count).step_by(FENCE_INTERVAL) {
+            file.seek(SeekFrom::Start(records_offset + block * RECORD_SIZE as u64))
+                .map_err(read_error)?;
+            fences.push(read_u64(&mut file).map_err(read_error)?);
+        }
+
+        Ok(LookupFile {
+            file: Mutex::new(file),
+            built_from,
+            count,
+            records_offset,
+            fences,
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn get(&self, hash: u64) -> Result<Option<String>, String> {
+        let block = match self.fences.partition_point(|&fence| fence <= hash) {
+            0 => return Ok(None),
+            after => after - 1,
+        };
+        let first = (block * FENCE_INTERVAL) as u64;
+        let records = (self.count - first).min(FENCE_INTERVAL as u64) as usize;
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| "Failed to lock lookup file".to_string())?;
+        let read_error = |e: std::io::Error| format!("Failed to read lookup file: {}", e);
+        let mut buffer = vec![0; records * RECORD_SIZE];
+        file.seek(SeekFrom::Start(
+            self.records_offset + first * RECORD_SIZE as u64,
+        ))
+        .map_err(read_error)?;
+        file.read_exact(&mut buffer).map_err(read_error)?;
+
+        let record = |index: usize| {
+            let bytes = &buffer[index * RECORD_SIZE..(index + 1) * RECORD_SIZE];
+            (
+                u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+                u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+                u32::from_le_bytes(bytes[16..20].try_into().unwrap()),
+            )
+        };
+        let (mut low, mut high) = (0, records);
+        while low < high {
+            let middle = (low + high) / 2;
+            let (middle_hash, offset, len) = record(middle);
+            if middle_hash == hash {
+                let values_offset = self.records_offset + self.count * RECORD_SIZE as u64;
+                let mut value = vec![0; len as usize];
+                file.seek(SeekFrom::Start(values_offset + offset))
+                    .map_err(read_error)?;
+                file.read_exact(&mut value).map_err(read_error)?;
+                return String::from_utf8(value)
+                    .map(Some)
+                    .map_err(|e| format!("Invalid value in lookup file: {}", e));
+            } else if middle_hash < hash {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Writes a lookup file for `files` at `path`. Only the hashes and value positions are held
+/// in memory while building; duplicate hashes keep the last value.
+pub fn build(path: &Path, files: &[StoreFile]) -> Result<(), String> {
+    let write_error = |e: std::io::Error| format!("Failed to write {:?}: {}", path, e);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(write_error)?;
+    }
+
+    // Values go straight to a scratch file in the order they are read
+    let values_path = path.with_extension("values.tmp");
+    let mut records: Vec<(u64, u64, u32)> = Vec::new();
+    {
+        let mut values = BufWriter::new(File::create(&values_path).map_err(write_error)?);
+        let mut offset = 0;
+        for file in files {
+            let reader = File::open(&file.path)
+                .map_err(|e| format!("Failed to open {:?}: {}", file.path, e))?;
+            read_streams(&file.file_name, reader, file.archive, |stream| {
+                for entry in read_entries(BufReader::new(stream)) {
+                    let (hash, value) = entry?;
+                    values.write_all(value.as_bytes()).map_err(write_error)?;
+                    records.push((hash, offset, value.len() as u32));
+                    offset += value.len() as u64;
+                }
+                Ok(())
+            })?;
+        }
+        values.flush().map_err(write_error)?;
+    }
+    // Stable, so of equal hashes the one read last stays last and survives the dedup
+    records.sort_by_key(|&(hash, _, _)| hash);
+    records.reverse();
+    records.dedup_by_key(|&mut (hash, _, _)| hash);
+    records.reverse();
+
+    let built_from = built_from(files);
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut writer = BufWriter::new(File::create(&tmp_path).map_err(write_error)?);
+        writer.write_all(MAGIC).map_err(write_error)?;
+        writer
+            .write_all(&VERSION.to_le_bytes())
+            .map_err(write_error)?;
+        writer
+            .write_all(&(built_from.len() as u32).to_le_bytes())
+            .map_err(write_error)?;
+        writer
+            .write_all(built_from.as_bytes())
+            .map_err(write_error)?;
+        writer
+            .write_all(&(records.len() as u64).to_le_bytes())
+            .map_err(write_error)?;
+        for (hash, offset, len) in &records {
+            writer.write_all(&hash.to_le_bytes()).map_err(write_error)?;
+            writer
+                .write_all(&offset.to_le_bytes())
+                .map_err(write_error)?;
+            writer.write_all(&len.to_le_bytes()).map_err(write_error)?;
+        }
+        let mut values = File::open(&values_path).map_err(write_error)?;
+        std::io::copy(&mut values, &mut writer).map_err(write_error)?;
+        writer.flush().map_err(write_error)?;
+    }
+    let _ = std::fs::remove_file(&values_path);
+    // Write then rename so a crash never leaves a partial file to be opened
+    std::fs::rename(&tmp_path, path).map_err(write_error)
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Lookup files of the game and bin tables, opened on the first refresh
+#[derive(Debug, Default)]
+pub(crate) struct DiskStore {
+    game: RwLock<Option<LookupFile>>,
+    bin: RwLock<Option<LookupFile>>,
+}
+
+impl DiskStore {
+    fn table(&self, hashtable_type: HashtableType) -> &RwLock<Option<LookupFile>> {
+        match hashtable_type {
+            HashtableType::Game => &self.game,
+            HashtableType::Bin => &self.bin,
+        }
+    }
+}
+
+impl LookupStore for DiskStore {
+    fn kind(&self) -> StoreKind {
+        StoreKind::Disk
+    }
+    fn refresh(
+        &self,
+        dir: &Path,
+        hashtable_type: HashtableType,
+        files: &[StoreFile],
+    ) -> Result<bool, String> {
+        let built_from = built_from(files);
+        let generation = current_generation(files);
+        let path = lookup_path(dir, hashtable_type, &generation);
+        let current = LookupFile::open(&path)
+            .ok()
+            .filter(|file| file.built_from == built_from);
+        let (file, rebuilt) = match current {
+            Some(file) => (file, false),
+            None => {
+                info!(
+                    hashtable_type = hashtable_type.as_str(),
+                    files = files.len(),
+                    %generation,
+                    "Building lookup file"
+                );
+                build(&path, files)?;
+                (LookupFile::open(&path)?, true)
+            }
+        };
+        // Dropping the previous generation closes it, so it can be deleted on Windows too
+        *self
+            .table(hashtable_type)
+            .write()
+            .map_err(|_| "Failed to lock lookup file".to_string())? = Some(file);
+        remove_stale_generations(dir, hashtable_type, &generation);
+        Ok(rebuilt)
+    }
+
+    fn close(&self) -> Result<(), String> {
+        for table in [&self.game, &self.bin] {
+            *table
+                .write()
+                .map_err(|_| "Failed to lock lookup file".to_string())? = None;
+        }
+        Ok(())
+    }
+
+    fn get_many(
+        &self,
+        hashtable_type: HashtableType,
+        hashes: &[u64],
+    ) -> Result<Vec<Option<String>>, String> {
+        let table = self
+            .table(hashtable_type)
+            .read()
+            .map_err(|_| "Failed to lock lookup file".to_string())?;
+        match table.as_ref() {
+            Some(file) => hashes.iter().map(|&hash| file.get(hash)).collect(),
+            None => Ok(vec![None; hashes.len()]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a lookup file from `sources`, each written as a table file, in a fresh dir
+    fn build_from(name: &str, sources: &[String]) -> (PathBuf, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("hash-service-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files: Vec<StoreFile> = sources
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let file_name = format!("hashes.game.txt.{}", index);
+                std::fs::write(dir.join(&file_name), text).unwrap();
+                StoreFile::new(dir.join(&file_name), file_name)
+            })
+            .collect();
+        let path = dir.join("game.lkp");
+        build(&path, &files).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn looks_up_every_built_entry() {
+        // Three blocks, the last one partial, with the hashes out of order in the file
+        let text: String = (0..600u64)
+            .rev()
+            .map(|index| format!("{:x} value {}\n", index * 2, index))
+            .collect();
+        let (dir, path) = build_from("lookup-build", &[text]);
+        let file = LookupFile::open(&path).unwrap();
+        let found: Vec<_> = (0..600u64)
+            .map(|index| file.get(index * 2).unwrap())
+            .collect();
+        let missing = [1, 511, 513, 1199, 5000].map(|hash| file.get(hash).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(file.len(), 600);
+        for (index, value) in found.into_iter().enumerate() {
+            assert_eq!(value, Some(format!("value {}", index)));
+        }
+        assert!(missing.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn lookups_on_block_boundaries() {
+        let text: String = (0..(FENCE_INTERVAL as u64 * 2 + 1))
+            .map(|hash| format!("{:x} {}\n", hash + 1, hash + 1))
+            .collect();
+        let (dir, path) = build_from("lookup-fences", &[text]);
+        let file = LookupFile::open(&path).unwrap();
+        let interval = FENCE_INTERVAL as u64;
+        // The first and last hash of each block, and the ones either side of the file
+        let hashes = [1, interval, interval + 1, interval * 2, interval * 2 + 1];
+        let found = hashes.map(|hash| file.get(hash).unwrap());
+        let outside = [0, interval * 2 + 2].map(|hash| file.get(hash).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(file.fences, [1, interval + 1, interval * 2 + 1]);
+        assert_eq!(found, hashes.map(|hash| Some(hash.to_string())));
+        assert_eq!(outside, [None, None]);
+    }
+
+    #[test]
+    fn duplicate_hashes_keep_the_last_value() {
+        let sources = [
+            "1 first\n2 only\n1 second\n".to_string(),
+            "1 third\n".to_string(),
+        ];
+        let (dir, path) = build_from("lookup-duplicates", &sources);
+        let file = LookupFile::open(&path).unwrap();
+        let values = [1, 2].map(|hash| file.get(hash).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(file.len(), 2);
+        assert_eq!(
+            values,
+            [Some("third".to_string()), Some("only".to_string())]
+        );
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let (dir, path) = build_from("lookup-damaged", &["1 a\n2 b\n".to_string()]);
+        let data = std::fs::read(&path).unwrap();
+        let damaged = dir.join("damaged.lkp");
+        let open = |bytes: &[u8]| {
+            std::fs::write(&damaged, bytes).unwrap();
+            LookupFile::open(&damaged)
+        };
+
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'X';
+        let bad_magic = open(&bad_magic);
+        let mut bad_version = data.clone();
+        bad_version[4] = 0xff;
+        let bad_version = open(&bad_version);
+        // Without the values and the second record
+        let records_cut = open(&data[..data.len() - 2 - RECORD_SIZE]);
+        // Records intact, the last value cut off
+        let values_cut = open(&data[..data.len() - 1]).unwrap().get(2);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(bad_magic.unwrap_err().contains("not a lookup file"));
+        assert!(bad_version.unwrap_err().contains("version"));
+        assert!(records_cut.unwrap_err().contains("truncated"));
+        assert!(values_cut.is_err());
+    }
+}
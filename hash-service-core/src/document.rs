@@ -0,0 +1,82 @@
+//! Substitutes known names for the unresolved `0x...` hash literals in ritobin text (`.py`)
+//! and JSON bin dumps.
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::engine::{HashEngine, HashtableType};
+
+/// `0x` followed by 16 (game/file) or 8 (bin entry, field, type, hash or link) hex digits
+static HASH_LITERAL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b0x([0-9a-fA-F]{16}|[0-9a-fA-F]{8})\b").expect("valid hash literal regex")
+});
+
+#[derive(Debug, Default)]
+pub struct ResolvedDocument {
+    pub document: String,
+    /// Literals replaced by a name
+    pub resolved: usize,
+    /// Literals left as they were
+    pub unresolved: usize,
+}
+
+/// Writes `name` in place of the literal at `start..end` the way ritobin would have
+/// printed it: bare for field (`name:`) and class (`Name {`) names, in place inside an
+/// existing string, and quoted everywhere else (entry keys, hash, link and file values)
+fn render_name(document: &str, start: usize, end: usize, name: &str, bin: bool, out: &mut String) {
+    let before = document[..start].chars().next_back();
+    let after = document[end..].chars().next();
+    let next_token = document[end..].trim_start().chars().next();
+
+    let in_string = before == Some('"') && after == Some('"');
+    let identifier = bin && matches!(next_token, Some(':') | Some('{'));
+    if in_string || identifier {
+        out.push_str(name);
+    } else {
+        out.push('"');
+        out.push_str(name);
+        out.push('"');
+    }
+}
+
+pub fn resolve_document(engine: &HashEngine, document: &str) -> Result<ResolvedDocument, String> {
+    let game = engine.read(HashtableType::Game)?;
+    let bin = engine.read(HashtableType::Bin)?;
+
+    let mut resolved = ResolvedDocument {
+        document: String::with_capacity(document.len()),
+        ..ResolvedDocument::default()
+    };
+    let mut last = 0;
+    for captures in HASH_LITERAL.captures_iter(document) {
+        let literal = captures.get(0).expect("capture 0 is the whole match");
+        let digits = &captures[1];
+        let Ok(hash) = u64::from_str_radix(digits, 16) else {
+            continue;
+        };
+        let is_bin = digits.len() == 8;
+        let table = if is_bin { &bin } else { &game };
+
+        resolved.document.push_str(&document[last..literal.start()]);
+        last = literal.end();
+        match table.get(&hash) {
+            Some(name) => {
+                render_name(
+                    document,
+                    literal.start(),
+                    literal.end(),
+                    name,
+                    is_bin,
+                    &mut resolved.document,
+                );
+                resolved.resolved += 1;
+            }
+            None => {
+                resolved.document.push_str(literal.as_str());
+                resolved.unresolved += 1;
+            }
+        }
+    }
+    resolved.document.push_str(&document[last..]);
+    Ok(resolved)
+}
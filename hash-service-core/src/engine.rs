@@ -0,0 +1,1903 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, broadcast, watch};
+use tracing::{info, instrument, warn};
+use walkdir::WalkDir;
+
+use crate::archive::{ArchiveKind, ArchiveMembers, TableArchive, read_streams};
+use crate::clean::{CleanReport, clean_dir};
+use crate::custom::{CustomTable, HashAlgorithm, validate_table_name};
+#[cfg(feature = "db-backend")]
+use crate::db::DbStore;
+use crate::diff::{TableDiff, load_diff};
+use crate::disk::DiskStore;
+use crate::events::{EVENT_CAPACITY, TableEvent};
+use crate::hash::{bin_hash, game_hash};
+use crate::local::{self, LocalSource, LocalSourceKind};
+use crate::lock::CacheLock;
+use crate::manifest;
+use crate::misses::MissCache;
+use crate::normalize::normalize;
+use crate::parse::read_entries;
+use crate::progress::{
+    CountingReader, DownloadProgress, FILE_PROGRESS_CAPACITY, FileProgress, LoadProgress,
+    PROGRESS_INTERVAL,
+};
+use crate::relocate::move_cache_dir;
+use crate::report::{
+    Collision, FailedFile, FailureStage, LoadReport, LoadedFile, TableLoad, failure_message,
+};
+use crate::snapshot::{self, SnapshotInfo};
+use crate::store::{StoreFile, StoreState};
+use crate::sync::{
+    Downloader, HASH_FILE_NAMES, HashSource, MirrorHealth, REPO_API_URL, SourceFile, UrlSource,
+    default_cache_dir, load_source_files, sync_hashtables,
+};
+use crate::table::{ConflictPolicy, EntryOrigin, Hashtable, InsertOutcome, TableLayout};
+
+/// Start of the error a load fails with when the cache could not be synced
+pub const SYNC_FAILED: &str = "Sync failed";
+/// Error of the calls that can't run while the tables load
+pub const LOADING_IN_PROGRESS: &str = "Hashtables are currently being loaded";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashtableType {
+    Game,
+    Bin,
+}
+
+impl HashtableType {
+    pub const ALL: [HashtableType; 2] = [HashtableType::Game, HashtableType::Bin];
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "game" => Ok(HashtableType::Game),
+            "bin" => Ok(HashtableType::Bin),
+            _ => Err("Invalid hashtable type".to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashtableType::Game => "game",
+            HashtableType::Bin => "bin",
+        }
+    }
+
+    /// Table a synced file belongs to, e.g. `hashes.game.txt.0` or `hashes.binentries.txt`
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        if file_name.contains(".game.") {
+            Some(HashtableType::Game)
+        } else if file_name.contains(".binentries.") {
+            Some(HashtableType::Bin)
+        } else {
+            None
+        }
+    }
+
+    /// Hash `value` the way the game does for this table; bin hashes use the lower 32 bits
+    pub fn hash(&self, value: &str) -> u64 {
+        match self {
+            HashtableType::Game => game_hash(value),
+            HashtableType::Bin => bin_hash(value) as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadingState {
+    Unloaded,
+    Loading,
+    Loaded,
+}
+
+/// The game and bin hashtables plus everything needed to (re)load them.
+///
+/// Cloning is cheap and clones share the same tables.
+#[derive(Debug, Clone)]
+pub struct HashEngine {
+    game_hashes: Arc<RwLock<Hashtable>>,
+    bin_hashes: Arc<RwLock<Hashtable>>,
+    /// Hashes recently looked up and not found, cleared whenever the table is written
+    game_misses: Arc<MissCache>,
+    bin_misses: Arc<MissCache>,
+    /// Combined state of both tables, see [`HashEngine::loading_state`]
+    loading_state: Arc<watch::Sender<LoadingState>>,
+    /// Game and bin tables load independently, each when first needed
+    game_state: Arc<watch::Sender<LoadingState>>,
+    bin_state: Arc<watch::Sender<LoadingState>>,
+    /// Whether the cache was synced since the tables were last unloaded
+    synced: Arc<AtomicBool>,
+    /// Held while syncing so tables loading at the same time do not download twice
+    sync_lock: Arc<Mutex<()>>,
+    load_progress: Arc<watch::Sender<LoadProgress>>,
+    file_progress: broadcast::Sender<FileProgress>,
+    /// Publishes the progress of each download and holds it to the rate limit
+    downloader: Downloader,
+    load_report: Arc<RwLock<LoadReport>>,
+    /// Files behind each table as of its last successful load
+    table_loads: Arc<RwLock<HashMap<HashtableType, TableLoad>>>,
+    /// Why the last load failed; cleared by the next successful one
+    last_error: Arc<RwLock<Option<String>>>,
+    /// Files the last sync of each could not bring up to date
+    sync_failures: Arc<RwLock<Vec<FailedFile>>>,
+    conflict_policy: Arc<RwLock<ConflictPolicy>>,
+    events: broadcast::Sender<TableEvent>,
+    /// Tables created at runtime, by name; untouched by loading and unloading
+    custom_tables: Arc<RwLock<HashMap<String, CustomTable>>>,
+    /// Changed at runtime by [`HashEngine::set_cache_dir`]; `None` for the default
+    cache_dir: Arc<RwLock<Option<PathBuf>>>,
+    /// Read-only dirs loaded on top of the cache dir, highest priority first
+    extra_dirs: Vec<PathBuf>,
+    /// Files and directories the user registered, read after the extra dirs in this order
+    local_sources: Arc<RwLock<Vec<LocalSource>>>,
+    /// Files synced from plain URLs along with the repository's
+    url_sources: Vec<UrlSource>,
+    source: HashSource,
+    /// When a lookup last went through [`HashEngine::ensure_loaded`] or a load finished, in
+    /// milliseconds since `created`
+    last_used: Arc<AtomicU64>,
+    /// What `last_used` counts milliseconds from; the same for every clone
+    created: Instant,
+    /// On-disk copy of the tables that lookups fall back to while they are not loaded
+    store: Option<Arc<StoreState>>,
+    /// Record which file each synced entry was read from
+    track_sources: bool,
+}
+
+/// A hashtable file in the cache dir
+#[derive(Debug, Clone)]
+pub struct CachedFile {
+    pub path: PathBuf,
+    pub file_name: String,
+    pub hashtable_type: HashtableType,
+    pub size: u64,
+    /// Blob SHA it was synced at, from its `.sha` file
+    pub sha: Option<String>,
+    /// When its current copy was written, i.e. last downloaded
+    pub modified: Option<SystemTime>,
+}
+
+/// A value as a lookup sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupEntry {
+    pub value: String,
+    pub origin: EntryOrigin,
+    /// The synced value a custom entry overrides, if it overrides one
+    pub official_value: Option<String>,
+    /// File the synced value was read from, when the engine tracks sources
+    pub source_file: Option<String>,
+}
+
+impl Default for HashEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HashEngine {
+    pub fn new() -> Self {
+        HashEngine {
+            game_hashes: Arc::new(RwLock::new(Hashtable::default())),
+            bin_hashes: Arc::new(RwLock::new(Hashtable::default())),
+            game_misses: Arc::new(MissCache::default()),
+            bin_misses: Arc::new(MissCache::default()),
+            loading_state: Arc::new(watch::Sender::new(LoadingState::Unloaded)),
+            game_state: Arc::new(watch::Sender::new(LoadingState::Unloaded)),
+            bin_state: Arc::new(watch::Sender::new(LoadingState::Unloaded)),
+            synced: Arc::new(AtomicBool::new(false)),
+            sync_lock: Arc::new(Mutex::new(())),
+            load_progress: Arc::new(watch::Sender::new(LoadProgress::default())),
+            file_progress: broadcast::Sender::new(FILE_PROGRESS_CAPACITY),
+            downloader: Downloader {
+                progress: broadcast::Sender::new(FILE_PROGRESS_CAPACITY),
+                rate_limit: None,
+                mirrors: Vec::new(),
+                health: MirrorHealth::default(),
+                api_url: REPO_API_URL.to_string(),
+            },
+            load_report: Arc::new(RwLock::new(LoadReport::default())),
+            table_loads: Arc::new(RwLock::new(HashMap::new())),
+            last_error: Arc::new(RwLock::new(None)),
+            sync_failures: Arc::new(RwLock::new(Vec::new())),
+            conflict_policy: Arc::new(RwLock::new(ConflictPolicy::default())),
+            events: broadcast::Sender::new(EVENT_CAPACITY),
+            custom_tables: Arc::new(RwLock::new(HashMap::new())),
+            cache_dir: Arc::new(RwLock::new(None)),
+            extra_dirs: Vec::new(),
+            local_sources: Arc::new(RwLock::new(Vec::new())),
+            url_sources: Vec::new(),
+            source: HashSource::default(),
+            last_used: Arc::new(AtomicU64::new(0)),
+            created: Instant::now(),
+            store: None,
+            track_sources: false,
+        }
+    }
+
+    /// Overrides the directory hashtables are synced to and loaded from
+    pub fn with_cache_dir(mut self, cache_dir: Option<PathBuf>) -> Self {
+        self.cache_dir = Arc::new(RwLock::new(cache_dir));
+        self
+    }
+
+    /// Adds read-only dirs of hashtable files, e.g. on a network share, loaded after the cache
+    /// dir in reverse order so the conflict policy sees the first one last. They are never
+    /// synced, locked or cleaned; a dir that is missing at load time is skipped.
+    pub fn with_extra_dirs(mut self, extra_dirs: Vec<PathBuf>) -> Self {
+        self.extra_dirs = extra_dirs;
+        self
+    }
+
+    pub fn extra_dirs(&self) -> &[PathBuf] {
+        &self.extra_dirs
+    }
+
+    /// Holds each download of a sync to `bytes_per_second`, so a background refresh does not
+    /// saturate the connection; unlimited when `None` or 0
+    pub fn with_download_limit(mut self, bytes_per_second: Option<u64>) -> Self {
+        self.downloader.rate_limit = bytes_per_second;
+        self
+    }
+
+    /// Downloads the CommunityDragon files from `mirrors` when GitHub's download fails, trying
+    /// them in order; see [`crate::sync::validate_mirror`] for their form
+    pub fn with_mirrors(mut self, mirrors: Vec<String>) -> Self {
+        self.downloader.mirrors = mirrors;
+        self
+    }
+
+    /// Shares the record of failed mirrors with other engines, e.g. those of other products,
+    /// so a blocked host is tried last by all of them
+    pub fn with_mirror_health(mut self, health: MirrorHealth) -> Self {
+        self.downloader.health = health;
+        self
+    }
+
+    /// Syncs the files at `url_sources` into the cache dir along with the repository's
+    pub fn with_url_sources(mut self, url_sources: Vec<UrlSource>) -> Self {
+        self.url_sources = url_sources;
+        self
+    }
+
+    pub fn url_sources(&self) -> &[UrlSource] {
+        &self.url_sources
+    }
+
+    /// Reads `configured` local sources, then the ones registered in the cache dir, on every
+    /// load. A registered source with the path of a configured one is left out.
+    pub fn with_local_sources(mut self, configured: Vec<LocalSource>) -> Self {
+        let registered = match self.cache_dir().and_then(|dir| local::load(&dir)) {
+            Ok(registered) => registered,
+            Err(e) => {
+                warn!("Failed to read registered hashtable sources: {}", e);
+                Vec::new()
+            }
+        };
+        let mut sources: Vec<LocalSource> = configured
+            .into_iter()
+            .map(|source| LocalSource {
+                from_config: true,
+                ..source
+            })
+            .collect();
+        for source in registered {
+            if !sources.iter().any(|existing| existing.path == source.path) {
+                sources.push(source);
+            }
+        }
+        self.local_sources = Arc::new(RwLock::new(sources));
+        self
+    }
+
+    fn local_sources_mut(&self) -> Result<RwLockWriteGuard<'_, Vec<LocalSource>>, String> {
+        self.local_sources
+            .write()
+            .map_err(|_| "Failed to lock local sources".to_string())
+    }
+
+    /// Local sources in load order
+    pub fn local_sources(&self) -> Result<Vec<LocalSource>, String> {
+        self.local_sources
+            .read()
+            .map(|sources| sources.clone())
+            .map_err(|_| "Failed to lock local sources".to_string())
+    }
+
+    /// Registers `source` to be read last on every load from the next one on, once it is
+    /// checked to exist
+    pub fn add_local_source(&self, source: LocalSource) -> Result<(), String> {
+        source.validate()?;
+        let mut sources = self.local_sources_mut()?;
+        if sources.iter().any(|existing| existing.path == source.path) {
+            return Err(format!("Source {:?} is already registered", source.path));
+        }
+        info!(path = ?source.path, kind = ?source.kind, "Registered local source");
+        sources.push(LocalSource {
+            from_config: false,
+            ..source
+        });
+        local::save(&self.cache_dir()?, &sources)
+    }
+
+    /// Turns the source at `path` on or off from the next load on, `None` if there is none.
+    /// The switch is saved for registered sources; configured ones are back on the config
+    /// file's setting after a restart.
+    pub fn set_local_source_enabled(
+        &self,
+        path: &Path,
+        enabled: bool,
+    ) -> Result<Option<LocalSource>, String> {
+        let mut sources = self.local_sources_mut()?;
+        let Some(source) = sources.iter_mut().find(|source| source.path == path) else {
+            return Ok(None);
+        };
+        source.enabled = enabled;
+        let source = source.clone();
+        local::save(&self.cache_dir()?, &sources)?;
+        Ok(Some(source))
+    }
+
+    /// Unregisters the source at `path`, `None` if there is none. Configured sources can only
+    /// be disabled.
+    pub fn remove_local_source(&self, path: &Path) -> Result<Option<LocalSource>, String> {
+        let mut sources = self.local_sources_mut()?;
+        let Some(index) = sources.iter().position(|source| source.path == path) else {
+            return Ok(None);
+        };
+        if sources[index].from_config {
+            return Err(format!(
+                "Source {:?} is set in the config file; disable it instead",
+                path
+            ));
+        }
+        let source = sources.remove(index);
+        local::save(&self.cache_dir()?, &sources)?;
+        info!(path = ?source.path, "Removed local source");
+        Ok(Some(source))
+    }
+
+    /// Sizes the per-table caches of missed lookups; a `capacity` of 0 disables them
+    pub fn with_miss_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.game_misses = Arc::new(MissCache::new(capacity, ttl));
+        self.bin_misses = Arc::new(MissCache::new(capacity, ttl));
+        self
+    }
+
+    /// Resolves conflicting values with `policy` until [`HashEngine::set_conflict_policy`]
+    /// changes it
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict_policy = Arc::new(RwLock::new(policy));
+        self
+    }
+
+    /// Holds the table in `layout` instead of a hash map
+    pub fn with_table_layout(mut self, hashtable_type: HashtableType, layout: TableLayout) -> Self {
+        let table = Arc::new(RwLock::new(Hashtable::with_layout(layout)));
+        match hashtable_type {
+            HashtableType::Game => self.game_hashes = table,
+            HashtableType::Bin => self.bin_hashes = table,
+        }
+        self
+    }
+
+    /// Keeps the synced tables in a database in the cache dir as well, with `cache_bytes` of
+    /// page cache, and answers lookups from it while the tables are not loaded
+    #[cfg(feature = "db-backend")]
+    pub fn with_database(mut self, cache_bytes: usize) -> Self {
+        self.store = Some(Arc::new(StoreState::new(DbStore::new(cache_bytes))));
+        self
+    }
+
+    /// Records the file each synced entry was read from, for lookups to report, at about 11
+    /// bytes per entry
+    pub fn with_source_tracking(mut self, track_sources: bool) -> Self {
+        self.track_sources = track_sources;
+        self
+    }
+
+    /// Keeps sorted lookup files of the synced tables in the cache dir and answers lookups
+    /// from them, a seek and a read each, while the tables are not loaded
+    pub fn with_disk_lookups(mut self) -> Self {
+        self.store = Some(Arc::new(StoreState::new(DiskStore::default())));
+        self
+    }
+
+    /// Whether lookups are answered from disk instead of loading the tables
+    pub fn uses_lookup_store(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Sets the product whose hashtables are synced into the cache dir
+    pub fn with_source(mut self, source: HashSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn source(&self) -> &HashSource {
+        &self.source
+    }
+
+    /// Upstream commit and patch of each synced file
+    pub fn source_files(&self) -> Result<Vec<SourceFile>, String> {
+        load_source_files(&self.cache_dir()?)
+    }
+
+    /// Hashtable files in the cache dir, by file name
+    pub fn cached_files(&self) -> Result<Vec<CachedFile>, String> {
+        let mut files: Vec<CachedFile> = list_table_files(&self.cache_dir()?, &HashtableType::ALL)
+            .into_iter()
+            .map(|file| {
+                let sha_path = file.path.with_file_name(format!("{}.sha", file.file_name));
+                CachedFile {
+                    modified: std::fs::metadata(&file.path)
+                        .and_then(|m| m.modified())
+                        .ok(),
+                    sha: std::fs::read_to_string(sha_path)
+                        .ok()
+                        .map(|sha| sha.trim().to_string()),
+                    path: file.path,
+                    file_name: file.file_name,
+                    hashtable_type: file.hashtable_type,
+                    size: file.size,
+                }
+            })
+            .collect();
+        files.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        // An archive holding both tables is listed once per table
+        files.dedup_by(|a, b| a.path == b.path);
+        Ok(files)
+    }
+
+    /// Names of the files a sync fetches: the CommunityDragon ones, then the URL sources'
+    pub fn synced_file_names(&self) -> Vec<String> {
+        HASH_FILE_NAMES
+            .iter()
+            .map(|file_name| file_name.to_string())
+            .chain(self.url_sources.iter().map(UrlSource::file_name))
+            .collect()
+    }
+
+    /// The synced files named in `file_names`, followed by those of `tables`, each once
+    pub fn select_synced_files(
+        &self,
+        file_names: &[String],
+        tables: &[HashtableType],
+    ) -> Result<Vec<String>, String> {
+        let synced = self.synced_file_names();
+        let mut selected: Vec<String> = Vec::new();
+        for file_name in file_names {
+            if !synced.contains(file_name) {
+                return Err(format!("'{}' is not a synced file", file_name));
+            }
+            if !selected.contains(file_name) {
+                selected.push(file_name.clone());
+            }
+        }
+        for file_name in synced {
+            let in_tables = HashtableType::from_file_name(&file_name)
+                .is_some_and(|hashtable_type| tables.contains(&hashtable_type));
+            if in_tables && !selected.contains(&file_name) {
+                selected.push(file_name);
+            }
+        }
+        Ok(selected)
+    }
+
+    /// Downloads the synced file `file_name` again, changed upstream or not, leaving the rest
+    /// of the cache alone. Returns how many files changed; loaded tables keep the old copy
+    /// until they are loaded again.
+    pub async fn redownload(&self, file_name: &str) -> Result<usize, String> {
+        let files = self.select_synced_files(&[file_name.to_string()], &[])?;
+        let hash_dir = self.cache_dir()?;
+        std::fs::create_dir_all(&hash_dir)
+            .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+        info!(file_name, "Downloading file again");
+        let updated_files = self.sync_cache(&hash_dir, &files, true).await?;
+        // Its old copy is kept, but the one file asked for did not come
+        match self
+            .sync_failures()?
+            .into_iter()
+            .find(|failed| failed.file_name == file_name)
+        {
+            Some(failed) => Err(format!("{}: {}", SYNC_FAILED, failed.error)),
+            None => Ok(updated_files),
+        }
+    }
+
+    /// The configured cache dir, or the default one
+    pub fn cache_dir(&self) -> Result<PathBuf, String> {
+        let cache_dir = self
+            .cache_dir
+            .read()
+            .map_err(|_| "Failed to lock cache dir".to_string())?
+            .clone();
+        match cache_dir {
+            Some(dir) => Ok(dir),
+            None => default_cache_dir(),
+        }
+    }
+
+    /// Switches to `cache_dir`, or the default one for `None`, and returns the new dir. With
+    /// `migrate` the files of the current dir move along; otherwise they stay behind and the
+    /// next load syncs into the new dir. Loaded tables stay loaded either way.
+    pub async fn set_cache_dir(
+        &self,
+        cache_dir: Option<PathBuf>,
+        migrate: bool,
+    ) -> Result<PathBuf, String> {
+        let _sync_guard = self.sync_lock.lock().await;
+        // Lookups must not refresh the store from a dir that is being moved
+        let _refresh_guard = match &self.store {
+            Some(store) => Some(store.refresh_lock.lock().await),
+            None => None,
+        };
+        let from = self.cache_dir()?;
+        let to = match &cache_dir {
+            Some(dir) => dir.clone(),
+            None => default_cache_dir()?,
+        };
+        if from != to {
+            if let Some(store) = &self.store {
+                store.store.close()?;
+                store.mark_stale();
+            }
+            if migrate && from.exists() {
+                move_cache_dir(&from, &to).await?;
+            } else {
+                self.synced.store(false, Ordering::SeqCst);
+            }
+            info!(?from, ?to, migrate, "Changed cache directory");
+        }
+        *self
+            .cache_dir
+            .write()
+            .map_err(|_| "Failed to lock cache dir".to_string())? = cache_dir;
+        Ok(to)
+    }
+
+    /// Syncs the cache dir without loading the tables, e.g. to fill a new cache dir, and
+    /// returns how many files changed upstream
+    pub async fn sync(&self) -> Result<usize, String> {
+        self.sync_files(&[], false).await
+    }
+
+    /// Syncs only the synced files named in `files`, or every one when it is empty, without
+    /// loading the tables, and returns how many changed upstream. With `force` they are
+    /// downloaded again even when unchanged.
+    pub async fn sync_files(&self, files: &[String], force: bool) -> Result<usize, String> {
+        let files = self.select_synced_files(files, &[])?;
+        let hash_dir = self.cache_dir()?;
+        std::fs::create_dir_all(&hash_dir)
+            .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+        if files.is_empty() {
+            self.synced.store(false, Ordering::SeqCst);
+        }
+        self.sync_cache(&hash_dir, &files, force).await
+    }
+
+    /// Removes leftovers from the cache dir: orphaned `.sha` files, interrupted downloads and
+    /// on-disk table copies the engine no longer reads. Waits for a running sync.
+    pub async fn clean_cache(&self, dry_run: bool) -> Result<CleanReport, String> {
+        let hash_dir = self.cache_dir()?;
+        if !hash_dir.exists() {
+            return Ok(CleanReport::default());
+        }
+        let _sync_guard = self.sync_lock.lock().await;
+        let _cache_lock = CacheLock::exclusive(&hash_dir).await?;
+        let store = self.store.as_ref().map(|state| state.store.kind());
+        Ok(clean_dir(
+            &hash_dir,
+            store,
+            |hashtable_type| self.store_files(&hash_dir, hashtable_type),
+            dry_run,
+        ))
+    }
+
+    /// `Loading` while either table loads, otherwise `Loaded` once either table is
+    pub fn loading_state(&self) -> LoadingState {
+        *self.loading_state.borrow()
+    }
+
+    pub fn table_loading_state(&self, hashtable_type: HashtableType) -> LoadingState {
+        *self.table_state(hashtable_type).borrow()
+    }
+
+    fn table_state(&self, hashtable_type: HashtableType) -> &watch::Sender<LoadingState> {
+        match hashtable_type {
+            HashtableType::Game => &self.game_state,
+            HashtableType::Bin => &self.bin_state,
+        }
+    }
+
+    fn set_table_states(&self, tables: &[HashtableType], state: LoadingState) {
+        for &hashtable_type in tables {
+            self.table_state(hashtable_type).send_replace(state);
+        }
+        self.update_loading_state();
+    }
+
+    fn update_loading_state(&self) {
+        let states =
+            HashtableType::ALL.map(|hashtable_type| self.table_loading_state(hashtable_type));
+        let combined = if states.contains(&LoadingState::Loading) {
+            LoadingState::Loading
+        } else if states.contains(&LoadingState::Loaded) {
+            LoadingState::Loaded
+        } else {
+            LoadingState::Unloaded
+        };
+        self.loading_state.send_if_modified(|state| {
+            let changed = *state != combined;
+            *state = combined;
+            changed
+        });
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loading_state() == LoadingState::Loaded
+    }
+
+    /// Notified on every loading state change
+    pub fn subscribe(&self) -> watch::Receiver<LoadingState> {
+        self.loading_state.subscribe()
+    }
+
+    /// Progress of the running load, or of the last one once it finished
+    pub fn load_progress(&self) -> LoadProgress {
+        self.load_progress.borrow().clone()
+    }
+
+    /// Notified as each file of a load is read, and once more when it is done
+    pub fn file_progress(&self) -> broadcast::Receiver<FileProgress> {
+        self.file_progress.subscribe()
+    }
+
+    /// Notified as each file of a sync downloads, by loads as well as explicit syncs, and
+    /// once it is done or found up to date
+    pub fn download_progress(&self) -> broadcast::Receiver<DownloadProgress> {
+        self.downloader.progress.subscribe()
+    }
+
+    /// Notified of every load, unload, sync and mutation of the tables
+    pub fn events(&self) -> broadcast::Receiver<TableEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, event: TableEvent) {
+        // Fails only when nobody is subscribed
+        let _ = self.events.send(event);
+    }
+
+    pub fn read(
+        &self,
+        hashtable_type: HashtableType,
+    ) -> Result<RwLockReadGuard<'_, Hashtable>, String> {
+        self.hashtable(hashtable_type)
+            .read()
+            .map_err(|_| "Failed to lock hashtable".to_string())
+    }
+
+    pub fn write(
+        &self,
+        hashtable_type: HashtableType,
+    ) -> Result<RwLockWriteGuard<'_, Hashtable>, String> {
+        let guard = self
+            .hashtable(hashtable_type)
+            .write()
+            .map_err(|_| "Failed to lock hashtable for writing".to_string())?;
+        // Whatever the writer does, earlier misses may no longer be
+        self.misses(hashtable_type).clear();
+        Ok(guard)
+    }
+
+    /// Whether `hash` was recently looked up in the table and not found. Answers without
+    /// taking the table's lock.
+    pub fn is_known_miss(&self, hashtable_type: HashtableType, hash: u64) -> bool {
+        self.misses(hashtable_type).contains(hash)
+    }
+
+    /// Taken before a lookup whose miss will be passed to [`HashEngine::record_miss`]
+    pub fn miss_generation(&self, hashtable_type: HashtableType) -> u64 {
+        self.misses(hashtable_type).generation()
+    }
+
+    /// Remembers that a lookup of `hash` found nothing, until the table is next written.
+    /// `generation` is [`HashEngine::miss_generation`] from before the lookup.
+    pub fn record_miss(&self, hashtable_type: HashtableType, hash: u64, generation: u64) {
+        self.misses(hashtable_type).insert(hash, generation);
+    }
+
+    fn misses(&self, hashtable_type: HashtableType) -> &MissCache {
+        match hashtable_type {
+            HashtableType::Game => &self.game_misses,
+            HashtableType::Bin => &self.bin_misses,
+        }
+    }
+
+    fn hashtable(&self, hashtable_type: HashtableType) -> &Arc<RwLock<Hashtable>> {
+        match hashtable_type {
+            HashtableType::Game => &self.game_hashes,
+            HashtableType::Bin => &self.bin_hashes,
+        }
+    }
+
+    pub fn get(&self, hashtable_type: HashtableType, hash: u64) -> Result<Option<String>, String> {
+        Ok(self.read(hashtable_type)?.get(&hash).map(str::to_string))
+    }
+
+    /// Looks up `hashes` in the table, or on disk while the table is not loaded.
+    /// Call [`HashEngine::ensure_lookups_ready`] first.
+    pub fn lookup_many(
+        &self,
+        hashtable_type: HashtableType,
+        hashes: &[u64],
+    ) -> Result<Vec<Option<LookupEntry>>, String> {
+        let entries: Vec<Option<LookupEntry>> = {
+            let table = self.read(hashtable_type)?;
+            hashes
+                .iter()
+                .map(|hash| {
+                    table.get(hash).map(|value| LookupEntry {
+                        value: value.to_string(),
+                        origin: table.origin(hash).unwrap_or(EntryOrigin::Official),
+                        official_value: table.shadowed(hash).cloned(),
+                        source_file: table.source_file(hash).map(str::to_string),
+                    })
+                })
+                .collect()
+        };
+        self.lookup_in_store(hashtable_type, hashes, entries)
+    }
+
+    /// Fills in the entries not found in memory from disk while the table is not loaded;
+    /// only runtime additions are in memory then, the synced entries are on disk
+    fn lookup_in_store(
+        &self,
+        hashtable_type: HashtableType,
+        hashes: &[u64],
+        mut entries: Vec<Option<LookupEntry>>,
+    ) -> Result<Vec<Option<LookupEntry>>, String> {
+        if let Some(store) = &self.store
+            && self.table_loading_state(hashtable_type) != LoadingState::Loaded
+        {
+            let missing: Vec<u64> = hashes
+                .iter()
+                .zip(&entries)
+                .filter(|(_, entry)| entry.is_none())
+                .map(|(&hash, _)| hash)
+                .collect();
+            let mut found = store.store.get_many(hashtable_type, &missing)?.into_iter();
+            for entry in entries.iter_mut().filter(|entry| entry.is_none()) {
+                *entry = found.next().flatten().map(|value| LookupEntry {
+                    value,
+                    origin: EntryOrigin::Official,
+                    official_value: None,
+                    source_file: None,
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Makes lookups in the table answerable. With a lookup store, the cache is synced and the
+    /// store brought up to date without loading the table into memory; otherwise the table
+    /// is loaded.
+    pub async fn ensure_lookups_ready(&self, hashtable_type: HashtableType) -> Result<(), String> {
+        if let Some(store) = &self.store
+            && self.table_loading_state(hashtable_type) != LoadingState::Loaded
+        {
+            self.touch();
+            if store.is_ready(hashtable_type) {
+                return Ok(());
+            }
+            let hash_dir = self.cache_dir()?;
+            std::fs::create_dir_all(&hash_dir)
+                .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+            self.sync_cache(&hash_dir, &[], false).await?;
+
+            let _refresh_guard = store.refresh_lock.lock().await;
+            if store.is_ready(hashtable_type) {
+                return Ok(());
+            }
+            // The cache dir may have moved while waiting
+            let hash_dir = self.cache_dir()?;
+            let _cache_lock = CacheLock::shared(&hash_dir).await?;
+            let files = self.store_files(&hash_dir, hashtable_type);
+            if store.store.refresh(&hash_dir, hashtable_type, &files)? {
+                self.misses(hashtable_type).clear();
+            }
+            store.set_ready(hashtable_type);
+            return Ok(());
+        }
+        self.ensure_table_loaded(hashtable_type).await
+    }
+
+    /// `(game, bin)` entry counts
+    pub fn counts(&self) -> Result<(usize, usize), String> {
+        Ok((
+            self.read(HashtableType::Game)?.len(),
+            self.read(HashtableType::Bin)?.len(),
+        ))
+    }
+
+    /// Approximate bytes held by the game, bin and custom tables
+    pub fn memory_estimate(&self) -> Result<usize, String> {
+        Ok(self.table_memory_estimate(HashtableType::Game)?
+            + self.table_memory_estimate(HashtableType::Bin)?
+            + self.custom_memory_estimate()?)
+    }
+
+    /// Approximate bytes held by one of the built-in tables
+    pub fn table_memory_estimate(&self, hashtable_type: HashtableType) -> Result<usize, String> {
+        Ok(self.read(hashtable_type)?.memory_estimate())
+    }
+
+    /// Approximate bytes held by all tables created at runtime
+    pub fn custom_memory_estimate(&self) -> Result<usize, String> {
+        Ok(self
+            .custom_tables
+            .read()
+            .map_err(|_| "Failed to lock custom tables".to_string())?
+            .values()
+            .map(|custom| custom.table.memory_estimate())
+            .sum())
+    }
+
+    pub fn conflict_policy(&self) -> Result<ConflictPolicy, String> {
+        self.conflict_policy
+            .read()
+            .map(|guard| *guard)
+            .map_err(|_| "Failed to lock conflict policy".to_string())
+    }
+
+    pub fn set_conflict_policy(&self, policy: ConflictPolicy) -> Result<(), String> {
+        *self
+            .conflict_policy
+            .write()
+            .map_err(|_| "Failed to lock conflict policy".to_string())? = policy;
+        Ok(())
+    }
+
+    pub fn load_report(&self) -> Result<RwLockReadGuard<'_, LoadReport>, String> {
+        self.load_report
+            .read()
+            .map_err(|_| "Failed to lock load report".to_string())
+    }
+
+    /// Files the table was last loaded from, `None` until a load of it succeeded
+    pub fn table_load(&self, hashtable_type: HashtableType) -> Result<Option<TableLoad>, String> {
+        self.table_loads
+            .read()
+            .map(|table_loads| table_loads.get(&hashtable_type).cloned())
+            .map_err(|_| "Failed to lock table loads".to_string())
+    }
+
+    /// Files the last sync of each could not bring up to date, which kept their previous copy
+    pub fn sync_failures(&self) -> Result<Vec<FailedFile>, String> {
+        self.sync_failures
+            .read()
+            .map(|sync_failures| sync_failures.clone())
+            .map_err(|_| "Failed to lock sync failures".to_string())
+    }
+
+    /// Files the loaded tables went without at their last load, because they could not be
+    /// synced or read
+    pub fn failed_files(&self) -> Result<Vec<FailedFile>, String> {
+        self.table_loads
+            .read()
+            .map(|table_loads| {
+                HashtableType::ALL
+                    .iter()
+                    .filter(|&&hashtable_type| {
+                        self.table_loading_state(hashtable_type) == LoadingState::Loaded
+                    })
+                    .filter_map(|hashtable_type| table_loads.get(hashtable_type))
+                    .flat_map(|table_load| table_load.failed.iter().cloned())
+                    .collect()
+            })
+            .map_err(|_| "Failed to lock table loads".to_string())
+    }
+
+    /// Changes made to the table by the last sync that changed it
+    pub fn diff(&self, hashtable_type: HashtableType) -> Result<Option<TableDiff>, String> {
+        load_diff(&self.cache_dir()?, hashtable_type)
+    }
+
+    pub fn create_table(&self, name: &str, algorithm: HashAlgorithm) -> Result<(), String> {
+        validate_table_name(name)?;
+        let mut tables = self.custom_tables_mut()?;
+        if tables.contains_key(name) {
+            return Err(format!("Table '{}' already exists", name));
+        }
+        tables.insert(
+            name.to_string(),
+            CustomTable {
+                algorithm,
+                table: Hashtable::default(),
+            },
+        );
+        info!(name, algorithm = algorithm.as_str(), "Created table");
+        Ok(())
+    }
+
+    /// Removes a custom table and returns how many entries it had, `None` if there is none
+    pub fn drop_table(&self, name: &str) -> Result<Option<usize>, String> {
+        Ok(self
+            .custom_tables_mut()?
+            .remove(name)
+            .map(|custom| custom.table.len()))
+    }
+
+    /// Algorithm of the named custom table, `None` if there is none
+    pub fn table_algorithm(&self, name: &str) -> Result<Option<HashAlgorithm>, String> {
+        Ok(self
+            .custom_tables()?
+            .get(name)
+            .map(|custom| custom.algorithm))
+    }
+
+    /// `(name, algorithm, entry count)` of every custom table, by name
+    pub fn list_tables(&self) -> Result<Vec<(String, HashAlgorithm, usize)>, String> {
+        let mut tables: Vec<_> = self
+            .custom_tables()?
+            .iter()
+            .map(|(name, custom)| (name.clone(), custom.algorithm, custom.table.len()))
+            .collect();
+        tables.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(tables)
+    }
+
+    /// Runs `f` on the named custom table, `None` if there is none
+    pub fn with_table<R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&Hashtable) -> R,
+    ) -> Result<Option<R>, String> {
+        Ok(self
+            .custom_tables()?
+            .get(name)
+            .map(|custom| f(&custom.table)))
+    }
+
+    /// Inserts `value` under `hash` in the named custom table, resolving conflicts with
+    /// `policy` or the current policy. Callers hash and validate the value.
+    pub fn add_to_table(
+        &self,
+        name: &str,
+        hash: u64,
+        value: String,
+        policy: Option<ConflictPolicy>,
+    ) -> Result<InsertOutcome, String> {
+        let policy = match policy {
+            Some(policy) => policy,
+            None => self.conflict_policy()?,
+        };
+        let mut tables = self.custom_tables_mut()?;
+        let custom = tables
+            .get_mut(name)
+            .ok_or_else(|| format!("Table '{}' does not exist", name))?;
+        Ok(custom
+            .table
+            .insert(hash, value, EntryOrigin::Custom, policy))
+    }
+
+    fn custom_tables(&self) -> Result<RwLockReadGuard<'_, HashMap<String, CustomTable>>, String> {
+        self.custom_tables
+            .read()
+            .map_err(|_| "Failed to lock custom tables".to_string())
+    }
+
+    fn custom_tables_mut(
+        &self,
+    ) -> Result<RwLockWriteGuard<'_, HashMap<String, CustomTable>>, String> {
+        self.custom_tables
+            .write()
+            .map_err(|_| "Failed to lock custom tables for writing".to_string())
+    }
+
+    /// Saves both tables, custom entries included, as a named snapshot
+    pub fn create_snapshot(&self, name: &str) -> Result<SnapshotInfo, String> {
+        let cache_dir = self.cache_dir()?;
+        let game = self.read(HashtableType::Game)?;
+        let bin = self.read(HashtableType::Bin)?;
+        snapshot::save(&cache_dir, name, &game, &bin)
+    }
+
+    pub fn list_snapshots(&self) -> Result<Vec<SnapshotInfo>, String> {
+        snapshot::list(&self.cache_dir()?)
+    }
+
+    pub fn delete_snapshot(&self, name: &str) -> Result<(), String> {
+        snapshot::delete(&self.cache_dir()?, name)
+    }
+
+    /// Replaces both tables with a snapshot and returns the `(game, bin)` counts restored
+    pub fn restore_snapshot(&self, name: &str) -> Result<(usize, usize), String> {
+        if self.loading_state() == LoadingState::Loading {
+            return Err(LOADING_IN_PROGRESS.to_string());
+        }
+        // Read before locking so lookups keep working while the file is parsed
+        let ((game_entries, game_custom), (bin_entries, bin_custom)) =
+            snapshot::load(&self.cache_dir()?, name)?;
+        let counts = (game_entries.len(), bin_entries.len());
+        {
+            let mut game_guard = self.write(HashtableType::Game)?;
+            let mut bin_guard = self.write(HashtableType::Bin)?;
+            game_guard.replace(game_entries, game_custom);
+            bin_guard.replace(bin_entries, bin_custom);
+        }
+
+        self.set_table_states(&HashtableType::ALL, LoadingState::Loaded);
+        info!(name, game = counts.0, bin = counts.1, "Restored snapshot");
+        self.emit(TableEvent::Restored {
+            snapshot: name.to_string(),
+        });
+        Ok(counts)
+    }
+
+    /// Normalizes and hashes `value` for the table and inserts it as a custom entry under the
+    /// current conflict policy, recording any conflict in the load report
+    pub fn add(
+        &self,
+        hashtable_type: HashtableType,
+        value: String,
+    ) -> Result<(u64, InsertOutcome), String> {
+        self.add_custom(hashtable_type, None, value, None)
+    }
+
+    /// Like [`HashEngine::add`] but stores `value` under a hash computed elsewhere, resolving
+    /// conflicts with `policy` instead of the current policy when given
+    pub fn add_with_hash(
+        &self,
+        hashtable_type: HashtableType,
+        hash: u64,
+        value: String,
+        policy: Option<ConflictPolicy>,
+    ) -> Result<InsertOutcome, String> {
+        self.add_custom(hashtable_type, Some(hash), value, policy)
+            .map(|(_, outcome)| outcome)
+    }
+
+    /// Normalizes `value` and stores it under `hash`, or its own hash without one
+    fn add_custom(
+        &self,
+        hashtable_type: HashtableType,
+        hash: Option<u64>,
+        value: String,
+        policy: Option<ConflictPolicy>,
+    ) -> Result<(u64, InsertOutcome), String> {
+        let value = normalize(hashtable_type, &value)?;
+        let hash = hash.unwrap_or_else(|| hashtable_type.hash(&value));
+        if hashtable_type == HashtableType::Bin && hash > u32::MAX as u64 {
+            return Err(format!("Bin hash {:x} does not fit in 32 bits", hash));
+        }
+        let policy = match policy {
+            Some(policy) => policy,
+            None => self.conflict_policy()?,
+        };
+        let outcome =
+            self.write(hashtable_type)?
+                .insert(hash, value.clone(), EntryOrigin::Custom, policy);
+
+        if let InsertOutcome::Conflict {
+            existing,
+            incoming,
+            replaced,
+        } = &outcome
+        {
+            self.load_report
+                .write()
+                .map_err(|_| "Failed to lock load report".to_string())?
+                .record_collision(Collision {
+                    hash,
+                    hashtable_type: hashtable_type.as_str(),
+                    existing_value: existing.clone(),
+                    new_value: incoming.clone(),
+                    source_file: String::new(),
+                    replaced: *replaced,
+                });
+        }
+        if matches!(
+            outcome,
+            InsertOutcome::Inserted | InsertOutcome::Conflict { replaced: true, .. }
+        ) {
+            self.emit(TableEvent::Added {
+                hashtable_type,
+                hash,
+                value,
+            });
+        }
+        Ok((hash, outcome))
+    }
+
+    /// Syncs and loads both hashtables, replacing whatever was loaded
+    #[instrument(skip_all)]
+    pub async fn load(&self) -> Result<(), String> {
+        // An explicit load always checks for updates
+        self.synced.store(false, Ordering::SeqCst);
+        self.set_table_states(&HashtableType::ALL, LoadingState::Loading);
+        let guard = LoadGuard::new(self, &HashtableType::ALL);
+        let result = self.load_impl(&HashtableType::ALL, &[], false).await;
+        guard.finish(&result);
+        result.map(|_| ())
+    }
+
+    /// Downloads every hashtable file again and reloads the tables from them, keeping the
+    /// entries added at runtime. Returns how many files changed upstream and how many
+    /// entries the tables gained.
+    #[instrument(skip_all)]
+    pub async fn resync(&self) -> Result<(usize, usize), String> {
+        let (game_before, bin_before) = self.counts()?;
+        self.set_table_states(&HashtableType::ALL, LoadingState::Loading);
+        let guard = LoadGuard::new(self, &HashtableType::ALL);
+        let result = self.load_impl(&HashtableType::ALL, &[], true).await;
+        guard.finish(&result);
+        let updated_files = result?;
+        let (game_after, bin_after) = self.counts()?;
+        Ok((
+            updated_files,
+            (game_after + bin_after).saturating_sub(game_before + bin_before),
+        ))
+    }
+
+    /// Syncs only `files`, names of synced files, and reloads the tables they belong to from
+    /// scratch, keeping the entries added at runtime; the other tables and files are left
+    /// alone. With `force` the files are downloaded again even when unchanged. Returns how many
+    /// files changed upstream and how many entries the tables gained.
+    #[instrument(skip_all, fields(?files))]
+    pub async fn refresh(&self, files: &[String], force: bool) -> Result<(usize, usize), String> {
+        let files = self.select_synced_files(files, &[])?;
+        if files.is_empty() {
+            return Err("No files to refresh".to_string());
+        }
+        let tables: Vec<HashtableType> = HashtableType::ALL
+            .into_iter()
+            .filter(|&hashtable_type| {
+                files
+                    .iter()
+                    .any(|file| HashtableType::from_file_name(file) == Some(hashtable_type))
+            })
+            .collect();
+        let (game_before, bin_before) = self.counts()?;
+        self.set_table_states(&tables, LoadingState::Loading);
+        let guard = LoadGuard::new(self, &tables);
+        let result = self.load_impl(&tables, &files, force).await;
+        guard.finish(&result);
+        let updated_files = result?;
+        let (game_after, bin_after) = self.counts()?;
+        Ok((
+            updated_files,
+            (game_after + bin_after).saturating_sub(game_before + bin_before),
+        ))
+    }
+
+    /// Loads both hashtables unless they already are; fails while another load is running
+    pub async fn ensure_loaded(&self) -> Result<(), String> {
+        self.ensure_tables_loaded(&HashtableType::ALL).await
+    }
+
+    /// Loads one hashtable unless it already is, leaving the other one alone
+    pub async fn ensure_table_loaded(&self, hashtable_type: HashtableType) -> Result<(), String> {
+        self.ensure_tables_loaded(&[hashtable_type]).await
+    }
+
+    #[instrument(skip_all)]
+    async fn ensure_tables_loaded(&self, tables: &[HashtableType]) -> Result<(), String> {
+        self.touch();
+        let unloaded: Vec<HashtableType> = tables
+            .iter()
+            .copied()
+            .filter(|&hashtable_type| {
+                self.table_state(hashtable_type).send_if_modified(|state| {
+                    if *state == LoadingState::Unloaded {
+                        *state = LoadingState::Loading;
+                        true
+                    } else {
+                        false
+                    }
+                })
+            })
+            .collect();
+
+        if !unloaded.is_empty() {
+            self.update_loading_state();
+            info!(
+                tables = ?unloaded.iter().map(|t| t.as_str()).collect::<Vec<_>>(),
+                "Hashtables are unloaded, loading them now..."
+            );
+            // Detached, so a caller that gives up does not abort the load others wait for
+            let engine = self.clone();
+            tokio::spawn(async move {
+                let guard = LoadGuard::new(&engine, &unloaded);
+                let result = engine.load_impl(&unloaded, &[], false).await;
+                guard.finish(&result);
+                result
+            })
+            .await
+            .map_err(|e| format!("Load failed: {}", e))??;
+        }
+
+        if tables.iter().any(|&hashtable_type| {
+            self.table_loading_state(hashtable_type) == LoadingState::Loading
+        }) {
+            return Err(LOADING_IN_PROGRESS.to_string());
+        }
+        Ok(())
+    }
+
+    /// Error of the last load, if it failed
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.read().ok().and_then(|error| error.clone())
+    }
+
+    fn finish_loading<T>(&self, tables: &[HashtableType], result: &Result<T, String>) {
+        // Idle time starts once the tables are usable, not when the load started
+        self.touch();
+        if let Ok(mut last_error) = self.last_error.write() {
+            *last_error = result.as_ref().err().cloned();
+        }
+        self.set_table_states(
+            tables,
+            if result.is_ok() {
+                LoadingState::Loaded
+            } else {
+                LoadingState::Unloaded // Reset to Unloaded on error
+            },
+        );
+        if result.is_ok()
+            && let Ok((game_count, bin_count)) = self.counts()
+        {
+            self.emit(TableEvent::Loaded {
+                game_count,
+                bin_count,
+            });
+        }
+    }
+
+    /// Empties one table, or with `custom_only` removes just the entries added at runtime,
+    /// and returns how many entries were removed. The other table stays loaded.
+    pub fn clear_table(
+        &self,
+        hashtable_type: HashtableType,
+        custom_only: bool,
+    ) -> Result<usize, String> {
+        let removed = {
+            let mut guard = self.write(hashtable_type)?;
+            if custom_only {
+                guard.clear_custom()
+            } else {
+                let removed = guard.len();
+                guard.clear();
+                guard.shrink_to_fit();
+                removed
+            }
+        };
+
+        info!(
+            hashtable_type = hashtable_type.as_str(),
+            custom_only, removed, "Cleared hashtable"
+        );
+        self.emit(TableEvent::Cleared {
+            hashtable_type,
+            custom_only,
+        });
+        Ok(removed)
+    }
+
+    fn touch(&self) {
+        // Every lookup gets here, so no lock
+        self.last_used
+            .store(self.created.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// How long since the tables were last needed
+    pub fn idle_for(&self) -> Duration {
+        let last_used = Duration::from_millis(self.last_used.load(Ordering::Relaxed));
+        self.created.elapsed().saturating_sub(last_used)
+    }
+
+    /// Unloads the tables if they are loaded and have not been needed for `timeout`;
+    /// returns the `(game, bin)` counts dropped when it did
+    pub fn unload_if_idle(&self, timeout: Duration) -> Result<Option<(usize, usize)>, String> {
+        if self.loading_state() != LoadingState::Loaded || self.idle_for() < timeout {
+            return Ok(None);
+        }
+        self.unload().map(Some)
+    }
+
+    /// Clears both tables to free memory and returns the `(game, bin)` counts dropped
+    pub fn unload(&self) -> Result<(usize, usize), String> {
+        let counts = {
+            let mut game_guard = self.write(HashtableType::Game)?;
+            let mut bin_guard = self.write(HashtableType::Bin)?;
+
+            let counts = (game_guard.len(), bin_guard.len());
+
+            game_guard.clear();
+            bin_guard.clear();
+
+            // Shrink capacity to minimize memory usage
+            game_guard.shrink_to_fit();
+            bin_guard.shrink_to_fit();
+            counts
+        };
+
+        // The next load checks for updates again
+        self.synced.store(false, Ordering::SeqCst);
+        self.set_table_states(&HashtableType::ALL, LoadingState::Unloaded);
+        self.emit(TableEvent::Unloaded);
+        Ok(counts)
+    }
+
+    /// Loads `tables` and returns how many files changed upstream. The cache is synced by the
+    /// first load after unloading; `force` downloads every file again and drops the synced
+    /// entries before loading. Naming `files` syncs only those, always, and drops the synced
+    /// entries too. Files that fail to sync or read are left out and recorded in the tables'
+    /// [`TableLoad`]; the load only fails when none could be read.
+    #[instrument(skip_all)]
+    async fn load_impl(
+        &self,
+        tables: &[HashtableType],
+        files: &[String],
+        force: bool,
+    ) -> Result<usize, String> {
+        let started = Instant::now();
+        let hash_dir = self.cache_dir()?;
+        std::fs::create_dir_all(&hash_dir)
+            .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+        self.load_progress.send_replace(LoadProgress::default());
+
+        let mut updated_files = self.sync_cache(&hash_dir, files, force).await?;
+        if self.remove_damaged_files(&hash_dir).await? > 0 {
+            updated_files += self.sync_cache(&hash_dir, &[], false).await?;
+        }
+        if force || !files.is_empty() {
+            for &hashtable_type in tables {
+                self.write(hashtable_type)?.clear_official();
+            }
+        }
+
+        // Load hashtables from directory, without another process syncing it meanwhile
+        let _cache_lock = CacheLock::shared(&hash_dir).await?;
+        info!(dir = ?hash_dir, extra_dirs = ?self.extra_dirs, "Loading hashtables");
+        let (loaded_files, read_failures) =
+            self.add_table_files(self.table_files(&hash_dir, tables), tables)?;
+        if loaded_files.is_empty() && !read_failures.is_empty() {
+            return Err(failure_message(&read_failures));
+        }
+        let sync_failures = self
+            .sync_failures
+            .read()
+            .map_err(|_| "Failed to lock sync failures".to_string())?
+            .clone();
+
+        let mut table_loads = self
+            .table_loads
+            .write()
+            .map_err(|_| "Failed to lock table loads".to_string())?;
+        for &hashtable_type in tables {
+            let files = loaded_files
+                .iter()
+                .filter(|file| file.hashtable_type == hashtable_type)
+                .cloned()
+                .collect();
+            let failed = sync_failures
+                .iter()
+                .chain(&read_failures)
+                .filter(|file| file.hashtable_type == hashtable_type)
+                .cloned()
+                .collect();
+            let table_load = TableLoad {
+                files,
+                failed,
+                duration: started.elapsed(),
+                finished_at: SystemTime::now(),
+            };
+            table_loads.insert(hashtable_type, table_load);
+        }
+        Ok(updated_files)
+    }
+
+    /// Syncs the cache dir unless it was already synced since the tables were last unloaded,
+    /// or always with `force` or named `files`, and returns how many files changed upstream.
+    /// Naming `files` syncs only those, which does not count as the cache being synced.
+    async fn sync_cache(
+        &self,
+        hash_dir: &Path,
+        files: &[String],
+        force: bool,
+    ) -> Result<usize, String> {
+        let _sync_guard = self.sync_lock.lock().await;
+        if !force && files.is_empty() && self.synced.load(Ordering::SeqCst) {
+            return Ok(0);
+        }
+        // Sync hashtables from GitHub
+        let outcome = sync_hashtables(
+            hash_dir,
+            &self.source,
+            &self.url_sources,
+            files,
+            force,
+            &self.downloader,
+        )
+        .await
+        .map_err(|e| format!("{}: {}", SYNC_FAILED, e))?;
+        let updated_files = outcome.updated_files;
+        {
+            let mut sync_failures = self
+                .sync_failures
+                .write()
+                .map_err(|_| "Failed to lock sync failures".to_string())?;
+            // Files outside a selection keep the outcome of their own last sync
+            sync_failures.retain(|file| !files.is_empty() && !files.contains(&file.file_name));
+            sync_failures.extend(outcome.failed);
+        }
+        if files.is_empty() {
+            self.synced.store(true, Ordering::SeqCst);
+        }
+        if let Some(store) = &self.store {
+            store.mark_stale();
+        }
+        self.emit(TableEvent::Synced { updated_files });
+
+        // Start a fresh report for this round of loads
+        *self
+            .load_report
+            .write()
+            .map_err(|_| "Failed to lock load report".to_string())? = LoadReport::default();
+        Ok(updated_files)
+    }
+
+    /// Removes the synced files that no longer match the cache manifest, so the next sync
+    /// downloads them again, and returns how many were
+    async fn remove_damaged_files(&self, hash_dir: &Path) -> Result<usize, String> {
+        let _sync_guard = self.sync_lock.lock().await;
+        let _cache_lock = CacheLock::exclusive(hash_dir).await?;
+        let damaged = manifest::verify(hash_dir).unwrap_or_else(|e| {
+            warn!("Failed to verify cache: {}", e);
+            Vec::new()
+        });
+        for file_name in &damaged {
+            warn!(file_name, "Cached file is damaged, downloading it again");
+            let path = hash_dir.join(file_name);
+            let _ = std::fs::remove_file(&path);
+            let _ = std::fs::remove_file(path.with_file_name(format!("{}.sha", file_name)));
+        }
+        if !damaged.is_empty() {
+            self.synced.store(false, Ordering::SeqCst);
+        }
+        Ok(damaged.len())
+    }
+
+    /// Loads the hashtable files directly inside `dir`. Subdirectories hold diffs,
+    /// snapshots and other products' caches, so they are not descended into.
+    pub fn add_from_dir(&self, dir: impl AsRef<Path>) -> Result<(), String> {
+        self.add_tables_from_dir(dir, &HashtableType::ALL)
+    }
+
+    #[instrument(skip_all, fields(dir = ?dir.as_ref()))]
+    fn add_tables_from_dir(
+        &self,
+        dir: impl AsRef<Path>,
+        tables: &[HashtableType],
+    ) -> Result<(), String> {
+        info!(dir = ?dir.as_ref(), "Loading hashtables from dir");
+        let (_, failed) = self.add_table_files(list_table_files(dir.as_ref(), tables), tables)?;
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failure_message(&failed))
+        }
+    }
+
+    /// Files of `tables` in the cache dir `hash_dir`, then in the extra dirs from the lowest
+    /// priority to the highest, then of the enabled local sources
+    fn table_files(&self, hash_dir: &Path, tables: &[HashtableType]) -> Vec<TableFile> {
+        let mut files = list_table_files(hash_dir, tables);
+        for dir in self.extra_dirs.iter().rev() {
+            if dir.is_dir() {
+                files.extend(list_table_files(dir, tables));
+            } else {
+                warn!(?dir, "Extra hashtable dir not available, skipping");
+            }
+        }
+        let local_sources = self.local_sources().unwrap_or_default();
+        for source in local_sources.into_iter().filter(|source| source.enabled) {
+            if !source.is_available() {
+                warn!(path = ?source.path, "Local source not available, skipping");
+                continue;
+            }
+            match (source.kind, source.hashtable_type) {
+                (LocalSourceKind::File, Some(hashtable_type))
+                    if tables.contains(&hashtable_type) =>
+                {
+                    let size = std::fs::metadata(&source.path)
+                        .map(|m| m.len())
+                        .unwrap_or(0);
+                    let file_name = source
+                        .path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let archive =
+                        ArchiveKind::from_file_name(&file_name).map(|kind| TableArchive {
+                            kind,
+                            members: ArchiveMembers::All,
+                        });
+                    files.push(TableFile {
+                        path: source.path,
+                        file_name,
+                        hashtable_type,
+                        size,
+                        archive,
+                    });
+                }
+                (LocalSourceKind::File, _) => {}
+                (LocalSourceKind::Directory, _) => {
+                    files.extend(list_table_files(&source.path, tables));
+                }
+            }
+        }
+        files
+    }
+
+    /// Files a lookup store builds the table from, in the order a load reads them
+    pub(crate) fn store_files(
+        &self,
+        hash_dir: &Path,
+        hashtable_type: HashtableType,
+    ) -> Vec<StoreFile> {
+        self.table_files(hash_dir, &[hashtable_type])
+            .into_iter()
+            .map(|file| StoreFile {
+                archive: file.archive,
+                ..StoreFile::new(file.path, file.file_name)
+            })
+            .collect()
+    }
+
+    /// Reads `files` into their tables and returns what was read from each, and the files
+    /// that could not be read, which the others are loaded without
+    fn add_table_files(
+        &self,
+        files: Vec<TableFile>,
+        tables: &[HashtableType],
+    ) -> Result<(Vec<LoadedFile>, Vec<FailedFile>), String> {
+        // Files are listed up front so progress can be reported against their total size
+        self.load_progress.send_modify(|progress| {
+            progress.file_count += files.len();
+            progress.total_bytes += files.iter().map(|file| file.size).sum::<u64>();
+        });
+
+        let mut loaded_files = Vec::with_capacity(files.len());
+        let mut failed_files = Vec::new();
+        for (index, table_file) in files.iter().enumerate() {
+            info!(path = ?table_file.path, "Loading hashtable");
+            self.load_progress
+                .send_modify(|progress| progress.current_file = table_file.file_name.clone());
+            let read = File::open(&table_file.path)
+                .map_err(|e| format!("Failed to open file {:?}: {}", table_file.path, e))
+                .and_then(|file| {
+                    let modified = file.metadata().and_then(|m| m.modified()).ok();
+                    let entries = self.read_file(
+                        file,
+                        table_file.archive,
+                        FileProgress {
+                            file_name: table_file.file_name.clone(),
+                            hashtable_type: table_file.hashtable_type,
+                            index,
+                            file_count: files.len(),
+                            bytes_read: 0,
+                            total_bytes: table_file.size,
+                            lines: 0,
+                            elapsed: Duration::ZERO,
+                            done: false,
+                        },
+                    )?;
+                    Ok((modified, entries))
+                });
+            self.load_progress.send_modify(|progress| {
+                progress.current_file.clear();
+                progress.files_done += 1;
+            });
+            // One unreadable file must not take the rest of its table down with it
+            let (modified, entries) = match read {
+                Ok(read) => read,
+                Err(e) => {
+                    warn!(path = ?table_file.path, "Failed to load hashtable, skipping it: {}", e);
+                    failed_files.push(FailedFile {
+                        file_name: table_file.file_name.clone(),
+                        hashtable_type: table_file.hashtable_type,
+                        stage: FailureStage::Read,
+                        error: e,
+                    });
+                    continue;
+                }
+            };
+            let sha_path = table_file
+                .path
+                .with_file_name(format!("{}.sha", table_file.file_name));
+            loaded_files.push(LoadedFile {
+                path: table_file.path.clone(),
+                file_name: table_file.file_name.clone(),
+                hashtable_type: table_file.hashtable_type,
+                sha: std::fs::read_to_string(sha_path)
+                    .ok()
+                    .map(|sha| sha.trim().to_string()),
+                modified,
+                size: table_file.size,
+                entries,
+            });
+        }
+        for &hashtable_type in tables {
+            self.write(hashtable_type)?.finish_inserts();
+        }
+
+        if failed_files.is_empty() {
+            info!("Hashtables loaded successfully");
+        } else {
+            warn!(
+                failed = failed_files.len(),
+                "Hashtables loaded with warnings"
+            );
+        }
+        Ok((loaded_files, failed_files))
+    }
+
+    /// Inserts every entry of a hashtable file as official, recording conflicts against `source`
+    #[instrument(skip(self, file))]
+    pub fn add_from_file(
+        &self,
+        file: File,
+        hashtable_type: HashtableType,
+        source: &str,
+    ) -> Result<(), String> {
+        let total_bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+        self.read_file(
+            file,
+            None,
+            FileProgress {
+                file_name: source.to_string(),
+                hashtable_type,
+                index: 0,
+                file_count: 1,
+                bytes_read: 0,
+                total_bytes,
+                lines: 0,
+                elapsed: Duration::ZERO,
+                done: false,
+            },
+        )?;
+        self.write(hashtable_type)?.finish_inserts();
+        Ok(())
+    }
+
+    /// One step of a load: reads `file`, or its part `archive`, into its table, publishing
+    /// `progress` as it goes, and returns how many entries it held. The whole file is read
+    /// before any of it is inserted, so one that fails partway leaves the table untouched.
+    fn read_file(
+        &self,
+        file: File,
+        archive: Option<TableArchive>,
+        mut progress: FileProgress,
+    ) -> Result<u64, String> {
+        let hashtable_type = progress.hashtable_type;
+        let source = progress.file_name.clone();
+        let started = Instant::now();
+        let mut count: u64 = 0;
+
+        let bytes_read = Cell::new(0);
+        let mut reported_bytes = 0;
+        let mut report_progress = |entries: u64, done: bool| {
+            let bytes = bytes_read.get();
+            self.load_progress.send_modify(|progress| {
+                progress.bytes_read += bytes - reported_bytes;
+                progress.entries += entries;
+            });
+            reported_bytes = bytes;
+            progress.bytes_read = bytes;
+            progress.lines += entries;
+            progress.elapsed = started.elapsed();
+            progress.done = done;
+            // Fails only when nobody is subscribed
+            let _ = self.file_progress.send(progress.clone());
+        };
+
+        let file = CountingReader::new(file, &bytes_read);
+        let mut entries = Vec::new();
+        read_streams(&source, file, archive, |stream| {
+            for entry in read_entries(BufReader::new(stream)) {
+                entries.push(entry?);
+                count += 1;
+                if count.is_multiple_of(PROGRESS_INTERVAL) {
+                    report_progress(PROGRESS_INTERVAL, false);
+                }
+            }
+            Ok(())
+        })?;
+
+        let mut guard = self.write(hashtable_type)?;
+        let mut report = self
+            .load_report
+            .write()
+            .map_err(|_| "Failed to lock load report".to_string())?;
+        let policy = self.conflict_policy()?;
+        if self.track_sources {
+            guard.set_source(Some(&source));
+        }
+        for (hash, path) in entries {
+            if let InsertOutcome::Conflict {
+                existing,
+                incoming,
+                replaced,
+            } = guard.insert(hash, path, EntryOrigin::Official, policy)
+            {
+                report.record_collision(Collision {
+                    hash,
+                    hashtable_type: hashtable_type.as_str(),
+                    existing_value: existing,
+                    new_value: incoming,
+                    source_file: source.clone(),
+                    replaced,
+                });
+            }
+        }
+        guard.set_source(None);
+        report_progress(count % PROGRESS_INTERVAL, true);
+
+        info!(
+            file = %source,
+            count,
+            collisions = report.collision_count,
+            elapsed = ?started.elapsed(),
+            "Loaded entries from file"
+        );
+        Ok(count)
+    }
+}
+
+/// Marks `tables` unloaded if a load is dropped before it finishes, e.g. because the client
+/// that asked for it went away, instead of leaving them loading forever
+struct LoadGuard<'a> {
+    engine: &'a HashEngine,
+    tables: &'a [HashtableType],
+    finished: bool,
+}
+
+impl<'a> LoadGuard<'a> {
+    fn new(engine: &'a HashEngine, tables: &'a [HashtableType]) -> Self {
+        LoadGuard {
+            engine,
+            tables,
+            finished: false,
+        }
+    }
+
+    fn finish<T>(mut self, result: &Result<T, String>) {
+        self.finished = true;
+        self.engine.finish_loading(self.tables, result);
+    }
+}
+
+impl Drop for LoadGuard<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            info!("Load was cancelled");
+            self.engine
+                .finish_loading::<()>(self.tables, &Err("Load was cancelled".to_string()));
+        }
+    }
+}
+
+/// A hashtable file a load reads
+pub(crate) struct TableFile {
+    pub(crate) path: PathBuf,
+    pub(crate) file_name: String,
+    hashtable_type: HashtableType,
+    size: u64,
+    /// Part of the file the table is read from when it is an archive
+    archive: Option<TableArchive>,
+}
+
+/// Hashtable files in `dir` that belong to one of `tables`, skipping the `.sha` sidecars. An
+/// archive named like a table file, e.g. `hashes.game.txt.zst`, is read whole into that table;
+/// any other `.zip` or `.tar.gz` is listed once per table, for its members named like one.
+pub(crate) fn list_table_files(dir: &Path, tables: &[HashtableType]) -> Vec<TableFile> {
+    WalkDir::new(dir)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|x| x.ok())
+        .filter(|entry| {
+            entry.file_type().is_file() && entry.path().extension().is_none_or(|ext| ext != "sha")
+        })
+        .flat_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            let path = entry.into_path();
+            let kind = ArchiveKind::from_file_name(&file_name);
+            let parts: Vec<(HashtableType, Option<TableArchive>)> =
+                match (HashtableType::from_file_name(&file_name), kind) {
+                    (Some(hashtable_type), kind) => vec![(
+                        hashtable_type,
+                        kind.map(|kind| TableArchive {
+                            kind,
+                            members: ArchiveMembers::All,
+                        }),
+                    )],
+                    (None, Some(kind)) if kind != ArchiveKind::Zstd => tables
+                        .iter()
+                        .map(|&hashtable_type| {
+                            let members = ArchiveMembers::Of(hashtable_type);
+                            (hashtable_type, Some(TableArchive { kind, members }))
+                        })
+                        .collect(),
+                    (None, _) => Vec::new(),
+                };
+            parts
+                .into_iter()
+                .filter(|(hashtable_type, _)| tables.contains(hashtable_type))
+                .map(|(hashtable_type, archive)| TableFile {
+                    path: path.clone(),
+                    file_name: file_name.clone(),
+                    hashtable_type,
+                    size,
+                    archive,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn custom_entries_survive_a_resync() {
+        let dir = std::env::temp_dir().join(format!("hash-service-resync-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("hashes.game.txt.0"),
+            "1 official/a\n2 official/b\n",
+        )
+        .unwrap();
+        let mut engine = HashEngine::new().with_cache_dir(Some(dir.clone()));
+        // Nothing listens there, so the sync fails and every file keeps its copy
+        engine.downloader.api_url = "http://127.0.0.1:1".to_string();
+
+        engine.load().await.unwrap();
+        let replaced = engine.add_with_hash(HashtableType::Game, 1, "custom/a".to_string(), None);
+        let (added, _) = engine
+            .add(HashtableType::Game, "custom/c".to_string())
+            .unwrap();
+        let resynced = engine.resync().await;
+        let game = engine.read(HashtableType::Game).unwrap();
+        let values = [1, 2, added].map(|hash| game.get(&hash).map(str::to_string));
+        let shadowed = game.shadowed(&1).cloned();
+        let custom = game.custom_len();
+        drop(game);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(
+            replaced,
+            Ok(InsertOutcome::Conflict { replaced: true, .. })
+        ));
+        resynced.unwrap();
+        assert_eq!(
+            values.map(Option::unwrap),
+            ["custom/a", "official/b", "custom/c"]
+        );
+        assert_eq!(shadowed.as_deref(), Some("official/a"));
+        assert_eq!(custom, 2);
+    }
+}
@@ -0,0 +1,34 @@
+//! Notifications of changes to the tables, for clients that cache lookups.
+use crate::engine::HashtableType;
+
+/// Events buffered per subscriber before the oldest are dropped
+pub const EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableEvent {
+    /// The tables were (re)loaded from the cache dir
+    Loaded {
+        game_count: usize,
+        bin_count: usize,
+    },
+    Unloaded,
+    /// Upstream hashtables were checked; `updated_files` were replaced
+    Synced {
+        updated_files: usize,
+    },
+    /// An entry was added or replaced at runtime
+    Added {
+        hashtable_type: HashtableType,
+        hash: u64,
+        value: String,
+    },
+    /// A table was emptied, or only its custom entries removed
+    Cleared {
+        hashtable_type: HashtableType,
+        custom_only: bool,
+    },
+    /// The tables were replaced by a snapshot
+    Restored {
+        snapshot: String,
+    },
+}
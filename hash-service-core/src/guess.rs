@@ -0,0 +1,355 @@
+//! Template based hash guessing: expands patterns like
+//! `data/characters/{champion}/skins/skin{0..100}.bin` and keeps the candidates whose hash
+//! is one of the targets.
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::{info, instrument};
+
+use crate::engine::{HashEngine, HashtableType};
+
+/// Candidates tried between checks for cancellation
+const CANCEL_CHECK_INTERVAL: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    /// `{name}`: every word of the named wordlist
+    Words(String),
+    /// `{0..100}`, inclusive; `{00..99}` pads to the width of the bounds
+    Range {
+        start: u64,
+        end: u64,
+        width: usize,
+    },
+    /// `{a,b,c}`
+    Choice(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .map(|close| open + close)
+                .ok_or_else(|| format!("Unclosed '{{' in template '{}'", template))?;
+            parts.push(Self::parse_placeholder(&rest[open + 1..close], template)?);
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Template { parts })
+    }
+
+    fn parse_placeholder(placeholder: &str, template: &str) -> Result<Part, String> {
+        if let Some((start, end)) = placeholder.split_once("..") {
+            let invalid = || format!("Invalid range '{{{}}}' in '{}'", placeholder, template);
+            let width = if start.len() > 1 && start.starts_with('0') {
+                start.len().max(end.len())
+            } else {
+                0
+            };
+            let start: u64 = start.parse().map_err(|_| invalid())?;
+            let end: u64 = end.parse().map_err(|_| invalid())?;
+            if start > end {
+                return Err(invalid());
+            }
+            Ok(Part::Range { start, end, width })
+        } else if placeholder.contains(',') {
+            Ok(Part::Choice(
+                placeholder.split(',').map(str::to_string).collect(),
+            ))
+        } else if !placeholder.is_empty()
+            && placeholder
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            Ok(Part::Words(placeholder.to_string()))
+        } else {
+            Err(format!(
+                "Invalid placeholder '{{{}}}' in '{}'",
+                placeholder, template
+            ))
+        }
+    }
+
+    /// Names of the wordlists the template uses
+    pub fn wordlists(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Words(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Number of candidates the template expands to, saturating on overflow
+    pub fn count(&self, wordlists: &HashMap<String, Vec<String>>) -> u64 {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(_) => 1,
+                Part::Words(name) => wordlists.get(name).map_or(0, |words| words.len() as u64),
+                Part::Range { start, end, .. } => (end - start).saturating_add(1),
+                Part::Choice(choices) => choices.len() as u64,
+            })
+            .fold(1u64, u64::saturating_mul)
+    }
+
+    /// Calls `f` with every candidate until it breaks
+    pub fn expand(
+        &self,
+        wordlists: &HashMap<String, Vec<String>>,
+        f: &mut impl FnMut(&str) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let mut candidate = String::new();
+        self.expand_from(0, wordlists, &mut candidate, f)
+    }
+
+    fn expand_from(
+        &self,
+        index: usize,
+        wordlists: &HashMap<String, Vec<String>>,
+        candidate: &mut String,
+        f: &mut impl FnMut(&str) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let Some(part) = self.parts.get(index) else {
+            return f(candidate);
+        };
+        let len = candidate.len();
+        let mut next = |value: &str, candidate: &mut String| {
+            candidate.push_str(value);
+            let flow = self.expand_from(index + 1, wordlists, candidate, f);
+            candidate.truncate(len);
+            flow
+        };
+        match part {
+            Part::Literal(value) => next(value, candidate),
+            Part::Words(name) => {
+                for word in wordlists.get(name).into_iter().flatten() {
+                    next(word, candidate)?;
+                }
+                ControlFlow::Continue(())
+            }
+            Part::Range { start, end, width } => {
+                for n in *start..=*end {
+                    next(&format!("{:0width$}", n, width = *width), candidate)?;
+                }
+                ControlFlow::Continue(())
+            }
+            Part::Choice(choices) => {
+                for choice in choices {
+                    next(choice, candidate)?;
+                }
+                ControlFlow::Continue(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GuessReport {
+    pub candidates: u64,
+    /// Targets that were cracked, added to the table as custom entries
+    pub cracked: Vec<(u64, String)>,
+}
+
+/// Expands every template and adds the candidates whose hash is in `targets` and not
+/// already known. Fails up front if the templates expand to more than `max_candidates`, and
+/// without adding anything once `cancelled` is raised.
+#[instrument(skip_all, fields(templates = templates.len(), targets = targets.len()))]
+pub fn guess(
+    engine: &HashEngine,
+    hashtable_type: HashtableType,
+    templates: &[Template],
+    wordlists: &HashMap<String, Vec<String>>,
+    mut targets: HashSet<u64>,
+    max_candidates: u64,
+    cancelled: &AtomicBool,
+) -> Result<GuessReport, String> {
+    let total = templates
+        .iter()
+        .map(|template| template.count(wordlists))
+        .fold(0u64, u64::saturating_add);
+    if total > max_candidates {
+        return Err(format!(
+            "Templates expand to {} candidates, more than the limit of {}",
+            total, max_candidates
+        ));
+    }
+
+    {
+        let table = engine.read(hashtable_type)?;
+        targets.retain(|hash| !table.contains(hash));
+    }
+
+    let mut report = GuessReport::default();
+    let mut cracked = Vec::new();
+    for template in templates {
+        let flow = template.expand(wordlists, &mut |candidate| {
+            if report.candidates.is_multiple_of(CANCEL_CHECK_INTERVAL)
+                && cancelled.load(Ordering::Relaxed)
+            {
+                return ControlFlow::Break(());
+            }
+            report.candidates += 1;
+            let hash = hashtable_type.hash(candidate);
+            if targets.remove(&hash) {
+                cracked.push((hash, candidate.to_string()));
+            }
+            ControlFlow::Continue(())
+        });
+        if flow.is_break() {
+            info!(candidates = report.candidates, "Guessing was cancelled");
+            return Err("Guessing was cancelled".to_string());
+        }
+    }
+
+    for (hash, value) in cracked {
+        engine.add(hashtable_type, value.clone())?;
+        report.cracked.push((hash, value));
+    }
+    info!(
+        candidates = report.candidates,
+        cracked = report.cracked.len(),
+        "Finished guessing hashes"
+    );
+    Ok(report)
+}
+
+/// One word per line; blank lines and `#` comments are skipped
+pub fn read_wordlist(path: &Path) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read wordlist {:?}: {}", path, e))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(template: &str, wordlists: &HashMap<String, Vec<String>>) -> Vec<String> {
+        let mut candidates = Vec::new();
+        let _ = Template::parse(template)
+            .unwrap()
+            .expand(wordlists, &mut |candidate| {
+                candidates.push(candidate.to_string());
+                ControlFlow::Continue(())
+            });
+        candidates
+    }
+
+    #[test]
+    fn expands_ranges_choices_and_words_in_order() {
+        let wordlists = HashMap::from([(
+            "champion".to_string(),
+            vec!["aatrox".to_string(), "ahri".to_string()],
+        )]);
+        assert_eq!(
+            expand("{champion}/skin{1..2}.{bin,tex}", &wordlists),
+            [
+                "aatrox/skin1.bin",
+                "aatrox/skin1.tex",
+                "aatrox/skin2.bin",
+                "aatrox/skin2.tex",
+                "ahri/skin1.bin",
+                "ahri/skin1.tex",
+                "ahri/skin2.bin",
+                "ahri/skin2.tex",
+            ]
+        );
+        assert_eq!(expand("plain", &wordlists), ["plain"]);
+        assert!(expand("{missing}.bin", &wordlists).is_empty());
+    }
+
+    #[test]
+    fn zero_padded_ranges_keep_their_width() {
+        let wordlists = HashMap::new();
+        assert_eq!(
+            expand("skin{08..11}", &wordlists),
+            ["skin08", "skin09", "skin10", "skin11"]
+        );
+        assert_eq!(expand("{9..10}", &wordlists), ["9", "10"]);
+        assert_eq!(expand("{0..0}", &wordlists), ["0"]);
+    }
+
+    #[test]
+    fn counts_what_it_expands() {
+        let wordlists = HashMap::from([("words".to_string(), vec!["a".to_string(); 3])]);
+        for template in [
+            "{words}/{0..9}{x,y}",
+            "{00..99}",
+            "{missing}{1..5}",
+            "plain",
+        ] {
+            assert_eq!(
+                Template::parse(template).unwrap().count(&wordlists),
+                expand(template, &wordlists).len() as u64,
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for template in ["skin{0..2", "{5..1}", "{a..b}", "{}", "{a b}"] {
+            assert!(Template::parse(template).is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn refuses_templates_over_the_candidate_limit() {
+        let engine = HashEngine::new();
+        let wordlists = HashMap::new();
+        let guess_with = |templates: &[&str], max_candidates| {
+            let templates: Vec<Template> = templates
+                .iter()
+                .map(|template| Template::parse(template).unwrap())
+                .collect();
+            let targets = HashSet::from([HashtableType::Game.hash("skin3.bin")]);
+            guess(
+                &engine,
+                HashtableType::Game,
+                &templates,
+                &wordlists,
+                targets,
+                max_candidates,
+                &AtomicBool::new(false),
+            )
+        };
+
+        // Counted over all templates together
+        let over = guess_with(&["skin{0..5}.bin", "skin{6..9}.bin"], 9);
+        assert!(over.unwrap_err().contains("10 candidates"));
+        // Saturates rather than overflowing
+        let huge = guess_with(&["{0..18446744073709551615}{0..9}"], u64::MAX - 1);
+        assert!(huge.unwrap_err().contains(&u64::MAX.to_string()));
+
+        let report = guess_with(&["skin{0..5}.bin", "skin{6..9}.bin"], 10).unwrap();
+        assert_eq!(report.candidates, 10);
+        assert_eq!(
+            report.cracked,
+            [(
+                HashtableType::Game.hash("skin3.bin"),
+                "skin3.bin".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,41 @@
+//! Hashing algorithms used by League of Legends: the two built-in hashtables use XXH64 and
+//! FNV-1a, the others are available to custom tables.
+use xxhash_rust::xxh3::xxh3_64;
+use xxhash_rust::xxh64::xxh64;
+
+const FNV1A_OFFSET_BASIS: u32 = 0x811C9DC5;
+const FNV1A_PRIME: u32 = 0x01000193;
+
+/// Hash of a game asset path: XXH64 of the lowercased path
+pub fn game_hash(path: &str) -> u64 {
+    xxh64(path.to_lowercase().as_bytes(), 0)
+}
+
+/// Hash of a bin entry, type or field name: 32-bit FNV-1a of the lowercased name
+pub fn bin_hash(name: &str) -> u32 {
+    let mut hash = FNV1A_OFFSET_BASIS;
+    for &byte in name.to_lowercase().as_bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(FNV1A_PRIME);
+    }
+    hash
+}
+
+/// XXH3 (64-bit) of the lowercased string, as used by newer string tables
+pub fn xxh3_hash(value: &str) -> u64 {
+    xxh3_64(value.to_lowercase().as_bytes())
+}
+
+/// ELF hash of the lowercased string, as used by inibin and troybin keys
+pub fn elf_hash(value: &str) -> u32 {
+    let mut hash: u32 = 0;
+    for &byte in value.to_lowercase().as_bytes() {
+        hash = (hash << 4).wrapping_add(byte as u32);
+        let high = hash & 0xF000_0000;
+        if high != 0 {
+            hash ^= high >> 24;
+        }
+        hash &= !high;
+    }
+    hash
+}
@@ -0,0 +1,70 @@
+use fst::automaton::{Automaton, Str};
+use fst::{IntoStreamer, Map, Streamer};
+
+/// Sorted index over lowercased table values for prefix and range queries.
+///
+/// Keys are `lowercase(value) \0 hash` so values shared by several hashes stay unique,
+/// and each key maps back to its hash.
+#[derive(Debug)]
+pub struct FstIndex {
+    map: Map<Vec<u8>>,
+}
+
+pub fn index_key(value: &str, hash: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(value.len() + 9);
+    key.extend(value.bytes().map(|b| b.to_ascii_lowercase()));
+    key.push(0);
+    key.extend_from_slice(&hash.to_be_bytes());
+    key
+}
+
+impl FstIndex {
+    pub fn build<'a>(entries: impl Iterator<Item = (&'a u64, &'a str)>) -> Self {
+        let mut keys: Vec<(Vec<u8>, u64)> = entries
+            .map(|(hash, value)| (index_key(value, *hash), *hash))
+            .collect();
+        keys.sort_unstable();
+
+        let map = Map::from_iter(keys).expect("index keys are sorted and unique");
+        FstIndex { map }
+    }
+
+    /// Hashes whose value starts with `prefix`, in key order after `after`
+    pub fn prefix(&self, prefix: &str, after: Option<&[u8]>, limit: usize) -> Vec<u64> {
+        let prefix = prefix.to_ascii_lowercase();
+        let automaton = Str::new(&prefix).starts_with();
+        let mut builder = self.map.search(automaton);
+        if let Some(after) = after {
+            builder = builder.gt(after);
+        }
+        collect_hashes(builder.into_stream(), limit)
+    }
+
+    /// Hashes whose value lies in `[start, end)`, in key order after `after`
+    pub fn range(&self, start: &str, end: &str, after: Option<&[u8]>, limit: usize) -> Vec<u64> {
+        let start = start.to_ascii_lowercase();
+        let end = end.to_ascii_lowercase();
+        let mut builder = self.map.range().ge(start.as_bytes());
+        if !end.is_empty() {
+            builder = builder.lt(end.as_bytes());
+        }
+        if let Some(after) = after {
+            builder = builder.gt(after);
+        }
+        collect_hashes(builder.into_stream(), limit)
+    }
+}
+
+fn collect_hashes<S>(mut stream: S, limit: usize) -> Vec<u64>
+where
+    S: for<'a> Streamer<'a, Item = (&'a [u8], u64)>,
+{
+    let mut hashes = Vec::new();
+    while hashes.len() < limit {
+        match stream.next() {
+            Some((_, hash)) => hashes.push(hash),
+            None => break,
+        }
+    }
+    hashes
+}
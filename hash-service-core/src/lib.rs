@@ -0,0 +1,45 @@
+//! Hashtable engine behind the League Toolkit hash service: in-memory storage of the
+//! CommunityDragon game and bin hashtables, parsing of their files, the hashing
+//! algorithms and syncing the files from GitHub.
+//!
+//! [`HashEngine`] is the entry point; the gRPC server is a thin wrapper around it.
+pub mod archive;
+pub mod clean;
+pub mod custom;
+#[cfg(feature = "db-backend")]
+pub mod db;
+pub mod diff;
+pub mod disk;
+pub mod document;
+mod engine;
+pub mod events;
+pub mod guess;
+pub mod hash;
+#[cfg(feature = "fst-index")]
+mod index;
+pub mod local;
+pub mod lock;
+pub mod manifest;
+pub mod misses;
+pub mod normalize;
+pub mod parse;
+pub mod perfect;
+pub mod progress;
+pub mod relocate;
+mod report;
+pub mod scan;
+pub mod search;
+pub mod snapshot;
+pub mod sorted;
+pub mod store;
+pub mod sync;
+mod table;
+mod throttle;
+pub mod tree;
+
+pub use engine::{
+    CachedFile, HashEngine, HashtableType, LOADING_IN_PROGRESS, LoadingState, LookupEntry,
+    SYNC_FAILED,
+};
+pub use report::{Collision, FailedFile, FailureStage, LoadReport, LoadedFile, TableLoad};
+pub use table::{ConflictPolicy, EntryOrigin, Hashtable, InsertOutcome, TableLayout};
@@ -0,0 +1,102 @@
+//! Hashtable files and directories on the user's machine read on every load next to the synced
+//! cache. Sources registered at runtime are kept in [`LOCAL_SOURCES_FILE_NAME`] in the cache
+//! dir; ones from the config file are added on startup and never saved there.
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::HashtableType;
+
+/// Registered sources inside the cache directory; named so it is never loaded as a table
+pub const LOCAL_SOURCES_FILE_NAME: &str = "local_sources.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocalSourceKind {
+    /// Every table file directly inside, named like the synced ones
+    Directory,
+    /// One file of any name, read into `hashtable_type`
+    File,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalSource {
+    pub path: PathBuf,
+    pub kind: LocalSourceKind,
+    /// Table a file source is read into; `None` for directories
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hashtable_type: Option<HashtableType>,
+    pub enabled: bool,
+    /// Comes from the config file, so it is not saved and cannot be removed at runtime
+    #[serde(skip)]
+    pub from_config: bool,
+}
+
+impl LocalSource {
+    pub fn directory(path: PathBuf) -> Self {
+        LocalSource {
+            path,
+            kind: LocalSourceKind::Directory,
+            hashtable_type: None,
+            enabled: true,
+            from_config: false,
+        }
+    }
+
+    pub fn file(path: PathBuf, hashtable_type: HashtableType) -> Self {
+        LocalSource {
+            path,
+            kind: LocalSourceKind::File,
+            hashtable_type: Some(hashtable_type),
+            enabled: true,
+            from_config: false,
+        }
+    }
+
+    /// Whether the directory or file is there right now; a share can go offline
+    pub fn is_available(&self) -> bool {
+        match self.kind {
+            LocalSourceKind::Directory => self.path.is_dir(),
+            LocalSourceKind::File => self.path.is_file(),
+        }
+    }
+
+    /// Checks a source before registering it
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.path.is_absolute() {
+            return Err(format!("Source path {:?} must be absolute", self.path));
+        }
+        if !self.is_available() {
+            let kind = match self.kind {
+                LocalSourceKind::Directory => "directory",
+                LocalSourceKind::File => "file",
+            };
+            return Err(format!("No {} at {:?}", kind, self.path));
+        }
+        Ok(())
+    }
+}
+
+/// Sources registered in `dir`, empty when none were
+pub fn load(dir: &Path) -> Result<Vec<LocalSource>, String> {
+    let path = dir.join(LOCAL_SOURCES_FILE_NAME);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+    serde_json::from_str(&text).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+}
+
+/// Saves the sources of `sources` registered at runtime into `dir`
+pub fn save(dir: &Path, sources: &[LocalSource]) -> Result<(), String> {
+    let registered: Vec<&LocalSource> = sources
+        .iter()
+        .filter(|source| !source.from_config)
+        .collect();
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create cache directory {:?}: {}", dir, e))?;
+    let path = dir.join(LOCAL_SOURCES_FILE_NAME);
+    let text = serde_json::to_string_pretty(&registered).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
@@ -0,0 +1,68 @@
+//! Advisory lock on a cache directory, shared between processes. Syncing holds it exclusively
+//! and loading holds it shared, so a second service instance (or a script taking the same
+//! lock, e.g. with `flock`) never reads or writes a half-synced cache.
+use std::fs::{File, TryLockError};
+use std::path::Path;
+
+use tracing::info;
+
+/// Lock file inside the cache directory; named so it is never loaded as a table
+pub const CACHE_LOCK_FILE_NAME: &str = "cache.lock";
+
+/// Released when dropped
+#[derive(Debug)]
+pub struct CacheLock {
+    _file: File,
+}
+
+impl CacheLock {
+    /// Waits until no other process reads or writes `dir`
+    pub async fn exclusive(dir: &Path) -> Result<CacheLock, String> {
+        Self::acquire(dir, true).await
+    }
+
+    /// Waits until no other process writes `dir`
+    pub async fn shared(dir: &Path) -> Result<CacheLock, String> {
+        Self::acquire(dir, false).await
+    }
+
+    async fn acquire(dir: &Path, exclusive: bool) -> Result<CacheLock, String> {
+        let path = dir.join(CACHE_LOCK_FILE_NAME);
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open lock file {:?}: {}", path, e))?;
+
+        let attempt = if exclusive {
+            file.try_lock()
+        } else {
+            file.try_lock_shared()
+        };
+        match attempt {
+            Ok(()) => return Ok(CacheLock { _file: file }),
+            Err(TryLockError::WouldBlock) => {
+                info!(?path, "Cache is in use by another process, waiting");
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(format!("Failed to lock {:?}: {}", path, e));
+            }
+        }
+
+        // Blocking until the other process is done can take a whole sync
+        let locked = tokio::task::spawn_blocking(move || {
+            let result = if exclusive {
+                file.lock()
+            } else {
+                file.lock_shared()
+            };
+            result.map(|()| file)
+        })
+        .await
+        .map_err(|e| format!("Failed to lock {:?}: {}", path, e))?;
+        locked
+            .map(|file| CacheLock { _file: file })
+            .map_err(|e| format!("Failed to lock {:?}: {}", path, e))
+    }
+}
@@ -0,0 +1,101 @@
+//! Sizes and content hashes of the synced files, recorded by each sync and checked before each
+//! load, so a file bit rot or an antivirus quarantine damaged is downloaded again instead of
+//! loading partially.
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use xxhash_rust::xxh64::Xxh64;
+
+/// Manifest inside the cache directory; named so it is never loaded as a table
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub file_name: String,
+    pub size: u64,
+    /// XXH64 of the content, in hex
+    pub xxh64: String,
+}
+
+impl ManifestEntry {
+    /// Hashes the file `file_name` in `dir`
+    fn read(dir: &Path, file_name: &str) -> Result<Self, String> {
+        let path = dir.join(file_name);
+        let read_error = |e: std::io::Error| format!("Failed to read {:?}: {}", path, e);
+        let mut file = File::open(&path).map_err(read_error)?;
+        let mut hasher = Xxh64::new(0);
+        let mut buf = vec![0; 64 * 1024];
+        let mut size = 0;
+        loop {
+            let read = file.read(&mut buf).map_err(read_error)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+            size += read as u64;
+        }
+        Ok(ManifestEntry {
+            file_name: file_name.to_string(),
+            size,
+            xxh64: format!("{:016x}", hasher.digest()),
+        })
+    }
+}
+
+/// Entries of the manifest in `dir`, empty before the first sync that wrote one
+pub fn load(dir: &Path) -> Result<Vec<ManifestEntry>, String> {
+    let path = dir.join(MANIFEST_FILE_NAME);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+    serde_json::from_str(&text).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+}
+
+fn save(dir: &Path, entries: &[ManifestEntry]) -> Result<(), String> {
+    let path = dir.join(MANIFEST_FILE_NAME);
+    let text = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Records the synced files `file_names` in the manifest in `dir`: those in `changed`, and
+/// those it has no entry for yet, are hashed, and entries of other files are dropped
+pub(crate) fn update(dir: &Path, file_names: &[String], changed: &[String]) -> Result<(), String> {
+    let known = load(dir)?;
+    let mut entries = Vec::new();
+    for file_name in file_names {
+        if !dir.join(file_name).exists() {
+            continue;
+        }
+        match known.iter().find(|entry| &entry.file_name == file_name) {
+            Some(entry) if !changed.contains(file_name) => entries.push(entry.clone()),
+            _ => entries.push(ManifestEntry::read(dir, file_name)?),
+        }
+    }
+    entries.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    if entries != known {
+        save(dir, &entries)?;
+    }
+    Ok(())
+}
+
+/// Names of the files in `dir` that are missing or no longer match the manifest
+pub fn verify(dir: &Path) -> Result<Vec<String>, String> {
+    let mut damaged = Vec::new();
+    for entry in load(dir)? {
+        // Sizes first, so a truncated file is caught without hashing it
+        let matches = std::fs::metadata(dir.join(&entry.file_name))
+            .is_ok_and(|metadata| metadata.len() == entry.size)
+            && ManifestEntry::read(dir, &entry.file_name)
+                .is_ok_and(|actual| actual.size == entry.size && actual.xxh64 == entry.xxh64);
+        if !matches {
+            damaged.push(entry.file_name);
+        }
+    }
+    debug!(?dir, damaged = damaged.len(), "Verified cache");
+    Ok(damaged)
+}
@@ -0,0 +1,116 @@
+//! Hashes a table recently did not have. Scanners that poll the same unknown hashes in a
+//! loop get their answer here instead of contending for the table's lock.
+use std::collections::{HashMap, VecDeque};
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_MISS_CACHE_SIZE: usize = 4096;
+pub const DEFAULT_MISS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Bounded set of missed hashes; the oldest are evicted first and entries expire after `ttl`
+#[derive(Debug)]
+pub struct MissCache {
+    capacity: usize,
+    ttl: Duration,
+    /// Bumped by every clear, so a miss read from the table before a write is not recorded
+    /// after it
+    generation: AtomicU64,
+    /// Lookups only read it; expired entries are dropped by the next insert
+    entries: RwLock<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// When each hash was missed, and the sequence number of its `order` entry
+    missed: HashMap<u64, (Instant, u64)>,
+    /// Hashes by when they were recorded; entries whose sequence number no longer matches
+    /// `missed` are stale
+    order: VecDeque<(u64, u64)>,
+    next_seq: u64,
+}
+
+impl MissCache {
+    /// A `capacity` of 0 disables the cache
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        MissCache {
+            capacity,
+            ttl,
+            generation: AtomicU64::new(0),
+            entries: RwLock::new(Entries::default()),
+        }
+    }
+
+    /// Whether `hash` was recorded as missing within the TTL
+    pub fn contains(&self, hash: u64) -> bool {
+        if self.capacity == 0 {
+            return false;
+        }
+        let Ok(entries) = self.entries.read() else {
+            return false;
+        };
+        entries
+            .missed
+            .get(&hash)
+            .is_some_and(|(at, _)| at.elapsed() < self.ttl)
+    }
+
+    /// Taken before reading the table, to pass to [`MissCache::insert`]
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Records a miss read from the table while it was at `generation`; dropped if the table
+    /// was written since
+    pub fn insert(&self, hash: u64, generation: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        let Ok(mut entries) = self.entries.write() else {
+            return;
+        };
+        if self.generation() != generation {
+            return;
+        }
+        let entries = &mut *entries;
+        // Entries are queued in the order they were recorded, so the expired ones are in front
+        while let Some(&(oldest, seq)) = entries.order.front() {
+            match entries.missed.get(&oldest) {
+                Some((at, s)) if *s == seq && at.elapsed() < self.ttl => break,
+                Some((_, s)) if *s == seq => {
+                    entries.missed.remove(&oldest);
+                }
+                _ => {}
+            }
+            entries.order.pop_front();
+        }
+        // Stale entries count towards the bound too, so the queue cannot outgrow the map
+        while entries.missed.len() >= self.capacity || entries.order.len() >= self.capacity * 2 {
+            let Some((oldest, seq)) = entries.order.pop_front() else {
+                break;
+            };
+            if entries.missed.get(&oldest).is_some_and(|(_, s)| *s == seq) {
+                entries.missed.remove(&oldest);
+            }
+        }
+        let seq = entries.next_seq;
+        entries.next_seq += 1;
+        entries.missed.insert(hash, (Instant::now(), seq));
+        entries.order.push_back((hash, seq));
+    }
+
+    /// Forgets every miss; the table changed, so any of them may be found now
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.write() {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+            entries.missed.clear();
+            entries.order.clear();
+        }
+    }
+}
+
+impl Default for MissCache {
+    fn default() -> Self {
+        MissCache::new(DEFAULT_MISS_CACHE_SIZE, DEFAULT_MISS_CACHE_TTL)
+    }
+}
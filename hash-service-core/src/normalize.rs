@@ -0,0 +1,93 @@
+//! Canonical form of strings added at runtime, so clients that format the same path
+//! differently don't create near-identical entries.
+use crate::engine::HashtableType;
+
+/// Longest value accepted; real paths and names are far shorter
+pub const MAX_VALUE_LEN: usize = 4096;
+
+/// Trims whitespace, turns backslashes into forward slashes and lowercases game paths.
+/// Bin names keep their case like the upstream files do; their hash ignores it anyway.
+/// Empty, overlong and control-character strings are rejected.
+pub fn normalize(hashtable_type: HashtableType, value: &str) -> Result<String, String> {
+    let value = validate(value)?.replace('\\', "/");
+    Ok(match hashtable_type {
+        HashtableType::Game => value.to_lowercase(),
+        HashtableType::Bin => value,
+    })
+}
+
+/// Trims whitespace and rejects empty, overlong and control-character strings, leaving
+/// the rest alone; used for custom tables, whose conventions the service doesn't know
+pub fn validate(value: &str) -> Result<&str, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err("Value is empty".to_string());
+    }
+    if value.len() > MAX_VALUE_LEN {
+        return Err(format!("Value is longer than {} bytes", MAX_VALUE_LEN));
+    }
+    if value.chars().any(char::is_control) {
+        return Err("Value contains control characters".to_string());
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: [&str; 4] = [
+        "  DATA\\Characters\\Aatrox\\Skins\\Base\\Aatrox.bin ",
+        "data/characters/aatrox/aatrox.bin",
+        "Characters/Aatrox/CharacterRecords/Root",
+        "mSpellCalculations",
+    ];
+
+    #[test]
+    fn game_paths_are_trimmed_slashed_and_lowercased() {
+        assert_eq!(
+            normalize(HashtableType::Game, VALUES[0]).unwrap(),
+            "data/characters/aatrox/skins/base/aatrox.bin"
+        );
+    }
+
+    #[test]
+    fn bin_names_keep_their_case() {
+        assert_eq!(
+            normalize(HashtableType::Bin, " Characters\\Aatrox ").unwrap(),
+            "Characters/Aatrox"
+        );
+    }
+
+    #[test]
+    fn normalizing_is_idempotent() {
+        for hashtable_type in HashtableType::ALL {
+            for value in VALUES {
+                let once = normalize(hashtable_type, value).unwrap();
+                assert_eq!(normalize(hashtable_type, &once).unwrap(), once);
+            }
+        }
+    }
+
+    #[test]
+    fn bin_values_are_hashed_lowercased() {
+        for value in VALUES {
+            let normalized = normalize(HashtableType::Bin, value).unwrap();
+            assert_eq!(
+                HashtableType::Bin.hash(&normalized),
+                HashtableType::Bin.hash(&normalized.to_lowercase())
+            );
+        }
+        // FNV-1a of "mspellcalculations"
+        assert_eq!(HashtableType::Bin.hash("mSpellCalculations"), 0x94572284);
+    }
+
+    #[test]
+    fn rejects_empty_overlong_and_control_values() {
+        for hashtable_type in HashtableType::ALL {
+            assert!(normalize(hashtable_type, "   ").is_err());
+            assert!(normalize(hashtable_type, &"a".repeat(MAX_VALUE_LEN + 1)).is_err());
+            assert!(normalize(hashtable_type, "a\nb").is_err());
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! Parsing of CommunityDragon hashtable files, one `<hex hash> <value>` pair per line.
+use std::io::BufRead;
+
+/// Splits a line into its hash and value; the value may itself contain spaces
+pub fn parse_line(line: &str) -> Result<(u64, &str), String> {
+    let (hash, value) = line.split_once(' ').unwrap_or((line, ""));
+    let hash = u64::from_str_radix(hash, 16)
+        .map_err(|e| format!("Failed to convert hash '{}': {}", hash, e))?;
+    Ok((hash, value))
+}
+
+/// Parses every line of a hashtable file. A read error, e.g. invalid UTF-8 or a truncated
+/// archive, is an error item like a malformed line, not the end of the file.
+pub fn read_entries(reader: impl BufRead) -> impl Iterator<Item = Result<(u64, String), String>> {
+    reader.lines().map(|line| {
+        let line = line.map_err(|e| format!("Failed to read line: {}", e))?;
+        parse_line(&line).map(|(hash, value)| (hash, value.to_string()))
+    })
+}
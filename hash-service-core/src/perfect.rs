@@ -0,0 +1,407 @@
+//! A table layout for tables that only change on sync: after each load the entries are indexed
+//! by a minimal perfect hash function, so a lookup is a few bit probes and one slot read. Entries
+//! added later sit in a small side map until the next load folds them in.
+//!
+//! The function follows BBHash: keys are hashed into a bit array twice their number, keys that
+//! landed alone keep their bit and the rest retry on the next, smaller level. A key's slot is
+//! the rank of its bit across all levels, about 3 bits per key plus half that again to rank them.
+use std::collections::HashMap;
+use std::time::Instant;
+
+use tracing::info;
+
+use crate::sorted::ValueBlob;
+
+/// Bits per key on each level; larger builds faster and needs fewer levels, for more memory
+const GAMMA: f64 = 2.0;
+/// Levels tried before the last few keys are stored in a sorted fallback list
+const MAX_LEVELS: u64 = 32;
+/// Offset of an entry that was removed or overridden since the build
+const REMOVED: u32 = u32::MAX;
+
+fn level_hash(key: u64, level: u64) -> u64 {
+    // splitmix64 finalizer, seeded per level
+    let mut x = key ^ level.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[derive(Debug, Default)]
+struct Mphf {
+    /// Bits of every level back to back
+    bits: Vec<u64>,
+    /// Set bits in the words before each word
+    ranks: Vec<u32>,
+    /// First word and bit length of each level
+    levels: Vec<(usize, u64)>,
+    /// Keys no level could place, sorted; their slots follow the ranked ones
+    fallback: Vec<u64>,
+}
+
+impl Mphf {
+    fn build(keys: Vec<u64>) -> Self {
+        let mut mphf = Mphf::default();
+        let mut remaining = keys;
+        let mut level = 0;
+        while !remaining.is_empty() && level < MAX_LEVELS {
+            let words = ((remaining.len() as f64 * GAMMA) as usize)
+                .div_ceil(64)
+                .max(1);
+            let len = words as u64 * 64;
+            let mut seen = vec![0u64; words];
+            let mut collided = vec![0u64; words];
+            for &key in &remaining {
+                let bit = level_hash(key, level) % len;
+                let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+                if seen[word] & mask != 0 {
+                    collided[word] |= mask;
+                }
+                seen[word] |= mask;
+            }
+            remaining.retain(|&key| {
+                let bit = level_hash(key, level) % len;
+                collided[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+            });
+            mphf.levels.push((mphf.bits.len(), len));
+            mphf.bits.extend(
+                seen.iter()
+                    .zip(&collided)
+                    .map(|(seen, collided)| seen & !collided),
+            );
+            level += 1;
+        }
+        mphf.bits.shrink_to_fit();
+        remaining.sort_unstable();
+        remaining.shrink_to_fit();
+        mphf.fallback = remaining;
+
+        let mut rank = 0;
+        mphf.ranks = mphf
+            .bits
+            .iter()
+            .map(|word| {
+                let before = rank;
+                rank += word.count_ones();
+                before
+            })
+            .collect();
+        mphf
+    }
+
+    fn ranked(&self) -> usize {
+        match (self.ranks.last(), self.bits.last()) {
+            (Some(rank), Some(word)) => (rank + word.count_ones()) as usize,
+            _ => 0,
+        }
+    }
+
+    /// Slot of `key` if it was one of the keys built from, otherwise any slot or none
+    fn index(&self, key: u64) -> Option<usize> {
+        for (level, &(first_word, len)) in self.levels.iter().enumerate() {
+            let bit = level_hash(key, level as u64) % len;
+            let word = first_word + (bit / 64) as usize;
+            let mask = 1u64 << (bit % 64);
+            if self.bits[word] & mask != 0 {
+                let rank = self.ranks[word] + (self.bits[word] & (mask - 1)).count_ones();
+                return Some(rank as usize);
+            }
+        }
+        self.fallback
+            .binary_search(&key)
+            .ok()
+            .map(|position| self.ranked() + position)
+    }
+
+    fn memory_estimate(&self) -> usize {
+        self.bits.capacity() * size_of::<u64>()
+            + self.ranks.capacity() * size_of::<u32>()
+            + self.levels.capacity() * size_of::<(usize, u64)>()
+            + self.fallback.capacity() * size_of::<u64>()
+    }
+}
+
+/// Value of an entry a build places: where it is in the old blob, or an added one
+enum BuildValue {
+    Built(u32),
+    Added(String),
+}
+
+#[derive(Debug, Default)]
+pub struct PerfectEntries {
+    mphf: Mphf,
+    /// Hash in each slot, to tell the built keys from other hashes
+    keys: Vec<u64>,
+    /// Offset of each slot's value in `values`, or [`REMOVED`]
+    offsets: Vec<u32>,
+    values: ValueBlob,
+    /// Entries inserted since the last build; a slot they override is marked removed
+    side: HashMap<u64, String>,
+    /// Slots marked removed since the last build
+    removed: usize,
+    len: usize,
+}
+
+impl PerfectEntries {
+    fn slot(&self, hash: u64) -> Option<usize> {
+        self.mphf
+            .index(hash)
+            .filter(|&slot| self.keys[slot] == hash && self.offsets[slot] != REMOVED)
+    }
+
+    pub fn get(&self, hash: &u64) -> Option<&str> {
+        match self.side.get(hash) {
+            Some(value) => Some(value),
+            None => self
+                .slot(*hash)
+                .map(|slot| self.values.get(self.offsets[slot])),
+        }
+    }
+
+    pub fn contains_key(&self, hash: &u64) -> bool {
+        self.side.contains_key(hash) || self.slot(*hash).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&u64, &str)> {
+        self.keys
+            .iter()
+            .zip(&self.offsets)
+            .filter(|(_, offset)| **offset != REMOVED)
+            .map(|(hash, offset)| (hash, self.values.get(*offset)))
+            .chain(self.side.iter().map(|(hash, value)| (hash, value.as_str())))
+    }
+
+    fn remove_slot(&mut self, hash: u64) -> Option<String> {
+        let slot = self.slot(hash)?;
+        let existing = self.values.get(self.offsets[slot]).to_string();
+        self.offsets[slot] = REMOVED;
+        self.removed += 1;
+        Some(existing)
+    }
+
+    /// Sets the value of `hash`, returning the one it replaces
+    pub fn insert(&mut self, hash: u64, value: String) -> Option<String> {
+        let existing = self
+            .side
+            .insert(hash, value)
+            .or_else(|| self.remove_slot(hash));
+        if existing.is_none() {
+            self.len += 1;
+        }
+        existing
+    }
+
+    pub fn remove(&mut self, hash: &u64) -> Option<String> {
+        let existing = self.side.remove(hash).or_else(|| self.remove_slot(*hash))?;
+        self.len -= 1;
+        Some(existing)
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&u64) -> bool) {
+        self.side.retain(|hash, _| keep(hash));
+        for (hash, offset) in self.keys.iter().zip(&mut self.offsets) {
+            if *offset != REMOVED && !keep(hash) {
+                *offset = REMOVED;
+                self.removed += 1;
+            }
+        }
+        self.len = self.iter().count();
+        self.build();
+    }
+
+    pub fn clear(&mut self) {
+        *self = PerfectEntries::default();
+    }
+
+    /// Rebuilds the function over every entry, emptying the side map
+    pub fn build(&mut self) {
+        if self.side.is_empty() && self.removed == 0 {
+            return;
+        }
+        let started = Instant::now();
+        let old = std::mem::take(self);
+        // Values of the side map are moved, not copied, and each is freed once it is in the
+        // new blob, so a load does not hold its entries twice
+        let mut entries: Vec<(u64, BuildValue)> = old
+            .keys
+            .iter()
+            .zip(&old.offsets)
+            .filter(|(_, offset)| **offset != REMOVED)
+            .map(|(hash, offset)| (*hash, BuildValue::Built(*offset)))
+            .chain(
+                old.side
+                    .into_iter()
+                    .map(|(hash, value)| (hash, BuildValue::Added(value))),
+            )
+            .collect();
+        entries.sort_unstable_by_key(|(hash, _)| *hash);
+
+        let mphf = Mphf::build(entries.iter().map(|(hash, _)| *hash).collect());
+        let mut keys = vec![0; entries.len()];
+        let mut offsets = vec![REMOVED; entries.len()];
+        let mut values = ValueBlob::default();
+        for (hash, value) in entries {
+            let slot = mphf.index(hash).expect("every built key has a slot");
+            keys[slot] = hash;
+            offsets[slot] = match value {
+                BuildValue::Built(offset) => values.push(old.values.get(offset)),
+                BuildValue::Added(value) => values.push(&value),
+            };
+        }
+        values.shrink_to_fit();
+
+        *self = PerfectEntries {
+            mphf,
+            keys,
+            offsets,
+            values,
+            side: HashMap::new(),
+            removed: 0,
+            len: old.len,
+        };
+        info!(
+            entries = self.len,
+            fallback = self.mphf.fallback.len(),
+            elapsed = ?started.elapsed(),
+            "Built perfect hash"
+        );
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.build();
+        self.side.shrink_to_fit();
+    }
+
+    /// Heap bytes held
+    pub fn memory_estimate(&self) -> usize {
+        // Key, value and the control byte of each side map slot
+        let slot = size_of::<u64>() + size_of::<String>() + 1;
+        let side: usize = self.side.values().map(String::capacity).sum();
+        self.mphf.memory_estimate()
+            + self.keys.capacity() * size_of::<u64>()
+            + self.offsets.capacity() * size_of::<u32>()
+            + self.values.capacity()
+            + self.side.capacity() * slot
+            + side
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    /// `count` distinct, well spread keys
+    fn keys(count: u64) -> Vec<u64> {
+        (0..count).map(|i| level_hash(i, u64::MAX)).collect()
+    }
+
+    #[test]
+    fn every_key_gets_its_own_slot() {
+        for count in [0, 1, 2, 63, 64, 65, 1000, 100_000] {
+            let keys = keys(count);
+            let mphf = Mphf::build(keys.clone());
+            let slots: HashSet<usize> = keys
+                .iter()
+                .map(|&key| mphf.index(key).expect("built key has a slot"))
+                .collect();
+            assert_eq!(slots.len(), keys.len(), "{} keys", count);
+            assert!(
+                slots.iter().all(|&slot| slot < keys.len()),
+                "{} keys",
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn fallback_keys_follow_the_ranked_ones() {
+        let keys = keys(1000);
+        let mut mphf = Mphf::build(keys.clone());
+        // Move the last level's keys to the fallback, as if no level could place them
+        let (first_word, _) = mphf.levels.pop().unwrap();
+        mphf.bits.truncate(first_word);
+        mphf.ranks.truncate(first_word);
+        let placed = |key: &u64| {
+            mphf.levels
+                .iter()
+                .enumerate()
+                .any(|(level, &(first_word, len))| {
+                    let bit = level_hash(*key, level as u64) % len;
+                    mphf.bits[first_word + (bit / 64) as usize] & (1 << (bit % 64)) != 0
+                })
+        };
+        let mut fallback: Vec<u64> = keys.iter().copied().filter(|key| !placed(key)).collect();
+        fallback.sort_unstable();
+        assert!(!fallback.is_empty());
+        mphf.fallback = fallback;
+
+        let slots: HashSet<usize> = keys.iter().filter_map(|&key| mphf.index(key)).collect();
+        assert_eq!(slots.len(), keys.len());
+        assert!(slots.iter().all(|&slot| slot < keys.len()));
+    }
+
+    fn built(count: u64) -> PerfectEntries {
+        let mut entries = PerfectEntries::default();
+        for key in keys(count) {
+            entries.insert(key, format!("value {:x}", key));
+        }
+        entries.build();
+        entries
+    }
+
+    #[test]
+    fn built_entries_resolve_and_absent_ones_miss() {
+        let entries = built(10_000);
+        assert_eq!(entries.len(), 10_000);
+        assert!(entries.side.is_empty());
+        for key in keys(10_000) {
+            assert_eq!(entries.get(&key), Some(format!("value {:x}", key).as_str()));
+        }
+        for absent in (10_000..20_000).map(|i| level_hash(i, u64::MAX)) {
+            assert_eq!(entries.get(&absent), None);
+            assert!(!entries.contains_key(&absent));
+        }
+        assert_eq!(entries.iter().count(), 10_000);
+    }
+
+    #[test]
+    fn changes_after_a_build_survive_the_next() {
+        let mut entries = built(1000);
+        let keys = keys(1000);
+        assert_eq!(
+            entries.insert(keys[0], "replaced".to_string()),
+            Some(format!("value {:x}", keys[0]))
+        );
+        assert_eq!(
+            entries.remove(&keys[1]),
+            Some(format!("value {:x}", keys[1]))
+        );
+        assert_eq!(entries.insert(1, "added".to_string()), None);
+        assert_eq!(entries.len(), 1000);
+
+        entries.build();
+        assert_eq!(entries.len(), 1000);
+        assert_eq!(entries.get(&keys[0]), Some("replaced"));
+        assert_eq!(entries.get(&keys[1]), None);
+        assert_eq!(entries.get(&1), Some("added"));
+        assert_eq!(
+            entries.get(&keys[2]),
+            Some(format!("value {:x}", keys[2]).as_str())
+        );
+
+        entries.retain(|&hash| hash != 1);
+        assert_eq!(entries.len(), 999);
+        assert_eq!(entries.get(&1), None);
+        assert_eq!(entries.iter().count(), 999);
+    }
+}
@@ -0,0 +1,122 @@
+//! Progress of a running load or sync, so front ends can tell a slow one from a hung one.
+use std::cell::Cell;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::time::Duration;
+
+use crate::engine::HashtableType;
+
+/// Entries read between two progress updates
+pub(crate) const PROGRESS_INTERVAL: u64 = 64 * 1024;
+/// File updates buffered per subscriber before the oldest are dropped
+pub const FILE_PROGRESS_CAPACITY: usize = 256;
+/// Time between two updates of a download
+pub(crate) const DOWNLOAD_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How far the current load has got; reset when a load starts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    /// File being read; empty before the first file and after the last
+    pub current_file: String,
+    pub files_done: usize,
+    pub file_count: usize,
+    pub bytes_read: u64,
+    /// Combined size of all files; 0 while syncing
+    pub total_bytes: u64,
+    pub entries: u64,
+}
+
+/// Progress through one file of a load, published while it is read and once it is done
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileProgress {
+    pub file_name: String,
+    pub hashtable_type: HashtableType,
+    /// Position of the file among those the load reads, from 0
+    pub index: usize,
+    pub file_count: usize,
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+    /// Lines read so far; every line is one entry
+    pub lines: u64,
+    /// Time since the file was opened
+    pub elapsed: Duration,
+    pub done: bool,
+}
+
+impl FileProgress {
+    /// Share of the file read, 0 to 100
+    pub fn percent(&self) -> f64 {
+        if self.total_bytes == 0 {
+            0.0
+        } else {
+            self.bytes_read as f64 * 100.0 / self.total_bytes as f64
+        }
+    }
+}
+
+/// Progress through one file of a sync, published while it downloads and once it is done
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadProgress {
+    pub file_name: String,
+    /// Position of the file among those the sync checks, from 0
+    pub index: usize,
+    pub file_count: usize,
+    pub bytes_downloaded: u64,
+    /// Content-Length of the download; 0 when the server did not send one
+    pub total_bytes: u64,
+    /// Time since the download started
+    pub elapsed: Duration,
+    pub done: bool,
+    /// The cached copy was current, so nothing was downloaded
+    pub up_to_date: bool,
+    /// Why the file could not be synced; the sync went on with its previous copy
+    pub error: Option<String>,
+}
+
+impl DownloadProgress {
+    /// Average speed of the download so far
+    pub fn bytes_per_second(&self) -> u64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            0
+        } else {
+            (self.bytes_downloaded as f64 / secs) as u64
+        }
+    }
+}
+
+impl LoadProgress {
+    /// Share of the bytes read, 0 to 100
+    pub fn percent(&self) -> f64 {
+        if self.total_bytes == 0 {
+            0.0
+        } else {
+            self.bytes_read as f64 * 100.0 / self.total_bytes as f64
+        }
+    }
+}
+
+/// Counts the bytes read through it into `count`
+pub(crate) struct CountingReader<'a, R> {
+    inner: R,
+    count: &'a Cell<u64>,
+}
+
+impl<'a, R> CountingReader<'a, R> {
+    pub(crate) fn new(inner: R, count: &'a Cell<u64>) -> Self {
+        CountingReader { inner, count }
+    }
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for CountingReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
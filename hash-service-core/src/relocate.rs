@@ -0,0 +1,73 @@
+//! Moving a cache dir to another location, e.g. another drive. Only what belongs to the dir's
+//! own product and branch moves; the caches of others nested inside it stay where they are.
+use std::path::Path;
+
+use tracing::info;
+use walkdir::WalkDir;
+
+use crate::diff::DIFF_DIR_NAME;
+use crate::disk::LOOKUP_DIR_NAME;
+use crate::lock::{CACHE_LOCK_FILE_NAME, CacheLock};
+use crate::snapshot::SNAPSHOT_DIR_NAME;
+
+/// Moves the files of the cache dir `from` into `to`, replacing any of the same name there,
+/// and returns how many entries moved. `from` is removed once nothing else is left in it.
+pub async fn move_cache_dir(from: &Path, to: &Path) -> Result<usize, String> {
+    std::fs::create_dir_all(to)
+        .map_err(|e| format!("Failed to create cache directory {:?}: {}", to, e))?;
+    let mut moved = 0;
+    {
+        let _from_lock = CacheLock::exclusive(from).await?;
+        let _to_lock = CacheLock::exclusive(to).await?;
+        let entries = std::fs::read_dir(from)
+            .map_err(|e| format!("Failed to read cache directory {:?}: {}", from, e))?;
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            let owned = if is_dir {
+                [DIFF_DIR_NAME, SNAPSHOT_DIR_NAME, LOOKUP_DIR_NAME]
+                    .iter()
+                    .any(|owned| name == *owned)
+            } else {
+                name != CACHE_LOCK_FILE_NAME
+            };
+            if owned {
+                move_path(&entry.path(), &to.join(&name))?;
+                moved += 1;
+            }
+        }
+    }
+    // Only once the lock is released, as an open file cannot be removed on Windows
+    let _ = std::fs::remove_file(from.join(CACHE_LOCK_FILE_NAME));
+    let _ = std::fs::remove_dir(from);
+    info!(?from, ?to, moved, "Moved cache directory");
+    Ok(moved)
+}
+
+/// Renames `from` to `to`, copying instead when they are on different drives
+fn move_path(from: &Path, to: &Path) -> Result<(), String> {
+    let move_error = |e: std::io::Error| format!("Failed to move {:?} to {:?}: {}", from, to, e);
+    if to.is_dir() {
+        std::fs::remove_dir_all(to).map_err(move_error)?;
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    for entry in WalkDir::new(from) {
+        let entry = entry.map_err(|e| format!("Failed to read {:?}: {}", from, e))?;
+        let target = match entry.path().strip_prefix(from) {
+            Ok(relative) if !relative.as_os_str().is_empty() => to.join(relative),
+            _ => to.to_path_buf(),
+        };
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target).map_err(move_error)?;
+        } else {
+            std::fs::copy(entry.path(), &target).map_err(move_error)?;
+        }
+    }
+    if from.is_dir() {
+        std::fs::remove_dir_all(from).map_err(move_error)
+    } else {
+        std::fs::remove_file(from).map_err(move_error)
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::engine::HashtableType;
+
+/// Upper bound on collisions kept in memory; further ones are only counted.
+const MAX_RECORDED_COLLISIONS: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct Collision {
+    pub hash: u64,
+    pub hashtable_type: &'static str,
+    pub existing_value: String,
+    pub new_value: String,
+    pub source_file: String,
+    pub replaced: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+    pub collisions: Vec<Collision>,
+    pub collision_count: u64,
+    pub kept_existing_count: u64,
+    pub replaced_count: u64,
+}
+
+impl LoadReport {
+    pub fn record_collision(&mut self, collision: Collision) {
+        self.collision_count += 1;
+        if collision.replaced {
+            self.replaced_count += 1;
+        } else {
+            self.kept_existing_count += 1;
+        }
+        if self.collisions.len() < MAX_RECORDED_COLLISIONS {
+            self.collisions.push(collision);
+        }
+    }
+}
+
+/// A file a load read into a table
+#[derive(Debug, Clone)]
+pub struct LoadedFile {
+    pub path: PathBuf,
+    pub file_name: String,
+    pub hashtable_type: HashtableType,
+    /// Blob SHA from the `.sha` file next to it, which synced files have
+    pub sha: Option<String>,
+    /// Modification time when it was read
+    pub modified: Option<SystemTime>,
+    pub size: u64,
+    /// Entries read from it, including those that collided with an earlier file's
+    pub entries: u64,
+}
+
+/// What went wrong with a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureStage {
+    /// It could not be brought up to date, so the copy from before is read, if there is one
+    Sync,
+    /// It could not be read to the end, so none of its entries were loaded
+    Read,
+}
+
+/// A file a load went on without
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedFile {
+    pub file_name: String,
+    pub hashtable_type: HashtableType,
+    pub stage: FailureStage,
+    pub error: String,
+}
+
+/// One line naming each of `failed` and what went wrong with it
+pub(crate) fn failure_message(failed: &[FailedFile]) -> String {
+    failed
+        .iter()
+        .map(|file| format!("{}: {}", file.file_name, file.error))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Where a table's entries came from, as of its last successful load
+#[derive(Debug, Clone)]
+pub struct TableLoad {
+    /// In the order they were read, so later files win conflicts under `ConflictPolicy::KeepLast`
+    pub files: Vec<LoadedFile>,
+    /// Files of the table that could not be synced or read; the load went on without them
+    pub failed: Vec<FailedFile>,
+    /// How long the load took, syncing included
+    pub duration: Duration,
+    pub finished_at: SystemTime,
+}
@@ -0,0 +1,329 @@
+//! Hash discovery: collects the hashes game files reference (WAD chunk tables, bin entry
+//! paths) and the strings they embed, and adds every string whose hash was unknown.
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, info, instrument, warn};
+use walkdir::WalkDir;
+
+use crate::engine::{HashEngine, HashtableType};
+use crate::hash::{bin_hash, game_hash};
+
+/// Larger non-WAD files are skipped; bins are far smaller
+const MAX_SCAN_FILE_SIZE: u64 = 64 * 1024 * 1024;
+/// Shortest printable run treated as a candidate string
+const MIN_STRING_LEN: usize = 4;
+/// WAD v3 keeps its chunk table right after a fixed 272 byte header
+const WAD_V3_TOC_OFFSET: u64 = 272;
+const WAD_V3_ENTRY_SIZE: u64 = 32;
+
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    pub files_scanned: usize,
+    /// Files that could not be read or parsed, and were left out
+    pub files_skipped: usize,
+    /// Distinct strings found in the files
+    pub candidates: usize,
+    /// Referenced hashes missing from the tables before the scan
+    pub unknown: usize,
+    /// Newly added `(table, hash, value)` entries
+    pub discovered: Vec<(HashtableType, u64, String)>,
+}
+
+#[derive(Debug, Default)]
+struct Findings {
+    game_hashes: HashSet<u64>,
+    bin_hashes: HashSet<u64>,
+    strings: HashSet<String>,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Chunk path hashes from a WAD's table of contents, reading only the header and table. The
+/// table must fit in the file, so a bogus entry count fails instead of allocating for it.
+fn wad_chunk_hashes(file: &mut File) -> Result<Vec<u64>, String> {
+    let truncated = || "WAD header is truncated".to_string();
+    let mut header = [0u8; WAD_V3_TOC_OFFSET as usize];
+    file.seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_exact(&mut header))
+        .map_err(|_| truncated())?;
+
+    let (toc_offset, entry_size, count) = match header[2] {
+        1 => (
+            read_u16(&header, 4).ok_or_else(truncated)? as u64,
+            read_u16(&header, 6).ok_or_else(truncated)? as u64,
+            read_u32(&header, 8).ok_or_else(truncated)?,
+        ),
+        2 => (
+            read_u16(&header, 96).ok_or_else(truncated)? as u64,
+            read_u16(&header, 98).ok_or_else(truncated)? as u64,
+            read_u32(&header, 100).ok_or_else(truncated)?,
+        ),
+        3 => (
+            WAD_V3_TOC_OFFSET,
+            WAD_V3_ENTRY_SIZE,
+            read_u32(&header, 268).ok_or_else(truncated)?,
+        ),
+        version => return Err(format!("Unsupported WAD version {}", version)),
+    };
+    if entry_size < 8 {
+        return Err(format!(
+            "WAD chunk entries of {} bytes are too small",
+            entry_size
+        ));
+    }
+
+    let file_len = file
+        .metadata()
+        .map_err(|e| format!("Failed to read WAD: {}", e))?
+        .len();
+    let toc_len = entry_size
+        .checked_mul(count as u64)
+        .filter(|len| {
+            toc_offset
+                .checked_add(*len)
+                .is_some_and(|end| end <= file_len)
+        })
+        .ok_or_else(|| format!("WAD chunk table of {} entries overruns the file", count))?;
+    let mut toc = vec![0u8; toc_len as usize];
+    file.seek(SeekFrom::Start(toc_offset))
+        .and_then(|_| file.read_exact(&mut toc))
+        .map_err(|e| format!("Failed to read WAD chunk table: {}", e))?;
+    Ok(toc
+        .chunks_exact(entry_size as usize)
+        .filter_map(|entry| read_u64(entry, 0))
+        .collect())
+}
+
+/// Entry path hashes from a `PROP` bin (optionally wrapped in a `PTCH` header); none for
+/// other files
+fn bin_entry_hashes(data: &[u8]) -> Result<Vec<u64>, String> {
+    let offset = match data.get(..4) {
+        Some(b"PTCH") => 16,
+        _ => 0,
+    };
+    if data.get(offset..offset + 4) != Some(b"PROP") {
+        return Ok(Vec::new());
+    }
+    prop_entry_hashes(data, offset + 4).ok_or_else(|| "Bin entries are truncated".to_string())
+}
+
+fn prop_entry_hashes(data: &[u8], mut offset: usize) -> Option<Vec<u64>> {
+    let version = read_u32(data, offset)?;
+    offset += 4;
+    if version >= 2 {
+        let linked = read_u32(data, offset)?;
+        offset += 4;
+        for _ in 0..linked {
+            offset = offset.checked_add(2 + read_u16(data, offset)? as usize)?;
+        }
+    }
+    let count = read_u32(data, offset)? as usize;
+    offset += 4;
+    // Each entry has a 4 byte type hash up front and at least a length and a path hash
+    // after, so a count the remaining data can't hold is rejected before allocating
+    if count.checked_mul(12)? > data.len().checked_sub(offset)? {
+        return None;
+    }
+    // Skip the entry type hashes
+    offset += count * 4;
+
+    let mut hashes = Vec::with_capacity(count);
+    for _ in 0..count {
+        let length = read_u32(data, offset)? as usize;
+        hashes.push(read_u32(data, offset + 4)? as u64);
+        offset = offset.checked_add(4 + length)?;
+    }
+    Some(hashes)
+}
+
+/// Runs of printable ASCII, which include the length-prefixed strings bins store
+fn printable_strings(data: &[u8], strings: &mut HashSet<String>) {
+    for run in data.split(|byte| !(0x20..0x7f).contains(byte)) {
+        if run.len() >= MIN_STRING_LEN
+            && let Ok(value) = std::str::from_utf8(run)
+        {
+            strings.insert(value.trim().to_string());
+        }
+    }
+}
+
+fn scan_file(path: &Path, findings: &mut Findings) -> Result<(), String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mut magic = [0u8; 4];
+    if file.read_exact(&mut magic).is_err() {
+        return Ok(());
+    }
+
+    if &magic[..2] == b"RW" {
+        let hashes = wad_chunk_hashes(&mut file).map_err(|e| format!("{:?}: {}", path, e))?;
+        debug!(?path, chunks = hashes.len(), "Read WAD chunk table");
+        findings.game_hashes.extend(hashes);
+        return Ok(());
+    }
+
+    let size = file
+        .metadata()
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?
+        .len();
+    if size > MAX_SCAN_FILE_SIZE {
+        debug!(?path, size, "Skipping large file");
+        return Ok(());
+    }
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    findings
+        .bin_hashes
+        .extend(bin_entry_hashes(&data).map_err(|e| format!("{:?}: {}", path, e))?);
+    printable_strings(&data, &mut findings.strings);
+    Ok(())
+}
+
+/// Scans every file under `paths` and adds the strings that resolve a referenced hash the
+/// tables didn't know yet. Game paths are tried as written and with `.dds` swapped for `.tex`.
+/// An unreadable or corrupt file is logged and skipped rather than failing the scan.
+#[instrument(skip_all, fields(paths = paths.len()))]
+pub fn discover(engine: &HashEngine, paths: &[PathBuf]) -> Result<ScanReport, String> {
+    let mut report = ScanReport::default();
+    let mut findings = Findings::default();
+    for path in paths {
+        for entry in WalkDir::new(path).into_iter() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("Failed to walk {:?}: {}", path, e);
+                    report.files_skipped += 1;
+                    continue;
+                }
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            match scan_file(entry.path(), &mut findings) {
+                Ok(()) => report.files_scanned += 1,
+                Err(e) => {
+                    warn!("Skipping file: {}", e);
+                    report.files_skipped += 1;
+                }
+            }
+        }
+    }
+    report.candidates = findings.strings.len();
+
+    let mut unknown_game = findings.game_hashes;
+    {
+        let game = engine.read(HashtableType::Game)?;
+        unknown_game.retain(|hash| !game.contains(hash));
+    }
+    let mut unknown_bin = findings.bin_hashes;
+    {
+        let bin = engine.read(HashtableType::Bin)?;
+        unknown_bin.retain(|hash| !bin.contains(hash));
+    }
+    report.unknown = unknown_game.len() + unknown_bin.len();
+
+    for value in &findings.strings {
+        let lowercase = value.to_lowercase();
+        let mut paths = vec![lowercase.clone()];
+        if let Some(stem) = lowercase.strip_suffix(".dds") {
+            paths.push(format!("{}.tex", stem));
+        }
+        for path in paths {
+            let hash = game_hash(&path);
+            if unknown_game.remove(&hash) {
+                engine.add(HashtableType::Game, path.clone())?;
+                report.discovered.push((HashtableType::Game, hash, path));
+            }
+        }
+
+        let hash = bin_hash(value) as u64;
+        if unknown_bin.remove(&hash) {
+            engine.add(HashtableType::Bin, value.clone())?;
+            report
+                .discovered
+                .push((HashtableType::Bin, hash, value.clone()));
+        }
+    }
+
+    info!(
+        files = report.files_scanned,
+        skipped = report.files_skipped,
+        unknown = report.unknown,
+        discovered = report.discovered.len(),
+        "Finished hash discovery scan"
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prop(count: u32, entries: &[u32]) -> Vec<u8> {
+        let mut data = b"PROP".to_vec();
+        data.extend(1u32.to_le_bytes());
+        data.extend(count.to_le_bytes());
+        data.extend(entries.iter().flat_map(|_| 0u32.to_le_bytes()));
+        for hash in entries {
+            data.extend(4u32.to_le_bytes());
+            data.extend(hash.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn reads_bin_entry_hashes() {
+        assert_eq!(
+            bin_entry_hashes(&prop(2, &[0xaa, 0xbb])).unwrap(),
+            [0xaa, 0xbb]
+        );
+        assert!(bin_entry_hashes(b"not a bin").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_entry_counts_the_bin_cannot_hold() {
+        assert!(bin_entry_hashes(&prop(u32::MAX, &[0xaa])).is_err());
+        assert!(bin_entry_hashes(&prop(3, &[0xaa, 0xbb])).is_err());
+    }
+
+    #[test]
+    fn rejects_wad_chunk_tables_past_the_end_of_the_file() {
+        let path =
+            std::env::temp_dir().join(format!("hash-service-scan-{}.wad", std::process::id()));
+        let mut header = vec![0u8; WAD_V3_TOC_OFFSET as usize];
+        header[..3].copy_from_slice(b"RW\x03");
+        let write = |count: u32, chunks: &[u64]| {
+            let mut data = header.clone();
+            data[268..272].copy_from_slice(&count.to_le_bytes());
+            for hash in chunks {
+                data.extend(hash.to_le_bytes());
+                data.extend([0u8; WAD_V3_ENTRY_SIZE as usize - 8]);
+            }
+            std::fs::write(&path, data).unwrap();
+            File::open(&path).unwrap()
+        };
+
+        let chunks = wad_chunk_hashes(&mut write(2, &[1, 2]));
+        let overrun = wad_chunk_hashes(&mut write(u32::MAX, &[1, 2]));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(chunks.unwrap(), [1, 2]);
+        assert!(overrun.is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+pub const DEFAULT_SCAN_TIMEOUT: Duration = Duration::from_secs(5);
+pub const MAX_SCAN_TIMEOUT: Duration = Duration::from_secs(30);
+/// Entries per message on streaming search responses
+pub const STREAM_CHUNK_SIZE: usize = 100;
+/// How many entries are scanned between deadline checks
+const DEADLINE_CHECK_INTERVAL: usize = 4096;
+
+#[derive(Debug, Default)]
+pub struct Page {
+    pub entries: Vec<(u64, String)>,
+    pub next_page_token: String,
+}
+
+#[cfg(not(feature = "fst-index"))]
+pub fn starts_with_ignore_case(value: &str, prefix: &str) -> bool {
+    value.len() >= prefix.len()
+        && value.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+}
+
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub entries: Vec<(u64, String)>,
+    pub truncated: bool,
+    pub timed_out: bool,
+}
+
+pub fn scan_timeout(timeout_ms: u32) -> Duration {
+    match timeout_ms {
+        0 => DEFAULT_SCAN_TIMEOUT,
+        ms => Duration::from_millis(ms as u64).min(MAX_SCAN_TIMEOUT),
+    }
+}
+
+/// Collects entries matching `predicate`, stopping at `limit` matches or once `timeout` elapses.
+pub fn scan_limited<'a>(
+    entries: impl Iterator<Item = (&'a u64, &'a str)>,
+    predicate: impl Fn(&str) -> bool,
+    limit: u32,
+    timeout: Duration,
+) -> ScanResult {
+    let limit = page_limit(limit);
+    let deadline = Instant::now() + timeout;
+    let mut result = ScanResult::default();
+
+    for (scanned, (hash, value)) in entries.enumerate() {
+        if scanned % DEADLINE_CHECK_INTERVAL == 0 && Instant::now() >= deadline {
+            result.timed_out = true;
+            break;
+        }
+        if predicate(value) {
+            if result.entries.len() == limit {
+                result.truncated = true;
+                break;
+            }
+            result.entries.push((*hash, value.to_string()));
+        }
+    }
+
+    result.entries.sort_unstable_by(|a, b| a.1.cmp(&b.1));
+    result
+}
+
+/// Page tokens are the (value, hash) cursor of the last returned entry,
+/// encoded as 16 hex digits of the hash followed by the value.
+pub fn page_limit(limit: u32) -> usize {
+    match limit as usize {
+        0 => DEFAULT_PAGE_SIZE,
+        n => n.min(MAX_PAGE_SIZE),
+    }
+}
+
+pub fn encode_page_token(hash: u64, value: &str) -> String {
+    format!("{:016x}{}", hash, value)
+}
+
+pub fn decode_page_token(token: &str) -> Result<(u64, &str), String> {
+    if token.len() < 16 || !token.is_char_boundary(16) {
+        return Err("Malformed page token".to_string());
+    }
+    let (hash, value) = token.split_at(16);
+    let hash = u64::from_str_radix(hash, 16).map_err(|_| "Malformed page token".to_string())?;
+    Ok((hash, value))
+}
+
+/// Sorts matches by value and returns the page following `page_token`.
+pub fn paginate(
+    mut matches: Vec<(u64, &str)>,
+    page_token: &str,
+    limit: u32,
+) -> Result<Page, String> {
+    let limit = page_limit(limit);
+
+    matches.sort_unstable_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(&b.0)));
+
+    let start = if page_token.is_empty() {
+        0
+    } else {
+        let (hash, value) = decode_page_token(page_token)?;
+        matches.partition_point(|(h, v)| (*v, *h) <= (value, hash))
+    };
+
+    let entries: Vec<(u64, String)> = matches[start..]
+        .iter()
+        .take(limit)
+        .map(|(hash, value)| (*hash, value.to_string()))
+        .collect();
+
+    let next_page_token = if start + entries.len() < matches.len() {
+        entries
+            .last()
+            .map(|(hash, value)| encode_page_token(*hash, value))
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+
+    Ok(Page {
+        entries,
+        next_page_token,
+    })
+}
@@ -0,0 +1,344 @@
+//! Named copies of the loaded tables, custom entries included, for rolling back bad imports
+//! without a re-sync. Stored as `<name>.snap` in the `snapshots` dir of the cache:
+//!
+//! ```text
+//! "HSNP" | version u32 | created_at u64 | game count u64 | bin count u64
+//! then per entry, game table first: hash u64 | custom u8 | value length u32 | UTF-8 value
+//! ```
+//!
+//! Integers are little-endian.
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::table::Hashtable;
+
+/// Subdirectory of the cache dir holding snapshots; skipped when loading hashtables
+pub const SNAPSHOT_DIR_NAME: &str = "snapshots";
+const SNAPSHOT_EXTENSION: &str = "snap";
+const MAGIC: &[u8; 4] = b"HSNP";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone)]
+pub struct SnapshotInfo {
+    pub name: String,
+    /// Unix seconds
+    pub created_at: u64,
+    pub game_count: u64,
+    pub bin_count: u64,
+    pub size_bytes: u64,
+}
+
+/// Snapshot names become file names, so only `[A-Za-z0-9._-]` is allowed
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid snapshot name '{}', use letters, digits, '.', '_' and '-'",
+            name
+        ))
+    }
+}
+
+pub fn snapshot_dir(cache_dir: &Path) -> PathBuf {
+    cache_dir.join(SNAPSHOT_DIR_NAME)
+}
+
+fn snapshot_path(cache_dir: &Path, name: &str) -> Result<PathBuf, String> {
+    validate_name(name)?;
+    Ok(snapshot_dir(cache_dir).join(format!("{}.{}", name, SNAPSHOT_EXTENSION)))
+}
+
+fn write_table(writer: &mut impl Write, table: &Hashtable) -> std::io::Result<()> {
+    for (hash, value) in table.iter() {
+        writer.write_all(&hash.to_le_bytes())?;
+        writer.write_all(&[table.is_custom(hash) as u8])?;
+        writer.write_all(&(value.len() as u32).to_le_bytes())?;
+        writer.write_all(value.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes both tables to the named snapshot, replacing any snapshot of that name
+pub fn save(
+    cache_dir: &Path,
+    name: &str,
+    game: &Hashtable,
+    bin: &Hashtable,
+) -> Result<SnapshotInfo, String> {
+    let path = snapshot_path(cache_dir, name)?;
+    let dir = snapshot_dir(cache_dir);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    // Written next to the target and renamed so a failed write keeps the old snapshot
+    let tmp_path = path.with_extension("tmp");
+    let write = || -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&created_at.to_le_bytes())?;
+        writer.write_all(&(game.len() as u64).to_le_bytes())?;
+        writer.write_all(&(bin.len() as u64).to_le_bytes())?;
+        write_table(&mut writer, game)?;
+        write_table(&mut writer, bin)?;
+        writer.into_inner()?.sync_all()?;
+        std::fs::rename(&tmp_path, &path)
+    };
+    if let Err(e) = write() {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(format!("Failed to write snapshot {:?}: {}", path, e));
+    }
+    info_of(&path)
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// `(created_at, game count, bin count)`
+fn read_header(reader: &mut impl Read) -> Result<(u64, u64, u64), String> {
+    let mut magic = [0; 4];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|e| format!("Failed to read snapshot header: {}", e))?;
+    if &magic != MAGIC {
+        return Err("Not a snapshot file".to_string());
+    }
+    let mut header = || -> std::io::Result<_> {
+        Ok((
+            read_u32(reader)?,
+            read_u64(reader)?,
+            read_u64(reader)?,
+            read_u64(reader)?,
+        ))
+    };
+    let (version, created_at, game_count, bin_count) =
+        header().map_err(|e| format!("Failed to read snapshot header: {}", e))?;
+    if version != VERSION {
+        return Err(format!("Unsupported snapshot version {}", version));
+    }
+    Ok((created_at, game_count, bin_count))
+}
+
+fn info_of(path: &Path) -> Result<SnapshotInfo, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let size_bytes = file.metadata().map_or(0, |metadata| metadata.len());
+    let (created_at, game_count, bin_count) = read_header(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    Ok(SnapshotInfo {
+        name: path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        created_at,
+        game_count,
+        bin_count,
+        size_bytes,
+    })
+}
+
+/// Entries and custom hashes of one table, as stored in a snapshot
+pub type TableParts = (HashMap<u64, String>, HashSet<u64>);
+
+fn read_table(reader: &mut impl Read, count: u64) -> Result<TableParts, String> {
+    let mut entries = HashMap::with_capacity(count as usize);
+    let mut custom = HashSet::new();
+    for _ in 0..count {
+        let mut read_entry = || -> std::io::Result<(u64, bool, Vec<u8>)> {
+            let hash = read_u64(reader)?;
+            let mut flag = [0; 1];
+            reader.read_exact(&mut flag)?;
+            let mut value = vec![0; read_u32(reader)? as usize];
+            reader.read_exact(&mut value)?;
+            Ok((hash, flag[0] != 0, value))
+        };
+        let (hash, is_custom, value) =
+            read_entry().map_err(|e| format!("Truncated snapshot: {}", e))?;
+        let value =
+            String::from_utf8(value).map_err(|_| "Snapshot value is not UTF-8".to_string())?;
+        if is_custom {
+            custom.insert(hash);
+        }
+        entries.insert(hash, value);
+    }
+    Ok((entries, custom))
+}
+
+/// Reads the named snapshot as `(game, bin)` tables
+pub fn load(cache_dir: &Path, name: &str) -> Result<(TableParts, TableParts), String> {
+    let path = snapshot_path(cache_dir, name)?;
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(format!("Snapshot '{}' not found", name));
+        }
+        Err(e) => return Err(format!("Failed to open {:?}: {}", path, e)),
+    };
+    let mut reader = BufReader::new(file);
+    let (_, game_count, bin_count) = read_header(&mut reader)?;
+    let game = read_table(&mut reader, game_count)?;
+    let bin = read_table(&mut reader, bin_count)?;
+    Ok((game, bin))
+}
+
+/// Snapshots in the cache dir, newest first; unreadable files are skipped
+pub fn list(cache_dir: &Path) -> Result<Vec<SnapshotInfo>, String> {
+    let dir = snapshot_dir(cache_dir);
+    let read_dir = match std::fs::read_dir(&dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", dir, e)),
+    };
+    let mut snapshots: Vec<SnapshotInfo> = read_dir
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == SNAPSHOT_EXTENSION)
+        })
+        .filter_map(|path| info_of(&path).ok())
+        .collect();
+    snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.name.cmp(&b.name)));
+    Ok(snapshots)
+}
+
+pub fn delete(cache_dir: &Path, name: &str) -> Result<(), String> {
+    let path = snapshot_path(cache_dir, name)?;
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(format!("Snapshot '{}' not found", name))
+        }
+        Err(e) => Err(format!("Failed to delete {:?}: {}", path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::{ConflictPolicy, EntryOrigin};
+
+    fn temp_cache_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("hash-service-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn table(entries: &[(u64, &str, EntryOrigin)]) -> Hashtable {
+        let mut table = Hashtable::default();
+        for &(hash, value, origin) in entries {
+            table.insert(hash, value.to_string(), origin, ConflictPolicy::KeepLast);
+        }
+        table
+    }
+
+    #[test]
+    fn accepts_plain_names() {
+        for name in ["before-import", "2024.10_patch", "a"] {
+            assert!(validate_name(name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_names_that_leave_the_snapshot_dir() {
+        for name in [
+            "",
+            "..",
+            "../escape",
+            "a/b",
+            "a\\b",
+            ".hidden",
+            "C:evil",
+            "name\0",
+        ] {
+            assert!(validate_name(name).is_err(), "{:?}", name);
+        }
+        let dir = temp_cache_dir("snapshot-names");
+        assert!(
+            save(
+                &dir,
+                "../escape",
+                &Hashtable::default(),
+                &Hashtable::default()
+            )
+            .is_err()
+        );
+        assert!(load(&dir, "../escape").is_err());
+        assert!(delete(&dir, "../escape").is_err());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn saves_lists_and_loads_with_custom_entries() {
+        let dir = temp_cache_dir("snapshot-round-trip");
+        let game = table(&[
+            (1, "synced", EntryOrigin::Official),
+            (2, "added", EntryOrigin::Custom),
+        ]);
+        let bin = table(&[(3, "mSpell", EntryOrigin::Official)]);
+
+        let info = save(&dir, "first", &game, &bin);
+        let listed = list(&dir);
+        let loaded = load(&dir, "first");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let info = info.unwrap();
+        assert_eq!(
+            (info.name.as_str(), info.game_count, info.bin_count),
+            ("first", 2, 1)
+        );
+        let listed = listed.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "first");
+        let ((game_entries, game_custom), (bin_entries, bin_custom)) = loaded.unwrap();
+        assert_eq!(
+            game_entries,
+            HashMap::from([(1, "synced".to_string()), (2, "added".to_string())])
+        );
+        assert_eq!(game_custom, HashSet::from([2]));
+        assert_eq!(bin_entries, HashMap::from([(3, "mSpell".to_string())]));
+        assert!(bin_custom.is_empty());
+    }
+
+    #[test]
+    fn a_deleted_snapshot_cannot_be_restored() {
+        let dir = temp_cache_dir("snapshot-delete");
+        let game = table(&[(1, "synced", EntryOrigin::Official)]);
+        save(&dir, "kept", &game, &Hashtable::default()).unwrap();
+        save(&dir, "dropped", &game, &Hashtable::default()).unwrap();
+
+        let deleted = delete(&dir, "dropped");
+        let restored = load(&dir, "dropped");
+        let deleted_again = delete(&dir, "dropped");
+        let listed = list(&dir);
+        let kept = load(&dir, "kept");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        deleted.unwrap();
+        assert_eq!(restored.unwrap_err(), "Snapshot 'dropped' not found");
+        assert_eq!(deleted_again.unwrap_err(), "Snapshot 'dropped' not found");
+        let listed: Vec<String> = listed.unwrap().into_iter().map(|info| info.name).collect();
+        assert_eq!(listed, ["kept"]);
+        assert_eq!(kept.unwrap().0.0.len(), 1);
+    }
+}
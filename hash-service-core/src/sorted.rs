@@ -0,0 +1,369 @@
+//! A table layout for large, mostly read tables: hashes sorted in one array next to their value
+//! offsets, and every value in one blob. About 16 bytes per entry plus the value, against
+//! roughly 50 for a `HashMap<u64, String>` slot and its own allocation, for a binary search
+//! per lookup.
+use std::collections::HashMap;
+
+/// Inserts collected before they are merged into the sorted array, unless the array is larger
+const MIN_PENDING: usize = 4096;
+
+/// Strings appended to one buffer, each prefixed with its length as a LEB128 varint and
+/// addressed by its offset
+#[derive(Debug, Default)]
+pub(crate) struct ValueBlob {
+    bytes: Vec<u8>,
+}
+
+impl ValueBlob {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        ValueBlob {
+            bytes: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn get(&self, offset: u32) -> &str {
+        let mut position = offset as usize;
+        let mut len = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = self.bytes[position];
+            position += 1;
+            len |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        // Only whole strings are ever pushed
+        std::str::from_utf8(&self.bytes[position..position + len]).expect("values are UTF-8")
+    }
+
+    pub(crate) fn push(&mut self, value: &str) -> u32 {
+        let offset = u32::try_from(self.bytes.len()).expect("table values exceed 4 GiB");
+        let mut len = value.len();
+        loop {
+            let byte = (len & 0x7f) as u8;
+            len >>= 7;
+            if len == 0 {
+                self.bytes.push(byte);
+                break;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+        self.bytes.extend_from_slice(value.as_bytes());
+        offset
+    }
+
+    /// Bytes `value` takes once pushed
+    pub(crate) fn stored_len(value: &str) -> usize {
+        let mut prefix = 1;
+        let mut len = value.len() >> 7;
+        while len > 0 {
+            prefix += 1;
+            len >>= 7;
+        }
+        prefix + value.len()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.bytes.capacity()
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.bytes.shrink_to_fit();
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SortedEntries {
+    /// Sorted by hash, with the offset of the value in `values`
+    index: Vec<(u64, u32)>,
+    /// Entries inserted since the last merge; these win over `index`
+    pending: HashMap<u64, u32>,
+    values: ValueBlob,
+    /// Bytes of `values` no entry points at anymore
+    garbage: usize,
+    len: usize,
+}
+
+impl SortedEntries {
+    fn indexed(&self, hash: u64) -> Option<usize> {
+        self.index
+            .binary_search_by_key(&hash, |&(entry, _)| entry)
+            .ok()
+    }
+
+    fn offset(&self, hash: u64) -> Option<u32> {
+        self.pending
+            .get(&hash)
+            .copied()
+            .or_else(|| self.indexed(hash).map(|position| self.index[position].1))
+    }
+
+    pub fn get(&self, hash: &u64) -> Option<&str> {
+        self.offset(*hash).map(|offset| self.values.get(offset))
+    }
+
+    pub fn contains_key(&self, hash: &u64) -> bool {
+        self.offset(*hash).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&u64, &str)> {
+        self.index
+            .iter()
+            .filter(|(hash, _)| !self.pending.contains_key(hash))
+            .map(|(hash, offset)| (hash, self.values.get(*offset)))
+            .chain(
+                self.pending
+                    .iter()
+                    .map(|(hash, offset)| (hash, self.values.get(*offset))),
+            )
+    }
+
+    /// Sets the value of `hash`, returning the one it replaces
+    pub fn insert(&mut self, hash: u64, value: &str) -> Option<String> {
+        let existing = self.get(&hash).map(str::to_string);
+        match &existing {
+            Some(existing) => self.garbage += ValueBlob::stored_len(existing),
+            None => self.len += 1,
+        }
+        let offset = self.values.push(value);
+        self.pending.insert(hash, offset);
+        if self.pending.len() > self.index.len().max(MIN_PENDING) {
+            self.merge();
+        }
+        existing
+    }
+
+    pub fn remove(&mut self, hash: &u64) -> Option<String> {
+        let existing = self.get(hash).map(str::to_string)?;
+        self.pending.remove(hash);
+        if let Some(position) = self.indexed(*hash) {
+            self.index.remove(position);
+        }
+        self.garbage += ValueBlob::stored_len(&existing);
+        self.len -= 1;
+        Some(existing)
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&u64) -> bool) {
+        self.merge();
+        self.index.retain(|(hash, _)| keep(hash));
+        self.len = self.index.len();
+        self.compact();
+    }
+
+    pub fn clear(&mut self) {
+        *self = SortedEntries::default();
+    }
+
+    /// Moves the pending inserts into the sorted array
+    pub fn merge(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let mut pending: Vec<(u64, u32)> = self.pending.drain().collect();
+        pending.sort_unstable_by_key(|&(hash, _)| hash);
+
+        let mut merged = Vec::with_capacity(self.len);
+        let mut index = std::mem::take(&mut self.index).into_iter().peekable();
+        for (hash, offset) in pending {
+            while let Some(&(indexed, _)) = index.peek()
+                && indexed < hash
+            {
+                merged.extend(index.next());
+            }
+            if index.peek().is_some_and(|&(indexed, _)| indexed == hash) {
+                index.next();
+            }
+            merged.push((hash, offset));
+        }
+        merged.extend(index);
+        self.index = merged;
+
+        if self.garbage > self.values.len() / 2 {
+            self.compact();
+        }
+    }
+
+    /// Rewrites the values without the ones nothing points at anymore
+    fn compact(&mut self) {
+        let live = ValueBlob::with_capacity(self.values.len().saturating_sub(self.garbage));
+        let old = std::mem::replace(&mut self.values, live);
+        for (_, offset) in &mut self.index {
+            *offset = self.values.push(old.get(*offset));
+        }
+        self.garbage = 0;
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.merge();
+        if self.garbage > 0 {
+            self.compact();
+        }
+        self.index.shrink_to_fit();
+        self.pending.shrink_to_fit();
+        self.values.shrink_to_fit();
+    }
+
+    /// Heap bytes held
+    pub fn memory_estimate(&self) -> usize {
+        self.index.capacity() * size_of::<(u64, u32)>()
+            + self.pending.capacity() * (size_of::<(u64, u32)>() + 1)
+            + self.values.capacity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::{ConflictPolicy, EntryOrigin, Hashtable, TableLayout};
+
+    #[test]
+    fn value_blob_round_trips_long_values() {
+        let mut blob = ValueBlob::default();
+        let long = "x".repeat(300);
+        let offsets = [blob.push(""), blob.push("a"), blob.push(&long)];
+        assert_eq!(blob.get(offsets[0]), "");
+        assert_eq!(blob.get(offsets[1]), "a");
+        assert_eq!(blob.get(offsets[2]), long);
+        assert_eq!(ValueBlob::stored_len(&long), 302);
+        assert_eq!(blob.len(), 1 + 2 + 302);
+    }
+
+    #[test]
+    fn merge_keeps_the_index_sorted_and_pending_inserts_win() {
+        let mut entries = SortedEntries::default();
+        for hash in (0..100u64).rev() {
+            entries.insert(hash * 2, &format!("old {}", hash));
+        }
+        entries.merge();
+        assert!(entries.pending.is_empty());
+        assert!(entries.index.is_sorted_by_key(|&(hash, _)| hash));
+
+        entries.insert(10, "new 5");
+        entries.insert(11, "added");
+        assert_eq!(entries.get(&10), Some("new 5"));
+        assert_eq!(entries.iter().filter(|(hash, _)| **hash == 10).count(), 1);
+        entries.merge();
+        assert!(entries.index.is_sorted_by_key(|&(hash, _)| hash));
+        assert_eq!(entries.len(), 101);
+        assert_eq!(entries.index.len(), 101);
+        assert_eq!(entries.get(&10), Some("new 5"));
+        assert_eq!(entries.get(&11), Some("added"));
+        assert_eq!(entries.get(&12), Some("old 6"));
+        assert_eq!(entries.get(&13), None);
+    }
+
+    #[test]
+    fn inserts_merge_once_pending_outgrows_the_index() {
+        let mut entries = SortedEntries::default();
+        for hash in 0..=MIN_PENDING as u64 {
+            entries.insert(hash, "value");
+        }
+        assert!(entries.pending.is_empty());
+        assert_eq!(entries.index.len(), MIN_PENDING + 1);
+    }
+
+    #[test]
+    fn duplicate_hashes_replace_and_count_once() {
+        let mut entries = SortedEntries::default();
+        assert_eq!(entries.insert(1, "first"), None);
+        assert_eq!(entries.insert(1, "second"), Some("first".to_string()));
+        entries.merge();
+        assert_eq!(entries.insert(1, "third"), Some("second".to_string()));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries.get(&1), Some("third"));
+        entries.merge();
+        assert_eq!(entries.index, vec![(1, entries.index[0].1)]);
+        assert_eq!(entries.iter().count(), 1);
+    }
+
+    #[test]
+    fn remove_finds_pending_and_indexed_entries() {
+        let mut entries = SortedEntries::default();
+        entries.insert(1, "indexed");
+        entries.insert(2, "indexed too");
+        entries.merge();
+        entries.insert(3, "pending");
+        entries.insert(2, "pending over indexed");
+
+        assert_eq!(entries.remove(&1), Some("indexed".to_string()));
+        assert_eq!(entries.remove(&3), Some("pending".to_string()));
+        assert_eq!(entries.remove(&2), Some("pending over indexed".to_string()));
+        assert_eq!(entries.remove(&2), None);
+        assert!(entries.is_empty());
+        assert_eq!(entries.iter().count(), 0);
+    }
+
+    #[test]
+    fn compact_drops_unreferenced_values() {
+        let mut entries = SortedEntries::default();
+        for hash in 0..10u64 {
+            entries.insert(hash, "0123456789");
+        }
+        entries.merge();
+        for hash in 0..10u64 {
+            entries.insert(hash, "abc");
+        }
+        entries.remove(&9);
+        entries.shrink_to_fit();
+        assert_eq!(entries.garbage, 0);
+        assert_eq!(entries.values.len(), 9 * ValueBlob::stored_len("abc"));
+        assert!((0..9u64).all(|hash| entries.get(&hash) == Some("abc")));
+        assert_eq!(entries.get(&9), None);
+
+        entries.retain(|&hash| hash % 2 == 0);
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries.values.len(), 5 * ValueBlob::stored_len("abc"));
+        assert_eq!(entries.get(&4), Some("abc"));
+        assert_eq!(entries.get(&5), None);
+    }
+
+    #[test]
+    fn custom_entries_shadow_synced_ones_in_a_sorted_table() {
+        let mut table = Hashtable::with_layout(TableLayout::Sorted);
+        let policy = ConflictPolicy::default();
+        table.insert(1, "synced".to_string(), EntryOrigin::Official, policy);
+        table.insert(2, "synced only".to_string(), EntryOrigin::Official, policy);
+        table.finish_inserts();
+        table.insert(1, "custom".to_string(), EntryOrigin::Custom, policy);
+        table.insert(3, "custom only".to_string(), EntryOrigin::Custom, policy);
+
+        assert_eq!(table.get(&1), Some("custom"));
+        assert_eq!(table.shadowed(&1).map(String::as_str), Some("synced"));
+        assert_eq!(table.origin(&1), Some(EntryOrigin::Custom));
+
+        // A reload keeps the custom entries, which shadow the synced value again
+        table.clear_official();
+        assert_eq!(table.get(&2), None);
+        table.insert(
+            1,
+            "synced".to_string(),
+            EntryOrigin::Official,
+            ConflictPolicy::KeepFirst,
+        );
+        table.insert(2, "synced only".to_string(), EntryOrigin::Official, policy);
+        table.finish_inserts();
+        assert_eq!(table.get(&1), Some("custom"));
+        assert_eq!(table.shadowed(&1).map(String::as_str), Some("synced"));
+
+        assert_eq!(table.clear_custom(), 2);
+        assert_eq!(table.get(&1), Some("synced"));
+        assert_eq!(table.get(&3), None);
+        assert_eq!(table.origin(&1), Some(EntryOrigin::Official));
+        assert_eq!(table.len(), 2);
+    }
+}
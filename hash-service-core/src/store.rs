@@ -0,0 +1,147 @@
+//! Copies of the synced tables kept on disk, which lookups read instead of loading the
+//! tables into memory. See [`crate::disk`] and, with the `db-backend` feature, `crate::db`.
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tokio::sync::Mutex;
+use xxhash_rust::xxh64::xxh64;
+
+use crate::archive::TableArchive;
+use crate::engine::HashtableType;
+
+/// Database file of the `db-backend` store inside the cache directory; named so it is never
+/// loaded as a table
+pub const DB_FILE_NAME: &str = "hashes.redb";
+
+/// A synced file a table is built from
+#[derive(Debug, Clone)]
+pub struct StoreFile {
+    pub path: PathBuf,
+    pub file_name: String,
+    /// Blob SHA the file was downloaded at, or its size and modification time when it has no
+    /// `.sha` file
+    pub version: String,
+    /// Part of the file the table is read from when it is an archive
+    pub archive: Option<TableArchive>,
+}
+
+impl StoreFile {
+    pub fn new(path: PathBuf, file_name: String) -> Self {
+        let sha_path = path.with_file_name(format!("{}.sha", file_name));
+        let version = match std::fs::read_to_string(sha_path) {
+            Ok(sha) => sha.trim().to_string(),
+            Err(_) => {
+                let metadata = std::fs::metadata(&path).ok();
+                let modified = metadata
+                    .as_ref()
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_nanos())
+                    .unwrap_or_default();
+                format!("{}@{}", metadata.map(|m| m.len()).unwrap_or(0), modified)
+            }
+        };
+        StoreFile {
+            path,
+            file_name,
+            version,
+            archive: None,
+        }
+    }
+}
+
+/// The file versions a table is built from, in a stable order, so a store can tell whether
+/// its copy is still current
+pub fn built_from(files: &[StoreFile]) -> String {
+    let mut versions: Vec<String> = files
+        .iter()
+        .map(|file| format!("{}:{}", file.file_name, file.version))
+        .collect();
+    versions.sort();
+    versions.join(",")
+}
+
+/// Short name for a copy built from `built_from` in format `format_version`; a change to
+/// either gives a new generation
+pub fn generation(built_from: &str, format_version: u32) -> String {
+    format!(
+        "{:016x}",
+        xxh64(built_from.as_bytes(), format_version as u64)
+    )
+}
+
+/// Which kind of copy a store keeps, so cache cleaning knows which files are still in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreKind {
+    /// Sorted lookup files, see [`crate::disk`]
+    Disk,
+    /// A redb database, see `crate::db`
+    Database,
+}
+
+pub(crate) trait LookupStore: Debug + Send + Sync {
+    fn kind(&self) -> StoreKind;
+
+    /// Brings the table in `dir` up to date with `files`; returns whether it was rebuilt
+    fn refresh(
+        &self,
+        dir: &Path,
+        hashtable_type: HashtableType,
+        files: &[StoreFile],
+    ) -> Result<bool, String>;
+
+    /// Closes the store's files so the cache dir can be moved; the next refresh reopens them
+    fn close(&self) -> Result<(), String>;
+
+    /// Values of `hashes`, in order; `None` for all of them before the first refresh
+    fn get_many(
+        &self,
+        hashtable_type: HashtableType,
+        hashes: &[u64],
+    ) -> Result<Vec<Option<String>>, String>;
+}
+
+/// A store plus whether each of its tables is current with the cache
+#[derive(Debug)]
+pub(crate) struct StoreState {
+    pub(crate) store: Box<dyn LookupStore>,
+    /// Whether each table was refreshed since the cache was last synced
+    game_ready: AtomicBool,
+    bin_ready: AtomicBool,
+    /// Held while refreshing so concurrent lookups do not rebuild twice
+    pub(crate) refresh_lock: Mutex<()>,
+}
+
+impl StoreState {
+    pub(crate) fn new(store: impl LookupStore + 'static) -> Self {
+        StoreState {
+            store: Box::new(store),
+            game_ready: AtomicBool::new(false),
+            bin_ready: AtomicBool::new(false),
+            refresh_lock: Mutex::new(()),
+        }
+    }
+
+    fn ready_flag(&self, hashtable_type: HashtableType) -> &AtomicBool {
+        match hashtable_type {
+            HashtableType::Game => &self.game_ready,
+            HashtableType::Bin => &self.bin_ready,
+        }
+    }
+
+    pub(crate) fn is_ready(&self, hashtable_type: HashtableType) -> bool {
+        self.ready_flag(hashtable_type).load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_ready(&self, hashtable_type: HashtableType) {
+        self.ready_flag(hashtable_type)
+            .store(true, Ordering::SeqCst);
+    }
+
+    /// After a sync the files may have changed, so the next lookup checks them again
+    pub(crate) fn mark_stale(&self) {
+        self.game_ready.store(false, Ordering::SeqCst);
+        self.bin_ready.store(false, Ordering::SeqCst);
+    }
+}
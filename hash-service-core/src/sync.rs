@@ -0,0 +1,1213 @@
+//! Keeps the local copies of the CommunityDragon hashtables up to date. Each file is
+//! stored next to a `.sha` file holding the blob SHA it was downloaded at.
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use directories_next::ProjectDirs;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+use tokio_stream::StreamExt;
+use tracing::{info, instrument, warn};
+use xxhash_rust::xxh64::xxh64;
+
+use crate::archive::ArchiveKind;
+use crate::diff::{diff_files, save_diff};
+use crate::engine::HashtableType;
+use crate::lock::CacheLock;
+use crate::manifest;
+use crate::progress::{DOWNLOAD_PROGRESS_INTERVAL, DownloadProgress};
+use crate::report::{FailedFile, FailureStage, failure_message};
+use crate::throttle::Throttled;
+
+/// Product synced when none is configured
+pub const DEFAULT_PRODUCT: &str = "lol";
+/// Branch name for the repository's default branch, which tracks the live game
+pub const DEFAULT_BRANCH: &str = "live";
+const MAX_SOURCE_NAME_LEN: usize = 32;
+/// Files fetched from each product's hash directory
+pub(crate) const HASH_FILE_NAMES: [&str; 3] = [
+    "hashes.binentries.txt",
+    "hashes.game.txt.0",
+    "hashes.game.txt.1",
+];
+
+pub(crate) const REPO_API_URL: &str = "https://api.github.com/repos/CommunityDragon/Data";
+/// Where each synced file came from upstream; named so it is never loaded as a table
+pub(crate) const SOURCE_FILES_NAME: &str = "sources.json";
+
+/// Downloads are staged as `<prefix>.<file name with dashes>` until every file is fetched
+pub(crate) const NEW_FILE_PREFIX: &str = "download";
+/// Marks the cache files of URL sources, `hashes.<table>.url-<url hash>.txt`
+const URL_FILE_MARKER: &str = ".url-";
+/// How long a host that failed a download is tried only after the others
+const MIRROR_PENALTY: Duration = Duration::from_secs(10 * 60);
+
+/// Documents/LeagueToolkit/ltk-hash-cache, or the platform cache dir on Linux
+pub fn default_cache_dir() -> Result<PathBuf, String> {
+    let project_dirs = ProjectDirs::from("io", "LeagueToolkit", "ltk-hash-cache")
+        .ok_or_else(|| "Failed to get project directories".to_string())?;
+
+    let hash_dir: PathBuf = if cfg!(target_os = "linux") {
+        project_dirs.cache_dir().to_path_buf()
+    } else {
+        directories_next::UserDirs::new()
+            .and_then(|ud| {
+                ud.document_dir()
+                    .map(|p| p.join("LeagueToolkit").join("ltk-hash-cache").to_path_buf())
+            })
+            .unwrap_or_else(|| project_dirs.cache_dir().to_path_buf())
+    };
+    Ok(hash_dir)
+}
+
+/// Which CommunityDragon hash directory (`hashes/<product>`) a cache mirrors, and on
+/// which branch of the data repository (e.g. `pbe`)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HashSource {
+    pub product: String,
+    pub branch: String,
+}
+
+impl Default for HashSource {
+    fn default() -> Self {
+        HashSource {
+            product: DEFAULT_PRODUCT.to_string(),
+            branch: DEFAULT_BRANCH.to_string(),
+        }
+    }
+}
+
+impl HashSource {
+    pub fn new(product: &str, branch: &str) -> Result<Self, String> {
+        validate_source_name("product", product)?;
+        validate_source_name("branch", branch)?;
+        Ok(HashSource {
+            product: product.to_string(),
+            branch: branch.to_string(),
+        })
+    }
+
+    /// The default product on the live branch keeps the cache root so existing caches
+    /// stay valid. Other products get a subdirectory of their own, and other branches an
+    /// `@<branch>` subdirectory of that, which no product name can collide with.
+    pub fn cache_dir(&self, base: &Path) -> PathBuf {
+        let product_dir = if self.product == DEFAULT_PRODUCT {
+            base.to_path_buf()
+        } else {
+            base.join(&self.product)
+        };
+        if self.branch == DEFAULT_BRANCH {
+            product_dir
+        } else {
+            product_dir.join(format!("@{}", self.branch))
+        }
+    }
+
+    fn contents_url(&self, api_url: &str, file_name: &str) -> String {
+        let url = format!("{}/contents/hashes/{}/{}", api_url, self.product, file_name);
+        if self.branch == DEFAULT_BRANCH {
+            url
+        } else {
+            format!("{}?ref={}", url, self.branch)
+        }
+    }
+
+    /// The latest commit that touched `file_name`
+    fn last_commit_url(&self, api_url: &str, file_name: &str) -> String {
+        let url = format!(
+            "{}/commits?path=hashes/{}/{}&per_page=1",
+            api_url, self.product, file_name
+        );
+        if self.branch == DEFAULT_BRANCH {
+            url
+        } else {
+            format!("{}&sha={}", url, self.branch)
+        }
+    }
+}
+
+/// Only HTTPS URLs are accepted, and HTTP ones to this machine for testing
+fn validate_url(url: &str) -> Result<(), String> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    let local = matches!(parsed.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+    if parsed.scheme() != "https" && !(parsed.scheme() == "http" && local) {
+        return Err(format!("URL '{}' must use https", url));
+    }
+    Ok(())
+}
+
+/// URL of `file_name` on a mirror of the CommunityDragon files, given as a template in which
+/// `{product}`, `{branch}` and `{file}` are replaced
+fn mirror_url(template: &str, source: &HashSource, file_name: &str) -> String {
+    template
+        .replace("{product}", &source.product)
+        .replace("{branch}", &source.branch)
+        .replace("{file}", file_name)
+}
+
+/// Checks a mirror template the way the URLs it expands to will be
+pub fn validate_mirror(template: &str) -> Result<(), String> {
+    validate_url(&mirror_url(
+        template,
+        &HashSource::default(),
+        HASH_FILE_NAMES[0],
+    ))
+}
+
+/// A hashtable file synced from a plain URL, e.g. a raw file on a web server, rather than from
+/// the CommunityDragon repository. Whether it changed is asked with its ETag or Last-Modified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlSource {
+    pub url: String,
+    pub hashtable_type: HashtableType,
+    /// Other URLs serving the same file, tried in order when `url` fails
+    pub mirrors: Vec<String>,
+}
+
+impl UrlSource {
+    /// Only HTTPS URLs are accepted, and HTTP ones to this machine for testing
+    pub fn new(url: &str, hashtable_type: HashtableType) -> Result<Self, String> {
+        validate_url(url)?;
+        Ok(UrlSource {
+            url: url.to_string(),
+            hashtable_type,
+            mirrors: Vec::new(),
+        })
+    }
+
+    /// Falls back to `mirrors` when the URL fails; they must serve the very same file
+    pub fn with_mirrors(mut self, mirrors: Vec<String>) -> Result<Self, String> {
+        for mirror in &mirrors {
+            validate_url(mirror)?;
+        }
+        self.mirrors = mirrors;
+        Ok(self)
+    }
+
+    /// The URL, then its mirrors
+    fn urls(&self) -> Vec<String> {
+        std::iter::once(self.url.clone())
+            .chain(self.mirrors.iter().cloned())
+            .collect()
+    }
+
+    /// Cache file the URL is stored as: named for its table, so it loads like the synced
+    /// files, and keeping an archive extension, so it is decompressed
+    pub fn file_name(&self) -> String {
+        let table = match self.hashtable_type {
+            HashtableType::Game => "game",
+            HashtableType::Bin => "binentries",
+        };
+        let url_path = Url::parse(&self.url)
+            .map(|url| url.path().to_ascii_lowercase())
+            .unwrap_or_default();
+        let extension = match ArchiveKind::from_file_name(&url_path) {
+            Some(ArchiveKind::Zip) => ".zip",
+            Some(ArchiveKind::TarGz) => ".tar.gz",
+            Some(ArchiveKind::Zstd) => ".zst",
+            None => "",
+        };
+        format!(
+            "hashes.{}{}{:016x}.txt{}",
+            table,
+            URL_FILE_MARKER,
+            xxh64(self.url.as_bytes(), 0),
+            extension
+        )
+    }
+}
+
+/// Product and branch names become URL segments and cache subdirectories
+fn validate_source_name(kind: &str, name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_SOURCE_NAME_LEN {
+        return Err(format!(
+            "The {} name must be 1 to {} characters",
+            kind, MAX_SOURCE_NAME_LEN
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "Invalid {} '{}', expected lowercase letters, digits, '_' or '-'",
+            kind, name
+        ));
+    }
+    Ok(())
+}
+
+/// Upstream version of a synced hashtable file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceFile {
+    pub file_name: String,
+    /// Blob SHA the file was downloaded at
+    pub blob_sha: String,
+    /// Last commit that changed the file; empty if it could not be fetched
+    pub commit_sha: String,
+    /// ISO 8601 date of that commit
+    pub commit_date: String,
+    /// Game patch named in the commit message, e.g. `14.20`
+    pub patch: Option<String>,
+    /// Where a URL source's file was downloaded from; `blob_sha` is then a hash of its content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Validators the server sent with a URL source's file, asked for on the next sync
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+/// Provenance of the files in `dir`, empty before the first sync that recorded it
+pub fn load_source_files(dir: &Path) -> Result<Vec<SourceFile>, String> {
+    let path = dir.join(SOURCE_FILES_NAME);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+    serde_json::from_str(&text).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+}
+
+fn save_source_files(dir: &Path, source_files: &[SourceFile]) -> Result<(), String> {
+    let path = dir.join(SOURCE_FILES_NAME);
+    let text = serde_json::to_string_pretty(source_files).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Replaces the entry for the same file, keeping the list in file name order
+fn upsert_source_file(source_files: &mut Vec<SourceFile>, source_file: SourceFile) {
+    source_files.retain(|existing| existing.file_name != source_file.file_name);
+    source_files.push(source_file);
+    source_files.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+}
+
+/// Looks up the commit behind a file. Provenance is informational, so failures only
+/// leave the commit fields empty.
+async fn fetch_source_file(
+    source: &HashSource,
+    file_name: &str,
+    blob_sha: &str,
+    downloader: &Downloader,
+) -> SourceFile {
+    let mut source_file = SourceFile {
+        file_name: file_name.to_string(),
+        blob_sha: blob_sha.to_string(),
+        ..SourceFile::default()
+    };
+    let commit = match get_git_data(&source.last_commit_url(&downloader.api_url, file_name)).await {
+        Ok(Value::Array(commits)) if !commits.is_empty() => commits[0].clone(),
+        Ok(_) => {
+            warn!(file_name, "No commit found for file");
+            return source_file;
+        }
+        Err(e) => {
+            warn!(file_name, "Failed to fetch commit for file: {}", e);
+            return source_file;
+        }
+    };
+    let field = |pointer: &str| {
+        commit
+            .pointer(pointer)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    source_file.commit_sha = field("/sha");
+    source_file.commit_date = field("/commit/committer/date");
+    source_file.patch = patch_version(&field("/commit/message"));
+    source_file
+}
+
+/// Newest patch any of the files was committed for
+pub fn latest_patch(source_files: &[SourceFile]) -> Option<&str> {
+    source_files
+        .iter()
+        .filter_map(|file| file.patch.as_deref())
+        .max_by_key(|patch| {
+            patch
+                .split('.')
+                .map(|part| part.parse::<u64>().unwrap_or(0))
+                .collect::<Vec<_>>()
+        })
+}
+
+/// First `major.minor` or `major.minor.build` number in a commit message
+fn patch_version(message: &str) -> Option<String> {
+    message
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|token| token.trim_matches('.'))
+        .find(|token| {
+            let parts: Vec<&str> = token.split('.').collect();
+            (2..=3).contains(&parts.len()) && parts.iter().all(|part| !part.is_empty())
+        })
+        .map(str::to_string)
+}
+
+/// When each host last failed a download, so the ones that work are tried first. Shared by
+/// the clones of a [`Downloader`] and kept for the life of the process.
+#[derive(Debug, Clone, Default)]
+pub struct MirrorHealth {
+    failures: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl MirrorHealth {
+    /// `urls` with those on hosts that failed within [`MIRROR_PENALTY`] moved to the end, the
+    /// longest ago first; the others keep their order
+    fn order(&self, urls: Vec<String>) -> Vec<String> {
+        let Ok(failures) = self.failures.lock() else {
+            return urls;
+        };
+        let mut urls: Vec<(Option<Instant>, String)> = urls
+            .into_iter()
+            .map(|url| {
+                let failed_at = failures
+                    .get(&host(&url))
+                    .copied()
+                    .filter(|failed_at| failed_at.elapsed() < MIRROR_PENALTY);
+                (failed_at, url)
+            })
+            .collect();
+        urls.sort_by_key(|(failed_at, _)| *failed_at);
+        urls.into_iter().map(|(_, url)| url).collect()
+    }
+
+    fn record(&self, url: &str, failed: bool) {
+        if let Ok(mut failures) = self.failures.lock() {
+            if failed {
+                failures.insert(host(url), Instant::now());
+            } else {
+                failures.remove(&host(url));
+            }
+        }
+    }
+}
+
+/// Scheme, host and port a URL is served from
+fn host(url: &str) -> String {
+    Url::parse(url)
+        .map(|url| url.origin().ascii_serialization())
+        .unwrap_or_else(|_| url.to_string())
+}
+
+/// How a sync downloads its files: where it reports their progress, how fast it may go and
+/// where else it may fetch them from
+#[derive(Debug, Clone)]
+pub struct Downloader {
+    pub progress: broadcast::Sender<DownloadProgress>,
+    /// Bytes per second each download is held to; unlimited when `None`. Files download one
+    /// after the other, so this bounds the whole sync.
+    pub rate_limit: Option<u64>,
+    /// Templates of mirrors of the CommunityDragon files, see [`validate_mirror`], tried in
+    /// order when GitHub's download fails, or its API does
+    pub mirrors: Vec<String>,
+    pub health: MirrorHealth,
+    /// GitHub API of the data repository, asked for each file's SHA and commit
+    pub api_url: String,
+}
+
+impl Downloader {
+    /// Tries `urls` until `fetch` succeeds with one, those on hosts that failed recently
+    /// last, and records how each host did. Fails with every URL's error if none succeeds.
+    async fn fetch_from_any<T, F, Fut>(&self, urls: Vec<String>, mut fetch: F) -> Result<T, String>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<T, String>>,
+    {
+        let urls = self.health.order(urls);
+        let count = urls.len();
+        let mut errors = Vec::new();
+        for (index, url) in urls.into_iter().enumerate() {
+            match fetch(url.clone()).await {
+                Ok(value) => {
+                    self.health.record(&url, false);
+                    return Ok(value);
+                }
+                Err(e) => {
+                    self.health.record(&url, true);
+                    if index + 1 < count {
+                        warn!(url, "Download failed, trying the next mirror: {}", e);
+                    }
+                    errors.push(if count > 1 {
+                        format!("{}: {}", url, e)
+                    } else {
+                        e
+                    });
+                }
+            }
+        }
+        Err(errors.join("; "))
+    }
+}
+
+/// A file whose new version was downloaded next to it, waiting to replace it
+struct PendingFile {
+    path: PathBuf,
+    new_path: PathBuf,
+    checksum: String,
+    /// Provenance of the new version; `None` when it was downloaded again unchanged
+    source_file: Option<SourceFile>,
+}
+
+/// What a sync did
+#[derive(Debug, Clone, Default)]
+pub struct SyncOutcome {
+    /// Files that changed upstream
+    pub updated_files: usize,
+    /// Files that could not be synced and kept their previous copy, if they had one
+    pub failed: Vec<FailedFile>,
+}
+
+/// Brings the hashtable files in `dir`, and those of `urls`, up to date. Only the files named
+/// in `only` are synced, or every one when it is empty. With `force`, files are downloaded
+/// again even when their SHA matches, which repairs a damaged cache. Files are fetched
+/// through `downloader`. A file that fails is left as it was and reported in the outcome;
+/// the sync only fails when every file did and none has a previous copy. Holds the cache
+/// lock exclusively throughout.
+#[instrument(skip(downloader))]
+pub async fn sync_hashtables(
+    dir: &Path,
+    source: &HashSource,
+    urls: &[UrlSource],
+    only: &[String],
+    force: bool,
+    downloader: &Downloader,
+) -> Result<SyncOutcome, String> {
+    let _cache_lock = CacheLock::exclusive(dir).await?;
+    let mut source_files = load_source_files(dir).unwrap_or_else(|e| {
+        warn!("{}", e);
+        Vec::new()
+    });
+    let known_source_files = source_files.clone();
+    let mut pending = Vec::new();
+    let mut failed = Vec::new();
+    let selected = |file_name: &str| only.is_empty() || only.iter().any(|name| name == file_name);
+    let repo_files: Vec<(&str, HashtableType)> = HASH_FILE_NAMES
+        .into_iter()
+        .filter(|file_name| selected(file_name))
+        .filter_map(|file_name| Some((file_name, HashtableType::from_file_name(file_name)?)))
+        .collect();
+    let url_sources: Vec<&UrlSource> = urls
+        .iter()
+        .filter(|url_source| selected(&url_source.file_name()))
+        .collect();
+    let repo_count = repo_files.len();
+    let file_count = repo_count + url_sources.len();
+    // One file failing must not hold back the others; it keeps its previous copy
+    let mut record_failure =
+        |file_progress: DownloadProgress, hashtable_type: HashtableType, e: String| {
+            warn!(
+                file_name = file_progress.file_name,
+                "Failed to sync hashtable, keeping the previous copy: {}", e
+            );
+            failed.push(FailedFile {
+                file_name: file_progress.file_name.clone(),
+                hashtable_type,
+                stage: FailureStage::Sync,
+                error: e.clone(),
+            });
+            let _ = downloader.progress.send(DownloadProgress {
+                done: true,
+                error: Some(e),
+                ..file_progress
+            });
+        };
+    for (index, (file_name, hashtable_type)) in repo_files.into_iter().enumerate() {
+        let file_progress = DownloadProgress {
+            file_name: file_name.to_string(),
+            index,
+            file_count,
+            ..DownloadProgress::default()
+        };
+        match sync_repo_file(
+            dir,
+            source,
+            file_name,
+            force,
+            &mut source_files,
+            file_progress.clone(),
+            downloader,
+        )
+        .await
+        {
+            Ok(Some(file)) => pending.push(file),
+            Ok(None) => {}
+            Err(e) => record_failure(file_progress, hashtable_type, e),
+        }
+    }
+    for (index, url_source) in url_sources.into_iter().enumerate() {
+        info!(url = url_source.url, "Syncing hashtable");
+        let file_progress = DownloadProgress {
+            file_name: url_source.file_name(),
+            index: repo_count + index,
+            file_count,
+            ..DownloadProgress::default()
+        };
+        match sync_url_file(
+            dir,
+            url_source,
+            force,
+            &mut source_files,
+            file_progress.clone(),
+            downloader,
+        )
+        .await
+        {
+            Ok(Some(file)) => pending.push(file),
+            Ok(None) => {}
+            Err(e) => record_failure(file_progress, url_source.hashtable_type, e),
+        }
+    }
+    // With nothing to fall back on, e.g. on the first sync while offline, the sync failed
+    if file_count > 0
+        && failed.len() == file_count
+        && failed
+            .iter()
+            .all(|file| !dir.join(&file.file_name).exists())
+    {
+        return Err(failure_message(&failed));
+    }
+    remove_stale_url_files(dir, urls, &mut source_files);
+    let mut source_files_changed = source_files != known_source_files;
+
+    record_diffs(dir, &pending);
+
+    let updated_files = pending
+        .iter()
+        .filter(|file| file.source_file.is_some())
+        .count();
+
+    let mut changed = Vec::new();
+    for file in pending {
+        std::fs::rename(&file.new_path, &file.path)
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+        let file_name = file
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let sha_path = file.path.with_file_name(format!("{}.sha", file_name));
+        std::fs::write(&sha_path, &file.checksum)
+            .map_err(|e| format!("Failed to write SHA file: {}", e))?;
+        info!(file_name, "Successfully synced");
+        if let Some(source_file) = file.source_file {
+            upsert_source_file(&mut source_files, source_file);
+            source_files_changed = true;
+        }
+        changed.push(file_name);
+    }
+    let synced: Vec<String> = HASH_FILE_NAMES
+        .iter()
+        .map(|file_name| file_name.to_string())
+        .chain(urls.iter().map(UrlSource::file_name))
+        .collect();
+    if let Err(e) = manifest::update(dir, &synced, &changed) {
+        warn!("Failed to update cache manifest: {}", e);
+    }
+    if source_files_changed && let Err(e) = save_source_files(dir, &source_files) {
+        warn!("{}", e);
+    }
+    Ok(SyncOutcome {
+        updated_files,
+        failed,
+    })
+}
+
+/// Downloads `file_name` from the repository of `source` next to its cache file, unless the
+/// cached copy has its SHA. Provenance of the cached copy is recorded in `source_files`.
+async fn sync_repo_file(
+    dir: &Path,
+    source: &HashSource,
+    file_name: &str,
+    force: bool,
+    source_files: &mut Vec<SourceFile>,
+    file_progress: DownloadProgress,
+    downloader: &Downloader,
+) -> Result<Option<PendingFile>, String> {
+    let git_url = source.contents_url(&downloader.api_url, file_name);
+    info!(url = git_url, "Syncing hashtable");
+    let git_data = match get_git_data(&git_url).await {
+        Ok(git_data) => git_data,
+        Err(e) if !downloader.mirrors.is_empty() => {
+            warn!(
+                file_name,
+                "Failed to fetch data from GitHub, trying the mirrors: {}", e
+            );
+            return sync_from_mirrors(
+                dir,
+                source,
+                file_name,
+                force,
+                source_files,
+                file_progress,
+                downloader,
+            )
+            .await
+            .map_err(|mirror_e| format!("Failed to fetch data from GitHub: {}; {}", e, mirror_e));
+        }
+        Err(e) => return Err(format!("Failed to fetch data from GitHub: {}", e)),
+    };
+
+    let checksum = git_data
+        .get("sha")
+        .and_then(|s| s.as_str())
+        .ok_or_else(|| "Missing 'sha' field in response".to_string())?;
+    let url = git_data
+        .get("download_url")
+        .and_then(|s| s.as_str())
+        .ok_or_else(|| "Missing 'download_url' field in response".to_string())?;
+
+    let file_path = dir.join(file_name);
+
+    let mut up_to_date = false;
+    if file_path.exists() {
+        // Append .sha to the file name (e.g., hashes.game.txt.0 -> hashes.game.txt.0.sha)
+        let sha_path = file_path.with_file_name(format!("{}.sha", file_name));
+        if let Ok(existing_sha) = std::fs::read_to_string(&sha_path)
+            && existing_sha.trim() == checksum
+        {
+            up_to_date = true;
+            // Caches synced before provenance was recorded catch up here
+            if !source_files
+                .iter()
+                .any(|known| known.file_name == file_name && known.blob_sha == checksum)
+            {
+                let source_file = fetch_source_file(source, file_name, checksum, downloader).await;
+                upsert_source_file(source_files, source_file);
+            }
+            if !force {
+                info!(file_name, "File is up to date, skipping");
+                send_up_to_date(downloader, file_progress);
+                return Ok(None);
+            }
+            info!(file_name, "File is up to date, downloading again...");
+        } else {
+            info!(file_name, "File needs update, downloading...");
+        }
+    } else {
+        info!(file_name, "File not found, downloading...");
+    }
+
+    let urls = std::iter::once(url.to_string())
+        .chain(
+            downloader
+                .mirrors
+                .iter()
+                .map(|template| mirror_url(template, source, file_name)),
+        )
+        .collect();
+    let new_path =
+        download_verified(dir, file_name, urls, checksum, file_progress, downloader).await?;
+    let source_file = if up_to_date {
+        None
+    } else {
+        Some(fetch_source_file(source, file_name, checksum, downloader).await)
+    };
+    Ok(Some(PendingFile {
+        path: file_path,
+        new_path,
+        checksum: checksum.to_string(),
+        source_file,
+    }))
+}
+
+/// Syncs `file_name` from the mirrors alone, for when GitHub's API can't say what its current
+/// SHA is. A mirror's copy is then only trusted at the blob SHA the file was last synced at,
+/// so this restores a missing or damaged copy, or one downloaded again with `force`, but never
+/// picks up a change; a cached copy at that SHA is kept as it is.
+async fn sync_from_mirrors(
+    dir: &Path,
+    source: &HashSource,
+    file_name: &str,
+    force: bool,
+    source_files: &[SourceFile],
+    file_progress: DownloadProgress,
+    downloader: &Downloader,
+) -> Result<Option<PendingFile>, String> {
+    let file_path = dir.join(file_name);
+    let sha_path = file_path.with_file_name(format!("{}.sha", file_name));
+    let cached_sha = std::fs::read_to_string(&sha_path)
+        .ok()
+        .filter(|_| file_path.exists())
+        .map(|sha| sha.trim().to_string());
+    let checksum = source_files
+        .iter()
+        .find(|known| known.file_name == file_name && known.url.is_none())
+        .map(|known| known.blob_sha.clone())
+        .or_else(|| cached_sha.clone())
+        .ok_or_else(|| "no known SHA to check a mirror's copy against".to_string())?;
+    if !force && cached_sha.as_deref() == Some(checksum.as_str()) {
+        return Err("the cached copy can't be checked for updates without it".to_string());
+    }
+
+    let urls = downloader
+        .mirrors
+        .iter()
+        .map(|template| mirror_url(template, source, file_name))
+        .collect();
+    let new_path =
+        download_verified(dir, file_name, urls, &checksum, file_progress, downloader).await?;
+    info!(
+        file_name,
+        "Restored file from a mirror at its last known SHA"
+    );
+    Ok(Some(PendingFile {
+        path: file_path,
+        new_path,
+        checksum,
+        source_file: None,
+    }))
+}
+
+/// Downloads `file_name` from the first of `urls` that serves it at blob SHA `checksum` and
+/// stages it next to its cache file
+async fn download_verified(
+    dir: &Path,
+    file_name: &str,
+    urls: Vec<String>,
+    checksum: &str,
+    file_progress: DownloadProgress,
+    downloader: &Downloader,
+) -> Result<PathBuf, String> {
+    let data = downloader
+        .fetch_from_any(urls, |url| {
+            let file_progress = file_progress.clone();
+            async move {
+                let data = download_file(&url, file_progress, downloader).await?;
+                // A stale or tampered mirror must not be recorded as being at `checksum`
+                let sha = blob_sha(&data);
+                if sha != checksum {
+                    return Err(format!(
+                        "Downloaded file has blob SHA {}, expected {}",
+                        sha, checksum
+                    ));
+                }
+                Ok(data)
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to download file: {}", e))?;
+    // Named .sha-less and table-less so a crash never leaves it to be loaded
+    let new_path = dir.join(format!(
+        "{}.{}",
+        NEW_FILE_PREFIX,
+        file_name.replace('.', "-")
+    ));
+    std::fs::write(&new_path, data).map_err(|e| format!("Failed to write file: {}", e))?;
+    Ok(new_path)
+}
+
+/// Downloads the file of `url_source` next to its cache file, unless the server says the cached
+/// copy is current or it arrives unchanged. Its validators are recorded in `source_files`.
+async fn sync_url_file(
+    dir: &Path,
+    url_source: &UrlSource,
+    force: bool,
+    source_files: &mut Vec<SourceFile>,
+    file_progress: DownloadProgress,
+    downloader: &Downloader,
+) -> Result<Option<PendingFile>, String> {
+    let file_name = url_source.file_name();
+    let file_path = dir.join(&file_name);
+    let sha_path = file_path.with_file_name(format!("{}.sha", file_name));
+    let existing_sha = std::fs::read_to_string(&sha_path)
+        .ok()
+        .filter(|_| file_path.exists());
+
+    // Mirrors are asked with the same validators; one that does not know them sends the file
+    let known = source_files
+        .iter()
+        .find(|known| known.file_name == file_name)
+        .filter(|_| !force && existing_sha.is_some());
+    let fetched = downloader
+        .fetch_from_any(url_source.urls(), |url| {
+            let file_progress = file_progress.clone();
+            async move {
+                let mut request = reqwest::Client::new()
+                    .get(&url)
+                    .header("User-Agent", "Rust-Client");
+                if let Some(known) = known {
+                    if let Some(etag) = &known.etag {
+                        request = request.header(IF_NONE_MATCH, etag);
+                    }
+                    if let Some(last_modified) = &known.last_modified {
+                        request = request.header(IF_MODIFIED_SINCE, last_modified);
+                    }
+                }
+                let response = request.send().await.map_err(|e| e.to_string())?;
+                if response.status() == StatusCode::NOT_MODIFIED {
+                    return Ok(None);
+                }
+                if !response.status().is_success() {
+                    return Err(format!("Failed to download file: {}", response.status()));
+                }
+                let header = |name| {
+                    response
+                        .headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string)
+                };
+                let etag = header(ETAG);
+                let last_modified = header(LAST_MODIFIED);
+                let data = read_body(response, file_progress, downloader).await?;
+                Ok(Some((etag, last_modified, data)))
+            }
+        })
+        .await?;
+    let Some((etag, last_modified, data)) = fetched else {
+        info!(file_name, "File is up to date, skipping");
+        send_up_to_date(downloader, file_progress);
+        return Ok(None);
+    };
+
+    let checksum = format!("{:016x}", xxh64(&data, 0));
+    let unchanged = existing_sha.is_some_and(|sha| sha.trim() == checksum);
+    let source_file = SourceFile {
+        file_name: file_name.clone(),
+        blob_sha: checksum.clone(),
+        url: Some(url_source.url.clone()),
+        etag,
+        last_modified,
+        ..SourceFile::default()
+    };
+    let source_file = if unchanged {
+        // The server does not answer conditional requests, or its validators changed
+        upsert_source_file(source_files, source_file);
+        if !force {
+            info!(file_name, "File is unchanged, skipping");
+            return Ok(None);
+        }
+        None
+    } else {
+        Some(source_file)
+    };
+
+    let new_path = dir.join(format!(
+        "{}.{}",
+        NEW_FILE_PREFIX,
+        file_name.replace('.', "-")
+    ));
+    std::fs::write(&new_path, data).map_err(|e| format!("Failed to write file: {}", e))?;
+    Ok(Some(PendingFile {
+        path: file_path,
+        new_path,
+        checksum,
+        source_file,
+    }))
+}
+
+/// Removes the files of URL sources no longer configured, so they stop being loaded
+fn remove_stale_url_files(dir: &Path, urls: &[UrlSource], source_files: &mut Vec<SourceFile>) {
+    let current: HashSet<String> = urls.iter().map(UrlSource::file_name).collect();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let file_name = name.strip_suffix(".sha").unwrap_or(&name);
+        if !file_name.starts_with("hashes.")
+            || !file_name.contains(URL_FILE_MARKER)
+            || current.contains(file_name)
+        {
+            continue;
+        }
+        match std::fs::remove_file(entry.path()) {
+            Ok(()) => info!(file_name = name, "Removed file of unconfigured URL source"),
+            Err(e) => warn!(file_name = name, "Failed to remove file: {}", e),
+        }
+        source_files.retain(|known| known.file_name != file_name);
+    }
+}
+
+/// Diffs each table whose files are being replaced, comparing all of its files before and
+/// after, so entries moving between files are no change. Skipped on the first sync and for
+/// tables that did not change, and a failed diff only costs the diff, not the sync.
+fn record_diffs(dir: &Path, pending: &[PendingFile]) {
+    for hashtable_type in HashtableType::ALL {
+        let files: Vec<&PendingFile> = pending
+            .iter()
+            .filter(|file| table_of(&file.path) == Some(hashtable_type))
+            .collect();
+        if files.iter().all(|file| file.source_file.is_none()) {
+            continue;
+        }
+        let old: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| {
+                        path.extension().is_none_or(|ext| ext != "sha")
+                            && table_of(path) == Some(hashtable_type)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        if old.is_empty() {
+            continue;
+        }
+
+        let replaced: HashMap<&Path, &Path> = files
+            .iter()
+            .map(|file| (file.path.as_path(), file.new_path.as_path()))
+            .collect();
+        let new: Vec<PathBuf> = old
+            .iter()
+            .map(|path| {
+                replaced
+                    .get(path.as_path())
+                    .map_or(path.as_path(), |new| new)
+            })
+            .chain(
+                files
+                    .iter()
+                    .filter(|file| !file.path.exists())
+                    .map(|file| file.new_path.as_path()),
+            )
+            .map(Path::to_path_buf)
+            .collect();
+        let result = diff_files(&old, &new).and_then(|diff| {
+            info!(
+                hashtable_type = hashtable_type.as_str(),
+                added = diff.added.len(),
+                removed = diff.removed.len(),
+                renamed = diff.renamed.len(),
+                "Hashtable changed"
+            );
+            save_diff(dir, hashtable_type, &diff)
+        });
+        if let Err(e) = result {
+            warn!(
+                hashtable_type = hashtable_type.as_str(),
+                "Failed to diff hashtable: {}", e
+            );
+        }
+    }
+}
+
+fn table_of(path: &Path) -> Option<HashtableType> {
+    path.file_name()
+        .and_then(|name| HashtableType::from_file_name(&name.to_string_lossy()))
+}
+
+#[instrument]
+async fn get_git_data(url: &str) -> Result<Value, String> {
+    let response = http_get(url).await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!(
+            "GitHub API request failed with status {}: {}",
+            status, text
+        ));
+    }
+
+    response.json().await.map_err(|e| e.to_string())
+}
+
+/// Git's SHA-1 of `data` as a blob, which the GitHub contents API reports as `sha`
+fn blob_sha(data: &[u8]) -> String {
+    let mut context = ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY);
+    context.update(format!("blob {}\0", data.len()).as_bytes());
+    context.update(data);
+    context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[instrument(skip(file_progress, downloader))]
+async fn download_file(
+    url: &str,
+    file_progress: DownloadProgress,
+    downloader: &Downloader,
+) -> Result<Vec<u8>, String> {
+    let response = http_get(url).await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Failed to download file: {}", response.status()));
+    }
+
+    read_body(response, file_progress, downloader).await
+}
+
+/// Reads the body of `response` at the downloader's rate, publishing how much of it arrived
+/// every [`DOWNLOAD_PROGRESS_INTERVAL`] and once it is complete
+async fn read_body(
+    response: reqwest::Response,
+    mut file_progress: DownloadProgress,
+    downloader: &Downloader,
+) -> Result<Vec<u8>, String> {
+    file_progress.total_bytes = response.content_length().unwrap_or(0);
+    let started = Instant::now();
+    let mut last_update = started;
+    let mut data = Vec::new();
+    let mut chunks = Throttled::new(Box::pin(response.bytes_stream()), downloader.rate_limit);
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        data.extend_from_slice(&chunk);
+        if last_update.elapsed() >= DOWNLOAD_PROGRESS_INTERVAL {
+            last_update = Instant::now();
+            file_progress.bytes_downloaded = data.len() as u64;
+            file_progress.elapsed = started.elapsed();
+            // Nobody listening is not an error
+            let _ = downloader.progress.send(file_progress.clone());
+        }
+    }
+    file_progress.bytes_downloaded = data.len() as u64;
+    file_progress.elapsed = started.elapsed();
+    file_progress.done = true;
+    let _ = downloader.progress.send(file_progress);
+    Ok(data)
+}
+
+/// Publishes that a file was skipped because the cached copy is current
+fn send_up_to_date(downloader: &Downloader, file_progress: DownloadProgress) {
+    let _ = downloader.progress.send(DownloadProgress {
+        done: true,
+        up_to_date: true,
+        ..file_progress
+    });
+}
+
+async fn http_get(url: &str) -> Result<reqwest::Response, String> {
+    reqwest::Client::new()
+        .get(url)
+        .header("User-Agent", "Rust-Client")
+        .send()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    /// Serves `body` to every request, like a mirror, and returns its template
+    async fn serve(body: &'static [u8]) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request).await;
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(header.as_bytes()).await;
+                let _ = stream.write_all(body).await;
+            }
+        });
+        format!("http://{}/{{file}}", addr)
+    }
+
+    #[test]
+    fn blob_sha_matches_git() {
+        // As `git hash-object` computes them
+        assert_eq!(
+            blob_sha(b"hello\n"),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+        assert_eq!(blob_sha(b""), "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+    }
+
+    #[tokio::test]
+    async fn restores_a_file_from_the_mirrors_when_the_api_is_down() {
+        let dir = std::env::temp_dir().join(format!("hash-service-mirror-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file_name = "hashes.game.txt.0";
+        let only = [file_name.to_string()];
+        save_source_files(
+            &dir,
+            &[SourceFile {
+                file_name: file_name.to_string(),
+                blob_sha: blob_sha(b"1 a\n"),
+                ..SourceFile::default()
+            }],
+        )
+        .unwrap();
+        let downloader = |mirror: String| Downloader {
+            progress: broadcast::Sender::new(16),
+            rate_limit: None,
+            mirrors: vec![mirror],
+            health: MirrorHealth::default(),
+            // Nothing listens there, so the API calls fail right away
+            api_url: "http://127.0.0.1:1".to_string(),
+        };
+        let source = HashSource::default();
+
+        // A mirror whose copy isn't at the last known SHA is not trusted
+        let stale = downloader(serve(b"2 b\n").await);
+        let stale = sync_hashtables(&dir, &source, &[], &only, false, &stale).await;
+        let stale_file = dir.join(file_name).exists();
+
+        let current = downloader(serve(b"1 a\n").await);
+        let outcome = sync_hashtables(&dir, &source, &[], &only, false, &current).await;
+        let data = std::fs::read(dir.join(file_name));
+        let sha = std::fs::read_to_string(dir.join(format!("{}.sha", file_name)));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(stale.unwrap_err().contains("expected"));
+        assert!(!stale_file);
+        let outcome = outcome.unwrap();
+        assert!(outcome.failed.is_empty());
+        assert_eq!(outcome.updated_files, 0);
+        assert_eq!(data.unwrap(), b"1 a\n");
+        assert_eq!(sha.unwrap(), blob_sha(b"1 a\n"));
+    }
+
+    #[test]
+    fn diffs_the_whole_table_not_just_the_changed_files() {
+        let dir = std::env::temp_dir().join(format!("hash-service-diff-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, text: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, text).unwrap();
+            path
+        };
+        // `b` moves from the first file to the second, which keeps `c`, and `d` is dropped
+        // from the first file but still listed in the third, which is unchanged
+        write("hashes.game.txt.0", "1 a\n2 b\n4 d\n");
+        write("hashes.game.txt.1", "3 c\n");
+        write("hashes.game.txt.2", "4 d\n");
+        let pending = |name: &str, text: &str| PendingFile {
+            path: dir.join(name),
+            new_path: write(
+                &format!("{}.{}", NEW_FILE_PREFIX, name.replace('.', "-")),
+                text,
+            ),
+            checksum: String::new(),
+            source_file: Some(SourceFile {
+                file_name: name.to_string(),
+                ..SourceFile::default()
+            }),
+        };
+        let pending = [
+            pending("hashes.game.txt.0", "1 A\n"),
+            pending("hashes.game.txt.1", "2 b\n3 c\n5 e\n"),
+        ];
+
+        record_diffs(&dir, &pending);
+        let diff = crate::diff::load_diff(&dir, HashtableType::Game)
+            .unwrap()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(diff.added, [(5, "e".to_string())]);
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.renamed, [(1, "a".to_string(), "A".to_string())]);
+    }
+}
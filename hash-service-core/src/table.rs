@@ -0,0 +1,646 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "fst-index")]
+use crate::index::{FstIndex, index_key};
+use crate::perfect::PerfectEntries;
+use crate::search::Page;
+#[cfg(feature = "fst-index")]
+use crate::search::{decode_page_token, encode_page_token, page_limit};
+#[cfg(not(feature = "fst-index"))]
+use crate::search::{paginate, starts_with_ignore_case};
+use crate::sorted::SortedEntries;
+use crate::tree::DirNode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryOrigin {
+    /// Loaded from a synced hashtable file
+    Official,
+    /// Added at runtime through `add_hash`
+    Custom,
+}
+
+/// Which value a table keeps when a hash it has is inserted with another one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// The last value wins, except that a synced value never replaces a custom one, so entries
+    /// added at runtime survive reloads
+    #[default]
+    KeepLast,
+    KeepFirst,
+    PreferOfficial,
+}
+
+impl ConflictPolicy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().replace('-', "_").as_str() {
+            "keep_last" => Ok(ConflictPolicy::KeepLast),
+            "keep_first" => Ok(ConflictPolicy::KeepFirst),
+            "prefer_official" => Ok(ConflictPolicy::PreferOfficial),
+            _ => Err(format!(
+                "Invalid conflict policy '{}', expected keep_last, keep_first or prefer_official",
+                value
+            )),
+        }
+    }
+
+    fn should_replace(self, existing: EntryOrigin, incoming: EntryOrigin) -> bool {
+        match self {
+            ConflictPolicy::KeepLast => {
+                !(existing == EntryOrigin::Custom && incoming == EntryOrigin::Official)
+            }
+            ConflictPolicy::KeepFirst => false,
+            // Official entries win over custom ones, otherwise the last one wins
+            ConflictPolicy::PreferOfficial => {
+                !(existing == EntryOrigin::Official && incoming == EntryOrigin::Custom)
+            }
+        }
+    }
+}
+
+/// How a table holds its entries in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableLayout {
+    /// A hash map: the fastest lookups and inserts
+    #[default]
+    Map,
+    /// Hashes in a sorted array and values in one blob, see [`SortedEntries`]: less than half
+    /// the memory of `Map`, for a binary search per lookup
+    Sorted,
+    /// Entries indexed by a minimal perfect hash built after every load, see
+    /// [`PerfectEntries`]: constant time lookups at about 12 bytes per entry plus the value
+    Perfect,
+}
+
+impl TableLayout {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "map" => Ok(TableLayout::Map),
+            "sorted" => Ok(TableLayout::Sorted),
+            "perfect" => Ok(TableLayout::Perfect),
+            _ => Err(format!(
+                "Invalid table layout '{}', expected map, sorted or perfect",
+                value
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TableLayout::Map => "map",
+            TableLayout::Sorted => "sorted",
+            TableLayout::Perfect => "perfect",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Entries {
+    Map(HashMap<u64, String>),
+    Sorted(SortedEntries),
+    Perfect(PerfectEntries),
+}
+
+impl Default for Entries {
+    fn default() -> Self {
+        Entries::new(TableLayout::default())
+    }
+}
+
+impl Entries {
+    fn new(layout: TableLayout) -> Self {
+        match layout {
+            TableLayout::Map => Entries::Map(HashMap::new()),
+            TableLayout::Sorted => Entries::Sorted(SortedEntries::default()),
+            TableLayout::Perfect => Entries::Perfect(PerfectEntries::default()),
+        }
+    }
+
+    fn layout(&self) -> TableLayout {
+        match self {
+            Entries::Map(_) => TableLayout::Map,
+            Entries::Sorted(_) => TableLayout::Sorted,
+            Entries::Perfect(_) => TableLayout::Perfect,
+        }
+    }
+
+    fn get(&self, hash: &u64) -> Option<&str> {
+        match self {
+            Entries::Map(map) => map.get(hash).map(String::as_str),
+            Entries::Sorted(sorted) => sorted.get(hash),
+            Entries::Perfect(perfect) => perfect.get(hash),
+        }
+    }
+
+    fn contains_key(&self, hash: &u64) -> bool {
+        match self {
+            Entries::Map(map) => map.contains_key(hash),
+            Entries::Sorted(sorted) => sorted.contains_key(hash),
+            Entries::Perfect(perfect) => perfect.contains_key(hash),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Entries::Map(map) => map.len(),
+            Entries::Sorted(sorted) => sorted.len(),
+            Entries::Perfect(perfect) => perfect.len(),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&u64, &str)> + '_> {
+        match self {
+            Entries::Map(map) => Box::new(map.iter().map(|(hash, value)| (hash, value.as_str()))),
+            Entries::Sorted(sorted) => Box::new(sorted.iter()),
+            Entries::Perfect(perfect) => Box::new(perfect.iter()),
+        }
+    }
+
+    fn insert(&mut self, hash: u64, value: String) -> Option<String> {
+        match self {
+            Entries::Map(map) => map.insert(hash, value),
+            Entries::Sorted(sorted) => sorted.insert(hash, &value),
+            Entries::Perfect(perfect) => perfect.insert(hash, value),
+        }
+    }
+
+    fn remove(&mut self, hash: &u64) -> Option<String> {
+        match self {
+            Entries::Map(map) => map.remove(hash),
+            Entries::Sorted(sorted) => sorted.remove(hash),
+            Entries::Perfect(perfect) => perfect.remove(hash),
+        }
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&u64) -> bool) {
+        match self {
+            Entries::Map(map) => map.retain(|hash, _| keep(hash)),
+            Entries::Sorted(sorted) => sorted.retain(keep),
+            Entries::Perfect(perfect) => perfect.retain(keep),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Entries::Map(map) => map.clear(),
+            Entries::Sorted(sorted) => sorted.clear(),
+            Entries::Perfect(perfect) => perfect.clear(),
+        }
+    }
+
+    fn shrink_to_fit(&mut self) {
+        match self {
+            Entries::Map(map) => map.shrink_to_fit(),
+            Entries::Sorted(sorted) => sorted.shrink_to_fit(),
+            Entries::Perfect(perfect) => perfect.shrink_to_fit(),
+        }
+    }
+
+    fn memory_estimate(&self) -> usize {
+        match self {
+            Entries::Map(map) => {
+                // Key, value and the control byte of each slot
+                let slot = size_of::<u64>() + size_of::<String>() + 1;
+                map.capacity() * slot + map.values().map(String::capacity).sum::<usize>()
+            }
+            Entries::Sorted(sorted) => sorted.memory_estimate(),
+            Entries::Perfect(perfect) => perfect.memory_estimate(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum InsertOutcome {
+    Inserted,
+    Unchanged,
+    /// The hash mapped to another value: `existing` was displaced when `replaced`, `incoming`
+    /// was rejected otherwise
+    Conflict {
+        existing: String,
+        incoming: String,
+        replaced: bool,
+    },
+}
+
+/// Synced entries plus an overlay of runtime additions. Custom entries that override a
+/// synced one keep the synced value around so it can still be reported.
+#[derive(Debug, Default)]
+pub struct Hashtable {
+    entries: Entries,
+    custom: HashSet<u64>,
+    /// Synced values hidden by a custom entry for the same hash
+    shadowed: HashMap<u64, String>,
+    /// File each synced value was read from, as an index into `source_files`; only filled
+    /// while a load tracks sources, see [`Hashtable::set_source`]
+    sources: HashMap<u64, u16>,
+    source_files: Vec<String>,
+    /// Index of the file being read
+    current_source: Option<u16>,
+    /// Lazily built directory view of the values, reset on every mutation
+    tree: OnceLock<DirNode>,
+    /// Cached [`Hashtable::memory_estimate`], reset on every mutation
+    memory: OnceLock<usize>,
+    /// Lazily built sorted index for prefix and range queries, reset on every mutation
+    #[cfg(feature = "fst-index")]
+    fst: OnceLock<FstIndex>,
+}
+
+impl Hashtable {
+    pub fn with_layout(layout: TableLayout) -> Self {
+        Hashtable {
+            entries: Entries::new(layout),
+            ..Hashtable::default()
+        }
+    }
+
+    pub fn layout(&self) -> TableLayout {
+        self.entries.layout()
+    }
+
+    pub fn get(&self, hash: &u64) -> Option<&str> {
+        self.entries.get(hash)
+    }
+
+    pub fn contains(&self, hash: &u64) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&u64, &str)> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() == 0
+    }
+
+    pub fn custom_len(&self) -> usize {
+        self.custom.len()
+    }
+
+    /// Whether the entry was added at runtime rather than loaded from a synced file
+    pub fn is_custom(&self, hash: &u64) -> bool {
+        self.custom.contains(hash)
+    }
+
+    /// Where the entry for `hash` came from, `None` if there is none
+    pub fn origin(&self, hash: &u64) -> Option<EntryOrigin> {
+        self.entries
+            .contains_key(hash)
+            .then(|| self.origin_of(*hash))
+    }
+
+    /// The synced value a custom entry overrides, if it overrides one
+    pub fn shadowed(&self, hash: &u64) -> Option<&String> {
+        self.shadowed.get(hash)
+    }
+
+    /// File the synced value for `hash` was read from: the entry's value, or the one a custom
+    /// entry hides. `None` when the load did not track sources.
+    pub fn source_file(&self, hash: &u64) -> Option<&str> {
+        self.sources
+            .get(hash)
+            .and_then(|&index| self.source_files.get(index as usize))
+            .map(String::as_str)
+    }
+
+    /// Attributes the synced values inserted from now on to `file_name`, or to nothing with
+    /// `None`. Attribution stops once a table has been read from more files than an index
+    /// holds.
+    pub(crate) fn set_source(&mut self, file_name: Option<&str>) {
+        self.current_source = file_name.and_then(|file_name| {
+            let index = match self
+                .source_files
+                .iter()
+                .position(|known| known == file_name)
+            {
+                Some(index) => index,
+                None => {
+                    self.source_files.push(file_name.to_string());
+                    self.source_files.len() - 1
+                }
+            };
+            u16::try_from(index).ok()
+        });
+    }
+
+    fn record_source(&mut self, hash: u64) {
+        if let Some(index) = self.current_source {
+            self.sources.insert(hash, index);
+        }
+    }
+
+    /// Number of entries per first path segment (the whole value if it has no `/`)
+    pub fn top_level_counts(&self) -> HashMap<String, u64> {
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for (_, value) in self.entries.iter() {
+            let segment = value.split('/').next().unwrap_or_default();
+            match counts.get_mut(segment) {
+                Some(count) => *count += 1,
+                None => {
+                    counts.insert(segment.to_string(), 1);
+                }
+            }
+        }
+        counts
+    }
+
+    /// Approximate heap bytes held: the entries plus the strings they own. Lazily built
+    /// indexes are not included.
+    pub fn memory_estimate(&self) -> usize {
+        *self.memory.get_or_init(|| {
+            // Key, value and the control byte of each slot
+            let slot = size_of::<u64>() + size_of::<String>() + 1;
+            let shadowed: usize = self.shadowed.values().map(String::capacity).sum();
+            let source_files: usize = self.source_files.iter().map(String::capacity).sum();
+            self.entries.memory_estimate()
+                + self.shadowed.capacity() * slot
+                + shadowed
+                + self.custom.capacity() * (size_of::<u64>() + 1)
+                + self.sources.capacity() * (size_of::<u64>() + size_of::<u16>() + 1)
+                + source_files
+        })
+    }
+
+    pub fn path_tree(&self) -> &DirNode {
+        self.tree
+            .get_or_init(|| DirNode::build(self.entries.iter()))
+    }
+
+    #[cfg(feature = "fst-index")]
+    fn fst_index(&self) -> &FstIndex {
+        self.fst
+            .get_or_init(|| FstIndex::build(self.entries.iter()))
+    }
+
+    /// Entries whose value starts with `prefix` (ASCII case-insensitive)
+    #[cfg(feature = "fst-index")]
+    pub fn prefix_page(&self, prefix: &str, page_token: &str, limit: u32) -> Result<Page, String> {
+        let limit = page_limit(limit);
+        let after = Self::index_cursor(page_token)?;
+        let hashes = self.fst_index().prefix(prefix, after.as_deref(), limit + 1);
+        Ok(self.index_page(hashes, limit))
+    }
+
+    /// Entries whose value starts with `prefix` (ASCII case-insensitive)
+    #[cfg(not(feature = "fst-index"))]
+    pub fn prefix_page(&self, prefix: &str, page_token: &str, limit: u32) -> Result<Page, String> {
+        let matches = self
+            .entries
+            .iter()
+            .filter(|(_, value)| starts_with_ignore_case(value, prefix))
+            .map(|(hash, value)| (*hash, value))
+            .collect();
+        paginate(matches, page_token, limit)
+    }
+
+    /// Entries whose lowercased value lies in `[start, end)`; an empty `end` is unbounded
+    #[cfg(feature = "fst-index")]
+    pub fn range_page(
+        &self,
+        start: &str,
+        end: &str,
+        page_token: &str,
+        limit: u32,
+    ) -> Result<Page, String> {
+        let limit = page_limit(limit);
+        let after = Self::index_cursor(page_token)?;
+        let hashes = self
+            .fst_index()
+            .range(start, end, after.as_deref(), limit + 1);
+        Ok(self.index_page(hashes, limit))
+    }
+
+    /// Entries whose lowercased value lies in `[start, end)`; an empty `end` is unbounded
+    #[cfg(not(feature = "fst-index"))]
+    pub fn range_page(
+        &self,
+        start: &str,
+        end: &str,
+        page_token: &str,
+        limit: u32,
+    ) -> Result<Page, String> {
+        let start = start.to_ascii_lowercase();
+        let end = end.to_ascii_lowercase();
+        let matches = self
+            .entries
+            .iter()
+            .filter(|(_, value)| {
+                let value = value.to_ascii_lowercase();
+                value >= start && (end.is_empty() || value < end)
+            })
+            .map(|(hash, value)| (*hash, value))
+            .collect();
+        paginate(matches, page_token, limit)
+    }
+
+    #[cfg(feature = "fst-index")]
+    fn index_cursor(page_token: &str) -> Result<Option<Vec<u8>>, String> {
+        if page_token.is_empty() {
+            return Ok(None);
+        }
+        let (hash, value) = decode_page_token(page_token)?;
+        Ok(Some(index_key(value, hash)))
+    }
+
+    /// Resolves up to `limit` hashes from an index query fetched with one extra lookahead entry
+    #[cfg(feature = "fst-index")]
+    fn index_page(&self, hashes: Vec<u64>, limit: usize) -> Page {
+        let has_more = hashes.len() > limit;
+        let entries: Vec<(u64, String)> = hashes
+            .into_iter()
+            .take(limit)
+            .filter_map(|hash| {
+                self.entries
+                    .get(&hash)
+                    .map(|value| (hash, value.to_string()))
+            })
+            .collect();
+        let next_page_token = match entries.last() {
+            Some((hash, value)) if has_more => encode_page_token(*hash, value),
+            _ => String::new(),
+        };
+
+        Page {
+            entries,
+            next_page_token,
+        }
+    }
+
+    fn invalidate_indexes(&mut self) {
+        self.tree = OnceLock::new();
+        self.memory = OnceLock::new();
+        #[cfg(feature = "fst-index")]
+        {
+            self.fst = OnceLock::new();
+        }
+    }
+
+    /// Drops every custom entry, bringing back the synced values they overrode, and returns
+    /// how many were dropped
+    pub fn clear_custom(&mut self) -> usize {
+        let custom = std::mem::take(&mut self.custom);
+        let shadowed = std::mem::take(&mut self.shadowed);
+        for hash in &custom {
+            match shadowed.get(hash) {
+                Some(official) => self.entries.insert(*hash, official.clone()),
+                None => self.entries.remove(hash),
+            };
+        }
+        if !custom.is_empty() {
+            self.invalidate_indexes();
+        }
+        custom.len()
+    }
+
+    /// Drops the synced entries ahead of a reload and keeps the ones added at runtime. The
+    /// synced values those overrode come back with the reload.
+    pub(crate) fn clear_official(&mut self) {
+        let custom = &self.custom;
+        self.entries.retain(|hash| custom.contains(hash));
+        self.shadowed.clear();
+        self.clear_sources();
+        self.invalidate_indexes();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.custom.clear();
+        self.shadowed.clear();
+        self.clear_sources();
+        self.invalidate_indexes();
+    }
+
+    fn clear_sources(&mut self) {
+        self.sources.clear();
+        self.source_files.clear();
+        self.current_source = None;
+    }
+
+    /// Replaces the whole table, e.g. with the contents of a snapshot, keeping its layout
+    pub(crate) fn replace(&mut self, entries: HashMap<u64, String>, custom: HashSet<u64>) {
+        self.entries = match self.entries.layout() {
+            TableLayout::Map => Entries::Map(entries),
+            layout => {
+                let mut table = Entries::new(layout);
+                for (hash, value) in entries {
+                    table.insert(hash, value);
+                }
+                table
+            }
+        };
+        self.custom = custom;
+        self.shadowed.clear();
+        self.clear_sources();
+        self.finish_inserts();
+        self.invalidate_indexes();
+    }
+
+    /// Settles a batch of inserts, e.g. a load: a sorted table merges them into its array and
+    /// a perfect one rebuilds its hash function
+    pub fn finish_inserts(&mut self) {
+        match &mut self.entries {
+            Entries::Map(_) => return,
+            Entries::Sorted(sorted) => sorted.merge(),
+            Entries::Perfect(perfect) => perfect.build(),
+        }
+        self.memory = OnceLock::new();
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+        self.custom.shrink_to_fit();
+        self.shadowed.shrink_to_fit();
+        self.sources.shrink_to_fit();
+        self.memory = OnceLock::new();
+    }
+
+    fn origin_of(&self, hash: u64) -> EntryOrigin {
+        if self.custom.contains(&hash) {
+            EntryOrigin::Custom
+        } else {
+            EntryOrigin::Official
+        }
+    }
+
+    fn set_origin(&mut self, hash: u64, origin: EntryOrigin) {
+        match origin {
+            EntryOrigin::Custom => self.custom.insert(hash),
+            EntryOrigin::Official => {
+                self.shadowed.remove(&hash);
+                self.custom.remove(&hash)
+            }
+        };
+        self.memory = OnceLock::new();
+    }
+
+    pub fn insert(
+        &mut self,
+        hash: u64,
+        value: String,
+        origin: EntryOrigin,
+        policy: ConflictPolicy,
+    ) -> InsertOutcome {
+        let existing_origin = self.origin_of(hash);
+
+        match self.entries.get(&hash) {
+            None => {
+                self.entries.insert(hash, value);
+                self.set_origin(hash, origin);
+                if origin == EntryOrigin::Official {
+                    self.record_source(hash);
+                }
+                self.invalidate_indexes();
+                InsertOutcome::Inserted
+            }
+            Some(existing) if existing == value => {
+                // Same mapping, only upgrade the origin if the policy allows it
+                if policy.should_replace(existing_origin, origin) {
+                    self.set_origin(hash, origin);
+                }
+                // Attributed to the first synced file that has it
+                if existing_origin == EntryOrigin::Custom && origin == EntryOrigin::Official {
+                    self.record_source(hash);
+                }
+                InsertOutcome::Unchanged
+            }
+            Some(existing) => {
+                if policy.should_replace(existing_origin, origin) {
+                    let incoming = value.clone();
+                    let existing = self.entries.insert(hash, value).unwrap_or_default();
+                    if existing_origin == EntryOrigin::Official && origin == EntryOrigin::Custom {
+                        self.shadowed.insert(hash, existing.clone());
+                    }
+                    self.set_origin(hash, origin);
+                    if origin == EntryOrigin::Official {
+                        self.record_source(hash);
+                    }
+                    self.invalidate_indexes();
+                    InsertOutcome::Conflict {
+                        existing,
+                        incoming,
+                        replaced: true,
+                    }
+                } else {
+                    let existing = existing.to_string();
+                    if existing_origin == EntryOrigin::Custom && origin == EntryOrigin::Official {
+                        self.shadowed.insert(hash, value.clone());
+                        self.record_source(hash);
+                    }
+                    InsertOutcome::Conflict {
+                        existing,
+                        incoming: value,
+                        replaced: false,
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Download rate limiting, so a background sync leaves bandwidth for everything else on the
+//! connection, e.g. a game being played.
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
+use tokio::time::{Instant, Sleep, sleep_until};
+use tokio_stream::Stream;
+
+/// Byte stream held to `bytes_per_second` on average: a chunk that gets ahead of the rate
+/// delays the next one until the bytes so far would have taken that long. Passes everything
+/// straight through without a rate.
+pub(crate) struct Throttled<S> {
+    inner: S,
+    bytes_per_second: Option<u64>,
+    started: Instant,
+    bytes: u64,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> Throttled<S> {
+    pub(crate) fn new(inner: S, bytes_per_second: Option<u64>) -> Self {
+        Throttled {
+            inner,
+            bytes_per_second: bytes_per_second.filter(|rate| *rate > 0),
+            started: Instant::now(),
+            bytes: 0,
+            delay: None,
+        }
+    }
+}
+
+impl<S, B, E> Stream for Throttled<S>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(delay) = &mut this.delay {
+            ready!(delay.as_mut().poll(cx));
+            this.delay = None;
+        }
+        let item = ready!(Pin::new(&mut this.inner).poll_next(cx));
+        if let (Some(Ok(chunk)), Some(rate)) = (&item, this.bytes_per_second) {
+            this.bytes += chunk.as_ref().len() as u64;
+            let due = this.started + Duration::from_secs_f64(this.bytes as f64 / rate as f64);
+            if due > Instant::now() {
+                this.delay = Some(Box::pin(sleep_until(due)));
+            }
+        }
+        Poll::Ready(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_stream::StreamExt;
+
+    use super::*;
+
+    /// Time from the start at which each chunk of `chunks` bytes came through, and when the
+    /// stream ended
+    async fn arrivals(
+        chunks: &[usize],
+        bytes_per_second: Option<u64>,
+    ) -> (Vec<Duration>, Duration) {
+        let started = Instant::now();
+        let chunks: Vec<Result<Vec<u8>, ()>> = chunks.iter().map(|&len| Ok(vec![0; len])).collect();
+        let mut stream = Throttled::new(tokio_stream::iter(chunks), bytes_per_second);
+        let mut arrivals = Vec::new();
+        while stream.next().await.is_some() {
+            arrivals.push(started.elapsed());
+        }
+        (arrivals, started.elapsed())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn holds_to_the_byte_budget() {
+        let (arrivals, total) = arrivals(&[1000; 5], Some(1000)).await;
+        let seconds: Vec<u64> = arrivals.iter().map(Duration::as_secs).collect();
+        assert_eq!(seconds, [0, 1, 2, 3, 4]);
+        assert_eq!(total.as_secs(), 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn small_chunks_add_up_to_the_budget() {
+        let (arrivals, total) = arrivals(&[250; 8], Some(1000)).await;
+        assert_eq!(arrivals[3].as_millis(), 750);
+        assert_eq!(arrivals[4].as_millis(), 1000);
+        assert_eq!(total.as_millis(), 2000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn passes_through_without_a_limit() {
+        for limit in [None, Some(0)] {
+            let (arrivals, total) = arrivals(&[1 << 20; 4], limit).await;
+            assert_eq!(arrivals.len(), 4);
+            assert_eq!(total, Duration::ZERO, "{:?}", limit);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::collections::BTreeMap;
+
+/// A directory in the virtual filesystem formed by the `/`-separated table values.
+#[derive(Debug, Default)]
+pub struct DirNode {
+    pub dirs: BTreeMap<String, DirNode>,
+    pub files: BTreeMap<String, u64>,
+}
+
+impl DirNode {
+    pub fn build<'a>(entries: impl Iterator<Item = (&'a u64, &'a str)>) -> Self {
+        let mut root = DirNode::default();
+
+        for (hash, value) in entries {
+            let mut segments = value.split('/').filter(|s| !s.is_empty()).peekable();
+            let mut node = &mut root;
+            while let Some(segment) = segments.next() {
+                if segments.peek().is_none() {
+                    node.files.insert(segment.to_string(), *hash);
+                } else {
+                    node = node.dirs.entry(segment.to_string()).or_default();
+                }
+            }
+        }
+
+        root
+    }
+
+    pub fn find(&self, path: &str) -> Option<&DirNode> {
+        path.split('/')
+            .filter(|s| !s.is_empty())
+            .try_fold(self, |node, segment| node.dirs.get(segment))
+    }
+}
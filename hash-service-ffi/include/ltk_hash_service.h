@@ -0,0 +1,89 @@
+#ifndef LTK_HASH_SERVICE_H
+#define LTK_HASH_SERVICE_H
+
+/* Generated with cbindgen from hash-service-ffi/src/lib.rs, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum HsStatus {
+  HS_STATUS_OK = 0,
+  /**
+   * The hash is not in the table
+   */
+  HS_STATUS_NOT_FOUND = 1,
+  HS_STATUS_INVALID_ARGUMENT = 2,
+  HS_STATUS_ERROR = 3,
+} HsStatus;
+
+typedef enum HsTable {
+  HS_TABLE_GAME = 0,
+  HS_TABLE_BIN = 1,
+} HsTable;
+
+/**
+ * Opaque engine handle, created with [`hs_engine_new`] and freed with [`hs_engine_free`]
+ */
+typedef struct HsEngine HsEngine;
+
+/**
+ * Creates an engine with empty tables. `cache_dir` may be NULL to use the default
+ * cache directory. Returns NULL on failure.
+ *
+ * # Safety
+ * `cache_dir` must be NULL or a NUL-terminated string.
+ */
+struct HsEngine *hs_engine_new(const char *cache_dir);
+
+/**
+ * # Safety
+ * `engine` must be NULL or a handle from [`hs_engine_new`] that is not used afterwards.
+ */
+void hs_engine_free(struct HsEngine *engine);
+
+/**
+ * Syncs the hashtables from GitHub and loads them, blocking until done
+ *
+ * # Safety
+ * `engine` must be a live handle from [`hs_engine_new`].
+ */
+enum HsStatus hs_load(const struct HsEngine *engine);
+
+/**
+ * Resolves `hash` in `table`. On success `*out` receives a string the caller frees
+ * with [`hs_string_free`]; it is left untouched otherwise.
+ *
+ * # Safety
+ * `engine` must be a live handle and `out` a valid pointer.
+ */
+enum HsStatus hs_lookup(const struct HsEngine *engine, enum HsTable table, uint64_t hash, char **out);
+
+/**
+ * Normalizes and hashes `value` for `table` and adds it as a custom entry. `out_hash` may
+ * be NULL, otherwise it receives the hash.
+ *
+ * # Safety
+ * `engine` must be a live handle, `value` a NUL-terminated string and `out_hash`
+ * NULL or a valid pointer.
+ */
+enum HsStatus hs_add(const struct HsEngine *engine,
+                     enum HsTable table,
+                     const char *value,
+                     uint64_t *out_hash);
+
+/**
+ * # Safety
+ * `value` must be NULL or a string returned by this library that is not used afterwards.
+ */
+void hs_string_free(char *value);
+
+/**
+ * Message for the last failed call on this thread, or NULL. Valid until the next
+ * failing call on the same thread.
+ */
+const char *hs_last_error(void);
+
+#endif /* LTK_HASH_SERVICE_H */
@@ -0,0 +1,212 @@
+//! C bindings over [`HashEngine`] for modding tools that can't speak gRPC.
+//!
+//! Every function returns an [`HsStatus`]; on failure [`hs_last_error`] describes what went
+//! wrong on the calling thread. The header in `include/` is generated with
+//! `cbindgen --config cbindgen.toml --output include/ltk_hash_service.h` (run from the crate dir).
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::path::PathBuf;
+use std::ptr;
+
+use hash_service_core::{HashEngine, HashtableType};
+use tokio::runtime::Runtime;
+
+/// Opaque engine handle, created with [`hs_engine_new`] and freed with [`hs_engine_free`]
+pub struct HsEngine {
+    engine: HashEngine,
+    runtime: Runtime,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HsTable {
+    Game = 0,
+    Bin = 1,
+}
+
+impl From<HsTable> for HashtableType {
+    fn from(table: HsTable) -> Self {
+        match table {
+            HsTable::Game => HashtableType::Game,
+            HsTable::Bin => HashtableType::Bin,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HsStatus {
+    Ok = 0,
+    /// The hash is not in the table
+    NotFound = 1,
+    InvalidArgument = 2,
+    Error = 3,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(status: HsStatus, message: impl Into<String>) -> HsStatus {
+    // Interior NULs can't be represented, drop them rather than the whole message
+    let message = message.into().replace('\0', "");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+    status
+}
+
+/// Converts a C string argument, failing on NULL or invalid UTF-8
+unsafe fn str_arg<'a>(value: *const c_char, name: &str) -> Result<&'a str, HsStatus> {
+    if value.is_null() {
+        return Err(fail(
+            HsStatus::InvalidArgument,
+            format!("{} must not be NULL", name),
+        ));
+    }
+    unsafe { CStr::from_ptr(value) }.to_str().map_err(|_| {
+        fail(
+            HsStatus::InvalidArgument,
+            format!("{} is not valid UTF-8", name),
+        )
+    })
+}
+
+/// Creates an engine with empty tables. `cache_dir` may be NULL to use the default
+/// cache directory. Returns NULL on failure.
+///
+/// # Safety
+/// `cache_dir` must be NULL or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hs_engine_new(cache_dir: *const c_char) -> *mut HsEngine {
+    let cache_dir = if cache_dir.is_null() {
+        None
+    } else {
+        match unsafe { str_arg(cache_dir, "cache_dir") } {
+            Ok(cache_dir) => Some(PathBuf::from(cache_dir)),
+            Err(_) => return ptr::null_mut(),
+        }
+    };
+
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            fail(HsStatus::Error, format!("Failed to start runtime: {}", e));
+            return ptr::null_mut();
+        }
+    };
+
+    Box::into_raw(Box::new(HsEngine {
+        engine: HashEngine::new().with_cache_dir(cache_dir),
+        runtime,
+    }))
+}
+
+/// # Safety
+/// `engine` must be NULL or a handle from [`hs_engine_new`] that is not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hs_engine_free(engine: *mut HsEngine) {
+    if !engine.is_null() {
+        drop(unsafe { Box::from_raw(engine) });
+    }
+}
+
+/// Syncs the hashtables from GitHub and loads them, blocking until done
+///
+/// # Safety
+/// `engine` must be a live handle from [`hs_engine_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hs_load(engine: *const HsEngine) -> HsStatus {
+    let Some(engine) = (unsafe { engine.as_ref() }) else {
+        return fail(HsStatus::InvalidArgument, "engine must not be NULL");
+    };
+    match engine.runtime.block_on(engine.engine.load()) {
+        Ok(()) => HsStatus::Ok,
+        Err(e) => fail(HsStatus::Error, e),
+    }
+}
+
+/// Resolves `hash` in `table`. On success `*out` receives a string the caller frees
+/// with [`hs_string_free`]; it is left untouched otherwise.
+///
+/// # Safety
+/// `engine` must be a live handle and `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hs_lookup(
+    engine: *const HsEngine,
+    table: HsTable,
+    hash: u64,
+    out: *mut *mut c_char,
+) -> HsStatus {
+    let Some(engine) = (unsafe { engine.as_ref() }) else {
+        return fail(HsStatus::InvalidArgument, "engine must not be NULL");
+    };
+    if out.is_null() {
+        return fail(HsStatus::InvalidArgument, "out must not be NULL");
+    }
+
+    match engine.engine.get(table.into(), hash) {
+        Ok(Some(value)) => match CString::new(value) {
+            Ok(value) => {
+                unsafe { *out = value.into_raw() };
+                HsStatus::Ok
+            }
+            Err(_) => fail(HsStatus::Error, "Value contains a NUL byte"),
+        },
+        Ok(None) => HsStatus::NotFound,
+        Err(e) => fail(HsStatus::Error, e),
+    }
+}
+
+/// Normalizes and hashes `value` for `table` and adds it as a custom entry. `out_hash` may
+/// be NULL, otherwise it receives the hash.
+///
+/// # Safety
+/// `engine` must be a live handle, `value` a NUL-terminated string and `out_hash`
+/// NULL or a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hs_add(
+    engine: *const HsEngine,
+    table: HsTable,
+    value: *const c_char,
+    out_hash: *mut u64,
+) -> HsStatus {
+    let Some(engine) = (unsafe { engine.as_ref() }) else {
+        return fail(HsStatus::InvalidArgument, "engine must not be NULL");
+    };
+    let value = match unsafe { str_arg(value, "value") } {
+        Ok(value) => value,
+        Err(status) => return status,
+    };
+
+    match engine.engine.add(table.into(), value.to_string()) {
+        Ok((hash, _)) => {
+            if let Some(out_hash) = unsafe { out_hash.as_mut() } {
+                *out_hash = hash;
+            }
+            HsStatus::Ok
+        }
+        Err(e) => fail(HsStatus::Error, e),
+    }
+}
+
+/// # Safety
+/// `value` must be NULL or a string returned by this library that is not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hs_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(unsafe { CString::from_raw(value) });
+    }
+}
+
+/// Message for the last failed call on this thread, or NULL. Valid until the next
+/// failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn hs_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
@@ -0,0 +1,192 @@
+//! Tower layer that logs every RPC with its method, peer, latency and gRPC status code.
+//!
+//! Each call runs in a span with its request id, the one the client sent in `x-request-id` or
+//! a generated one, so its log lines can be found from the client side. The response headers
+//! carry the id back along with when the server received the call and how long it took.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tonic::transport::server::TcpConnectInfo;
+use tower::{Layer, Service};
+use tracing::{Instrument, info, info_span, warn};
+
+use crate::config::{env_flag, env_var};
+
+/// Id a client may send to find its call in the server logs; echoed in the response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// When the server received the call, in milliseconds since the Unix epoch
+const SERVER_TIME_HEADER: &str = "x-server-time-ms";
+/// How long the server took until it sent the response headers, in milliseconds
+const SERVER_DURATION_HEADER: &str = "x-server-duration-ms";
+/// Longer client ids are replaced by a generated one rather than logged
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessLogConfig {
+    /// Log one in every `sample_every` successful calls; 0 disables them. Failures are always logged.
+    pub sample_every: u64,
+    /// Replace peer addresses and user agents with a placeholder
+    pub redact: bool,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        AccessLogConfig {
+            sample_every: 1,
+            redact: false,
+        }
+    }
+}
+
+impl AccessLogConfig {
+    /// Applies `HASH_SERVICE_ACCESS_LOG_SAMPLE_EVERY` and `HASH_SERVICE_ACCESS_LOG_REDACT`
+    pub fn apply_env(&mut self) {
+        if let Some(sample_every) =
+            env_var("HASH_SERVICE_ACCESS_LOG_SAMPLE_EVERY").and_then(|v| v.parse().ok())
+        {
+            self.sample_every = sample_every;
+        }
+        if let Some(redact) = env_flag("HASH_SERVICE_ACCESS_LOG_REDACT") {
+            self.redact = redact;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccessLogLayer {
+    config: Arc<AccessLogConfig>,
+    calls: Arc<AtomicU64>,
+}
+
+impl AccessLogLayer {
+    pub fn new(config: AccessLogConfig) -> Self {
+        AccessLogLayer {
+            config: Arc::new(config),
+            calls: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn sampled(&self, call: u64) -> bool {
+        self.config.sample_every != 0 && call.is_multiple_of(self.config.sample_every)
+    }
+}
+
+impl<S> Layer<S> for AccessLogLayer {
+    type Service = AccessLog<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AccessLog {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccessLog<S> {
+    inner: S,
+    layer: AccessLogLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for AccessLog<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let method = request.uri().path().to_string();
+        let (peer, user_agent) = if self.layer.config.redact {
+            ("<redacted>".to_string(), "<redacted>".to_string())
+        } else {
+            (
+                request
+                    .extensions()
+                    .get::<TcpConnectInfo>()
+                    .and_then(|info| info.remote_addr())
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default(),
+                request
+                    .headers()
+                    .get(http::header::USER_AGENT)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+        };
+        let call = self.layer.calls.fetch_add(1, Ordering::Relaxed);
+        let sampled = self.layer.sampled(call);
+        let request_id = request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{:x}-{:x}", std::process::id(), call));
+        let received_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let span = info_span!("rpc", %request_id);
+        let start = Instant::now();
+        let future = span.in_scope(|| self.inner.call(request));
+
+        Box::pin(
+            async move {
+                let mut result = future.await;
+                let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+                if let Ok(response) = &mut result {
+                    let headers = response.headers_mut();
+                    for (name, value) in [
+                        (REQUEST_ID_HEADER, request_id),
+                        (SERVER_TIME_HEADER, received_ms.to_string()),
+                        (SERVER_DURATION_HEADER, format!("{:.3}", latency_ms)),
+                    ] {
+                        if let Ok(value) = http::HeaderValue::from_str(&value) {
+                            headers.insert(name, value);
+                        }
+                    }
+                }
+
+                match &result {
+                    Ok(response) => {
+                        let code = grpc_code(response);
+                        if code != tonic::Code::Ok {
+                            warn!(method, peer, user_agent, latency_ms, ?code, "RPC failed");
+                        } else if sampled {
+                            info!(method, peer, user_agent, latency_ms, ?code, "RPC completed");
+                        }
+                    }
+                    Err(_) => warn!(method, peer, user_agent, latency_ms, "RPC transport error"),
+                }
+
+                result
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Errors are returned trailers-only, so a missing `grpc-status` header means the call succeeded
+fn grpc_code<B>(response: &http::Response<B>) -> tonic::Code {
+    response
+        .headers()
+        .get("grpc-status")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i32>().ok())
+        .map(tonic::Code::from_i32)
+        .unwrap_or(tonic::Code::Ok)
+}
@@ -0,0 +1,210 @@
+//! Administrative RPCs for stopping or restarting the resident service.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::sync::watch;
+use tonic::{Request, Response, Status};
+use tracing::{info, instrument};
+
+use crate::cache::CacheManager;
+use crate::clients::ClientTracker;
+use crate::config::Config;
+use crate::hash_service::admin_server::Admin;
+use crate::hash_service::{
+    ConnectedClient, ListClientsRequest, ListClientsResponse, RestartRequest, RestartResponse,
+    SetCacheDirRequest, SetCacheDirResponse, ShutdownRequest, ShutdownResponse,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownKind {
+    Stop,
+    Restart,
+}
+
+#[derive(Debug)]
+pub struct AdminService {
+    shutdown: watch::Sender<Option<ShutdownKind>>,
+    clients: ClientTracker,
+    cache_manager: Option<Arc<CacheManager>>,
+    /// Config file SetCacheDir saves to when asked to persist
+    config_path: Option<PathBuf>,
+}
+
+impl AdminService {
+    pub fn new(shutdown: watch::Sender<Option<ShutdownKind>>, clients: ClientTracker) -> Self {
+        AdminService {
+            shutdown,
+            clients,
+            cache_manager: None,
+            config_path: None,
+        }
+    }
+
+    pub fn with_cache_manager(
+        mut self,
+        cache_manager: Arc<CacheManager>,
+        config_path: Option<PathBuf>,
+    ) -> Self {
+        self.cache_manager = Some(cache_manager);
+        self.config_path = config_path;
+        self
+    }
+
+    fn request_shutdown(&self, kind: ShutdownKind) {
+        request_shutdown(&self.shutdown, kind);
+    }
+}
+
+/// Records the first shutdown request; later ones are ignored
+pub fn request_shutdown(shutdown: &watch::Sender<Option<ShutdownKind>>, kind: ShutdownKind) {
+    shutdown.send_if_modified(|current| {
+        if current.is_some() {
+            return false;
+        }
+        *current = Some(kind);
+        true
+    });
+}
+
+/// Admin RPCs, and other RPCs that change the server's machine, are limited to local callers
+/// and clients that presented a trusted certificate
+pub(crate) fn authorize<T>(request: &Request<T>) -> Result<(), Status> {
+    if request.peer_certs().is_some_and(|certs| !certs.is_empty()) {
+        return Ok(());
+    }
+    match request.remote_addr() {
+        Some(addr) if addr.ip().to_canonical().is_loopback() => Ok(()),
+        _ => Err(Status::permission_denied(
+            "Only accepted from loopback or mutually authenticated clients",
+        )),
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    #[instrument(skip_all)]
+    async fn shutdown(
+        &self,
+        request: Request<ShutdownRequest>,
+    ) -> Result<Response<ShutdownResponse>, Status> {
+        authorize(&request)?;
+        info!(peer = ?request.remote_addr(), "Shutdown requested");
+
+        self.request_shutdown(ShutdownKind::Stop);
+        Ok(Response::new(ShutdownResponse {
+            success: true,
+            message: "Service is shutting down".to_string(),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn restart(
+        &self,
+        request: Request<RestartRequest>,
+    ) -> Result<Response<RestartResponse>, Status> {
+        authorize(&request)?;
+        info!(peer = ?request.remote_addr(), "Restart requested");
+
+        self.request_shutdown(ShutdownKind::Restart);
+        Ok(Response::new(RestartResponse {
+            success: true,
+            message: "Service is restarting".to_string(),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn list_clients(
+        &self,
+        request: Request<ListClientsRequest>,
+    ) -> Result<Response<ListClientsResponse>, Status> {
+        authorize(&request)?;
+        info!("ListClients called");
+
+        let clients = self
+            .clients
+            .clients()
+            .into_iter()
+            .map(|client| ConnectedClient {
+                peer: client.peer.to_string(),
+                user_agent: client.user_agent,
+                authenticated: client.authenticated,
+                connected_at: unix_secs(client.connected_at),
+                last_call_at: unix_secs(client.last_call_at),
+                active_calls: client.active_calls,
+                calls: client.calls,
+            })
+            .collect();
+        Ok(Response::new(ListClientsResponse { clients }))
+    }
+
+    #[instrument(skip_all)]
+    async fn set_cache_dir(
+        &self,
+        request: Request<SetCacheDirRequest>,
+    ) -> Result<Response<SetCacheDirResponse>, Status> {
+        authorize(&request)?;
+        let req = request.into_inner();
+        info!(cache_dir = %req.cache_dir, migrate = req.migrate, persist = req.persist, "SetCacheDir called");
+
+        let cache_manager = self
+            .cache_manager
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("Cache dir cannot be changed at runtime"))?;
+        let cache_dir = (!req.cache_dir.is_empty()).then(|| PathBuf::from(&req.cache_dir));
+        if cache_dir.as_ref().is_some_and(|dir| !dir.is_absolute()) {
+            return Err(Status::invalid_argument(
+                "Cache dir must be an absolute path",
+            ));
+        }
+        if req.persist && self.config_path.is_none() {
+            return Err(Status::failed_precondition(
+                "No config file to save the cache dir to",
+            ));
+        }
+
+        let root = cache_manager
+            .relocate(cache_dir.clone(), req.migrate)
+            .await
+            .map_err(Status::internal)?;
+        if req.persist
+            && let Some(path) = &self.config_path
+        {
+            persist_cache_dir(path, cache_dir).map_err(Status::internal)?;
+        }
+        Ok(Response::new(SetCacheDirResponse {
+            cache_dir: root.display().to_string(),
+        }))
+    }
+}
+
+/// Sets `cache_dir` in the config file at `path`, keeping its other settings
+fn persist_cache_dir(path: &Path, cache_dir: Option<PathBuf>) -> Result<(), String> {
+    let mut config = if path.exists() {
+        Config::load(Some(path))?
+    } else {
+        Config::default()
+    };
+    config.cache_dir = cache_dir;
+    config.save(path)
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Resolves once a shutdown or restart has been requested
+pub async fn shutdown_signal(mut shutdown: watch::Receiver<Option<ShutdownKind>>) {
+    let _ = shutdown.wait_for(Option::is_some).await;
+}
+
+/// Starts a fresh copy of this process with the same arguments
+pub fn spawn_replacement() -> std::io::Result<()> {
+    let exe = std::env::current_exe()?;
+    std::process::Command::new(exe)
+        .args(std::env::args_os().skip(1))
+        .spawn()?;
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! Optional mimalloc allocator. The system allocator fragments after repeated load/unload
+//! cycles and keeps the freed pages; mimalloc can hand them back and report what it holds.
+
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+/// Memory the allocator holds, as it reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocatorStats {
+    pub name: &'static str,
+    /// Pages of the process that are in RAM
+    pub resident_bytes: u64,
+    /// Memory committed to the allocator's heaps, whether or not it is in use
+    pub committed_bytes: u64,
+}
+
+/// Statistics of the global allocator, `None` with the system allocator which keeps none
+#[cfg(feature = "mimalloc")]
+pub fn stats() -> Option<AllocatorStats> {
+    let (mut elapsed, mut user, mut system) = (0, 0, 0);
+    let (mut rss, mut peak_rss, mut commit, mut peak_commit, mut page_faults) = (0, 0, 0, 0, 0);
+    // SAFETY: every pointer is to a live local the call only writes through
+    unsafe {
+        libmimalloc_sys::mi_process_info(
+            &mut elapsed,
+            &mut user,
+            &mut system,
+            &mut rss,
+            &mut peak_rss,
+            &mut commit,
+            &mut peak_commit,
+            &mut page_faults,
+        );
+    }
+    Some(AllocatorStats {
+        name: "mimalloc",
+        resident_bytes: rss as u64,
+        committed_bytes: commit as u64,
+    })
+}
+
+#[cfg(not(feature = "mimalloc"))]
+pub fn stats() -> Option<AllocatorStats> {
+    None
+}
+
+/// Returns memory freed by unloading to the OS instead of keeping it for later allocations
+pub fn release_memory() {
+    #[cfg(feature = "mimalloc")]
+    // SAFETY: only frees pages no allocation uses
+    unsafe {
+        libmimalloc_sys::mi_collect(true);
+    }
+}
@@ -0,0 +1,45 @@
+//! Start-at-login registration: a Run key entry on Windows, a LaunchAgent on macOS and an
+//! XDG autostart entry on Linux.
+use auto_launch::{AutoLaunch, AutoLaunchBuilder};
+
+const APP_NAME: &str = "ltk-hash-service";
+
+fn auto_launch() -> Result<AutoLaunch, String> {
+    let exe =
+        std::env::current_exe().map_err(|e| format!("Failed to get executable path: {}", e))?;
+    let exe = exe
+        .to_str()
+        .ok_or_else(|| "Invalid executable path".to_string())?;
+
+    AutoLaunchBuilder::new()
+        .set_app_name(APP_NAME)
+        .set_app_path(exe)
+        .set_use_launch_agent(true)
+        .build()
+        .map_err(|e| format!("Failed to configure autostart: {}", e))
+}
+
+pub fn is_enabled() -> Result<bool, String> {
+    auto_launch()?
+        .is_enabled()
+        .map_err(|e| format!("Failed to read autostart state: {}", e))
+}
+
+pub fn set_enabled(enabled: bool) -> Result<(), String> {
+    let auto_launch = auto_launch()?;
+    #[cfg(target_os = "linux")]
+    if enabled {
+        // The XDG autostart dir does not exist until something registers there
+        let dir = directories_next::BaseDirs::new()
+            .ok_or_else(|| "Failed to get config directory".to_string())?
+            .config_dir()
+            .join("autostart");
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    }
+    let result = if enabled {
+        auto_launch.enable()
+    } else {
+        auto_launch.disable()
+    };
+    result.map_err(|e| format!("Failed to update autostart: {}", e))
+}
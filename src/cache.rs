@@ -0,0 +1,90 @@
+//! The caches of every configured product and branch: garbage collection behind the
+//! CleanCache RPC and the tray's Clean Cache action, and moving them all to a new root behind
+//! the SetCacheDir admin RPC.
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use hash_service_core::HashEngine;
+use hash_service_core::clean::{CleanReport, clear_cache_dir, unconfigured_caches};
+use hash_service_core::sync::{HashSource, default_cache_dir};
+use tracing::{error, info};
+
+#[derive(Debug)]
+pub struct CacheManager {
+    /// Cache root the product and branch dirs live under; `None` when it cannot be resolved
+    base: RwLock<Option<PathBuf>>,
+    sources: Vec<HashSource>,
+    engines: Vec<HashEngine>,
+}
+
+impl CacheManager {
+    pub fn new(base: Option<PathBuf>, sources: Vec<HashSource>, engines: Vec<HashEngine>) -> Self {
+        CacheManager {
+            base: RwLock::new(base),
+            sources,
+            engines,
+        }
+    }
+
+    fn base(&self) -> Result<Option<PathBuf>, String> {
+        self.base
+            .read()
+            .map(|base| base.clone())
+            .map_err(|_| "Failed to lock cache root".to_string())
+    }
+
+    /// Cleans the cache of every engine, then removes the caches of products and branches
+    /// no longer configured
+    pub async fn clean(&self, dry_run: bool) -> Result<CleanReport, String> {
+        let mut report = CleanReport::default();
+        for engine in &self.engines {
+            report.merge(engine.clean_cache(dry_run).await?);
+        }
+        if let Some(base) = self.base()? {
+            for dir in unconfigured_caches(&base, &self.sources) {
+                info!(?dir, dry_run, "Removing cache of unconfigured product");
+                report.merge(clear_cache_dir(&dir, dry_run).await?);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Moves every engine to its dir under `root`, or under the default root for `None`, and
+    /// returns the new root. Without `migrate` the files stay behind and engines that had a
+    /// cache sync the new one in the background.
+    pub async fn relocate(&self, root: Option<PathBuf>, migrate: bool) -> Result<PathBuf, String> {
+        let to = match root {
+            Some(root) => root,
+            None => default_cache_dir()?,
+        };
+        if let Some(from) = self.base()?
+            && from != to
+            && (to.starts_with(&from) || from.starts_with(&to))
+        {
+            return Err(format!(
+                "Cache dir {:?} must not be inside {:?} or contain it",
+                to, from
+            ));
+        }
+
+        for (source, engine) in self.sources.iter().zip(&self.engines) {
+            let had_cache = engine.cache_dir().is_ok_and(|dir| dir.exists());
+            engine
+                .set_cache_dir(Some(source.cache_dir(&to)), migrate)
+                .await?;
+            if !migrate && had_cache {
+                let engine = engine.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = engine.sync().await {
+                        error!("Failed to sync relocated cache: {}", e);
+                    }
+                });
+            }
+        }
+        *self
+            .base
+            .write()
+            .map_err(|_| "Failed to lock cache root".to_string())? = Some(to.clone());
+        Ok(to)
+    }
+}
@@ -0,0 +1,80 @@
+use std::io;
+
+use hyper_util::rt::TokioIo;
+use tokio::io::DuplexStream;
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint, Server, Uri};
+use tower::service_fn;
+use tracing::error;
+
+use crate::config::ConnectionConfig;
+use crate::hash_service::hash_loader_client::HashLoaderClient;
+use crate::hash_service::hash_loader_server::HashLoaderServer;
+use crate::state::ServiceHashLoader;
+
+/// Buffer size of each in-process connection
+const IN_PROCESS_BUFFER_SIZE: usize = 64 * 1024;
+
+/// How in-process clients (the tray) reach the hash loader
+#[derive(Debug, Clone)]
+pub enum ClientSettings {
+    /// Connect to the gRPC server over the network
+    Network {
+        endpoint: String,
+        tls: Option<ClientTlsConfig>,
+        connection: Box<ConnectionConfig>,
+    },
+    /// Talk to a [`ServiceHashLoader`] in this process over in-memory pipes
+    InProcess(mpsc::UnboundedSender<DuplexStream>),
+}
+
+impl ClientSettings {
+    /// Serves `loader` in this process; clients get the same API without a network hop
+    pub fn in_process(loader: ServiceHashLoader) -> ClientSettings {
+        let (connections_tx, connections_rx) = mpsc::unbounded_channel();
+        let incoming = UnboundedReceiverStream::new(connections_rx).map(Ok::<_, io::Error>);
+        tokio::spawn(async move {
+            if let Err(e) = Server::builder()
+                .add_service(HashLoaderServer::new(loader))
+                .serve_with_incoming(incoming)
+                .await
+            {
+                error!("In-process gRPC server error: {:?}", e);
+            }
+        });
+        ClientSettings::InProcess(connections_tx)
+    }
+
+    pub async fn connect(&self) -> Result<HashLoaderClient<Channel>, tonic::transport::Error> {
+        match self {
+            ClientSettings::Network {
+                endpoint,
+                tls,
+                connection,
+            } => {
+                let mut endpoint = connection.endpoint(Endpoint::from_shared(endpoint.clone())?);
+                if let Some(tls) = tls {
+                    endpoint = endpoint.tls_config(tls.clone())?;
+                }
+                Ok(HashLoaderClient::new(endpoint.connect().await?))
+            }
+            ClientSettings::InProcess(connections) => {
+                let connections = connections.clone();
+                // The URI is never resolved, every connection is a fresh duplex pipe
+                let channel = Endpoint::from_static("http://in-process")
+                    .connect_with_connector(service_fn(move |_: Uri| {
+                        let (client_io, server_io) = tokio::io::duplex(IN_PROCESS_BUFFER_SIZE);
+                        let sent = connections.send(server_io);
+                        async move {
+                            sent.map_err(|_| io::Error::other("In-process server stopped"))?;
+                            Ok::<_, io::Error>(TokioIo::new(client_io))
+                        }
+                    }))
+                    .await?;
+                Ok(HashLoaderClient::new(channel))
+            }
+        }
+    }
+}
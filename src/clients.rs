@@ -0,0 +1,216 @@
+//! Tracks who is using the service: one entry per connection with its user agent, open calls
+//! and call counts per RPC, listed by the Admin `ListClients` RPC.
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+use http_body_util::BodyExt;
+use tonic::body::Body;
+use tonic::codegen::{Bytes, StdError};
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
+use tower::{Layer, Service};
+
+/// Clients without open calls are forgotten after this long
+const CLIENT_IDLE_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug)]
+struct ClientEntry {
+    user_agent: String,
+    authenticated: bool,
+    connected_at: SystemTime,
+    last_call_at: SystemTime,
+    last_call: Instant,
+    active_calls: u32,
+    calls: HashMap<String, u64>,
+}
+
+/// A client as last seen by the tracker
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub peer: SocketAddr,
+    pub user_agent: String,
+    /// Presented a trusted certificate under mutual TLS
+    pub authenticated: bool,
+    pub connected_at: SystemTime,
+    pub last_call_at: SystemTime,
+    /// Calls in progress, including open streams
+    pub active_calls: u32,
+    /// Calls made per RPC method path
+    pub calls: HashMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ClientTracker {
+    clients: Arc<Mutex<HashMap<SocketAddr, ClientEntry>>>,
+}
+
+impl ClientTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn start_call(
+        &self,
+        peer: SocketAddr,
+        user_agent: String,
+        authenticated: bool,
+        method: String,
+    ) -> CallGuard {
+        let now = SystemTime::now();
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|_, client| {
+            client.active_calls > 0 || client.last_call.elapsed() < CLIENT_IDLE_EXPIRY
+        });
+        let client = clients.entry(peer).or_insert_with(|| ClientEntry {
+            user_agent: String::new(),
+            authenticated,
+            connected_at: now,
+            last_call_at: now,
+            last_call: Instant::now(),
+            active_calls: 0,
+            calls: HashMap::new(),
+        });
+        if !user_agent.is_empty() {
+            client.user_agent = user_agent;
+        }
+        client.last_call_at = now;
+        client.last_call = Instant::now();
+        client.active_calls += 1;
+        *client.calls.entry(method).or_default() += 1;
+        CallGuard {
+            tracker: self.clone(),
+            peer,
+        }
+    }
+
+    fn finish_call(&self, peer: SocketAddr) {
+        if let Some(client) = self.clients.lock().unwrap().get_mut(&peer) {
+            client.active_calls = client.active_calls.saturating_sub(1);
+            client.last_call = Instant::now();
+        }
+    }
+
+    /// Clients with open calls or a call in the last few minutes, longest connected first
+    pub fn clients(&self) -> Vec<ClientInfo> {
+        let clients = self.clients.lock().unwrap();
+        let mut infos: Vec<ClientInfo> = clients
+            .iter()
+            .filter(|(_, client)| {
+                client.active_calls > 0 || client.last_call.elapsed() < CLIENT_IDLE_EXPIRY
+            })
+            .map(|(peer, client)| ClientInfo {
+                peer: *peer,
+                user_agent: client.user_agent.clone(),
+                authenticated: client.authenticated,
+                connected_at: client.connected_at,
+                last_call_at: client.last_call_at,
+                active_calls: client.active_calls,
+                calls: client.calls.clone(),
+            })
+            .collect();
+        infos.sort_by_key(|info| (info.connected_at, info.peer));
+        infos
+    }
+}
+
+/// Marks a call finished when dropped
+struct CallGuard {
+    tracker: ClientTracker,
+    peer: SocketAddr,
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        self.tracker.finish_call(self.peer);
+    }
+}
+
+/// Peer address and whether the peer presented a client certificate
+fn peer_info<B>(request: &http::Request<B>) -> Option<(SocketAddr, bool)> {
+    let extensions = request.extensions();
+    if let Some(info) = extensions.get::<TlsConnectInfo<TcpConnectInfo>>() {
+        let authenticated = info.peer_certs().is_some_and(|certs| !certs.is_empty());
+        return info
+            .get_ref()
+            .remote_addr()
+            .map(|addr| (addr, authenticated));
+    }
+    extensions
+        .get::<TcpConnectInfo>()
+        .and_then(|info| info.remote_addr())
+        .map(|addr| (addr, false))
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientTrackerLayer {
+    tracker: ClientTracker,
+}
+
+impl ClientTrackerLayer {
+    pub fn new(tracker: ClientTracker) -> Self {
+        ClientTrackerLayer { tracker }
+    }
+}
+
+impl<S> Layer<S> for ClientTrackerLayer {
+    type Service = TrackClients<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TrackClients {
+            inner,
+            tracker: self.tracker.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackClients<S> {
+    inner: S,
+    tracker: ClientTracker,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for TrackClients<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: tonic::codegen::Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<StdError>,
+{
+    type Response = http::Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let guard = peer_info(&request).map(|(peer, authenticated)| {
+            let user_agent = request
+                .headers()
+                .get(http::header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let method = request.uri().path().to_string();
+            self.tracker
+                .start_call(peer, user_agent, authenticated, method)
+        });
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = future.await?;
+            // The guard lives as long as the body, so streams count as open until they end
+            Ok(response.map(|body| {
+                Body::new(body.map_frame(move |frame| {
+                    let _guard = &guard;
+                    frame
+                }))
+            }))
+        })
+    }
+}
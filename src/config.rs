@@ -0,0 +1,928 @@
+//! Service configuration, resolved as defaults < config file < environment < CLI flags.
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use hash_service_core::local::LocalSource;
+use hash_service_core::misses::{DEFAULT_MISS_CACHE_SIZE, DEFAULT_MISS_CACHE_TTL};
+use hash_service_core::sync::{
+    DEFAULT_BRANCH, DEFAULT_PRODUCT, HashSource, MirrorHealth, UrlSource, default_cache_dir,
+    validate_mirror,
+};
+use hash_service_core::{ConflictPolicy, HashEngine, HashtableType, TableLayout};
+use serde::{Deserialize, Serialize};
+use tonic::codec::CompressionEncoding;
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity, Server, ServerTlsConfig};
+
+use crate::access_log::AccessLogConfig;
+use crate::client::ClientSettings;
+use crate::paths::project_dirs;
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+const WORDLIST_DIR_NAME: &str = "wordlists";
+/// Cache location in container mode, meant to be mounted as a volume
+const CONTAINER_CACHE_DIR: &str = "/data";
+/// Fits a full `GetStrings` batch or `ResolveDocument` of a large bin dump with room to spare
+const DEFAULT_MAX_DECODING_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+/// Fits a full `GetStrings` batch of long paths and `ExportUnknown` of every table
+const DEFAULT_MAX_ENCODING_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub host: IpAddr,
+    pub port: u16,
+    /// Addresses also bound on `port`, e.g. IPv4 loopback next to `[::1]` for clients that
+    /// resolve `localhost` to `127.0.0.1`; ones `host` already covers are skipped
+    pub extra_hosts: Vec<IpAddr>,
+    /// Port for the JSON HTTP gateway on `host`; disabled when unset. It is plain HTTP without
+    /// authentication even when `tls` is set, so lookups are open to anyone who can reach
+    /// `host`; only `POST /load` is limited to loopback callers.
+    pub http_port: Option<u16>,
+    /// Advertise the gRPC endpoint on the LAN over mDNS as `_hashservice._tcp`; needs the
+    /// `mdns` feature and a `host` other machines can reach
+    pub mdns: bool,
+    /// mDNS instance name, defaults to the machine's host name
+    pub mdns_name: Option<String>,
+    /// Hashtable cache directory, defaults to Documents/LeagueToolkit/ltk-hash-cache
+    pub cache_dir: Option<PathBuf>,
+    /// Read-only dirs of hashtable files, e.g. curated ones on a network share, loaded on top
+    /// of the synced tables, highest priority first. Laid out like the cache dir, so other
+    /// products and branches are read from the same subdirectories.
+    pub extra_dirs: Vec<PathBuf>,
+    /// Hashtable files and directories of the user's own, read on every load after the extra
+    /// dirs. More can be registered at runtime with the AddSourceDirectory and AddSourceFile
+    /// RPCs.
+    pub local_sources: Vec<LocalSourceConfig>,
+    /// Hashtable files at plain HTTPS URLs, synced into the cache along with the
+    /// CommunityDragon ones
+    pub url_sources: Vec<UrlSourceConfig>,
+    /// Mirrors of the CommunityDragon files, tried in order when GitHub's download fails.
+    /// `{product}`, `{branch}` and `{file}` in them are replaced with the file's. While
+    /// api.github.com is unreachable, they only restore files at the SHA last synced.
+    pub mirrors: Vec<String>,
+    /// CommunityDragon products (`hashes/<product>`) to sync and serve side by side
+    pub products: Vec<String>,
+    /// Product served to requests without an `x-hash-product` header; must be in `products`
+    pub product: String,
+    /// Data repository branches (`live`, `pbe`, ...) each product is synced from, side by side
+    pub branches: Vec<String>,
+    /// Branch served to requests without an `x-hash-branch` header; must be in `branches`
+    pub branch: String,
+    /// Show the tray icon; disable to run only the gRPC server
+    pub tray: bool,
+    /// Let the tray call the hash loader in process instead of over the network,
+    /// so it works without client certificates
+    pub tray_in_process: bool,
+    /// Sync and load hashtables on startup instead of on the first lookup
+    pub preload: bool,
+    /// Unload the hashtables after this many minutes without lookups; never when unset or 0
+    pub idle_unload_minutes: Option<u64>,
+    /// Download rate of hashtable syncs in KiB/s, so a background refresh leaves room for a
+    /// game; unlimited when unset or 0
+    pub download_limit_kbps: Option<u64>,
+    /// Recently missed hashes remembered per table, answered without the table lock; 0 disables
+    pub miss_cache_size: usize,
+    /// Seconds a missed hash is remembered, unless the table changes first
+    pub miss_cache_ttl_secs: u64,
+    /// Where lookups read the synced tables from
+    pub storage: Storage,
+    /// Page cache of the `database` storage, in MiB
+    pub db_cache_mb: usize,
+    /// How the game table is held in memory once loaded
+    pub game_layout: TableLayout,
+    /// How the bin table is held in memory once loaded
+    pub bin_layout: TableLayout,
+    /// Record which file each synced entry was read from, for lookups and exports to report
+    pub track_sources: bool,
+    /// Which value wins when a hash is loaded or added with a different one; the
+    /// `SetConflictPolicy` RPC overrides it until the next restart
+    pub conflict_policy: ConflictPolicy,
+    /// Directory of `<name>.txt` wordlists for hash guessing, defaults to `wordlists` in
+    /// the project data dir
+    pub wordlist_dir: Option<PathBuf>,
+    /// Register (`true`) or unregister (`false`) start at login on every launch;
+    /// left as is when unset so the tray toggle sticks
+    pub autostart: Option<bool>,
+    /// Look for a newer release when the tray starts
+    pub update_check: bool,
+    /// Global hotkey that opens the tray's quick search, e.g. "ctrl+shift+h"; blank disables it
+    pub search_hotkey: Option<String>,
+    /// Global hotkey that looks up the hash on the clipboard; blank disables it
+    pub lookup_hotkey: Option<String>,
+    /// `tracing` filter directive, e.g. "info" or "hash_service=debug"
+    pub log_level: String,
+    pub log_format: LogFormat,
+    /// Also write logs to a rotating file under the project data dir
+    pub log_file: bool,
+    pub access_log: AccessLogConfig,
+    pub compression: CompressionConfig,
+    pub connection: ConnectionConfig,
+    /// Largest request message the server accepts, in bytes
+    pub max_decoding_message_size: usize,
+    /// Largest response message the server sends, in bytes
+    pub max_encoding_message_size: usize,
+    /// Serve over TLS instead of plaintext when set
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Invalid log format '{}', expected text or json", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    /// Load the tables into memory on first use
+    #[default]
+    Memory,
+    /// Keep the tables in a database in the cache dir and look hashes up there; searches
+    /// still load the tables into memory. Needs the `db-backend` feature.
+    Database,
+    /// Look hashes up in sorted files in the cache dir with a seek and a read each, for
+    /// machines that cannot spare the memory; searches still load the tables
+    Disk,
+}
+
+impl std::str::FromStr for Storage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "memory" => Ok(Storage::Memory),
+            "database" => Ok(Storage::Database),
+            "disk" => Ok(Storage::Disk),
+            _ => Err(format!(
+                "Invalid storage '{}', expected memory, database or disk",
+                s
+            )),
+        }
+    }
+}
+
+/// gRPC message compression, per direction. Responses are only compressed for clients that
+/// say they accept the encoding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Encodings the server decompresses requests in
+    pub accept: Vec<Compression>,
+    /// Encodings the server compresses responses in
+    pub send: Vec<Compression>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            accept: vec![Compression::Gzip, Compression::Zstd],
+            send: vec![Compression::Gzip, Compression::Zstd],
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Applies `HASH_SERVICE_COMPRESSION_ACCEPT` and `HASH_SERVICE_COMPRESSION_SEND`, comma
+    /// separated; blank turns the direction off
+    fn apply_env(&mut self) -> Result<(), String> {
+        if let Ok(accept) = std::env::var("HASH_SERVICE_COMPRESSION_ACCEPT") {
+            self.accept = parse_compressions(&accept)?;
+        }
+        if let Ok(send) = std::env::var("HASH_SERVICE_COMPRESSION_SEND") {
+            self.send = parse_compressions(&send)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_compressions(list: &str) -> Result<Vec<Compression>, String> {
+    split_list(list).iter().map(|name| name.parse()).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl std::str::FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!(
+                "Invalid compression '{}', expected gzip or zstd",
+                s
+            )),
+        }
+    }
+}
+
+impl From<Compression> for CompressionEncoding {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::Gzip => CompressionEncoding::Gzip,
+            Compression::Zstd => CompressionEncoding::Zstd,
+        }
+    }
+}
+
+/// HTTP/2 keepalive and per-connection limits. Keepalive pings notice connections that died
+/// while idle, e.g. across sleep and resume, instead of failing the next call on a broken pipe.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionConfig {
+    /// Seconds between HTTP/2 pings on each connection; 0 disables them
+    pub keepalive_interval_secs: u64,
+    /// Seconds to wait for a ping to be acknowledged before closing the connection
+    pub keepalive_timeout_secs: u64,
+    /// Seconds between TCP keepalive probes; 0 disables them
+    pub tcp_keepalive_secs: u64,
+    /// Most concurrent streams a client may open per connection; unlimited when unset
+    pub max_concurrent_streams: Option<u32>,
+    /// Most calls handled at once per connection, the rest wait; unlimited when unset
+    pub concurrency_limit_per_connection: Option<usize>,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        ConnectionConfig {
+            keepalive_interval_secs: 30,
+            keepalive_timeout_secs: 20,
+            tcp_keepalive_secs: 60,
+            max_concurrent_streams: None,
+            concurrency_limit_per_connection: None,
+        }
+    }
+}
+
+impl ConnectionConfig {
+    /// Applies `HASH_SERVICE_KEEPALIVE_INTERVAL_SECS`, `HASH_SERVICE_KEEPALIVE_TIMEOUT_SECS`,
+    /// `HASH_SERVICE_TCP_KEEPALIVE_SECS`, `HASH_SERVICE_MAX_CONCURRENT_STREAMS` and
+    /// `HASH_SERVICE_CONCURRENCY_LIMIT_PER_CONNECTION`
+    fn apply_env(&mut self) {
+        let parsed = |name| env_var(name).and_then(|v| v.parse().ok());
+        if let Some(secs) = parsed("HASH_SERVICE_KEEPALIVE_INTERVAL_SECS") {
+            self.keepalive_interval_secs = secs;
+        }
+        if let Some(secs) = parsed("HASH_SERVICE_KEEPALIVE_TIMEOUT_SECS") {
+            self.keepalive_timeout_secs = secs;
+        }
+        if let Some(secs) = parsed("HASH_SERVICE_TCP_KEEPALIVE_SECS") {
+            self.tcp_keepalive_secs = secs;
+        }
+        if let Some(max) =
+            env_var("HASH_SERVICE_MAX_CONCURRENT_STREAMS").and_then(|v| v.parse().ok())
+        {
+            self.max_concurrent_streams = Some(max);
+        }
+        if let Some(limit) =
+            env_var("HASH_SERVICE_CONCURRENCY_LIMIT_PER_CONNECTION").and_then(|v| v.parse().ok())
+        {
+            self.concurrency_limit_per_connection = Some(limit);
+        }
+    }
+
+    fn keepalive_interval(&self) -> Option<Duration> {
+        non_zero_secs(self.keepalive_interval_secs)
+    }
+
+    fn tcp_keepalive(&self) -> Option<Duration> {
+        non_zero_secs(self.tcp_keepalive_secs)
+    }
+
+    /// Server builder with these settings applied
+    pub fn server(&self) -> Server {
+        let mut server = Server::builder()
+            .http2_keepalive_interval(self.keepalive_interval())
+            .http2_keepalive_timeout(Some(Duration::from_secs(self.keepalive_timeout_secs)))
+            .tcp_keepalive(self.tcp_keepalive())
+            .max_concurrent_streams(self.max_concurrent_streams);
+        if let Some(limit) = self.concurrency_limit_per_connection {
+            server = server.concurrency_limit_per_connection(limit);
+        }
+        server
+    }
+
+    /// Applies the matching client side settings; idle connections are pinged too, so a
+    /// client learns about a dead connection before its next call
+    pub fn endpoint(&self, endpoint: Endpoint) -> Endpoint {
+        let endpoint = endpoint.tcp_keepalive(self.tcp_keepalive());
+        match self.keepalive_interval() {
+            Some(interval) => endpoint
+                .http2_keep_alive_interval(interval)
+                .keep_alive_timeout(Duration::from_secs(self.keepalive_timeout_secs))
+                .keep_alive_while_idle(true),
+            None => endpoint,
+        }
+    }
+}
+
+fn non_zero_secs(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain presented by the server
+    pub cert_path: PathBuf,
+    /// PEM private key for `cert_path`
+    pub key_path: PathBuf,
+    /// PEM CA local clients use to verify the server, defaults to `cert_path`
+    #[serde(default)]
+    pub ca_path: Option<PathBuf>,
+    /// Name the server certificate was issued for
+    #[serde(default = "default_tls_domain_name")]
+    pub domain_name: String,
+    /// PEM CA that client certificates must be signed by; enables mutual TLS when set
+    #[serde(default)]
+    pub client_ca_path: Option<PathBuf>,
+    /// PEM certificate local clients present when mutual TLS is enabled
+    #[serde(default)]
+    pub client_cert_path: Option<PathBuf>,
+    /// PEM private key for `client_cert_path`
+    #[serde(default)]
+    pub client_key_path: Option<PathBuf>,
+}
+
+fn default_tls_domain_name() -> String {
+    "localhost".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalSourceConfig {
+    /// Absolute path of a directory of table files named like the synced ones, or of a file
+    pub path: PathBuf,
+    /// Table the file at `path` is read into; `path` is a directory when unset
+    #[serde(default)]
+    pub table: Option<HashtableType>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Product whose tables the source is added to, defaults to `product`
+    #[serde(default)]
+    pub product: Option<String>,
+    /// Branch whose tables the source is added to, defaults to `branch`
+    #[serde(default)]
+    pub branch: Option<String>,
+}
+
+impl LocalSourceConfig {
+    fn local_source(&self) -> LocalSource {
+        let source = match self.table {
+            Some(table) => LocalSource::file(self.path.clone(), table),
+            None => LocalSource::directory(self.path.clone()),
+        };
+        LocalSource {
+            enabled: self.enabled,
+            ..source
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UrlSourceConfig {
+    /// HTTPS URL of a raw hashtable file, or of a `.zip`, `.tar.gz` or `.zst` of one
+    pub url: String,
+    /// Table the file is read into
+    pub table: HashtableType,
+    /// Product whose tables the file is added to, defaults to `product`
+    #[serde(default)]
+    pub product: Option<String>,
+    /// Branch whose tables the file is added to, defaults to `branch`
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Other URLs serving the same file, tried in order when `url` fails
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            host: IpAddr::V6(Ipv6Addr::LOCALHOST),
+            port: 50051,
+            extra_hosts: vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            http_port: None,
+            mdns: false,
+            mdns_name: None,
+            cache_dir: None,
+            extra_dirs: Vec::new(),
+            local_sources: Vec::new(),
+            url_sources: Vec::new(),
+            mirrors: Vec::new(),
+            products: vec![DEFAULT_PRODUCT.to_string()],
+            product: DEFAULT_PRODUCT.to_string(),
+            branches: vec![DEFAULT_BRANCH.to_string()],
+            branch: DEFAULT_BRANCH.to_string(),
+            tray: true,
+            tray_in_process: false,
+            preload: false,
+            idle_unload_minutes: None,
+            download_limit_kbps: None,
+            miss_cache_size: DEFAULT_MISS_CACHE_SIZE,
+            miss_cache_ttl_secs: DEFAULT_MISS_CACHE_TTL.as_secs(),
+            storage: Storage::Memory,
+            db_cache_mb: 32,
+            game_layout: TableLayout::default(),
+            bin_layout: TableLayout::default(),
+            track_sources: false,
+            conflict_policy: ConflictPolicy::default(),
+            wordlist_dir: None,
+            autostart: None,
+            update_check: true,
+            search_hotkey: Some("ctrl+shift+h".to_string()),
+            lookup_hotkey: None,
+            log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            log_file: true,
+            access_log: AccessLogConfig::default(),
+            compression: CompressionConfig::default(),
+            connection: ConnectionConfig::default(),
+            max_decoding_message_size: DEFAULT_MAX_DECODING_MESSAGE_SIZE,
+            max_encoding_message_size: DEFAULT_MAX_ENCODING_MESSAGE_SIZE,
+            tls: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        project_dirs().map(|dirs| dirs.config_dir().join(CONFIG_FILE_NAME))
+    }
+
+    /// Defaults for running in a container: listen on all interfaces, no GUI,
+    /// JSON logs on stdout only and the cache under a mountable `/data`
+    pub fn container() -> Config {
+        Config {
+            host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            cache_dir: Some(PathBuf::from(CONTAINER_CACHE_DIR)),
+            tray: false,
+            log_format: LogFormat::Json,
+            log_file: false,
+            ..Config::default()
+        }
+    }
+
+    /// Loads the config file at `path`, or the default location if `None`.
+    /// A missing file at the default location yields the defaults.
+    pub fn load(path: Option<&Path>) -> Result<Config, String> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default());
+            }
+            Err(e) => return Err(format!("Failed to read config file {:?}: {}", path, e)),
+        };
+
+        toml::from_str(&text).map_err(|e| format!("Failed to parse config file {:?}: {}", path, e))
+    }
+
+    /// Writes the config to `path`, creating its directory. Comments in an existing file
+    /// are not kept.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize config: {}", e))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create config dir {:?}: {}", dir, e))?;
+        }
+        std::fs::write(path, text)
+            .map_err(|e| format!("Failed to write config file {:?}: {}", path, e))
+    }
+
+    /// Applies `HASH_SERVICE_*` environment overrides
+    pub fn apply_env(&mut self) -> Result<(), String> {
+        if let Some(host) = env_var("HASH_SERVICE_HOST") {
+            self.host = host
+                .parse()
+                .map_err(|e| format!("Invalid HASH_SERVICE_HOST '{}': {}", host, e))?;
+        }
+        if let Some(port) = env_var("HASH_SERVICE_PORT") {
+            self.port = port
+                .parse()
+                .map_err(|e| format!("Invalid HASH_SERVICE_PORT '{}': {}", port, e))?;
+        }
+        if let Some(extra_hosts) = env_var("HASH_SERVICE_EXTRA_HOSTS") {
+            self.extra_hosts = split_list(&extra_hosts)
+                .iter()
+                .map(|host| {
+                    host.parse()
+                        .map_err(|e| format!("Invalid HASH_SERVICE_EXTRA_HOSTS '{}': {}", host, e))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(http_port) = env_var("HASH_SERVICE_HTTP_PORT") {
+            self.http_port =
+                Some(http_port.parse().map_err(|e| {
+                    format!("Invalid HASH_SERVICE_HTTP_PORT '{}': {}", http_port, e)
+                })?);
+        }
+        if let Some(mdns) = env_flag("HASH_SERVICE_MDNS") {
+            self.mdns = mdns;
+        }
+        if let Some(mdns_name) = env_var("HASH_SERVICE_MDNS_NAME") {
+            self.mdns_name = Some(mdns_name);
+        }
+        if let Some(cache_dir) = env_var("HASH_SERVICE_CACHE_DIR") {
+            self.cache_dir = Some(PathBuf::from(cache_dir));
+        }
+        if let Some(extra_dirs) = env_var("HASH_SERVICE_EXTRA_DIRS") {
+            self.extra_dirs = std::env::split_paths(&extra_dirs).collect();
+        }
+        if let Some(products) = env_var("HASH_SERVICE_PRODUCTS") {
+            self.products = split_list(&products);
+        }
+        if let Some(product) = env_var("HASH_SERVICE_PRODUCT") {
+            self.product = product;
+        }
+        if let Some(branches) = env_var("HASH_SERVICE_BRANCHES") {
+            self.branches = split_list(&branches);
+        }
+        if let Some(mirrors) = env_var("HASH_SERVICE_MIRRORS") {
+            self.mirrors = split_list(&mirrors);
+        }
+        if let Some(branch) = env_var("HASH_SERVICE_BRANCH") {
+            self.branch = branch;
+        }
+        if let Some(wordlist_dir) = env_var("HASH_SERVICE_WORDLIST_DIR") {
+            self.wordlist_dir = Some(PathBuf::from(wordlist_dir));
+        }
+        if let Some(tray) = env_flag("HASH_SERVICE_TRAY") {
+            self.tray = tray;
+        }
+        if let Some(tray_in_process) = env_flag("HASH_SERVICE_TRAY_IN_PROCESS") {
+            self.tray_in_process = tray_in_process;
+        }
+        if let Some(preload) = env_flag("HASH_SERVICE_PRELOAD") {
+            self.preload = preload;
+        }
+        if let Some(minutes) = env_var("HASH_SERVICE_IDLE_UNLOAD_MINUTES") {
+            self.idle_unload_minutes = Some(minutes.parse().map_err(|e| {
+                format!(
+                    "Invalid HASH_SERVICE_IDLE_UNLOAD_MINUTES '{}': {}",
+                    minutes, e
+                )
+            })?);
+        }
+        if let Some(kbps) = env_var("HASH_SERVICE_DOWNLOAD_LIMIT_KBPS") {
+            self.download_limit_kbps = Some(kbps.parse().map_err(|e| {
+                format!("Invalid HASH_SERVICE_DOWNLOAD_LIMIT_KBPS '{}': {}", kbps, e)
+            })?);
+        }
+        if let Some(size) = env_var("HASH_SERVICE_MISS_CACHE_SIZE") {
+            self.miss_cache_size = size
+                .parse()
+                .map_err(|e| format!("Invalid HASH_SERVICE_MISS_CACHE_SIZE '{}': {}", size, e))?;
+        }
+        if let Some(secs) = env_var("HASH_SERVICE_MISS_CACHE_TTL_SECS") {
+            self.miss_cache_ttl_secs = secs.parse().map_err(|e| {
+                format!("Invalid HASH_SERVICE_MISS_CACHE_TTL_SECS '{}': {}", secs, e)
+            })?;
+        }
+        if let Some(size) = env_var("HASH_SERVICE_MAX_DECODING_MESSAGE_SIZE") {
+            self.max_decoding_message_size = size.parse().map_err(|e| {
+                format!(
+                    "Invalid HASH_SERVICE_MAX_DECODING_MESSAGE_SIZE '{}': {}",
+                    size, e
+                )
+            })?;
+        }
+        if let Some(size) = env_var("HASH_SERVICE_MAX_ENCODING_MESSAGE_SIZE") {
+            self.max_encoding_message_size = size.parse().map_err(|e| {
+                format!(
+                    "Invalid HASH_SERVICE_MAX_ENCODING_MESSAGE_SIZE '{}': {}",
+                    size, e
+                )
+            })?;
+        }
+        if let Some(storage) = env_var("HASH_SERVICE_STORAGE") {
+            self.storage = storage.parse()?;
+        }
+        if let Some(mb) = env_var("HASH_SERVICE_DB_CACHE_MB") {
+            self.db_cache_mb = mb
+                .parse()
+                .map_err(|e| format!("Invalid HASH_SERVICE_DB_CACHE_MB '{}': {}", mb, e))?;
+        }
+        if let Some(layout) = env_var("HASH_SERVICE_GAME_LAYOUT") {
+            self.game_layout = TableLayout::parse(&layout)?;
+        }
+        if let Some(layout) = env_var("HASH_SERVICE_BIN_LAYOUT") {
+            self.bin_layout = TableLayout::parse(&layout)?;
+        }
+        if let Some(track_sources) = env_flag("HASH_SERVICE_TRACK_SOURCES") {
+            self.track_sources = track_sources;
+        }
+        if let Some(policy) = env_var("HASH_SERVICE_CONFLICT_POLICY") {
+            self.conflict_policy = ConflictPolicy::parse(&policy)?;
+        }
+        if let Some(autostart) = env_flag("HASH_SERVICE_AUTOSTART") {
+            self.autostart = Some(autostart);
+        }
+        if let Some(update_check) = env_flag("HASH_SERVICE_UPDATE_CHECK") {
+            self.update_check = update_check;
+        }
+        // Read unfiltered, a blank value disables the hotkey
+        if let Ok(search_hotkey) = std::env::var("HASH_SERVICE_SEARCH_HOTKEY") {
+            self.search_hotkey = Some(search_hotkey);
+        }
+        if let Ok(lookup_hotkey) = std::env::var("HASH_SERVICE_LOOKUP_HOTKEY") {
+            self.lookup_hotkey = Some(lookup_hotkey);
+        }
+        if let Some(log_level) = env_var("HASH_SERVICE_LOG_LEVEL") {
+            self.log_level = log_level;
+        }
+        if let Some(log_format) = env_var("HASH_SERVICE_LOG_FORMAT") {
+            self.log_format = log_format.parse()?;
+        }
+        if let Some(log_file) = env_flag("HASH_SERVICE_LOG_FILE") {
+            self.log_file = log_file;
+        }
+        if let (Some(cert_path), Some(key_path)) = (
+            env_var("HASH_SERVICE_TLS_CERT"),
+            env_var("HASH_SERVICE_TLS_KEY"),
+        ) {
+            self.tls = Some(TlsConfig {
+                cert_path: PathBuf::from(cert_path),
+                key_path: PathBuf::from(key_path),
+                ca_path: env_var("HASH_SERVICE_TLS_CA").map(PathBuf::from),
+                domain_name: default_tls_domain_name(),
+                client_ca_path: env_var("HASH_SERVICE_TLS_CLIENT_CA").map(PathBuf::from),
+                client_cert_path: env_var("HASH_SERVICE_TLS_CLIENT_CERT").map(PathBuf::from),
+                client_key_path: env_var("HASH_SERVICE_TLS_CLIENT_KEY").map(PathBuf::from),
+            });
+        }
+        self.access_log.apply_env();
+        self.compression.apply_env()?;
+        self.connection.apply_env();
+        Ok(())
+    }
+
+    /// One source per configured product and branch, the default product and branch first
+    pub fn hash_sources(&self) -> Result<Vec<HashSource>, String> {
+        if !self.products.contains(&self.product) {
+            return Err(format!(
+                "Default product '{}' is not in products {:?}",
+                self.product, self.products
+            ));
+        }
+        if !self.branches.contains(&self.branch) {
+            return Err(format!(
+                "Default branch '{}' is not in branches {:?}",
+                self.branch, self.branches
+            ));
+        }
+        let default_source = HashSource::new(&self.product, &self.branch)?;
+        let mut sources = vec![default_source];
+        for product in &self.products {
+            for branch in &self.branches {
+                let source = HashSource::new(product, branch)?;
+                if !sources.contains(&source) {
+                    sources.push(source);
+                }
+            }
+        }
+        Ok(sources)
+    }
+
+    /// Cache dir of the default product and branch, which the others live under
+    pub fn cache_root(&self) -> Result<PathBuf, String> {
+        match &self.cache_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => default_cache_dir(),
+        }
+    }
+
+    /// Cache dir for `source`'s engine; `None` leaves the engine on its default
+    pub fn source_cache_dir(&self, source: &HashSource) -> Result<Option<PathBuf>, String> {
+        match &self.cache_dir {
+            Some(dir) => Ok(Some(source.cache_dir(dir))),
+            None if *source == HashSource::default() => Ok(None),
+            None => Ok(Some(source.cache_dir(&default_cache_dir()?))),
+        }
+    }
+
+    pub fn idle_unload(&self) -> Option<Duration> {
+        self.idle_unload_minutes
+            .filter(|minutes| *minutes > 0)
+            .map(|minutes| Duration::from_secs(minutes * 60))
+    }
+
+    /// Download rate limit in bytes per second
+    pub fn download_limit(&self) -> Option<u64> {
+        self.download_limit_kbps
+            .filter(|kbps| *kbps > 0)
+            .map(|kbps| kbps * 1024)
+    }
+
+    pub fn miss_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.miss_cache_ttl_secs)
+    }
+
+    /// Whether a source set for `product` and `branch`, the defaults when unset, belongs to
+    /// `source`'s tables
+    fn is_for(
+        &self,
+        source: &HashSource,
+        product: &Option<String>,
+        branch: &Option<String>,
+    ) -> bool {
+        product.as_ref().unwrap_or(&self.product) == &source.product
+            && branch.as_ref().unwrap_or(&self.branch) == &source.branch
+    }
+
+    /// Engine for one product and branch's tables, with the configured storage. Engines given
+    /// the same `mirror_health` try the mirrors that failed any of them last.
+    pub fn engine(
+        &self,
+        source: &HashSource,
+        mirror_health: &MirrorHealth,
+    ) -> Result<HashEngine, String> {
+        for mirror in &self.mirrors {
+            validate_mirror(mirror)?;
+        }
+        let engine = HashEngine::new()
+            .with_cache_dir(self.source_cache_dir(source)?)
+            .with_extra_dirs(
+                self.extra_dirs
+                    .iter()
+                    .map(|dir| source.cache_dir(dir))
+                    .collect(),
+            )
+            .with_local_sources(
+                self.local_sources
+                    .iter()
+                    .filter(|local| self.is_for(source, &local.product, &local.branch))
+                    .map(LocalSourceConfig::local_source)
+                    .collect(),
+            )
+            .with_url_sources(
+                self.url_sources
+                    .iter()
+                    .filter(|url| self.is_for(source, &url.product, &url.branch))
+                    .map(|url| {
+                        UrlSource::new(&url.url, url.table)
+                            .and_then(|source| source.with_mirrors(url.mirrors.clone()))
+                    })
+                    .collect::<Result<_, _>>()?,
+            )
+            .with_mirrors(self.mirrors.clone())
+            .with_mirror_health(mirror_health.clone())
+            .with_source(source.clone())
+            .with_download_limit(self.download_limit())
+            .with_miss_cache(self.miss_cache_size, self.miss_cache_ttl())
+            .with_table_layout(HashtableType::Game, self.game_layout)
+            .with_table_layout(HashtableType::Bin, self.bin_layout)
+            .with_source_tracking(self.track_sources)
+            .with_conflict_policy(self.conflict_policy);
+        match self.storage {
+            Storage::Memory => Ok(engine),
+            Storage::Disk => Ok(engine.with_disk_lookups()),
+            #[cfg(feature = "db-backend")]
+            Storage::Database => Ok(engine.with_database(self.db_cache_mb * 1024 * 1024)),
+            #[cfg(not(feature = "db-backend"))]
+            Storage::Database => {
+                Err("storage = \"database\" needs the db-backend feature".to_string())
+            }
+        }
+    }
+
+    pub fn wordlist_dir(&self) -> Option<PathBuf> {
+        self.wordlist_dir
+            .clone()
+            .or_else(|| project_dirs().map(|dirs| dirs.data_dir().join(WORDLIST_DIR_NAME)))
+    }
+
+    pub fn bind_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+
+    /// Addresses bound besides [`Config::bind_addr`], leaving out the ones it already covers:
+    /// itself, and any of the same family when `host` is unspecified
+    pub fn extra_bind_addrs(&self) -> Vec<SocketAddr> {
+        let mut addrs: Vec<SocketAddr> = Vec::new();
+        for &host in &self.extra_hosts {
+            let covered = host == self.host
+                || (self.host.is_unspecified() && host.is_ipv4() == self.host.is_ipv4());
+            let addr = SocketAddr::new(host, self.port);
+            if !covered && !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+        addrs
+    }
+
+    /// Endpoint local clients should connect to; unspecified bind addresses map to loopback
+    pub fn client_endpoint(&self) -> String {
+        let host = match self.host {
+            IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ip => ip,
+        };
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        format!("{}://{}", scheme, SocketAddr::new(host, self.port))
+    }
+
+    pub fn server_tls(&self) -> Result<Option<ServerTlsConfig>, String> {
+        let Some(tls) = &self.tls else {
+            return Ok(None);
+        };
+        let identity = Identity::from_pem(read_pem(&tls.cert_path)?, read_pem(&tls.key_path)?);
+        let mut server_tls = ServerTlsConfig::new().identity(identity);
+        if let Some(client_ca_path) = &tls.client_ca_path {
+            server_tls = server_tls
+                .client_ca_root(Certificate::from_pem(read_pem(client_ca_path)?))
+                .client_auth_optional(false);
+        }
+        Ok(Some(server_tls))
+    }
+
+    #[cfg_attr(not(feature = "tray"), allow(dead_code))]
+    pub fn client_settings(&self) -> Result<ClientSettings, String> {
+        let tls = match &self.tls {
+            Some(tls) => {
+                let ca_path = tls.ca_path.as_ref().unwrap_or(&tls.cert_path);
+                let mut client_tls = ClientTlsConfig::new()
+                    .ca_certificate(Certificate::from_pem(read_pem(ca_path)?))
+                    .domain_name(tls.domain_name.clone());
+                match (&tls.client_cert_path, &tls.client_key_path) {
+                    (Some(cert_path), Some(key_path)) => {
+                        client_tls = client_tls.identity(Identity::from_pem(
+                            read_pem(cert_path)?,
+                            read_pem(key_path)?,
+                        ));
+                    }
+                    (None, None) => {}
+                    _ => {
+                        return Err(
+                            "client_cert_path and client_key_path must be set together".to_string()
+                        );
+                    }
+                }
+                Some(client_tls)
+            }
+            None => None,
+        };
+        Ok(ClientSettings::Network {
+            endpoint: self.client_endpoint(),
+            tls,
+            connection: Box::new(self.connection.clone()),
+        })
+    }
+}
+
+/// Comma separated list, ignoring blanks
+pub fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))
+}
+
+pub fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+/// Parses `1`/`true`/`yes` and `0`/`false`/`no`; anything else is ignored
+pub fn env_flag(name: &str) -> Option<bool> {
+    match env_var(name)?.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
@@ -0,0 +1,116 @@
+//! Crash reports. Without a console window a panic's message would go nowhere, so a panic
+//! hook writes it with a backtrace, the version and a summary of the config to a file under
+//! the project data dir, and the tray build shows a notification pointing at it.
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::error;
+
+use crate::config::Config;
+use crate::paths::project_dirs;
+
+const CRASH_DIR_NAME: &str = "crashes";
+/// Oldest reports beyond this many are removed when a new one is written
+const MAX_CRASH_REPORTS: usize = 10;
+
+/// Installs the panic hook; Rust's default one still runs afterwards
+pub fn install(config: &Config) {
+    let summary = config_summary(config);
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        match write_report(info, &summary) {
+            Ok(path) => {
+                error!(?path, "Panicked, crash report written: {}", info);
+                #[cfg(feature = "tray")]
+                crate::tray::notify(
+                    "Hash Service crashed",
+                    &format!("A crash report was saved to {}", path.display()),
+                );
+            }
+            Err(e) => error!("Panicked, failed to write crash report ({}): {}", e, info),
+        }
+        default_hook(info);
+    }));
+}
+
+fn crash_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_dir().join(CRASH_DIR_NAME))
+}
+
+fn config_summary(config: &Config) -> String {
+    let mut summary = String::new();
+    let _ = writeln!(summary, "bind address: {}", config.bind_addr());
+    let _ = writeln!(summary, "tls: {}", config.tls.is_some());
+    let _ = writeln!(summary, "cache dir: {:?}", config.cache_dir);
+    let _ = writeln!(summary, "products: {:?}", config.products);
+    let _ = writeln!(summary, "branches: {:?}", config.branches);
+    let _ = writeln!(summary, "storage: {:?}", config.storage);
+    let _ = writeln!(
+        summary,
+        "layouts: game {:?}, bin {:?}",
+        config.game_layout, config.bin_layout
+    );
+    let _ = writeln!(summary, "tray: {}", config.tray);
+    let _ = writeln!(summary, "preload: {}", config.preload);
+    summary
+}
+
+fn write_report(info: &PanicHookInfo, config_summary: &str) -> Result<PathBuf, String> {
+    let dir = crash_dir().ok_or_else(|| "Failed to get project directories".to_string())?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create crash directory {:?}: {}", dir, e))?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let thread = std::thread::current();
+
+    let mut report = String::new();
+    let _ = writeln!(report, "{}", info);
+    let _ = writeln!(report);
+    let _ = writeln!(
+        report,
+        "version: {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(
+        report,
+        "platform: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(report, "time: {} (unix)", time);
+    let _ = writeln!(report, "thread: {}", thread.name().unwrap_or("<unnamed>"));
+    let _ = writeln!(report);
+    let _ = writeln!(report, "{}", config_summary);
+    let _ = writeln!(report, "backtrace:\n{}", Backtrace::force_capture());
+
+    let path = dir.join(format!("crash-{}-{}.txt", time, std::process::id()));
+    std::fs::write(&path, report).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    prune(&dir);
+    Ok(path)
+}
+
+/// Keeps the newest [`MAX_CRASH_REPORTS`] reports
+fn prune(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut reports: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("crash-"))
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+    reports.sort();
+    let excess = reports.len().saturating_sub(MAX_CRASH_REPORTS);
+    for (_, path) in reports.into_iter().take(excess) {
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,78 @@
+//! Process lifecycle integration for running as a background service: termination
+//! signals, systemd readiness notification and a generated unit file.
+use std::path::Path;
+
+#[cfg(unix)]
+use tracing::error;
+
+/// Resolves on Ctrl+C, or SIGTERM on unix
+pub async fn termination_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Tells systemd the server is accepting connections; a no-op outside `Type=notify` units
+pub fn notify_ready() {
+    #[cfg(target_os = "linux")]
+    if let Err(e) = sd_notify::notify(false, &[sd_notify::NotifyState::Ready]) {
+        error!("Failed to notify systemd: {}", e);
+    }
+}
+
+pub fn notify_stopping() {
+    #[cfg(target_os = "linux")]
+    if let Err(e) = sd_notify::notify(false, &[sd_notify::NotifyState::Stopping]) {
+        error!("Failed to notify systemd: {}", e);
+    }
+}
+
+/// A systemd unit that runs this executable in daemon mode, for `systemctl --user`
+pub fn systemd_unit(config_path: Option<&Path>) -> Result<String, String> {
+    let exe =
+        std::env::current_exe().map_err(|e| format!("Failed to get executable path: {}", e))?;
+
+    let mut exec_start = format!("{} --daemon", quote(&exe));
+    if let Some(config_path) = config_path {
+        let config_path = std::path::absolute(config_path)
+            .map_err(|e| format!("Failed to resolve {:?}: {}", config_path, e))?;
+        exec_start.push_str(&format!(" --config {}", quote(&config_path)));
+    }
+
+    Ok(format!(
+        "[Unit]
+Description=League Toolkit hash lookup service
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=notify
+ExecStart={exec_start}
+Restart=on-failure
+
+[Install]
+WantedBy=default.target
+"
+    ))
+}
+
+fn quote(path: &Path) -> String {
+    format!("\"{}\"", path.display())
+}
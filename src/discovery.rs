@@ -0,0 +1,93 @@
+//! Single-instance lock and the discovery file advertising where the running server listens.
+//!
+//! Both live in the project data dir. The lock is held for the lifetime of the process,
+//! and the discovery file is rewritten once the listener is bound so clients can find the
+//! service even when the port was picked by the OS (`port = 0`).
+use std::fs::{File, TryLockError};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths::project_dirs;
+
+const LOCK_FILE_NAME: &str = "instance.lock";
+const DISCOVERY_FILE_NAME: &str = "endpoint.json";
+
+/// Contents of the discovery file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Discovery {
+    pub pid: u32,
+    /// URL clients should connect to, e.g. `http://[::1]:50051`
+    pub endpoint: String,
+    /// Address the server is actually bound to
+    pub addr: SocketAddr,
+    /// Port of `addr`, for clients that don't parse URLs
+    pub port: u16,
+    /// `grpc`, or `grpcs` when TLS is on
+    pub protocol: String,
+    pub tls: bool,
+    /// When the server started, in seconds since the Unix epoch
+    pub started_at: u64,
+}
+
+/// Held while this process is the running instance
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+fn data_dir() -> Result<PathBuf, String> {
+    let dir = project_dirs()
+        .ok_or_else(|| "Failed to get project directories".to_string())?
+        .data_dir()
+        .to_path_buf();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create data directory {:?}: {}", dir, e))?;
+    Ok(dir)
+}
+
+/// Takes the instance lock, or returns `None` if another instance holds it
+pub fn acquire_instance_lock() -> Result<Option<InstanceLock>, String> {
+    let path = data_dir()?.join(LOCK_FILE_NAME);
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open lock file {:?}: {}", path, e))?;
+
+    match file.try_lock() {
+        Ok(()) => Ok(Some(InstanceLock { _file: file })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(format!("Failed to lock {:?}: {}", path, e)),
+    }
+}
+
+pub fn discovery_path() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_dir().join(DISCOVERY_FILE_NAME))
+}
+
+/// Reads the discovery file left by the running instance, if any
+pub fn read() -> Option<Discovery> {
+    let text = std::fs::read_to_string(discovery_path()?).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+pub fn write(discovery: &Discovery) -> Result<(), String> {
+    let path = data_dir()?.join(DISCOVERY_FILE_NAME);
+    let tmp_path = path.with_extension("json.tmp");
+    let text = serde_json::to_string_pretty(discovery)
+        .map_err(|e| format!("Failed to serialize discovery file: {}", e))?;
+
+    // Write then rename so readers never see a partial file
+    std::fs::write(&tmp_path, text)
+        .map_err(|e| format!("Failed to write {:?}: {}", tmp_path, e))?;
+    std::fs::rename(&tmp_path, &path).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+pub fn remove() {
+    if let Some(path) = discovery_path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
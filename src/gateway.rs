@@ -0,0 +1,293 @@
+//! JSON over HTTP gateway for scripts and quick `curl` checks. Requests are served by the
+//! same `ServiceHashLoader` as gRPC, in process.
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tonic::{Code, Request, Status};
+
+use crate::hash_service::hash_loader_server::HashLoader;
+use crate::hash_service::{
+    EntryOrigin, ErrorCode, GetCountsRequest, GetStatusRequest, GetStringRequest,
+    LoadHashesRequest, SearchEntry, SearchPrefixRequest, SearchRegexRequest,
+};
+use crate::state::{ERROR_CODE_HEADER, ServiceHashLoader};
+
+pub fn router(loader: ServiceHashLoader) -> Router {
+    Router::new()
+        .route("/lookup/{hashtable_type}/{hash}", get(lookup))
+        .route("/search", get(search))
+        .route("/status", get(status))
+        .route("/load", post(load))
+        .with_state(loader)
+}
+
+struct ApiError(Status);
+
+impl From<Status> for ApiError {
+    fn from(status: Status) -> Self {
+        ApiError(status)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let code = match self.0.code() {
+            Code::InvalidArgument | Code::OutOfRange => StatusCode::BAD_REQUEST,
+            Code::NotFound => StatusCode::NOT_FOUND,
+            Code::AlreadyExists | Code::FailedPrecondition => StatusCode::CONFLICT,
+            Code::PermissionDenied => StatusCode::FORBIDDEN,
+            Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+            Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let body = ErrorBody {
+            error: self.0.message().to_string(),
+            code: self
+                .0
+                .metadata()
+                .get(ERROR_CODE_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        };
+        (code, Json(body)).into_response()
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+    /// Name of the `ErrorCode`, when the service sent one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+}
+
+/// Hashes are rendered as 16 hex digits, matching how tools print them
+#[derive(Serialize)]
+struct Entry {
+    hash: String,
+    value: String,
+}
+
+impl From<SearchEntry> for Entry {
+    fn from(entry: SearchEntry) -> Self {
+        Entry {
+            hash: format_hash(entry.hash),
+            value: entry.value,
+        }
+    }
+}
+
+fn format_hash(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+fn parse_hash(hash: &str) -> Result<u64, ApiError> {
+    let digits = hash.trim_start_matches("0x");
+    u64::from_str_radix(digits, 16).map_err(|_| {
+        ApiError(Status::invalid_argument(format!(
+            "Invalid hash '{}', expected hex",
+            hash
+        )))
+    })
+}
+
+#[derive(Serialize)]
+struct LookupResponse {
+    hash: String,
+    found: bool,
+    value: Option<String>,
+    /// "official" or "custom" when found
+    origin: Option<&'static str>,
+    /// File the synced value was read from, with `track_sources`
+    source_file: Option<String>,
+}
+
+async fn lookup(
+    State(loader): State<ServiceHashLoader>,
+    Path((hashtable_type, hash)): Path<(String, String)>,
+) -> Result<Json<LookupResponse>, ApiError> {
+    let hash = parse_hash(&hash)?;
+    if !matches!(hashtable_type.as_str(), "game" | "bin") {
+        return Err(ApiError(Status::invalid_argument("Invalid hashtable type")));
+    }
+
+    let response = loader
+        .get_string(Request::new(GetStringRequest {
+            hash,
+            hashtable_type,
+        }))
+        .await?
+        .into_inner();
+
+    let origin = match response.origin() {
+        EntryOrigin::Official => Some("official"),
+        EntryOrigin::Custom => Some("custom"),
+        EntryOrigin::None => None,
+    };
+    Ok(Json(LookupResponse {
+        hash: format_hash(hash),
+        found: response.found,
+        value: response.found.then_some(response.value),
+        origin,
+        source_file: (!response.source_file.is_empty()).then_some(response.source_file),
+    }))
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    #[serde(rename = "type")]
+    hashtable_type: String,
+    prefix: Option<String>,
+    regex: Option<String>,
+    #[serde(default)]
+    limit: u32,
+    #[serde(default)]
+    page_token: String,
+    #[serde(default)]
+    case_insensitive: bool,
+}
+
+#[derive(Serialize)]
+struct SearchResponse {
+    entries: Vec<Entry>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    next_page_token: String,
+    truncated: bool,
+    timed_out: bool,
+}
+
+/// `?type=game&prefix=...` pages through a prefix search, `?type=game&regex=...` runs a regex scan
+async fn search(
+    State(loader): State<ServiceHashLoader>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    match (query.prefix, query.regex) {
+        (Some(prefix), None) => {
+            let response = loader
+                .search_prefix(Request::new(SearchPrefixRequest {
+                    prefix,
+                    hashtable_type: query.hashtable_type,
+                    limit: query.limit,
+                    page_token: query.page_token,
+                }))
+                .await?
+                .into_inner();
+            Ok(Json(SearchResponse {
+                entries: response.entries.into_iter().map(Entry::from).collect(),
+                next_page_token: response.next_page_token,
+                truncated: false,
+                timed_out: false,
+            }))
+        }
+        (None, Some(pattern)) => {
+            let response = loader
+                .search_regex(Request::new(SearchRegexRequest {
+                    pattern,
+                    hashtable_type: query.hashtable_type,
+                    limit: query.limit,
+                    case_insensitive: query.case_insensitive,
+                    timeout_ms: 0,
+                }))
+                .await?
+                .into_inner();
+            Ok(Json(SearchResponse {
+                entries: response.entries.into_iter().map(Entry::from).collect(),
+                next_page_token: String::new(),
+                truncated: response.truncated,
+                timed_out: response.timed_out,
+            }))
+        }
+        _ => Err(ApiError(Status::invalid_argument(
+            "Exactly one of prefix or regex is required",
+        ))),
+    }
+}
+
+#[derive(Serialize)]
+struct TableStatus {
+    hashtable_type: String,
+    total: u64,
+    custom: u64,
+}
+
+#[derive(Serialize)]
+struct StatusResponse {
+    loaded: bool,
+    product: String,
+    branch: String,
+    /// Newest game patch the synced files were committed for
+    patch: Option<String>,
+    tables: Vec<TableStatus>,
+}
+
+async fn status(State(loader): State<ServiceHashLoader>) -> Result<Json<StatusResponse>, ApiError> {
+    let counts = loader
+        .get_counts(Request::new(GetCountsRequest::default()))
+        .await?
+        .into_inner();
+    let status = loader
+        .get_status(Request::new(GetStatusRequest {}))
+        .await?
+        .into_inner();
+
+    Ok(Json(StatusResponse {
+        loaded: loader.is_loaded(),
+        product: status.product,
+        branch: status.branch,
+        patch: Some(status.patch).filter(|patch| !patch.is_empty()),
+        tables: counts
+            .tables
+            .into_iter()
+            .map(|table| TableStatus {
+                hashtable_type: table.hashtable_type,
+                total: table.total,
+                custom: table.custom,
+            })
+            .collect(),
+    }))
+}
+
+#[derive(Serialize)]
+struct LoadResponse {
+    success: bool,
+    message: String,
+    count: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+}
+
+/// Plain HTTP carries no client certificate, so only local callers may load, as for the
+/// admin RPCs
+async fn load(
+    State(loader): State<ServiceHashLoader>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Result<Response, ApiError> {
+    if !addr.ip().to_canonical().is_loopback() {
+        return Err(Status::permission_denied("Only accepted from loopback").into());
+    }
+    let response = loader
+        .load_hashes(Request::new(LoadHashesRequest::default()))
+        .await?
+        .into_inner();
+
+    let error_code = response.error_code();
+    let code = match error_code {
+        _ if response.success => StatusCode::OK,
+        ErrorCode::SyncFailed => StatusCode::BAD_GATEWAY,
+        ErrorCode::LoadingInProgress => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let body = LoadResponse {
+        success: response.success,
+        message: response.message,
+        count: response.count,
+        code: (error_code != ErrorCode::None).then(|| error_code.as_str_name()),
+    };
+    Ok((code, Json(body)).into_response())
+}
@@ -0,0 +1,60 @@
+//! Unloads hashtables that no lookup has needed for a while, to give the memory back.
+use std::time::Duration;
+
+use hash_service_core::HashEngine;
+use tokio::sync::watch;
+use tracing::{error, info};
+
+use crate::alloc;
+
+/// Longest wait between idle checks, so a shorter timeout set later takes effect soon
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Checks `engines` in the background and unloads those idle for longer than `timeout`.
+/// `timeout` can change at runtime; `None` turns unloading off.
+pub fn spawn_idle_unload(engines: Vec<HashEngine>, mut timeout: watch::Receiver<Option<Duration>>) {
+    tokio::spawn(async move {
+        let mut sender_alive = true;
+        loop {
+            let current = *timeout.borrow_and_update();
+            let check = async {
+                match current {
+                    Some(timeout) => tokio::time::sleep(timeout.min(MAX_CHECK_INTERVAL)).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                () = check => {
+                    if let Some(current) = current {
+                        unload_idle(&engines, current);
+                    }
+                }
+                changed = timeout.changed(), if sender_alive => sender_alive = changed.is_ok(),
+            }
+        }
+    });
+}
+
+fn unload_idle(engines: &[HashEngine], timeout: Duration) {
+    let mut unloaded = false;
+    for engine in engines {
+        match engine.unload_if_idle(timeout) {
+            Ok(Some((game_count, bin_count))) => {
+                unloaded = true;
+                info!(
+                    product = %engine.source().product,
+                    branch = %engine.source().branch,
+                    game_count,
+                    bin_count,
+                    idle_secs = timeout.as_secs(),
+                    "Unloaded idle hashtables"
+                );
+            }
+            Ok(None) => {}
+            Err(e) => error!("Failed to unload idle hashtables: {}", e),
+        }
+    }
+    if unloaded {
+        alloc::release_memory();
+    }
+}
@@ -0,0 +1,85 @@
+//! mDNS/DNS-SD advertisement of the gRPC endpoint as `_hashservice._tcp`, so tools on the LAN
+//! find an instance shared by a team without being told its address.
+use std::net::SocketAddr;
+
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use tracing::{info, warn};
+
+use crate::config::Config;
+
+const SERVICE_TYPE: &str = "_hashservice._tcp.local.";
+const DEFAULT_INSTANCE_NAME: &str = "ltk-hash-service";
+
+/// Registered service, withdrawn from the network when dropped
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl Drop for Advertisement {
+    fn drop(&mut self) {
+        let _ = self.daemon.unregister(&self.fullname);
+        let _ = self.daemon.shutdown();
+    }
+}
+
+fn host_name() -> Option<String> {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Advertises the server bound to `addr`; `None` when it only listens on loopback, where no
+/// other machine could reach it
+pub fn advertise(config: &Config, addr: SocketAddr) -> Result<Option<Advertisement>, String> {
+    if addr.ip().is_loopback() {
+        warn!(%addr, "mdns is set but the server only listens on loopback, not advertising it");
+        return Ok(None);
+    }
+    let host = host_name().unwrap_or_else(|| DEFAULT_INSTANCE_NAME.to_string());
+    let instance_name = config.mdns_name.clone().unwrap_or_else(|| host.clone());
+    let host_name = format!(
+        "{}.local.",
+        host.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "-")
+    );
+    // Bound to every interface: advertise whichever addresses the machine has, as they change
+    let ip = if addr.ip().is_unspecified() {
+        String::new()
+    } else {
+        addr.ip().to_string()
+    };
+    let protocol = if config.tls.is_some() {
+        "grpcs"
+    } else {
+        "grpc"
+    };
+    let properties = [
+        ("version", env!("CARGO_PKG_VERSION")),
+        ("protocol", protocol),
+        ("product", &config.product),
+        ("branch", &config.branch),
+    ];
+    let mut service = ServiceInfo::new(
+        SERVICE_TYPE,
+        &instance_name,
+        &host_name,
+        ip.as_str(),
+        addr.port(),
+        &properties[..],
+    )
+    .map_err(|e| format!("Failed to describe mDNS service: {}", e))?;
+    if addr.ip().is_unspecified() {
+        service = service.enable_addr_auto();
+    }
+    let fullname = service.get_fullname().to_string();
+
+    let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS: {}", e))?;
+    daemon
+        .register(service)
+        .map_err(|e| format!("Failed to register mDNS service: {}", e))?;
+    info!(%fullname, port = addr.port(), "Advertising over mDNS");
+    Ok(Some(Advertisement { daemon, fullname }))
+}
@@ -0,0 +1,6 @@
+use directories_next::ProjectDirs;
+
+/// Project directories for files owned by the service itself (logs, configuration).
+pub fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("io", "LeagueToolkit", "ltk-hash-service")
+}
@@ -1,57 +1,446 @@
 #![windows_subsystem = "windows"]
-use tonic::transport::Server;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+use hash_service_core::ConflictPolicy;
+use hash_service_core::sync::MirrorHealth;
+use tokio::net::TcpStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::transport::server::TcpIncoming;
+use tracing::{error, info, warn};
+
+use access_log::AccessLogLayer;
+use admin::{AdminService, ShutdownKind};
+use cache::CacheManager;
+use clients::{ClientTracker, ClientTrackerLayer};
+use config::{Config, LogFormat, env_flag};
+use discovery::{Discovery, InstanceLock};
+use source::SourceRouter;
 
 mod state;
-use state::ServiceHashLoader;
 pub use state::hash_service;
+use state::hash_service::admin_server::AdminServer;
 use state::hash_service::hash_loader_server::HashLoaderServer;
+use state::hash_service::v2::hash_loader_server::HashLoaderServer as HashLoaderV2Server;
+use state::{HashLoaderV2, ServiceHashLoader, UnknownHashes};
 
+#[cfg(feature = "tray")]
 mod tray;
-pub use tray::{Application, UserEvent};
 
-use tray_icon::{TrayIconEvent, menu::MenuEvent};
+mod access_log;
+mod admin;
+mod alloc;
+mod autostart;
+mod cache;
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+mod client;
+mod clients;
+mod config;
+mod crash;
+mod daemon;
+mod discovery;
+#[cfg(feature = "http-gateway")]
+mod gateway;
+mod idle;
+#[cfg(feature = "mdns")]
+mod mdns;
+mod paths;
+mod source;
+mod telemetry;
 
-use winit::event_loop::EventLoop;
+use tokio::sync::watch;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "League Toolkit hash lookup service")]
+struct Cli {
+    /// Path to the config file (defaults to config.toml in the project config dir)
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Address to bind the gRPC server to
+    #[arg(long)]
+    host: Option<IpAddr>,
+    /// Port to bind the gRPC server to
+    #[arg(long)]
+    port: Option<u16>,
+    /// Address also bound on the same port; repeat for several. Replaces `extra_hosts`.
+    #[arg(long = "extra-host")]
+    extra_hosts: Vec<IpAddr>,
+    /// Port to serve the JSON HTTP gateway on (requires the http-gateway feature)
+    #[arg(long)]
+    http_port: Option<u16>,
+    /// Advertise the gRPC endpoint on the LAN over mDNS (requires the mdns feature)
+    #[arg(long)]
+    mdns: bool,
+    /// Directory hashtables are synced to and loaded from
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+    /// Read-only directory of hashtable files loaded on top of the synced ones; repeat in
+    /// priority order
+    #[arg(long = "extra-dir")]
+    extra_dirs: Vec<PathBuf>,
+    /// Run only the gRPC server, without the tray icon or any GUI dependencies
+    #[arg(long, visible_alias = "headless")]
+    no_tray: bool,
+    /// Sync and load hashtables on startup
+    #[arg(long)]
+    preload: bool,
+    /// Record which file each synced entry was read from
+    #[arg(long)]
+    track_sources: bool,
+    /// Which value wins when a hash is loaded or added with a different one: keep_last,
+    /// keep_first or prefer_official
+    #[arg(long, value_parser = ConflictPolicy::parse)]
+    conflict_policy: Option<ConflictPolicy>,
+    /// Limit hashtable downloads to this many KiB/s
+    #[arg(long, value_name = "KIB_PER_SEC")]
+    download_limit: Option<u64>,
+    /// Log filter, e.g. "debug" or "hash_service=trace"
+    #[arg(long)]
+    log_level: Option<String>,
+    /// Log output format
+    #[arg(long)]
+    log_format: Option<LogFormat>,
+    /// Run as a background service: no tray, logs on stdout only, systemd readiness notification
+    #[arg(long)]
+    daemon: bool,
+    /// Print a systemd unit for running this executable in daemon mode and exit
+    #[arg(long)]
+    print_systemd_unit: bool,
+    /// Container defaults: bind 0.0.0.0, no tray, JSON stdout logs, cache in /data.
+    /// The config file is only read when --config is given.
+    #[arg(long)]
+    container: bool,
+}
+
+impl Cli {
+    fn resolve_config(&self) -> Result<Config, String> {
+        let container = self.container || env_flag("HASH_SERVICE_CONTAINER").unwrap_or(false);
+        let mut config = match &self.config {
+            None if container => Config::container(),
+            path => Config::load(path.as_deref())?,
+        };
+        config.apply_env()?;
+        if let Some(host) = self.host {
+            config.host = host;
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if !self.extra_hosts.is_empty() {
+            config.extra_hosts = self.extra_hosts.clone();
+        }
+        if let Some(http_port) = self.http_port {
+            config.http_port = Some(http_port);
+        }
+        if self.mdns {
+            config.mdns = true;
+        }
+        if let Some(cache_dir) = &self.cache_dir {
+            config.cache_dir = Some(cache_dir.clone());
+        }
+        if !self.extra_dirs.is_empty() {
+            config.extra_dirs = self.extra_dirs.clone();
+        }
+        if self.no_tray {
+            config.tray = false;
+        }
+        if self.daemon {
+            // journald already captures and rotates stdout
+            config.tray = false;
+            config.log_file = false;
+        }
+        if self.preload {
+            config.preload = true;
+        }
+        if self.track_sources {
+            config.track_sources = true;
+        }
+        if let Some(conflict_policy) = self.conflict_policy {
+            config.conflict_policy = conflict_policy;
+        }
+        if let Some(download_limit) = self.download_limit {
+            config.download_limit_kbps = Some(download_limit);
+        }
+        if let Some(log_level) = &self.log_level {
+            config.log_level = log_level.clone();
+        }
+        if let Some(log_format) = self.log_format {
+            config.log_format = log_format;
+        }
+        Ok(config)
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let hash_loader = ServiceHashLoader::new();
+    let cli = Cli::parse();
+    if cli.print_systemd_unit {
+        print!("{}", daemon::systemd_unit(cli.config.as_deref())?);
+        return Ok(());
+    }
+    let mut config = cli.resolve_config()?;
+    let _telemetry = telemetry::init(&config.log_level, config.log_format, config.log_file);
+    crash::install(&config);
+
+    let Some(instance_lock) = discovery::acquire_instance_lock()? else {
+        match discovery::read() {
+            Some(running) => error!(
+                endpoint = %running.endpoint,
+                pid = running.pid,
+                "Another instance is already running"
+            ),
+            None => error!("Another instance is already running"),
+        }
+        return Ok(());
+    };
+
+    if let Some(enabled) = config.autostart
+        && autostart::is_enabled() != Ok(enabled)
+    {
+        match autostart::set_enabled(enabled) {
+            Ok(()) => info!(enabled, "Updated start at login"),
+            Err(e) => error!("{}", e),
+        }
+    }
 
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    health_reporter
+        .set_serving::<HashLoaderServer<ServiceHashLoader>>()
+        .await;
+    health_reporter
+        .set_serving::<HashLoaderV2Server<HashLoaderV2>>()
+        .await;
+
+    let sources = config.hash_sources()?;
+    let mirror_health = MirrorHealth::default();
+    let engines = sources
+        .iter()
+        .map(|source| config.engine(source, &mirror_health))
+        .collect::<Result<Vec<_>, _>>()?;
+    let cache_root = config
+        .cache_root()
+        .inspect_err(|e| {
+            error!(
+                "Cache root unknown, not cleaning unconfigured caches: {}",
+                e
+            );
+        })
+        .ok();
+    let cache_manager = Arc::new(CacheManager::new(
+        cache_root,
+        sources.clone(),
+        engines.clone(),
+    ));
+
+    let mut sources = sources.into_iter().zip(engines.iter().cloned());
+    let (default_source, engine) = sources.next().ok_or("No products configured")?;
+    let mut hash_loader = ServiceHashLoader::new(engine)
+        .with_wordlist_dir(config.wordlist_dir())
+        .with_cache_manager(cache_manager.clone())
+        .with_health_reporter(health_reporter);
+    if let Some(path) = UnknownHashes::default_path() {
+        match UnknownHashes::open(path) {
+            Ok(unknown_hashes) => hash_loader = hash_loader.with_unknown_hashes(unknown_hashes),
+            Err(e) => error!("Failed to open unknown hash store: {}", e),
+        }
+    }
+
+    // Other products and branches share the statistics and unknown hash store of the default one
+    let mut source_loaders = vec![(default_source.clone(), hash_loader.clone())];
+    for (source, engine) in sources {
+        source_loaders.push((source, hash_loader.with_engine(engine)));
+    }
+    // The tray settings window changes the timeout live
+    #[cfg_attr(not(feature = "tray"), allow(unused_variables))]
+    let (idle_unload_tx, idle_unload_rx) = watch::channel(config.idle_unload());
+    idle::spawn_idle_unload(engines, idle_unload_rx);
+
+    if config.preload {
+        for (_, hash_loader) in &source_loaders {
+            let hash_loader = hash_loader.clone();
+            tokio::spawn(async move { hash_loader.preload().await });
+        }
+    }
+    let source_router = SourceRouter::v1(default_source.clone(), &source_loaders, &config);
+    let source_router_v2 = SourceRouter::v2(default_source, &source_loaders, &config);
+
+    let incoming = TcpIncoming::bind(config.bind_addr())?.with_nodelay(Some(true));
+    let addr = incoming.local_addr()?;
+    // Report the port the OS picked when binding to port 0
+    config.port = addr.port();
+    // The other addresses share the port, so each is optional: one that can't be bound
+    // shouldn't take down the service on the main one
+    let mut incoming: Pin<Box<dyn Stream<Item = std::io::Result<TcpStream>> + Send>> =
+        Box::pin(incoming);
+    let mut extra_addrs = Vec::new();
+    // A dual-stack `[::]` socket already takes IPv4 connections on most platforms
+    let dual_stack = config.host.is_unspecified();
+    for extra_addr in config.extra_bind_addrs() {
+        match TcpIncoming::bind(extra_addr) {
+            Ok(extra) => {
+                extra_addrs.push(extra_addr);
+                incoming = Box::pin(incoming.merge(extra.with_nodelay(Some(true))));
+            }
+            Err(e) if dual_stack && e.kind() == std::io::ErrorKind::AddrInUse => {
+                info!(addr = %extra_addr, "Served by the dual-stack socket")
+            }
+            Err(e) => warn!(addr = %extra_addr, "Failed to bind additional address: {}", e),
+        }
+    }
+    if let Err(e) = discovery::write(&Discovery {
+        pid: std::process::id(),
+        endpoint: config.client_endpoint(),
+        addr,
+        port: addr.port(),
+        protocol: if config.tls.is_some() {
+            "grpcs"
+        } else {
+            "grpc"
+        }
+        .to_string(),
+        tls: config.tls.is_some(),
+        started_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+    }) {
+        error!("Failed to write discovery file: {}", e);
+    }
+
+    let access_log = config.access_log.clone();
+    let mut builder = config.connection.server();
+    if let Some(tls) = config.server_tls()? {
+        builder = builder.tls_config(tls)?;
+    }
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(hash_service::FILE_DESCRIPTOR_SET)
+        .build_v1()?;
+    let (shutdown_tx, shutdown_rx) = watch::channel(None);
+    let client_tracker = ClientTracker::new();
+    let admin = AdminService::new(shutdown_tx.clone(), client_tracker.clone()).with_cache_manager(
+        cache_manager,
+        cli.config.clone().or_else(Config::default_path),
+    );
+    #[cfg(feature = "tray")]
+    let tray_settings = tray::SettingsContext {
+        config_path: cli.config.clone().or_else(Config::default_path),
+        running: config.clone(),
+        idle_unload: idle_unload_tx,
+        shutdown: shutdown_tx.clone(),
+    };
     tokio::spawn(async move {
-        let addr = "[::1]:50051".parse().expect("Failed to parse address");
+        daemon::termination_signal().await;
+        info!("Termination signal received, shutting down");
+        admin::request_shutdown(&shutdown_tx, ShutdownKind::Stop);
+    });
+    let shutdown_signal = {
+        let shutdown_rx = shutdown_rx.clone();
+        async move {
+            admin::shutdown_signal(shutdown_rx).await;
+            daemon::notify_stopping();
+        }
+    };
+    #[cfg(feature = "http-gateway")]
+    let http_loader = hash_loader.clone();
+    let final_loader = hash_loader.clone();
+    #[cfg(feature = "tray")]
+    let tray_client = if config.tray_in_process {
+        client::ClientSettings::in_process(hash_loader.clone())
+    } else {
+        config.client_settings()?
+    };
+    let server = tokio::spawn(async move {
+        info!(%addr, ?extra_addrs, "Starting gRPC server");
 
-        if let Err(e) = Server::builder()
-            .add_service(HashLoaderServer::new(hash_loader))
-            .serve(addr)
+        if let Err(e) = builder
+            .layer(AccessLogLayer::new(access_log))
+            .layer(ClientTrackerLayer::new(client_tracker))
+            .add_service(source_router)
+            .add_service(source_router_v2)
+            .add_service(AdminServer::new(admin))
+            .add_service(health_service)
+            .add_service(reflection)
+            .serve_with_incoming_shutdown(incoming, shutdown_signal)
             .await
         {
-            eprintln!("gRPC server error: {:?}", e);
+            error!("gRPC server error: {:?}", e);
         }
     });
 
-    let event_loop = EventLoop::<UserEvent>::with_user_event().build().unwrap();
+    #[cfg(feature = "http-gateway")]
+    if let Some(http_port) = config.http_port {
+        let http_addr = std::net::SocketAddr::new(config.host, http_port);
+        let listener = tokio::net::TcpListener::bind(http_addr).await?;
+        let router = gateway::router(http_loader);
+        let shutdown_signal = admin::shutdown_signal(shutdown_rx.clone());
+        tokio::spawn(async move {
+            info!(addr = %http_addr, "Starting HTTP gateway");
+            if let Err(e) = axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal)
+            .await
+            {
+                error!("HTTP gateway error: {:?}", e);
+            }
+        });
+    }
+    #[cfg(not(feature = "http-gateway"))]
+    if config.http_port.is_some() {
+        error!("http_port is set but the http-gateway feature is not enabled");
+    }
 
-    let proxy = event_loop.create_proxy();
-    TrayIconEvent::set_event_handler(Some(move |event| {
-        let _ = proxy.send_event(UserEvent::TrayIconEvent(event));
-    }));
-    let proxy = event_loop.create_proxy();
-    MenuEvent::set_event_handler(Some(move |event| {
-        let _ = proxy.send_event(UserEvent::MenuEvent(event));
-    }));
+    // Withdrawn from the network when dropped at the end of main
+    #[cfg(feature = "mdns")]
+    let _advertisement = if config.mdns {
+        mdns::advertise(&config, addr).unwrap_or_else(|e| {
+            error!("Failed to advertise over mDNS: {}", e);
+            None
+        })
+    } else {
+        None
+    };
+    #[cfg(not(feature = "mdns"))]
+    if config.mdns {
+        error!("mdns is set but the mdns feature is not enabled");
+    }
 
-    let mut app = Application::new(); // No need to pass hash_loader
+    daemon::notify_ready();
 
-    #[cfg(target_os = "linux")]
-    {
-        gtk::init().unwrap();
-        let _tray_icon = Application::new_tray_icon();
-        gtk::main();
+    #[cfg(feature = "tray")]
+    if config.tray {
+        tray::run(tray_client, tray_settings, shutdown_rx.clone());
+        // The tray only returns on its own when it failed; the service then runs on headless
+        if shutdown_rx.borrow().is_none() {
+            error!("Tray exited, running headless");
+        }
     }
-
-    if let Err(err) = event_loop.run_app(&mut app) {
-        println!("TrayIcon Error: {err:?}");
+    #[cfg(not(feature = "tray"))]
+    if config.tray {
+        warn!("Built without the tray feature, running headless");
     }
 
+    server.await?;
+    finish(&final_loader, &shutdown_rx, instance_lock)
+}
+
+/// Flushes state, releases the instance and relaunches the process if a restart was requested
+fn finish(
+    hash_loader: &ServiceHashLoader,
+    shutdown_rx: &watch::Receiver<Option<ShutdownKind>>,
+    instance_lock: InstanceLock,
+) -> Result<(), Box<dyn std::error::Error>> {
+    hash_loader.save_unknown_hashes();
+    discovery::remove();
+    drop(instance_lock);
+    if *shutdown_rx.borrow() == Some(ShutdownKind::Restart) {
+        info!("Restarting");
+        admin::spawn_replacement()?;
+    }
     Ok(())
 }
@@ -0,0 +1,141 @@
+//! Serves one hash loader per CommunityDragon product and branch behind a single HashLoader
+//! service (each of v1 and v2), picking them from the `x-hash-product` and `x-hash-branch`
+//! request headers.
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use hash_service_core::sync::HashSource;
+use tonic::Status;
+use tonic::body::Body;
+use tonic::codegen::BoxFuture;
+use tonic::server::NamedService;
+use tower::Service;
+
+use crate::config::Config;
+use crate::state::hash_service::hash_loader_server::HashLoaderServer;
+use crate::state::hash_service::v2::hash_loader_server::HashLoaderServer as HashLoaderV2Server;
+use crate::state::{HashLoaderV2, ServiceHashLoader};
+
+/// Request header naming the product a call is for; the default product when absent
+pub const PRODUCT_HEADER: &str = "x-hash-product";
+/// Request header naming the data branch a call is for; the default branch when absent
+pub const BRANCH_HEADER: &str = "x-hash-branch";
+
+/// Generated server for `loader`, with the configured message sizes and compression
+macro_rules! configured_server {
+    ($server:ident, $loader:expr, $config:expr) => {{
+        let mut server = $server::new($loader)
+            .max_decoding_message_size($config.max_decoding_message_size)
+            .max_encoding_message_size($config.max_encoding_message_size);
+        for &encoding in &$config.compression.accept {
+            server = server.accept_compressed(encoding.into());
+        }
+        for &encoding in &$config.compression.send {
+            server = server.send_compressed(encoding.into());
+        }
+        server
+    }};
+}
+
+#[derive(Clone)]
+pub struct SourceRouter<S> {
+    default_source: HashSource,
+    servers: Arc<HashMap<HashSource, S>>,
+}
+
+impl<S> SourceRouter<S> {
+    fn new(default_source: HashSource, servers: impl Iterator<Item = (HashSource, S)>) -> Self {
+        SourceRouter {
+            default_source,
+            servers: Arc::new(servers.collect()),
+        }
+    }
+}
+
+impl SourceRouter<HashLoaderServer<ServiceHashLoader>> {
+    /// `loaders` must include `default_source`
+    pub fn v1(
+        default_source: HashSource,
+        loaders: &[(HashSource, ServiceHashLoader)],
+        config: &Config,
+    ) -> Self {
+        SourceRouter::new(
+            default_source,
+            loaders.iter().map(|(source, loader)| {
+                let server = configured_server!(HashLoaderServer, loader.clone(), config);
+                (source.clone(), server)
+            }),
+        )
+    }
+}
+
+impl SourceRouter<HashLoaderV2Server<HashLoaderV2>> {
+    /// `loaders` must include `default_source`
+    pub fn v2(
+        default_source: HashSource,
+        loaders: &[(HashSource, ServiceHashLoader)],
+        config: &Config,
+    ) -> Self {
+        SourceRouter::new(
+            default_source,
+            loaders.iter().map(|(source, loader)| {
+                let loader = HashLoaderV2::new(loader.clone());
+                (
+                    source.clone(),
+                    configured_server!(HashLoaderV2Server, loader, config),
+                )
+            }),
+        )
+    }
+}
+
+impl<S: NamedService> NamedService for SourceRouter<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S> Service<http::Request<Body>> for SourceRouter<S>
+where
+    S: Service<
+            http::Request<Body>,
+            Response = http::Response<Body>,
+            Error = Infallible,
+            Future = BoxFuture<http::Response<Body>, Infallible>,
+        > + Clone,
+{
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let header = |name: &str, default: &str| match request.headers().get(name) {
+            Some(value) => value.to_str().unwrap_or_default().to_string(),
+            None => default.to_string(),
+        };
+        let source = HashSource {
+            product: header(PRODUCT_HEADER, &self.default_source.product),
+            branch: header(BRANCH_HEADER, &self.default_source.branch),
+        };
+        match self.servers.get(&source) {
+            Some(server) => server.clone().call(request),
+            None => {
+                let mut sources: Vec<String> = self
+                    .servers
+                    .keys()
+                    .map(|source| format!("{}@{}", source.product, source.branch))
+                    .collect();
+                sources.sort();
+                let status = Status::invalid_argument(format!(
+                    "Unknown product '{}' or branch '{}', this service serves {:?}",
+                    source.product, source.branch, sources
+                ));
+                Box::pin(async move { Ok(status.into_http()) })
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Cancellation and deadlines of incoming calls. tonic drops a handler's future when its
+//! client goes away or the call's deadline passes; work running outside that future, like a
+//! blocking task or a synchronous scan, has to be told separately.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tonic::Request;
+
+/// Header clients send their deadline in, e.g. `500m` for half a second
+const TIMEOUT_HEADER: &str = "grpc-timeout";
+
+/// Raises its flag when dropped, which happens to a handler's locals when the call is
+/// cancelled
+#[derive(Debug, Default)]
+pub struct CancelOnDrop(Arc<AtomicBool>);
+
+impl CancelOnDrop {
+    /// Flag for the work to check; also raised once the handler returns normally
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.0.clone()
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Time the client gave the call, if it set a deadline
+pub fn request_deadline<T>(request: &Request<T>) -> Option<Duration> {
+    let value = request.metadata().get(TIMEOUT_HEADER)?.to_str().ok()?;
+    // At most 8 digits followed by a unit
+    let (amount, unit) = value.split_at_checked(value.len().checked_sub(1)?)?;
+    let amount: u64 = amount.parse().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(amount * 60 * 60),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
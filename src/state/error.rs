@@ -0,0 +1,55 @@
+//! [`ErrorCode`]s of failed calls. They travel in the `x-hash-error-code` metadata under a
+//! gRPC status code that fits them, so clients can branch on either instead of the message.
+use hash_service_core::{LOADING_IN_PROGRESS, SYNC_FAILED};
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+
+use super::hash_service::ErrorCode;
+
+pub const ERROR_CODE_HEADER: &str = "x-hash-error-code";
+
+/// Status of a failure with `code`
+pub fn error_status(code: ErrorCode, message: impl Into<String>) -> Status {
+    let grpc_code = match code {
+        ErrorCode::None => Code::Unknown,
+        ErrorCode::NotLoaded | ErrorCode::LoadingInProgress => Code::FailedPrecondition,
+        ErrorCode::InvalidTable | ErrorCode::HashMismatch => Code::InvalidArgument,
+        ErrorCode::SyncFailed => Code::Unavailable,
+        ErrorCode::LoadFailed => Code::Internal,
+        ErrorCode::Conflict => Code::AlreadyExists,
+    };
+    let mut status = Status::new(grpc_code, message);
+    status.metadata_mut().insert(
+        ERROR_CODE_HEADER,
+        MetadataValue::from_static(code.as_str_name()),
+    );
+    status
+}
+
+/// Code of a failed load; the engine's errors are strings, a failed sync's starts with
+/// [`SYNC_FAILED`]
+pub fn load_error_code(e: &str) -> ErrorCode {
+    if e.starts_with(SYNC_FAILED) {
+        ErrorCode::SyncFailed
+    } else if e == LOADING_IN_PROGRESS {
+        ErrorCode::LoadingInProgress
+    } else {
+        ErrorCode::LoadFailed
+    }
+}
+
+pub fn load_error_status(e: String) -> Status {
+    error_status(
+        load_error_code(&e),
+        format!("Failed to load hashtables: {}", e),
+    )
+}
+
+/// Status of an engine error other than a failed load: internal, unless a load was running
+pub fn engine_status(e: String) -> Status {
+    if e == LOADING_IN_PROGRESS {
+        error_status(ErrorCode::LoadingInProgress, e)
+    } else {
+        Status::internal(e)
+    }
+}
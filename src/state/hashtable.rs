@@ -1,174 +1,335 @@
-use directories_next::ProjectDirs;
+use globset::GlobBuilder;
 use hash_service::hash_loader_server::HashLoader;
 use hash_service::{
-    AddHashRequest, AddHashResponse, GetStringRequest, GetStringResponse, LoadHashesRequest,
-    LoadHashesResponse, UnloadHashesRequest, UnloadHashesResponse,
+    AddHashRequest, AddHashResponse, AddSourceDirectoryRequest, AddSourceDirectoryResponse,
+    AddSourceFileRequest, AddSourceFileResponse, CleanCacheRequest, CleanCacheResponse,
+    ClearTableRequest, ClearTableResponse, ContainsHashRequest, ContainsHashResponse,
+    CreateSnapshotRequest, CreateSnapshotResponse, CreateTableRequest, CreateTableResponse,
+    CustomTableInfo, DeleteSnapshotRequest, DeleteSnapshotResponse, DirectoryFile,
+    DiscoverHashesRequest, DiscoverHashesResponse, DiscoveredHash, DropTableRequest,
+    DropTableResponse, ErrorCode, ExportUnknownRequest, ExportUnknownResponse, ExportedTable,
+    ForceRedownloadRequest, ForceRedownloadResponse, GetCountsRequest, GetCountsResponse,
+    GetDiffRequest, GetDiffResponse, GetLoadReportRequest, GetLoadReportResponse,
+    GetStatisticsRequest, GetStatisticsResponse, GetStatusRequest, GetStatusResponse,
+    GetStringRequest, GetStringResponse, GetStringsRequest, GetStringsResponse,
+    GetTableInfoRequest, GetTableInfoResponse, GuessHashesRequest, GuessHashesResponse,
+    HashCollision, ListDirectoryRequest, ListDirectoryResponse, ListSnapshotsRequest,
+    ListSnapshotsResponse, ListSourceFilesRequest, ListSourceFilesResponse, ListSourcesRequest,
+    ListSourcesResponse, ListTablesRequest, ListTablesResponse, ListUnknownHashesRequest,
+    ListUnknownHashesResponse, LoadHashesRequest, LoadHashesResponse, LoadHashesUpdate, LoadedFile,
+    LocalSource, MissedHash, OnConflict, RemoveSourceRequest, RemoveSourceResponse, RenamedEntry,
+    ResolveDocumentRequest, ResolveDocumentResponse, ResolvedHash, RestoreSnapshotRequest,
+    RestoreSnapshotResponse, SearchEntry, SearchGlobRequest, SearchGlobResponse,
+    SearchPrefixRequest, SearchPrefixResponse, SearchRangeRequest, SearchRangeResponse,
+    SearchRegexRequest, SearchRegexResponse, SetConflictPolicyRequest, SetConflictPolicyResponse,
+    SetSourceEnabledRequest, SetSourceEnabledResponse, Snapshot, SubscribeRequest,
+    SyncHashtablesRequest, SyncHashtablesResult, SyncHashtablesUpdate, TableCounts, TableDiff,
+    TableEvent, TableEventKind, TableInfo, UnknownHash, UnloadHashesRequest, UnloadHashesResponse,
 };
-use serde_json::Value;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use hash_service_core::custom::{HashAlgorithm, validate_table_name};
+use hash_service_core::document::resolve_document;
+use hash_service_core::events;
+use hash_service_core::guess::{Template, guess, read_wordlist};
+use hash_service_core::local::{self, LocalSourceKind};
+use hash_service_core::normalize::{normalize, validate};
+use hash_service_core::progress::{DownloadProgress, FileProgress};
+use hash_service_core::scan::discover;
+use hash_service_core::search::{
+    STREAM_CHUNK_SIZE, page_limit, paginate, scan_limited, scan_timeout,
+};
+use hash_service_core::snapshot::{SnapshotInfo, validate_name};
+use hash_service_core::sync::{SourceFile, latest_patch};
+use hash_service_core::{
+    CachedFile, ConflictPolicy, EntryOrigin, FailedFile, FailureStage, HashEngine, Hashtable,
+    HashtableType, InsertOutcome, LOADING_IN_PROGRESS, LoadingState, TableLoad,
+};
+use regex::RegexBuilder;
+use std::collections::{BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
-use walkdir::WalkDir;
-use xxhash_rust::xxh64::xxh64;
+use tonic_health::ServingStatus;
+use tonic_health::server::HealthReporter;
+use tracing::{debug, error, info, instrument};
+
+use super::cancel::{CancelOnDrop, request_deadline};
+use super::error::{engine_status, error_status, load_error_code, load_error_status};
+use super::stats::{DEFAULT_TOP_MISSED, Statistics};
+use super::unknown::UnknownHashes;
+use crate::admin::authorize;
+use crate::alloc;
+use crate::cache::CacheManager;
 
 pub mod hash_service {
     tonic::include_proto!("hashservice");
+
+    pub mod v2 {
+        tonic::include_proto!("hashservice.v2");
+    }
+
+    /// Encoded descriptors for the service, served through gRPC reflection
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("hashservice_descriptor");
 }
 
+/// Health check service name that is SERVING only while the hashtables are loaded
+pub const TABLES_HEALTH_SERVICE: &str = "hashservice.HashLoader.Tables";
+
+/// Compiled program size cap for user-supplied regexes
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// Most hashes a single GetStrings call may resolve
+const MAX_BATCH_SIZE: usize = 10_000;
+
+const DEFAULT_MAX_GUESS_CANDIDATES: u64 = 10_000_000;
+const MAX_GUESS_CANDIDATES: u64 = 1_000_000_000;
+
+/// How often recorded unknown hashes are flushed to disk
+const UNKNOWN_HASHES_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// gRPC front end over a [`HashEngine`], adding per-RPC statistics and health reporting
 #[derive(Debug, Clone)]
 pub struct ServiceHashLoader {
-    game_hashes: Arc<RwLock<HashMap<u64, String>>>,
-    bin_hashes: Arc<RwLock<HashMap<u64, String>>>,
-    loading_state: Arc<RwLock<LoadingState>>,
+    engine: HashEngine,
+    statistics: Arc<Mutex<Statistics>>,
+    unknown_hashes: Arc<Mutex<UnknownHashes>>,
+    /// Where GuessHashes looks up `<name>.txt` wordlists
+    wordlist_dir: Option<PathBuf>,
+    /// Cleans every product's cache on CleanCache; without one only this engine's is cleaned
+    cache_manager: Option<Arc<CacheManager>>,
 }
 
-enum HashtableType {
-    Game,
-    Bin,
+fn parse_hashtable_type(value: &str) -> Result<HashtableType, Status> {
+    HashtableType::parse(value).map_err(|e| error_status(ErrorCode::InvalidTable, e))
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum LoadingState {
-    Unloaded,
-    Loading,
-    Loaded,
+/// Table a request names: a built-in one or a custom table created at runtime
+#[derive(Clone, Copy)]
+enum TableTarget {
+    Builtin(HashtableType),
+    Custom(HashAlgorithm),
 }
 
 #[tonic::async_trait]
 impl HashLoader for ServiceHashLoader {
+    type LoadHashesStreamStream = ReceiverStream<Result<LoadHashesUpdate, Status>>;
+    type SyncHashtablesStream = ReceiverStream<Result<SyncHashtablesUpdate, Status>>;
+    type SearchGlobStream = ReceiverStream<Result<SearchGlobResponse, Status>>;
+    type SubscribeStream = ReceiverStream<Result<TableEvent, Status>>;
+
+    #[instrument(skip_all)]
     async fn load_hashes(
         &self,
         request: Request<LoadHashesRequest>,
     ) -> Result<Response<LoadHashesResponse>, Status> {
-        println!("load_hashes called: {:?}", request);
+        self.record_call("LoadHashes");
+        info!(?request, "load_hashes called");
 
-        // Set state to Loading
-        {
-            let mut state_guard = self
-                .loading_state
-                .write()
-                .map_err(|_| Status::internal("Failed to lock loading state"))?;
-            *state_guard = LoadingState::Loading;
-        }
-
-        let result = self.load_hashes_impl().await;
+        let req = request.get_ref();
+        let files = file_selection(&self.engine, &req.files, &req.hashtable_types)?;
+        load_hashes(&self.engine, req.resync, &files)
+            .await
+            .map(Response::new)
+    }
 
-        // Update state based on result
-        {
-            let mut state_guard = self
-                .loading_state
-                .write()
-                .map_err(|_| Status::internal("Failed to lock loading state"))?;
-            *state_guard = if result.is_ok() {
-                LoadingState::Loaded
-            } else {
-                LoadingState::Unloaded
+    #[instrument(skip_all)]
+    async fn load_hashes_stream(
+        &self,
+        request: Request<LoadHashesRequest>,
+    ) -> Result<Response<Self::LoadHashesStreamStream>, Status> {
+        self.record_call("LoadHashesStream");
+        info!(?request, "load_hashes_stream called");
+
+        let resync = request.get_ref().resync;
+        let selection = file_selection(
+            &self.engine,
+            &request.get_ref().files,
+            &request.get_ref().hashtable_types,
+        )?;
+        let engine = self.engine.clone();
+        let mut files = engine.file_progress();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let load = load_hashes(&engine, resync, &selection);
+            tokio::pin!(load);
+            let result = loop {
+                tokio::select! {
+                    result = &mut load => break result,
+                    // Dropping the load stops the download and marks the tables unloaded
+                    _ = tx.closed() => {
+                        info!("Load abandoned by its client");
+                        return;
+                    }
+                    progress = files.recv() => match progress {
+                        Ok(progress) => {
+                            let _ = tx.send(Ok(file_update(progress))).await;
+                        }
+                        // Later updates carry the running totals, so missed ones are not needed
+                        Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => {}
+                    },
+                }
             };
-        }
-
-        match result {
-            Ok(()) => {
-                let (game_count, bin_count) = self.get_counts()?;
-                Ok(Response::new(LoadHashesResponse {
-                    success: true,
-                    message: format!(
-                        "Hashtables loaded: {} game, {} bin hashes!",
-                        game_count, bin_count
-                    ),
-                    count: (game_count + bin_count) as i32,
+            // Updates sent just before the load returned
+            while let Ok(progress) = files.try_recv() {
+                let _ = tx.send(Ok(file_update(progress))).await;
+            }
+            let _ = tx
+                .send(result.map(|result| LoadHashesUpdate {
+                    update: Some(hash_service::load_hashes_update::Update::Result(result)),
                 }))
+                .await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    #[instrument(skip_all)]
+    async fn sync_hashtables(
+        &self,
+        request: Request<SyncHashtablesRequest>,
+    ) -> Result<Response<Self::SyncHashtablesStream>, Status> {
+        self.record_call("SyncHashtables");
+        info!(?request, "sync_hashtables called");
+
+        let req = request.into_inner();
+        let files = file_selection(&self.engine, &req.files, &req.hashtable_types)?;
+        let engine = self.engine.clone();
+        let mut downloads = engine.download_progress();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let sync = engine.sync_files(&files, req.force);
+            tokio::pin!(sync);
+            let result = loop {
+                tokio::select! {
+                    result = &mut sync => break result,
+                    // Dropping the sync stops the download; finished files stay staged
+                    _ = tx.closed() => {
+                        info!("Sync abandoned by its client");
+                        return;
+                    }
+                    progress = downloads.recv() => match progress {
+                        Ok(progress) => {
+                            let _ = tx.send(Ok(download_update(progress))).await;
+                        }
+                        // Later updates carry the running totals, so missed ones are not needed
+                        Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => {}
+                    },
+                }
+            };
+            // Updates sent just before the sync returned
+            while let Ok(progress) = downloads.try_recv() {
+                let _ = tx.send(Ok(download_update(progress))).await;
             }
-            Err(e) => Ok(Response::new(LoadHashesResponse {
-                success: false,
-                message: format!("Failed to load hashtables: {}", e),
-                count: 0,
-            })),
-        }
+            let result = result
+                .and_then(|updated_files| Ok((updated_files, engine.sync_failures()?)))
+                .map(|(updated_files, sync_failures)| SyncHashtablesUpdate {
+                    update: Some(hash_service::sync_hashtables_update::Update::Result(
+                        SyncHashtablesResult {
+                            updated_files: updated_files as u32,
+                            // Only this sync's; the other files keep those of their own
+                            failed_files: sync_failures
+                                .into_iter()
+                                .filter(|failed| {
+                                    files.is_empty() || files.contains(&failed.file_name)
+                                })
+                                .map(failed_file_message)
+                                .collect(),
+                        },
+                    )),
+                })
+                .map_err(|e| error_status(load_error_code(&e), e));
+            let _ = tx.send(result).await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
     }
 
+    #[instrument(skip_all)]
     async fn get_string(
         &self,
         request: Request<GetStringRequest>,
     ) -> Result<Response<GetStringResponse>, Status> {
+        self.record_call("GetString");
         let req = request.into_inner();
-        println!(
-            "get_string called for hash: {}, type: {}",
-            req.hash, req.hashtable_type
-        );
-
-        self.ensure_loaded_status().await?;
-
-        let hashtable_type = match req.hashtable_type.as_str() {
-            "game" => HashtableType::Game,
-            "bin" => HashtableType::Bin,
-            _ => {
-                return Ok(Response::new(GetStringResponse {
-                    found: false,
-                    value: String::new(),
-                }));
+        debug!(hash = req.hash, hashtable_type = %req.hashtable_type, "get_string called");
+
+        let hashtable_type = match self.table_target(&req.hashtable_type)? {
+            Some(TableTarget::Builtin(hashtable_type)) => hashtable_type,
+            Some(TableTarget::Custom(_)) => {
+                let response = self
+                    .engine
+                    .with_table(&req.hashtable_type, |table| {
+                        table.get(&req.hash).map(|value| GetStringResponse {
+                            found: true,
+                            value: value.to_string(),
+                            origin: origin_to_proto(table.origin(&req.hash)) as i32,
+                            ..GetStringResponse::default()
+                        })
+                    })
+                    .map_err(Status::internal)?
+                    .flatten()
+                    .unwrap_or_default();
+                return Ok(Response::new(response));
             }
+            None => return Ok(Response::new(GetStringResponse::default())),
         };
 
-        let hashtable = self.get_hashtable(&hashtable_type);
-        let guard = hashtable
-            .read()
-            .map_err(|_| Status::internal("Failed to lock hashtable"))?;
+        self.ensure_lookups_ready_status(hashtable_type).await?;
 
-        let response = guard
-            .get(&req.hash)
-            .map(|value| GetStringResponse {
+        // Scanners repeat lookups of unknown hashes; answer those without the table lock
+        if self.engine.is_known_miss(hashtable_type, req.hash) {
+            self.record_lookup(hashtable_type.as_str(), req.hash, false);
+            return Ok(Response::new(GetStringResponse::default()));
+        }
+        let generation = self.engine.miss_generation(hashtable_type);
+        let response = match self
+            .engine
+            .lookup_many(hashtable_type, &[req.hash])
+            .map_err(Status::internal)?
+            .pop()
+            .flatten()
+        {
+            Some(entry) => GetStringResponse {
                 found: true,
-                value: value.clone(),
-            })
-            .unwrap_or_else(|| GetStringResponse {
-                found: false,
-                value: String::new(),
-            });
+                value: entry.value,
+                origin: origin_to_proto(Some(entry.origin)) as i32,
+                official_value: entry.official_value.unwrap_or_default(),
+                source_file: entry.source_file.unwrap_or_default(),
+            },
+            None => GetStringResponse::default(),
+        };
+        if !response.found {
+            self.engine
+                .record_miss(hashtable_type, req.hash, generation);
+        }
+
+        self.record_lookup(hashtable_type.as_str(), req.hash, response.found);
 
         Ok(Response::new(response))
     }
 
+    #[instrument(skip_all)]
     async fn unload_hashes(
         &self,
         _request: Request<UnloadHashesRequest>,
     ) -> Result<Response<UnloadHashesResponse>, Status> {
-        println!("unload_hashes called");
-
-        // Clear the hashtables to free memory
-        {
-            let mut game_guard = self
-                .game_hashes
-                .write()
-                .map_err(|_| Status::internal("Failed to lock game hashtable"))?;
-            let mut bin_guard = self
-                .bin_hashes
-                .write()
-                .map_err(|_| Status::internal("Failed to lock bin hashtable"))?;
-
-            let game_count = game_guard.len();
-            let bin_count = bin_guard.len();
-
-            game_guard.clear();
-            bin_guard.clear();
-
-            // Shrink capacity to minimize memory usage
-            game_guard.shrink_to_fit();
-            bin_guard.shrink_to_fit();
-
-            println!("Unloaded {} game and {} bin hashes", game_count, bin_count);
-        }
-
-        // Update state to Unloaded
-        {
-            let mut state_guard = self
-                .loading_state
-                .write()
-                .map_err(|_| Status::internal("Failed to lock loading state"))?;
-            *state_guard = LoadingState::Unloaded;
+        self.record_call("UnloadHashes");
+        info!("unload_hashes called");
+
+        if self.engine.loading_state() == LoadingState::Loading {
+            return Err(error_status(
+                ErrorCode::LoadingInProgress,
+                LOADING_IN_PROGRESS,
+            ));
         }
+        let (game_count, bin_count) = self.engine.unload().map_err(Status::internal)?;
+        alloc::release_memory();
+        info!(game_count, bin_count, "Unloaded hashtables");
 
         Ok(Response::new(UnloadHashesResponse {
             success: true,
@@ -176,335 +337,2038 @@ impl HashLoader for ServiceHashLoader {
         }))
     }
 
+    #[instrument(skip_all)]
     async fn add_hash(
         &self,
         request: Request<AddHashRequest>,
     ) -> Result<Response<AddHashResponse>, Status> {
+        self.record_call("AddHash");
         let req = request.into_inner();
-        println!(
-            "add_hash called for , value: {}, type: {}",
-            req.string, req.hashtable_type
-        );
-
-        self.ensure_loaded_status().await?;
+        info!(value = %req.string, hashtable_type = %req.hashtable_type, "add_hash called");
 
-        // if game xxhash64 if bin fnv1a
-        let hash = match req.hashtable_type.as_str() {
-            "game" => xxh64(req.string.to_lowercase().as_bytes(), 0),
-            "bin" => {
-                // Correct FNV-1a 32-bit implementation (standard offset basis)
-                // 32-bit offset basis: 0x811C9DC5, prime: 0x01000193
-                let mut hash: u32 = 0x811C9DC5;
-                for &byte in req.string.to_lowercase().as_bytes() {
-                    hash ^= byte as u32;
-                    hash = hash.wrapping_mul(0x01000193);
-                }
-                // return as u64 with value in lower 32 bits (matches client 32-bit hex -> decimal)
-                hash as u64
+        let Some(target) = self.table_target(&req.hashtable_type)? else {
+            return Ok(Response::new(AddHashResponse {
+                success: false,
+                message: "Invalid hashtable type".to_string(),
+                error_code: ErrorCode::InvalidTable as i32,
+                ..AddHashResponse::default()
+            }));
+        };
+        let (value, computed) = match target {
+            TableTarget::Builtin(hashtable_type) => {
+                self.ensure_table_loaded_status(hashtable_type).await?;
+                let value =
+                    normalize(hashtable_type, &req.string).map_err(Status::invalid_argument)?;
+                let computed = hashtable_type.hash(&value);
+                (value, computed)
             }
-            _ => {
-                return Ok(Response::new(AddHashResponse {
-                    success: false,
-                    message: "Invalid hashtable type".to_string(),
-                }));
+            TableTarget::Custom(algorithm) => {
+                let value = validate(&req.string)
+                    .map_err(Status::invalid_argument)?
+                    .to_string();
+                (value.clone(), algorithm.hash(&value))
             }
         };
-        println!("Computed hash: {}", hash);
+        let hash = req.hash.unwrap_or(computed);
+        debug!(hash, computed, "Computed hash");
+        let verified = hash == computed;
+        if req.validate && !verified {
+            return Ok(Response::new(AddHashResponse {
+                success: false,
+                message: format!(
+                    "Hash {:x} does not match the computed hash {:x}",
+                    hash, computed
+                ),
+                hash,
+                verified,
+                error_code: ErrorCode::HashMismatch as i32,
+                ..AddHashResponse::default()
+            }));
+        }
 
-        // Insert into appropriate hashtable
-        let hashtable = match req.hashtable_type.as_str() {
-            "game" => &self.game_hashes,
-            "bin" => &self.bin_hashes,
-            _ => unreachable!(),
+        let on_conflict = req.on_conflict();
+        let policy = match on_conflict {
+            OnConflict::Default => None,
+            OnConflict::Overwrite => Some(ConflictPolicy::KeepLast),
+            // Errors are reported after the fact, keeping the entry makes that atomic
+            OnConflict::KeepExisting | OnConflict::Error => Some(ConflictPolicy::KeepFirst),
+        };
+        let outcome = match target {
+            TableTarget::Builtin(hashtable_type) => {
+                self.engine
+                    .add_with_hash(hashtable_type, hash, value.clone(), policy)
+            }
+            TableTarget::Custom(_) => {
+                self.engine
+                    .add_to_table(&req.hashtable_type, hash, value.clone(), policy)
+            }
+        }
+        .map_err(Status::invalid_argument)?;
+        if on_conflict == OnConflict::Error
+            && let InsertOutcome::Conflict { existing, .. } = &outcome
+        {
+            return Err(error_status(
+                ErrorCode::Conflict,
+                format!("Hash {:x} already maps to '{}'", hash, existing),
+            ));
+        }
+        let (existed, existing_value, replaced) = match &outcome {
+            InsertOutcome::Inserted => (false, String::new(), false),
+            InsertOutcome::Unchanged => (true, value, false),
+            InsertOutcome::Conflict {
+                existing, replaced, ..
+            } => (true, existing.clone(), *replaced),
         };
-        let mut guard = hashtable
-            .write()
-            .map_err(|_| Status::internal("Failed to lock hashtable for writing"))?;
 
-        guard.insert(hash, req.string);
+        let message = match outcome {
+            InsertOutcome::Conflict {
+                existing, replaced, ..
+            } => {
+                if replaced {
+                    format!("Added hash successfully, replaced '{}'", existing)
+                } else {
+                    format!("Kept existing value '{}' due to conflict policy", existing)
+                }
+            }
+            InsertOutcome::Inserted | InsertOutcome::Unchanged => {
+                "Added hash successfully".to_string()
+            }
+        };
 
         Ok(Response::new(AddHashResponse {
             success: true,
-            message: "Added hash successfully".to_string(),
+            message,
+            hash,
+            verified,
+            existed,
+            existing_value,
+            replaced,
+            error_code: ErrorCode::None as i32,
         }))
     }
-}
 
-impl ServiceHashLoader {
-    pub fn new() -> Self {
-        ServiceHashLoader {
-            game_hashes: Arc::new(RwLock::new(HashMap::default())),
-            bin_hashes: Arc::new(RwLock::new(HashMap::default())),
-            loading_state: Arc::new(RwLock::new(LoadingState::Unloaded)),
+    #[instrument(skip_all)]
+    async fn get_load_report(
+        &self,
+        _request: Request<GetLoadReportRequest>,
+    ) -> Result<Response<GetLoadReportResponse>, Status> {
+        self.record_call("GetLoadReport");
+        info!("get_load_report called");
+
+        let conflict_policy = self.engine.conflict_policy().map_err(Status::internal)?;
+        let report = self.engine.load_report().map_err(Status::internal)?;
+
+        let collisions = report
+            .collisions
+            .iter()
+            .map(|c| HashCollision {
+                hash: c.hash,
+                hashtable_type: c.hashtable_type.to_string(),
+                existing_value: c.existing_value.clone(),
+                new_value: c.new_value.clone(),
+                source_file: c.source_file.clone(),
+                replaced: c.replaced,
+            })
+            .collect();
+
+        Ok(Response::new(GetLoadReportResponse {
+            collisions,
+            collision_count: report.collision_count,
+            conflict_policy: conflict_policy_to_proto(conflict_policy) as i32,
+            kept_existing_count: report.kept_existing_count,
+            replaced_count: report.replaced_count,
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn set_conflict_policy(
+        &self,
+        request: Request<SetConflictPolicyRequest>,
+    ) -> Result<Response<SetConflictPolicyResponse>, Status> {
+        self.record_call("SetConflictPolicy");
+        let req = request.into_inner();
+        info!(policy = ?req.policy(), "set_conflict_policy called");
+
+        let policy = conflict_policy_from_proto(req.policy());
+        self.engine
+            .set_conflict_policy(policy)
+            .map_err(Status::internal)?;
+
+        Ok(Response::new(SetConflictPolicyResponse {
+            success: true,
+            message: format!("Conflict policy set to {:?}", policy),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn contains_hash(
+        &self,
+        request: Request<ContainsHashRequest>,
+    ) -> Result<Response<ContainsHashResponse>, Status> {
+        self.record_call("ContainsHash");
+        let req = request.into_inner();
+
+        let candidates: &[HashtableType] = match req.hashtable_type.as_str() {
+            "game" => &[HashtableType::Game],
+            "bin" => &[HashtableType::Bin],
+            "" => &HashtableType::ALL,
+            _ => {
+                return Err(error_status(
+                    ErrorCode::InvalidTable,
+                    "Invalid hashtable type",
+                ));
+            }
+        };
+
+        for &hashtable_type in candidates {
+            self.ensure_lookups_ready_status(hashtable_type).await?;
+            if self.engine.is_known_miss(hashtable_type, req.hash) {
+                continue;
+            }
+            let generation = self.engine.miss_generation(hashtable_type);
+            let found = self
+                .engine
+                .lookup_many(hashtable_type, &[req.hash])
+                .map_err(Status::internal)?
+                .iter()
+                .any(Option::is_some);
+            if found {
+                self.record_lookup(hashtable_type.as_str(), req.hash, true);
+                return Ok(Response::new(ContainsHashResponse {
+                    found: true,
+                    hashtable_type: hashtable_type.as_str().to_string(),
+                }));
+            }
+            self.engine
+                .record_miss(hashtable_type, req.hash, generation);
         }
+
+        let searched = match candidates {
+            [hashtable_type] => hashtable_type.as_str(),
+            _ => "any",
+        };
+        self.record_lookup(searched, req.hash, false);
+
+        Ok(Response::new(ContainsHashResponse {
+            found: false,
+            hashtable_type: String::new(),
+        }))
     }
 
-    async fn ensure_loaded_status(&self) -> Result<(), Status> {
-        self.ensure_loaded()
-            .await
-            .map_err(|e| Status::internal(format!("Failed to load hashtables: {}", e)))
+    #[instrument(skip_all)]
+    async fn search_prefix(
+        &self,
+        request: Request<SearchPrefixRequest>,
+    ) -> Result<Response<SearchPrefixResponse>, Status> {
+        self.record_call("SearchPrefix");
+        let req = request.into_inner();
+        info!(prefix = %req.prefix, hashtable_type = %req.hashtable_type, "search_prefix called");
+
+        let hashtable_type = parse_hashtable_type(&req.hashtable_type)?;
+        self.ensure_table_loaded_status(hashtable_type).await?;
+        let guard = self.engine.read(hashtable_type).map_err(Status::internal)?;
+
+        let page = guard
+            .prefix_page(&req.prefix, &req.page_token, req.limit)
+            .map_err(Status::invalid_argument)?;
+
+        Ok(Response::new(SearchPrefixResponse {
+            entries: page
+                .entries
+                .into_iter()
+                .map(|(hash, value)| SearchEntry { hash, value })
+                .collect(),
+            next_page_token: page.next_page_token,
+        }))
     }
 
-    async fn ensure_loaded(&self) -> Result<(), String> {
-        // Check current state and transition if needed
-        let should_load = {
-            let mut state_guard = self
-                .loading_state
-                .write()
-                .map_err(|_| "Failed to lock loading state".to_string())?;
+    #[instrument(skip_all)]
+    async fn search_regex(
+        &self,
+        request: Request<SearchRegexRequest>,
+    ) -> Result<Response<SearchRegexResponse>, Status> {
+        self.record_call("SearchRegex");
+        let deadline = request_deadline(&request);
+        let req = request.into_inner();
+        info!(pattern = %req.pattern, hashtable_type = %req.hashtable_type, "search_regex called");
+
+        let regex = RegexBuilder::new(&req.pattern)
+            .case_insensitive(req.case_insensitive)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .build()
+            .map_err(|e| Status::invalid_argument(format!("Invalid regex: {}", e)))?;
+
+        let hashtable_type = parse_hashtable_type(&req.hashtable_type)?;
+        self.ensure_table_loaded_status(hashtable_type).await?;
+        let guard = self.engine.read(hashtable_type).map_err(Status::internal)?;
+
+        let result = scan_limited(
+            guard.iter(),
+            |value| regex.is_match(value),
+            req.limit,
+            // The scan blocks the handler, so it cannot be dropped when the deadline passes
+            deadline.map_or(scan_timeout(req.timeout_ms), |deadline| {
+                scan_timeout(req.timeout_ms).min(deadline)
+            }),
+        );
 
-            match *state_guard {
-                LoadingState::Loaded => {
-                    // Already loaded, nothing to do
-                    return Ok(());
-                }
-                LoadingState::Loading => {
-                    // Already loading, return error
-                    return Err("Hashtables are currently being loaded".to_string());
-                }
-                LoadingState::Unloaded => {
-                    // Transition to Loading
-                    *state_guard = LoadingState::Loading;
-                    true
+        Ok(Response::new(SearchRegexResponse {
+            entries: result
+                .entries
+                .into_iter()
+                .map(|(hash, value)| SearchEntry { hash, value })
+                .collect(),
+            truncated: result.truncated,
+            timed_out: result.timed_out,
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn search_glob(
+        &self,
+        request: Request<SearchGlobRequest>,
+    ) -> Result<Response<Self::SearchGlobStream>, Status> {
+        self.record_call("SearchGlob");
+        let req = request.into_inner();
+        info!(pattern = %req.pattern, hashtable_type = %req.hashtable_type, "search_glob called");
+
+        let matcher = GlobBuilder::new(&req.pattern)
+            .literal_separator(true)
+            .case_insensitive(req.case_insensitive)
+            .build()
+            .map_err(|e| Status::invalid_argument(format!("Invalid glob: {}", e)))?
+            .compile_matcher();
+
+        let hashtable_type = parse_hashtable_type(&req.hashtable_type)?;
+        self.ensure_table_loaded_status(hashtable_type).await?;
+        let page = {
+            let guard = self.engine.read(hashtable_type).map_err(Status::internal)?;
+
+            let matches = guard
+                .iter()
+                .filter(|(_, value)| matcher.is_match(value))
+                .map(|(hash, value)| (*hash, value))
+                .collect();
+            paginate(matches, &req.page_token, req.limit).map_err(Status::invalid_argument)?
+        };
+
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let mut entries = page.entries.into_iter().peekable();
+            loop {
+                let chunk: Vec<SearchEntry> = entries
+                    .by_ref()
+                    .take(STREAM_CHUNK_SIZE)
+                    .map(|(hash, value)| SearchEntry { hash, value })
+                    .collect();
+                let is_last = entries.peek().is_none();
+                let response = SearchGlobResponse {
+                    entries: chunk,
+                    next_page_token: if is_last {
+                        page.next_page_token.clone()
+                    } else {
+                        String::new()
+                    },
+                };
+                if tx.send(Ok(response)).await.is_err() || is_last {
+                    // Client went away or everything was sent
+                    break;
                 }
             }
-        };
+        });
 
-        if should_load {
-            println!("Hashtables are unloaded, loading them now...");
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
 
-            // Load the hashtables
-            let result = self.load_hashes_impl().await;
+    #[instrument(skip_all)]
+    async fn search_range(
+        &self,
+        request: Request<SearchRangeRequest>,
+    ) -> Result<Response<SearchRangeResponse>, Status> {
+        self.record_call("SearchRange");
+        let req = request.into_inner();
+        info!(
+            start = %req.start,
+            end = %req.end,
+            hashtable_type = %req.hashtable_type,
+            "search_range called"
+        );
+
+        let hashtable_type = parse_hashtable_type(&req.hashtable_type)?;
+        self.ensure_table_loaded_status(hashtable_type).await?;
+        let guard = self.engine.read(hashtable_type).map_err(Status::internal)?;
+
+        let page = guard
+            .range_page(&req.start, &req.end, &req.page_token, req.limit)
+            .map_err(Status::invalid_argument)?;
+
+        Ok(Response::new(SearchRangeResponse {
+            entries: page
+                .entries
+                .into_iter()
+                .map(|(hash, value)| SearchEntry { hash, value })
+                .collect(),
+            next_page_token: page.next_page_token,
+        }))
+    }
 
-            // Update state based on result
-            let mut state_guard = self
-                .loading_state
-                .write()
-                .map_err(|_| "Failed to lock loading state".to_string())?;
+    #[instrument(skip_all)]
+    async fn get_counts(
+        &self,
+        request: Request<GetCountsRequest>,
+    ) -> Result<Response<GetCountsResponse>, Status> {
+        self.record_call("GetCounts");
+        let req = request.into_inner();
+        info!(hashtable_type = %req.hashtable_type, "get_counts called");
 
-            *state_guard = if result.is_ok() {
-                LoadingState::Loaded
-            } else {
-                LoadingState::Unloaded // Reset to Unloaded on error
-            };
+        let hashtable_types = match req.hashtable_type.as_str() {
+            "" => vec![HashtableType::Game, HashtableType::Bin],
+            other => vec![parse_hashtable_type(other)?],
+        };
 
-            result?;
+        let mut tables = Vec::with_capacity(hashtable_types.len());
+        for hashtable_type in hashtable_types {
+            let guard = self.engine.read(hashtable_type).map_err(Status::internal)?;
+            tables.push(TableCounts {
+                hashtable_type: hashtable_type.as_str().to_string(),
+                total: guard.len() as u64,
+                custom: guard.custom_len() as u64,
+                by_top_level_segment: guard.top_level_counts(),
+            });
         }
 
-        Ok(())
+        Ok(Response::new(GetCountsResponse { tables }))
     }
 
-    fn get_hashtable(&self, hashtable_type: &HashtableType) -> &Arc<RwLock<HashMap<u64, String>>> {
-        match hashtable_type {
-            HashtableType::Game => &self.game_hashes,
-            HashtableType::Bin => &self.bin_hashes,
+    #[instrument(skip_all)]
+    async fn get_statistics(
+        &self,
+        request: Request<GetStatisticsRequest>,
+    ) -> Result<Response<GetStatisticsResponse>, Status> {
+        self.record_call("GetStatistics");
+        let req = request.into_inner();
+        info!(reset = req.reset, "get_statistics called");
+
+        let mut statistics = self
+            .statistics
+            .lock()
+            .map_err(|_| Status::internal("Failed to lock statistics"))?;
+
+        let top_missed = match req.top_missed {
+            0 => DEFAULT_TOP_MISSED,
+            n => n as usize,
+        };
+        let response = GetStatisticsResponse {
+            hits: statistics.hits,
+            misses: statistics.misses,
+            most_missed: statistics
+                .most_missed(top_missed)
+                .into_iter()
+                .map(|(hashtable_type, hash, count)| MissedHash {
+                    hash,
+                    hashtable_type: hashtable_type.to_string(),
+                    count,
+                })
+                .collect(),
+            rpc_calls: statistics
+                .rpc_calls
+                .iter()
+                .map(|(method, count)| (method.to_string(), *count))
+                .collect(),
+        };
+
+        if req.reset {
+            *statistics = Statistics::default();
         }
+
+        Ok(Response::new(response))
     }
 
-    fn get_counts(&self) -> Result<(usize, usize), Status> {
-        let game_guard = self
-            .game_hashes
-            .read()
-            .map_err(|_| Status::internal("Failed to lock game hashtable"))?;
-        let bin_guard = self
-            .bin_hashes
-            .read()
-            .map_err(|_| Status::internal("Failed to lock bin hashtable"))?;
-        Ok((game_guard.len(), bin_guard.len()))
+    #[instrument(skip_all)]
+    async fn list_directory(
+        &self,
+        request: Request<ListDirectoryRequest>,
+    ) -> Result<Response<ListDirectoryResponse>, Status> {
+        self.record_call("ListDirectory");
+        let req = request.into_inner();
+        info!(path = %req.path, hashtable_type = %req.hashtable_type, "list_directory called");
+
+        let hashtable_type = parse_hashtable_type(&req.hashtable_type)?;
+        self.ensure_table_loaded_status(hashtable_type).await?;
+        let guard = self.engine.read(hashtable_type).map_err(Status::internal)?;
+
+        let response = match guard.path_tree().find(&req.path) {
+            Some(node) => ListDirectoryResponse {
+                found: true,
+                directories: node.dirs.keys().cloned().collect(),
+                files: node
+                    .files
+                    .iter()
+                    .map(|(name, hash)| DirectoryFile {
+                        name: name.clone(),
+                        hash: *hash,
+                    })
+                    .collect(),
+            },
+            None => ListDirectoryResponse::default(),
+        };
+
+        Ok(Response::new(response))
     }
 
-    async fn load_hashes_impl(&self) -> Result<(), String> {
-        let project_dirs = ProjectDirs::from("io", "LeagueToolkit", "ltk-hash-cache")
-            .ok_or_else(|| "Failed to get project directories".to_string())?;
+    #[instrument(skip_all)]
+    async fn get_strings(
+        &self,
+        request: Request<GetStringsRequest>,
+    ) -> Result<Response<GetStringsResponse>, Status> {
+        self.record_call("GetStrings");
+        let req = request.into_inner();
+        debug!(count = req.hashes.len(), hashtable_type = %req.hashtable_type, "get_strings called");
+
+        if req.hashes.len() > MAX_BATCH_SIZE {
+            return Err(Status::invalid_argument(format!(
+                "At most {} hashes can be resolved per request",
+                MAX_BATCH_SIZE
+            )));
+        }
+        let resolve = |table: &Hashtable| -> Vec<ResolvedHash> {
+            req.hashes
+                .iter()
+                .map(|&hash| match table.get(&hash) {
+                    Some(value) => ResolvedHash {
+                        hash,
+                        found: true,
+                        value: value.to_string(),
+                        origin: origin_to_proto(table.origin(&hash)) as i32,
+                        official_value: table.shadowed(&hash).cloned().unwrap_or_default(),
+                        source_file: table.source_file(&hash).unwrap_or_default().to_string(),
+                    },
+                    None => ResolvedHash {
+                        hash,
+                        ..ResolvedHash::default()
+                    },
+                })
+                .collect()
+        };
+        let hashtable_type = match self.table_target(&req.hashtable_type)? {
+            Some(TableTarget::Builtin(hashtable_type)) => hashtable_type,
+            Some(TableTarget::Custom(_)) => {
+                let results = self
+                    .engine
+                    .with_table(&req.hashtable_type, resolve)
+                    .map_err(Status::internal)?
+                    .unwrap_or_default();
+                return Ok(Response::new(GetStringsResponse { results }));
+            }
+            None => {
+                return Err(error_status(
+                    ErrorCode::InvalidTable,
+                    "Invalid hashtable type",
+                ));
+            }
+        };
+
+        self.ensure_lookups_ready_status(hashtable_type).await?;
 
-        let hash_dir: PathBuf = if cfg!(target_os = "linux") {
-            project_dirs.cache_dir().to_path_buf()
+        let generation = self.engine.miss_generation(hashtable_type);
+        let results: Vec<ResolvedHash> = if req
+            .hashes
+            .iter()
+            .all(|&hash| self.engine.is_known_miss(hashtable_type, hash))
+        {
+            // Nothing the table could answer, skip its lock
+            req.hashes
+                .iter()
+                .map(|&hash| ResolvedHash {
+                    hash,
+                    ..ResolvedHash::default()
+                })
+                .collect()
         } else {
-            directories_next::UserDirs::new()
-                .and_then(|ud| {
-                    ud.document_dir()
-                        .map(|p| p.join("LeagueToolkit").join("ltk-hash-cache").to_path_buf())
+            self.engine
+                .lookup_many(hashtable_type, &req.hashes)
+                .map_err(Status::internal)?
+                .into_iter()
+                .zip(&req.hashes)
+                .map(|(entry, &hash)| match entry {
+                    Some(entry) => ResolvedHash {
+                        hash,
+                        found: true,
+                        value: entry.value,
+                        origin: origin_to_proto(Some(entry.origin)) as i32,
+                        official_value: entry.official_value.unwrap_or_default(),
+                        source_file: entry.source_file.unwrap_or_default(),
+                    },
+                    None => ResolvedHash {
+                        hash,
+                        ..ResolvedHash::default()
+                    },
                 })
-                .unwrap_or_else(|| project_dirs.cache_dir().to_path_buf())
+                .collect()
         };
-        std::fs::create_dir_all(&hash_dir)
-            .map_err(|e| format!("Failed to create cache directory: {}", e))?;
 
-        // Sync hashtables from GitHub
-        let cache_dir_str = hash_dir
-            .to_str()
-            .ok_or_else(|| "Invalid cache directory path".to_string())?;
-        sync_hashtables(cache_dir_str).await?;
+        for result in &results {
+            if !result.found {
+                self.engine
+                    .record_miss(hashtable_type, result.hash, generation);
+            }
+            self.record_lookup(hashtable_type.as_str(), result.hash, result.found);
+        }
+
+        Ok(Response::new(GetStringsResponse { results }))
+    }
+
+    #[instrument(skip_all)]
+    async fn resolve_document(
+        &self,
+        request: Request<ResolveDocumentRequest>,
+    ) -> Result<Response<ResolveDocumentResponse>, Status> {
+        self.record_call("ResolveDocument");
+        let req = request.into_inner();
+        info!(len = req.document.len(), "resolve_document called");
+
+        self.ensure_loaded_status().await?;
 
-        // Load hashtables from directory
-        self.add_from_dir(hash_dir)?;
+        let resolved = resolve_document(&self.engine, &req.document).map_err(Status::internal)?;
+        debug!(
+            resolved = resolved.resolved,
+            unresolved = resolved.unresolved,
+            "Resolved document"
+        );
 
-        Ok(())
+        Ok(Response::new(ResolveDocumentResponse {
+            document: resolved.document,
+            resolved: resolved.resolved as u32,
+            unresolved: resolved.unresolved as u32,
+        }))
     }
 
-    fn add_from_dir(&self, dir: impl AsRef<Path>) -> Result<(), String> {
-        println!("Loading hashtables from dir: {:?}", dir.as_ref());
+    #[instrument(skip_all)]
+    async fn discover_hashes(
+        &self,
+        request: Request<DiscoverHashesRequest>,
+    ) -> Result<Response<DiscoverHashesResponse>, Status> {
+        self.record_call("DiscoverHashes");
+        authorize(&request)?;
+        let req = request.into_inner();
+        info!(paths = ?req.paths, "discover_hashes called");
+
+        if req.paths.is_empty() {
+            return Err(Status::invalid_argument("No paths to scan"));
+        }
+        self.ensure_loaded_status().await?;
+
+        let engine = self.engine.clone();
+        let paths: Vec<PathBuf> = req.paths.iter().map(PathBuf::from).collect();
+        let report = tokio::task::spawn_blocking(move || discover(&engine, &paths))
+            .await
+            .map_err(|e| Status::internal(format!("Discovery scan failed: {}", e)))?
+            .map_err(Status::internal)?;
+
+        Ok(Response::new(DiscoverHashesResponse {
+            files_scanned: report.files_scanned as u32,
+            files_skipped: report.files_skipped as u32,
+            candidates: report.candidates as u32,
+            unknown: report.unknown as u32,
+            discovered: report
+                .discovered
+                .into_iter()
+                .map(|(hashtable_type, hash, value)| DiscoveredHash {
+                    hash,
+                    hashtable_type: hashtable_type.as_str().to_string(),
+                    value,
+                })
+                .collect(),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn guess_hashes(
+        &self,
+        request: Request<GuessHashesRequest>,
+    ) -> Result<Response<GuessHashesResponse>, Status> {
+        self.record_call("GuessHashes");
+        let req = request.into_inner();
+        info!(templates = ?req.templates, hashtable_type = %req.hashtable_type, "guess_hashes called");
+
+        let hashtable_type = parse_hashtable_type(&req.hashtable_type)?;
+        let templates = req
+            .templates
+            .iter()
+            .map(|template| Template::parse(template))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Status::invalid_argument)?;
+        if templates.is_empty() {
+            return Err(Status::invalid_argument("No templates to expand"));
+        }
 
-        for entry in WalkDir::new(dir).into_iter().filter_map(|x| x.ok()) {
-            if !entry.file_type().is_file()
-                || entry.path().extension().map_or(false, |ext| ext == "sha")
-            {
+        let mut wordlists: HashMap<String, Vec<String>> = req
+            .wordlists
+            .into_iter()
+            .map(|(name, wordlist)| (name, wordlist.words))
+            .collect();
+        for name in templates.iter().flat_map(Template::wordlists) {
+            if wordlists.contains_key(name) {
                 continue;
             }
+            let Some(dir) = &self.wordlist_dir else {
+                return Err(Status::invalid_argument(format!(
+                    "Wordlist '{}' was not provided",
+                    name
+                )));
+            };
+            let words = read_wordlist(&dir.join(format!("{}.txt", name)))
+                .map_err(Status::invalid_argument)?;
+            wordlists.insert(name.to_string(), words);
+        }
 
-            let file_name = entry.file_name().to_string_lossy();
-            let is_game = file_name.contains(".game.");
-            let is_bin = file_name.contains(".binentries.");
+        let targets = if req.targets.is_empty() {
+            self.unknown_hashes
+                .lock()
+                .map_err(|_| Status::internal("Failed to lock unknown hashes"))?
+                .hashes(hashtable_type.as_str())
+        } else {
+            req.targets.into_iter().collect()
+        };
+        let max_candidates = match req.max_candidates {
+            0 => DEFAULT_MAX_GUESS_CANDIDATES,
+            n => n.min(MAX_GUESS_CANDIDATES),
+        };
 
-            if is_game || is_bin {
-                println!("Loading hashtable: {:?}", entry.path());
-                let mut file = File::open(entry.path())
-                    .map_err(|e| format!("Failed to open file {:?}: {}", entry.path(), e))?;
-                self.add_from_file(&mut file, is_game)?;
-            }
+        self.ensure_loaded_status().await?;
+
+        let engine = self.engine.clone();
+        let target_count = targets.len();
+        // Stops the blocking task if the client gives up on the call
+        let cancel = CancelOnDrop::default();
+        let cancelled = cancel.flag();
+        let report = tokio::task::spawn_blocking(move || {
+            guess(
+                &engine,
+                hashtable_type,
+                &templates,
+                &wordlists,
+                targets,
+                max_candidates,
+                &cancelled,
+            )
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Guessing failed: {}", e)))?
+        .map_err(Status::invalid_argument)?;
+
+        Ok(Response::new(GuessHashesResponse {
+            candidates: report.candidates,
+            targets: target_count as u32,
+            cracked: report
+                .cracked
+                .into_iter()
+                .map(|(hash, value)| SearchEntry { hash, value })
+                .collect(),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn list_unknown_hashes(
+        &self,
+        request: Request<ListUnknownHashesRequest>,
+    ) -> Result<Response<ListUnknownHashesResponse>, Status> {
+        self.record_call("ListUnknownHashes");
+        let req = request.into_inner();
+        info!(hashtable_type = %req.hashtable_type, "list_unknown_hashes called");
+
+        if !matches!(req.hashtable_type.as_str(), "" | "any") {
+            parse_hashtable_type(&req.hashtable_type)?;
         }
+        let offset: usize = match req.page_token.as_str() {
+            "" => 0,
+            token => token
+                .parse()
+                .map_err(|_| Status::invalid_argument("Malformed page token"))?,
+        };
+        let limit = page_limit(req.limit);
+
+        // Resolve against whatever is loaded; nothing is loaded just to list
+        let resolve = |hashtable_type: &str, hash: u64| -> Option<String> {
+            let candidates: &[HashtableType] = match hashtable_type {
+                "game" => &[HashtableType::Game],
+                "bin" => &[HashtableType::Bin],
+                _ => &[HashtableType::Game, HashtableType::Bin],
+            };
+            candidates
+                .iter()
+                .find_map(|t| self.engine.get(*t, hash).ok().flatten())
+        };
 
-        println!("Hashtables loaded successfully");
-        Ok(())
+        let unknown_hashes = self
+            .unknown_hashes
+            .lock()
+            .map_err(|_| Status::internal("Failed to lock unknown hashes"))?;
+        let matches: Vec<UnknownHash> = unknown_hashes
+            .list(&req.hashtable_type)
+            .into_iter()
+            .filter_map(|entry| {
+                let value = resolve(&entry.hashtable_type, entry.hash);
+                if value.is_some() && !req.include_resolved {
+                    return None;
+                }
+                Some(UnknownHash {
+                    hash: entry.hash,
+                    hashtable_type: entry.hashtable_type.clone(),
+                    count: entry.count,
+                    first_seen: entry.first_seen,
+                    last_seen: entry.last_seen,
+                    value: value.unwrap_or_default(),
+                })
+            })
+            .collect();
+
+        let total = matches.len();
+        let hashes: Vec<UnknownHash> = matches.into_iter().skip(offset).take(limit).collect();
+        let next_offset = offset + hashes.len();
+        Ok(Response::new(ListUnknownHashesResponse {
+            hashes,
+            next_page_token: if next_offset < total {
+                next_offset.to_string()
+            } else {
+                String::new()
+            },
+            total: total as u32,
+        }))
     }
 
-    fn add_from_file(&self, file: &mut File, to_game: bool) -> Result<(), String> {
-        let reader = BufReader::new(file);
-        let mut lines = reader.lines();
-        let mut count = 0;
+    #[instrument(skip_all)]
+    async fn export_unknown(
+        &self,
+        request: Request<ExportUnknownRequest>,
+    ) -> Result<Response<ExportUnknownResponse>, Status> {
+        self.record_call("ExportUnknown");
+        let req = request.into_inner();
+        info!(hashtable_type = %req.hashtable_type, "export_unknown called");
 
-        let hashtable = if to_game {
-            &self.game_hashes
+        let hashtable_types = match req.hashtable_type.as_str() {
+            "" => vec![HashtableType::Game, HashtableType::Bin],
+            other => vec![parse_hashtable_type(other)?],
+        };
+        // Official entries are already upstream, so only custom ones are exported by default
+        let origins: Vec<EntryOrigin> = if req.origins.is_empty() {
+            vec![EntryOrigin::Custom]
         } else {
-            &self.bin_hashes
+            req.origins()
+                .filter_map(|origin| match origin {
+                    hash_service::EntryOrigin::Official => Some(EntryOrigin::Official),
+                    hash_service::EntryOrigin::Custom => Some(EntryOrigin::Custom),
+                    hash_service::EntryOrigin::None => None,
+                })
+                .collect()
+        };
+        let recorded: Vec<(String, u64)> = self
+            .unknown_hashes
+            .lock()
+            .map_err(|_| Status::internal("Failed to lock unknown hashes"))?
+            .list("")
+            .into_iter()
+            .map(|entry| (entry.hashtable_type.clone(), entry.hash))
+            .collect();
+
+        let mut tables = Vec::new();
+        for hashtable_type in hashtable_types {
+            let table = self.engine.read(hashtable_type).map_err(Status::internal)?;
+            let mut unresolved = BTreeSet::new();
+            let mut resolved = BTreeSet::new();
+            let mut sources = BTreeSet::new();
+            for (recorded_type, hash) in &recorded {
+                // Lookups across tables are attributed by whether the hash fits in 32 bits
+                let belongs = match recorded_type.as_str() {
+                    "any" => (*hash > u32::MAX as u64) == (hashtable_type == HashtableType::Game),
+                    other => other == hashtable_type.as_str(),
+                };
+                if !belongs {
+                    continue;
+                }
+                match table.get(hash) {
+                    Some(value) => {
+                        if table
+                            .origin(hash)
+                            .is_some_and(|origin| origins.contains(&origin))
+                        {
+                            resolved.insert((value.to_string(), *hash));
+                            if let Some(source_file) = table.source_file(hash) {
+                                sources.insert((source_file.to_string(), *hash));
+                            }
+                        }
+                    }
+                    None => {
+                        unresolved.insert(*hash);
+                    }
+                }
+            }
+
+            let format_hash = |hash: u64| match hashtable_type {
+                HashtableType::Game => format!("{:016x}", hash),
+                HashtableType::Bin => format!("{:08x}", hash),
+            };
+            tables.push(ExportedTable {
+                hashtable_type: hashtable_type.as_str().to_string(),
+                unresolved: unresolved
+                    .into_iter()
+                    .map(|hash| format!("{}\n", format_hash(hash)))
+                    .collect(),
+                resolved: resolved
+                    .into_iter()
+                    .map(|(value, hash)| format!("{} {}\n", format_hash(hash), value))
+                    .collect(),
+                sources: sources
+                    .into_iter()
+                    .map(|(source_file, hash)| format!("{} {}\n", format_hash(hash), source_file))
+                    .collect(),
+            });
+        }
+
+        Ok(Response::new(ExportUnknownResponse { tables }))
+    }
+
+    #[instrument(skip_all)]
+    async fn get_diff(
+        &self,
+        request: Request<GetDiffRequest>,
+    ) -> Result<Response<GetDiffResponse>, Status> {
+        self.record_call("GetDiff");
+        let req = request.into_inner();
+        info!(hashtable_type = %req.hashtable_type, prefix = %req.prefix, "get_diff called");
+
+        let hashtable_types = match req.hashtable_type.as_str() {
+            "" => vec![HashtableType::Game, HashtableType::Bin],
+            other => vec![parse_hashtable_type(other)?],
         };
+        let prefix = req.prefix.to_lowercase();
+        let matches = |value: &str| value.to_lowercase().starts_with(&prefix);
+        let limit = match req.limit {
+            0 => usize::MAX,
+            limit => limit as usize,
+        };
+
+        let mut diffs = Vec::new();
+        for hashtable_type in hashtable_types {
+            let Some(diff) = self.engine.diff(hashtable_type).map_err(Status::internal)? else {
+                continue;
+            };
+            let mut truncated = false;
+            let mut entries = |entries: Vec<(u64, String)>| -> Vec<SearchEntry> {
+                let mut entries = entries.into_iter().filter(|(_, value)| matches(value));
+                let page: Vec<SearchEntry> = entries
+                    .by_ref()
+                    .take(limit)
+                    .map(|(hash, value)| SearchEntry { hash, value })
+                    .collect();
+                truncated |= entries.next().is_some();
+                page
+            };
+            let added = entries(diff.added);
+            let removed = entries(diff.removed);
+            let mut renamed = diff
+                .renamed
+                .into_iter()
+                .filter(|(_, old_value, new_value)| matches(old_value) || matches(new_value));
+            let renamed_page = renamed
+                .by_ref()
+                .take(limit)
+                .map(|(hash, old_value, new_value)| RenamedEntry {
+                    hash,
+                    old_value,
+                    new_value,
+                })
+                .collect();
+            truncated |= renamed.next().is_some();
+
+            diffs.push(TableDiff {
+                hashtable_type: hashtable_type.as_str().to_string(),
+                synced_at: diff.synced_at,
+                added,
+                removed,
+                renamed: renamed_page,
+                truncated,
+            });
+        }
+
+        Ok(Response::new(GetDiffResponse { diffs }))
+    }
+
+    #[instrument(skip_all)]
+    async fn create_snapshot(
+        &self,
+        request: Request<CreateSnapshotRequest>,
+    ) -> Result<Response<CreateSnapshotResponse>, Status> {
+        self.record_call("CreateSnapshot");
+        let req = request.into_inner();
+        info!(name = %req.name, "create_snapshot called");
+
+        validate_name(&req.name).map_err(Status::invalid_argument)?;
+        self.ensure_loaded_status().await?;
+
+        let engine = self.engine.clone();
+        let snapshot = tokio::task::spawn_blocking(move || engine.create_snapshot(&req.name))
+            .await
+            .map_err(|e| Status::internal(format!("Snapshot failed: {}", e)))?
+            .map_err(Status::internal)?;
+        info!(name = %snapshot.name, size_bytes = snapshot.size_bytes, "Created snapshot");
+
+        Ok(Response::new(CreateSnapshotResponse {
+            snapshot: Some(snapshot_message(snapshot)),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn list_snapshots(
+        &self,
+        _request: Request<ListSnapshotsRequest>,
+    ) -> Result<Response<ListSnapshotsResponse>, Status> {
+        self.record_call("ListSnapshots");
+        info!("list_snapshots called");
+
+        let snapshots = self.engine.list_snapshots().map_err(Status::internal)?;
+        Ok(Response::new(ListSnapshotsResponse {
+            snapshots: snapshots.into_iter().map(snapshot_message).collect(),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn restore_snapshot(
+        &self,
+        request: Request<RestoreSnapshotRequest>,
+    ) -> Result<Response<RestoreSnapshotResponse>, Status> {
+        self.record_call("RestoreSnapshot");
+        let req = request.into_inner();
+        info!(name = %req.name, "restore_snapshot called");
+
+        self.require_snapshot(&req.name)?;
+        let engine = self.engine.clone();
+        let (game_count, bin_count) =
+            tokio::task::spawn_blocking(move || engine.restore_snapshot(&req.name))
+                .await
+                .map_err(|e| Status::internal(format!("Restore failed: {}", e)))?
+                .map_err(engine_status)?;
+
+        Ok(Response::new(RestoreSnapshotResponse {
+            game_count: game_count as u64,
+            bin_count: bin_count as u64,
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_snapshot(
+        &self,
+        request: Request<DeleteSnapshotRequest>,
+    ) -> Result<Response<DeleteSnapshotResponse>, Status> {
+        self.record_call("DeleteSnapshot");
+        let req = request.into_inner();
+        info!(name = %req.name, "delete_snapshot called");
+
+        self.require_snapshot(&req.name)?;
+        self.engine
+            .delete_snapshot(&req.name)
+            .map_err(Status::internal)?;
+        Ok(Response::new(DeleteSnapshotResponse {}))
+    }
 
-        let mut guard = hashtable
-            .write()
-            .map_err(|_| "Failed to lock hashtable for writing".to_string())?;
+    #[instrument(skip_all)]
+    async fn clear_table(
+        &self,
+        request: Request<ClearTableRequest>,
+    ) -> Result<Response<ClearTableResponse>, Status> {
+        self.record_call("ClearTable");
+        let req = request.into_inner();
+        info!(hashtable_type = %req.hashtable_type, custom_only = req.custom_only, "clear_table called");
 
-        while let Some(Ok(line)) = lines.next() {
-            let mut components = line.split(' ');
+        let hashtable_type = parse_hashtable_type(&req.hashtable_type)?;
+        let removed = self
+            .engine
+            .clear_table(hashtable_type, req.custom_only)
+            .map_err(Status::internal)?;
 
-            let hash_str = components
-                .next()
-                .ok_or_else(|| "Failed to read hash from line".to_string())?;
-            let hash = u64::from_str_radix(hash_str, 16)
-                .map_err(|e| format!("Failed to convert hash '{}': {}", hash_str, e))?;
-            let path = components.collect::<Vec<_>>().join(" ");
+        Ok(Response::new(ClearTableResponse {
+            removed: removed as u64,
+        }))
+    }
 
-            guard.insert(hash, path);
-            count += 1;
+    #[instrument(skip_all)]
+    async fn clean_cache(
+        &self,
+        request: Request<CleanCacheRequest>,
+    ) -> Result<Response<CleanCacheResponse>, Status> {
+        self.record_call("CleanCache");
+        let req = request.into_inner();
+        info!(dry_run = req.dry_run, "clean_cache called");
+
+        let report = match &self.cache_manager {
+            Some(cache_manager) => cache_manager.clean(req.dry_run).await,
+            None => self.engine.clean_cache(req.dry_run).await,
         }
+        .map_err(Status::internal)?;
+        info!(
+            removed = report.removed.len(),
+            bytes_freed = report.bytes_freed,
+            dry_run = req.dry_run,
+            "Cleaned cache"
+        );
 
-        println!("Loaded {} entries from file", count);
-        Ok(())
+        Ok(Response::new(CleanCacheResponse {
+            removed: report
+                .removed
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect(),
+            bytes_freed: report.bytes_freed,
+        }))
     }
-}
 
-// fn create_project_dirs(project_dirs: &ProjectDirs) {
-
-//     let cache_dir = project_dirs.cache_dir();
-//     // check if directory exists
-//     if !cache_dir.exists() {
-//         // create directory
-//         if let Err(e) = std::fs::create_dir_all(cache_dir) {
-//             eprintln!("Failed to create cache directory: {:?}", e);
-//         }
-//     }
-// }
-
-async fn sync_hashtables(appdatadir: &str) -> Result<(), String> {
-    let git_links: Vec<&str> = vec![
-        "https://api.github.com/repos/CommunityDragon/Data/contents/hashes/lol/hashes.binentries.txt",
-        "https://api.github.com/repos/CommunityDragon/Data/contents/hashes/lol/hashes.game.txt.0",
-        "https://api.github.com/repos/CommunityDragon/Data/contents/hashes/lol/hashes.game.txt.1",
-    ];
-
-    for git_url in git_links {
-        println!("Syncing hashtable from: {}", git_url);
-        let git_data = get_git_data(git_url)
+    #[instrument(skip_all)]
+    async fn list_source_files(
+        &self,
+        _request: Request<ListSourceFilesRequest>,
+    ) -> Result<Response<ListSourceFilesResponse>, Status> {
+        self.record_call("ListSourceFiles");
+        info!("list_source_files called");
+
+        let source_files = self.engine.source_files().map_err(Status::internal)?;
+        let files = self
+            .engine
+            .cached_files()
+            .map_err(Status::internal)?
+            .into_iter()
+            .map(|file| self.cached_file_message(file, &source_files))
+            .collect::<Result<_, _>>()?;
+        Ok(Response::new(ListSourceFilesResponse {
+            cache_dir: self
+                .engine
+                .cache_dir()
+                .map_err(Status::internal)?
+                .display()
+                .to_string(),
+            files,
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn force_redownload(
+        &self,
+        request: Request<ForceRedownloadRequest>,
+    ) -> Result<Response<ForceRedownloadResponse>, Status> {
+        self.record_call("ForceRedownload");
+        let req = request.into_inner();
+        info!(file_name = %req.file_name, "force_redownload called");
+
+        if !self.engine.synced_file_names().contains(&req.file_name) {
+            return Err(Status::not_found(format!(
+                "'{}' is not a synced file",
+                req.file_name
+            )));
+        }
+        let updated_files = self
+            .engine
+            .redownload(&req.file_name)
             .await
-            .map_err(|e| format!("Failed to fetch data from GitHub: {}", e))?;
-
-        let checksum = git_data
-            .get("sha")
-            .and_then(|s| s.as_str())
-            .ok_or_else(|| "Missing 'sha' field in response".to_string())?;
-        let url = git_data
-            .get("download_url")
-            .and_then(|s| s.as_str())
-            .ok_or_else(|| "Missing 'download_url' field in response".to_string())?;
-        let file_name = git_data
-            .get("name")
-            .and_then(|s| s.as_str())
-            .ok_or_else(|| "Missing 'name' field in response".to_string())?;
-
-        let file_path = PathBuf::from(appdatadir).join(file_name);
-
-        if file_path.exists() {
-            // Append .sha to the file name (e.g., hashes.game.txt.0 -> hashes.game.txt.0.sha)
-            let sha_path = file_path.with_file_name(format!("{}.sha", file_name));
-            if sha_path.exists() {
-                if let Ok(existing_sha) = std::fs::read_to_string(&sha_path) {
-                    if existing_sha.trim() == checksum {
-                        println!("File {} is up to date, skipping", file_name);
-                        continue;
-                    }
-                }
+            .map_err(|e| error_status(load_error_code(&e), e))?;
+
+        let source_files = self.engine.source_files().map_err(Status::internal)?;
+        let file = self
+            .engine
+            .cached_files()
+            .map_err(Status::internal)?
+            .into_iter()
+            .find(|file| file.file_name == req.file_name)
+            .map(|file| self.cached_file_message(file, &source_files))
+            .transpose()?;
+        Ok(Response::new(ForceRedownloadResponse {
+            file,
+            updated_files: updated_files as u32,
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn add_source_directory(
+        &self,
+        request: Request<AddSourceDirectoryRequest>,
+    ) -> Result<Response<AddSourceDirectoryResponse>, Status> {
+        self.record_call("AddSourceDirectory");
+        authorize(&request)?;
+        let req = request.into_inner();
+        info!(path = %req.path, "add_source_directory called");
+
+        let source = local::LocalSource::directory(PathBuf::from(req.path));
+        let source = self.add_local_source(source)?;
+        Ok(Response::new(AddSourceDirectoryResponse {
+            source: Some(source),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn add_source_file(
+        &self,
+        request: Request<AddSourceFileRequest>,
+    ) -> Result<Response<AddSourceFileResponse>, Status> {
+        self.record_call("AddSourceFile");
+        authorize(&request)?;
+        let req = request.into_inner();
+        info!(path = %req.path, hashtable_type = %req.hashtable_type, "add_source_file called");
+
+        let hashtable_type = parse_hashtable_type(&req.hashtable_type)?;
+        let source = local::LocalSource::file(PathBuf::from(req.path), hashtable_type);
+        let source = self.add_local_source(source)?;
+        Ok(Response::new(AddSourceFileResponse {
+            source: Some(source),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn list_sources(
+        &self,
+        _request: Request<ListSourcesRequest>,
+    ) -> Result<Response<ListSourcesResponse>, Status> {
+        self.record_call("ListSources");
+        info!("list_sources called");
+
+        let sources = self
+            .engine
+            .local_sources()
+            .map_err(Status::internal)?
+            .iter()
+            .map(local_source_message)
+            .collect();
+        Ok(Response::new(ListSourcesResponse { sources }))
+    }
+
+    #[instrument(skip_all)]
+    async fn set_source_enabled(
+        &self,
+        request: Request<SetSourceEnabledRequest>,
+    ) -> Result<Response<SetSourceEnabledResponse>, Status> {
+        self.record_call("SetSourceEnabled");
+        authorize(&request)?;
+        let req = request.into_inner();
+        info!(path = %req.path, enabled = req.enabled, "set_source_enabled called");
+
+        let source = self
+            .engine
+            .set_local_source_enabled(Path::new(&req.path), req.enabled)
+            .map_err(Status::internal)?
+            .ok_or_else(|| Status::not_found(format!("Source '{}' not found", req.path)))?;
+        Ok(Response::new(SetSourceEnabledResponse {
+            source: Some(local_source_message(&source)),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn remove_source(
+        &self,
+        request: Request<RemoveSourceRequest>,
+    ) -> Result<Response<RemoveSourceResponse>, Status> {
+        self.record_call("RemoveSource");
+        authorize(&request)?;
+        let req = request.into_inner();
+        info!(path = %req.path, "remove_source called");
+
+        let path = Path::new(&req.path);
+        let sources = self.engine.local_sources().map_err(Status::internal)?;
+        match sources.iter().find(|source| source.path == path) {
+            None => {
+                return Err(Status::not_found(format!(
+                    "Source '{}' not found",
+                    req.path
+                )));
             }
-            println!("File {} needs update, downloading...", file_name);
+            Some(source) if source.from_config => {
+                return Err(Status::failed_precondition(format!(
+                    "Source '{}' is set in the config file; disable it instead",
+                    req.path
+                )));
+            }
+            Some(_) => {}
+        }
+        self.engine
+            .remove_local_source(path)
+            .map_err(Status::internal)?;
+        Ok(Response::new(RemoveSourceResponse {}))
+    }
+
+    #[instrument(skip_all)]
+    async fn create_table(
+        &self,
+        request: Request<CreateTableRequest>,
+    ) -> Result<Response<CreateTableResponse>, Status> {
+        self.record_call("CreateTable");
+        let req = request.into_inner();
+        info!(name = %req.name, algorithm = ?req.algorithm(), "create_table called");
+
+        validate_table_name(&req.name).map_err(Status::invalid_argument)?;
+        if self
+            .engine
+            .table_algorithm(&req.name)
+            .map_err(Status::internal)?
+            .is_some()
+        {
+            return Err(Status::already_exists(format!(
+                "Table '{}' already exists",
+                req.name
+            )));
+        }
+        self.engine
+            .create_table(&req.name, algorithm_from_proto(req.algorithm()))
+            .map_err(Status::internal)?;
+
+        Ok(Response::new(CreateTableResponse {}))
+    }
+
+    #[instrument(skip_all)]
+    async fn drop_table(
+        &self,
+        request: Request<DropTableRequest>,
+    ) -> Result<Response<DropTableResponse>, Status> {
+        self.record_call("DropTable");
+        let req = request.into_inner();
+        info!(name = %req.name, "drop_table called");
+
+        let removed = self
+            .engine
+            .drop_table(&req.name)
+            .map_err(Status::internal)?
+            .ok_or_else(|| Status::not_found(format!("Table '{}' not found", req.name)))?;
+
+        Ok(Response::new(DropTableResponse {
+            removed: removed as u64,
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn list_tables(
+        &self,
+        _request: Request<ListTablesRequest>,
+    ) -> Result<Response<ListTablesResponse>, Status> {
+        self.record_call("ListTables");
+        info!("list_tables called");
+
+        let tables = self
+            .engine
+            .list_tables()
+            .map_err(Status::internal)?
+            .into_iter()
+            .map(|(name, algorithm, count)| CustomTableInfo {
+                name,
+                algorithm: algorithm_to_proto(algorithm) as i32,
+                count: count as u64,
+            })
+            .collect();
+
+        Ok(Response::new(ListTablesResponse { tables }))
+    }
+
+    #[instrument(skip_all)]
+    async fn get_status(
+        &self,
+        _request: Request<GetStatusRequest>,
+    ) -> Result<Response<GetStatusResponse>, Status> {
+        self.record_call("GetStatus");
+        info!("get_status called");
+
+        let source_files = self.engine.source_files().map_err(Status::internal)?;
+        let source = self.engine.source();
+        let loading_state = self.engine.loading_state();
+        let failed_files = self.engine.failed_files().map_err(Status::internal)?;
+        let table_state = |hashtable_type: HashtableType| {
+            loading_state_to_proto(
+                self.engine.table_loading_state(hashtable_type),
+                failed_files
+                    .iter()
+                    .any(|failed| failed.hashtable_type == hashtable_type),
+            ) as i32
+        };
+        // A running load holds the table locks, so counts would wait for it to finish
+        let (game_count, bin_count, game_memory, bin_memory, custom_memory) =
+            if loading_state == LoadingState::Loading {
+                (0, 0, 0, 0, 0)
+            } else {
+                let (game_count, bin_count) = self.engine.counts().map_err(Status::internal)?;
+                let memory = |hashtable_type| {
+                    self.engine
+                        .table_memory_estimate(hashtable_type)
+                        .map_err(Status::internal)
+                };
+                (
+                    game_count,
+                    bin_count,
+                    memory(HashtableType::Game)?,
+                    memory(HashtableType::Bin)?,
+                    self.engine
+                        .custom_memory_estimate()
+                        .map_err(Status::internal)?,
+                )
+            };
+        let progress = self.engine.load_progress();
+        Ok(Response::new(GetStatusResponse {
+            loading_state: loading_state_to_proto(loading_state, !failed_files.is_empty()) as i32,
+            product: source.product.clone(),
+            branch: source.branch.clone(),
+            patch: latest_patch(&source_files).unwrap_or_default().to_string(),
+            files: source_files.into_iter().map(source_file_message).collect(),
+            progress: Some(hash_service::LoadProgress {
+                current_file: progress.current_file,
+                files_done: progress.files_done as u32,
+                file_count: progress.file_count as u32,
+                bytes_read: progress.bytes_read,
+                total_bytes: progress.total_bytes,
+                entries: progress.entries,
+            }),
+            game_count: game_count as u64,
+            bin_count: bin_count as u64,
+            last_error: self.engine.last_error().unwrap_or_default(),
+            memory_bytes: (game_memory + bin_memory + custom_memory) as u64,
+            game_memory_bytes: game_memory as u64,
+            bin_memory_bytes: bin_memory as u64,
+            allocator: alloc::stats().map(|stats| hash_service::AllocatorStats {
+                name: stats.name.to_string(),
+                resident_bytes: stats.resident_bytes,
+                committed_bytes: stats.committed_bytes,
+            }),
+            game_state: table_state(HashtableType::Game),
+            bin_state: table_state(HashtableType::Bin),
+            failed_files: failed_files.into_iter().map(failed_file_message).collect(),
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn get_table_info(
+        &self,
+        request: Request<GetTableInfoRequest>,
+    ) -> Result<Response<GetTableInfoResponse>, Status> {
+        self.record_call("GetTableInfo");
+        let req = request.into_inner();
+        info!(hashtable_type = %req.hashtable_type, "get_table_info called");
+
+        let (builtin, custom) = if req.hashtable_type.is_empty() {
+            let custom = self
+                .engine
+                .list_tables()
+                .map_err(Status::internal)?
+                .into_iter()
+                .map(|(name, _, _)| name)
+                .collect();
+            (HashtableType::ALL.to_vec(), custom)
         } else {
-            println!("File {} not found, downloading...", file_name);
+            match self.table_target(&req.hashtable_type)? {
+                Some(TableTarget::Builtin(hashtable_type)) => (vec![hashtable_type], Vec::new()),
+                Some(TableTarget::Custom(_)) => (Vec::new(), vec![req.hashtable_type]),
+                None => {
+                    return Err(error_status(
+                        ErrorCode::InvalidTable,
+                        format!("Table '{}' not found", req.hashtable_type),
+                    ));
+                }
+            }
+        };
+
+        let source_files = self.engine.source_files().map_err(Status::internal)?;
+        let mut tables = Vec::new();
+        for hashtable_type in builtin {
+            let state = self.engine.table_loading_state(hashtable_type);
+            // A running load holds the table lock, so counts would wait for it to finish
+            let (entries, custom_entries) = if state == LoadingState::Loading {
+                (0, 0)
+            } else {
+                let table = self.engine.read(hashtable_type).map_err(Status::internal)?;
+                (table.len(), table.custom_len())
+            };
+            let table_load = self
+                .engine
+                .table_load(hashtable_type)
+                .map_err(Status::internal)?;
+            let with_warnings = table_load
+                .as_ref()
+                .is_some_and(|table_load| !table_load.failed.is_empty());
+            tables.push(TableInfo {
+                hashtable_type: hashtable_type.as_str().to_string(),
+                state: loading_state_to_proto(state, with_warnings) as i32,
+                entries: entries as u64,
+                custom_entries: custom_entries as u64,
+                ..table_load
+                    .map(|table_load| table_load_info(table_load, &source_files))
+                    .unwrap_or_default()
+            });
+        }
+        for name in custom {
+            let entries = self
+                .engine
+                .with_table(&name, |table| table.len())
+                .map_err(Status::internal)?
+                .unwrap_or_default();
+            tables.push(TableInfo {
+                hashtable_type: name,
+                state: hash_service::LoadingState::Loaded as i32,
+                entries: entries as u64,
+                custom_entries: entries as u64,
+                ..TableInfo::default()
+            });
         }
 
-        let data = download_file(url)
-            .await
-            .map_err(|e| format!("Failed to download file: {}", e))?;
-        std::fs::write(&file_path, data).map_err(|e| format!("Failed to write file: {}", e))?;
+        Ok(Response::new(GetTableInfoResponse { tables }))
+    }
+
+    #[instrument(skip_all)]
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        self.record_call("Subscribe");
+        let req = request.into_inner();
+        let kinds: Vec<i32> = req.kinds.clone();
+        info!(kinds = ?req.kinds(), "subscribe called");
+
+        let mut events = self.engine.events();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    // Stop as soon as the client goes away, even if nothing changes
+                    _ = tx.closed() => break,
+                    event = events.recv() => match event {
+                        Ok(event) => event_message(event),
+                        Err(broadcast::error::RecvError::Lagged(missed)) => TableEvent {
+                            kind: TableEventKind::Lagged as i32,
+                            missed,
+                            ..TableEvent::default()
+                        },
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                };
+                let wanted = message.kind == TableEventKind::Lagged as i32
+                    || kinds.is_empty()
+                    || kinds.contains(&message.kind);
+                if wanted && tx.send(Ok(message)).await.is_err() {
+                    break;
+                }
+            }
+            debug!("Subscriber disconnected");
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Synced files a load or sync request selects, empty when it covers everything
+fn file_selection(
+    engine: &HashEngine,
+    files: &[String],
+    hashtable_types: &[String],
+) -> Result<Vec<String>, Status> {
+    let tables = hashtable_types
+        .iter()
+        .map(|name| HashtableType::parse(name))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| error_status(ErrorCode::InvalidTable, e))?;
+    engine
+        .select_synced_files(files, &tables)
+        .map_err(Status::not_found)
+}
+
+/// Loads (or with `resync` re-syncs) the tables, or refreshes only the synced `files` when
+/// there are any; a failed load is reported in the response
+async fn load_hashes(
+    engine: &HashEngine,
+    resync: bool,
+    files: &[String],
+) -> Result<LoadHashesResponse, Status> {
+    let result = if !files.is_empty() {
+        engine.refresh(files, resync).await
+    } else if resync {
+        engine.resync().await
+    } else {
+        engine.load().await.map(|()| (0, 0))
+    };
+    match result {
+        Ok((updated_files, added)) => {
+            let (game_count, bin_count) = engine.counts().map_err(Status::internal)?;
+            let failed_files = engine.failed_files().map_err(Status::internal)?;
+            let warnings = if failed_files.is_empty() {
+                String::new()
+            } else {
+                format!(", without {} files that failed", failed_files.len())
+            };
+            Ok(LoadHashesResponse {
+                success: true,
+                message: format!(
+                    "Hashtables loaded: {} game, {} bin hashes{}!",
+                    game_count, bin_count, warnings
+                ),
+                count: (game_count + bin_count) as i32,
+                updated_files: updated_files as u32,
+                added: added as u64,
+                error_code: ErrorCode::None as i32,
+                failed_files: failed_files.into_iter().map(failed_file_message).collect(),
+            })
+        }
+        Err(e) => Ok(LoadHashesResponse {
+            success: false,
+            error_code: load_error_code(&e) as i32,
+            message: format!("Failed to load hashtables: {}", e),
+            count: 0,
+            ..LoadHashesResponse::default()
+        }),
+    }
+}
 
-        let sha_path = file_path.with_file_name(format!("{}.sha", file_name));
-        std::fs::write(&sha_path, checksum)
-            .map_err(|e| format!("Failed to write SHA file: {}", e))?;
-        println!("Successfully synced {}", file_name);
+fn file_update(progress: FileProgress) -> LoadHashesUpdate {
+    LoadHashesUpdate {
+        update: Some(hash_service::load_hashes_update::Update::File(
+            hash_service::FileProgress {
+                file_name: progress.file_name,
+                hashtable_type: progress.hashtable_type.as_str().to_string(),
+                index: progress.index as u32,
+                file_count: progress.file_count as u32,
+                bytes_read: progress.bytes_read,
+                total_bytes: progress.total_bytes,
+                lines: progress.lines,
+                elapsed_ms: progress.elapsed.as_millis() as u64,
+                done: progress.done,
+            },
+        )),
     }
-    Ok(())
 }
 
-async fn get_git_data(url: &str) -> Result<Value, String> {
-    let response = http_get(url).await?;
+fn download_update(progress: DownloadProgress) -> SyncHashtablesUpdate {
+    SyncHashtablesUpdate {
+        update: Some(hash_service::sync_hashtables_update::Update::File(
+            hash_service::DownloadProgress {
+                bytes_per_second: progress.bytes_per_second(),
+                file_name: progress.file_name,
+                index: progress.index as u32,
+                file_count: progress.file_count as u32,
+                bytes_downloaded: progress.bytes_downloaded,
+                total_bytes: progress.total_bytes,
+                elapsed_ms: progress.elapsed.as_millis() as u64,
+                done: progress.done,
+                up_to_date: progress.up_to_date,
+                error: progress.error.unwrap_or_default(),
+            },
+        )),
+    }
+}
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let text = response.text().await.unwrap_or_default();
-        return Err(format!(
-            "GitHub API request failed with status {}: {}",
-            status, text
-        ));
+fn algorithm_from_proto(algorithm: hash_service::HashAlgorithm) -> HashAlgorithm {
+    match algorithm {
+        hash_service::HashAlgorithm::Xxh64 => HashAlgorithm::Xxh64,
+        hash_service::HashAlgorithm::Xxh3 => HashAlgorithm::Xxh3,
+        hash_service::HashAlgorithm::Fnv1a => HashAlgorithm::Fnv1a,
+        hash_service::HashAlgorithm::Elf => HashAlgorithm::Elf,
     }
+}
 
-    response.json().await.map_err(|e| e.to_string())
+fn algorithm_to_proto(algorithm: HashAlgorithm) -> hash_service::HashAlgorithm {
+    match algorithm {
+        HashAlgorithm::Xxh64 => hash_service::HashAlgorithm::Xxh64,
+        HashAlgorithm::Xxh3 => hash_service::HashAlgorithm::Xxh3,
+        HashAlgorithm::Fnv1a => hash_service::HashAlgorithm::Fnv1a,
+        HashAlgorithm::Elf => hash_service::HashAlgorithm::Elf,
+    }
 }
 
-async fn download_file(url: &str) -> Result<Vec<u8>, String> {
-    let response = http_get(url).await?;
+/// `with_warnings` when the loaded tables went without some files
+fn loading_state_to_proto(
+    loading_state: LoadingState,
+    with_warnings: bool,
+) -> hash_service::LoadingState {
+    match loading_state {
+        LoadingState::Unloaded => hash_service::LoadingState::Unloaded,
+        LoadingState::Loading => hash_service::LoadingState::Loading,
+        LoadingState::Loaded if with_warnings => hash_service::LoadingState::LoadedWithWarnings,
+        LoadingState::Loaded => hash_service::LoadingState::Loaded,
+    }
+}
 
-    if !response.status().is_success() {
-        return Err(format!("Failed to download file: {}", response.status()));
+fn failed_file_message(file: FailedFile) -> hash_service::FailedFile {
+    hash_service::FailedFile {
+        file_name: file.file_name,
+        hashtable_type: file.hashtable_type.as_str().to_string(),
+        stage: match file.stage {
+            FailureStage::Sync => hash_service::FailureStage::Sync,
+            FailureStage::Read => hash_service::FailureStage::Read,
+        } as i32,
+        error: file.error,
     }
+}
 
-    response
-        .bytes()
-        .await
-        .map_err(|e| e.to_string())
-        .map(|b| b.to_vec())
+fn origin_to_proto(origin: Option<EntryOrigin>) -> hash_service::EntryOrigin {
+    match origin {
+        Some(EntryOrigin::Official) => hash_service::EntryOrigin::Official,
+        Some(EntryOrigin::Custom) => hash_service::EntryOrigin::Custom,
+        None => hash_service::EntryOrigin::None,
+    }
 }
 
-async fn http_get(url: &str) -> Result<reqwest::Response, String> {
-    reqwest::Client::new()
-        .get(url)
-        .header("User-Agent", "Rust-Client")
-        .send()
-        .await
-        .map_err(|e| e.to_string())
+fn event_message(event: events::TableEvent) -> TableEvent {
+    match event {
+        events::TableEvent::Loaded {
+            game_count,
+            bin_count,
+        } => TableEvent {
+            kind: TableEventKind::Loaded as i32,
+            game_count: game_count as u64,
+            bin_count: bin_count as u64,
+            ..TableEvent::default()
+        },
+        events::TableEvent::Unloaded => TableEvent {
+            kind: TableEventKind::Unloaded as i32,
+            ..TableEvent::default()
+        },
+        events::TableEvent::Synced { updated_files } => TableEvent {
+            kind: TableEventKind::Synced as i32,
+            updated_files: updated_files as u32,
+            ..TableEvent::default()
+        },
+        events::TableEvent::Added {
+            hashtable_type,
+            hash,
+            value,
+        } => TableEvent {
+            kind: TableEventKind::Added as i32,
+            hashtable_type: hashtable_type.as_str().to_string(),
+            hash,
+            value,
+            ..TableEvent::default()
+        },
+        events::TableEvent::Cleared {
+            hashtable_type,
+            custom_only,
+        } => TableEvent {
+            kind: TableEventKind::Cleared as i32,
+            hashtable_type: hashtable_type.as_str().to_string(),
+            custom_only,
+            ..TableEvent::default()
+        },
+        events::TableEvent::Restored { snapshot } => TableEvent {
+            kind: TableEventKind::Restored as i32,
+            snapshot,
+            ..TableEvent::default()
+        },
+    }
+}
+
+fn local_source_message(source: &local::LocalSource) -> LocalSource {
+    LocalSource {
+        path: source.path.display().to_string(),
+        directory: source.kind == LocalSourceKind::Directory,
+        hashtable_type: source
+            .hashtable_type
+            .map(|hashtable_type| hashtable_type.as_str().to_string())
+            .unwrap_or_default(),
+        enabled: source.enabled,
+        from_config: source.from_config,
+        available: source.is_available(),
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Files and timing of a table's last load, with the upstream commit of the synced files
+fn table_load_info(table_load: TableLoad, source_files: &[SourceFile]) -> TableInfo {
+    let files = table_load
+        .files
+        .into_iter()
+        .map(|file| {
+            let blob_sha = file.sha.unwrap_or_default();
+            let commit_sha = source_files
+                .iter()
+                .find(|source| {
+                    !blob_sha.is_empty()
+                        && source.file_name == file.file_name
+                        && source.blob_sha == blob_sha
+                })
+                .map(|source| source.commit_sha.clone())
+                .unwrap_or_default();
+            LoadedFile {
+                path: file.path.display().to_string(),
+                file_name: file.file_name,
+                blob_sha,
+                commit_sha,
+                modified: file.modified.map(unix_secs).unwrap_or_default(),
+                size: file.size,
+                entries: file.entries,
+            }
+        })
+        .collect();
+    TableInfo {
+        files,
+        failed_files: table_load
+            .failed
+            .into_iter()
+            .map(failed_file_message)
+            .collect(),
+        load_duration_ms: table_load.duration.as_millis() as u64,
+        loaded_at: unix_secs(table_load.finished_at),
+        ..TableInfo::default()
+    }
+}
+
+fn source_file_message(file: SourceFile) -> hash_service::SourceFile {
+    hash_service::SourceFile {
+        file_name: file.file_name,
+        blob_sha: file.blob_sha,
+        commit_sha: file.commit_sha,
+        commit_date: file.commit_date,
+        patch: file.patch.unwrap_or_default(),
+        url: file.url.unwrap_or_default(),
+    }
+}
+
+fn snapshot_message(snapshot: SnapshotInfo) -> Snapshot {
+    Snapshot {
+        name: snapshot.name,
+        created_at: snapshot.created_at,
+        game_count: snapshot.game_count,
+        bin_count: snapshot.bin_count,
+        size_bytes: snapshot.size_bytes,
+    }
+}
+
+impl ServiceHashLoader {
+    /// Checks and registers a local source for AddSourceDirectory and AddSourceFile
+    fn add_local_source(&self, source: local::LocalSource) -> Result<LocalSource, Status> {
+        let sources = self.engine.local_sources().map_err(Status::internal)?;
+        if sources.iter().any(|existing| existing.path == source.path) {
+            return Err(Status::already_exists(format!(
+                "Source {:?} is already registered",
+                source.path
+            )));
+        }
+        source.validate().map_err(Status::invalid_argument)?;
+        let message = local_source_message(&source);
+        self.engine
+            .add_local_source(source)
+            .map_err(Status::internal)?;
+        Ok(message)
+    }
+
+    /// A file in the cache with its provenance, and whether its table holds this copy of it
+    fn cached_file_message(
+        &self,
+        file: CachedFile,
+        source_files: &[SourceFile],
+    ) -> Result<hash_service::CachedFile, Status> {
+        let load_state =
+            if self.engine.table_loading_state(file.hashtable_type) != LoadingState::Loaded {
+                hash_service::FileLoadState::NotLoaded
+            } else {
+                let table_load = self
+                    .engine
+                    .table_load(file.hashtable_type)
+                    .map_err(Status::internal)?;
+                let read = table_load.is_some_and(|table_load| {
+                    table_load.files.iter().any(|loaded| {
+                        loaded.path == file.path
+                            && loaded.sha == file.sha
+                            && loaded.modified == file.modified
+                    })
+                });
+                if read {
+                    hash_service::FileLoadState::Loaded
+                } else {
+                    hash_service::FileLoadState::Stale
+                }
+            };
+        let blob_sha = file.sha.unwrap_or_default();
+        let upstream = source_files
+            .iter()
+            .find(|source| source.file_name == file.file_name && source.blob_sha == blob_sha)
+            .cloned()
+            .map(source_file_message);
+        Ok(hash_service::CachedFile {
+            file_name: file.file_name,
+            hashtable_type: file.hashtable_type.as_str().to_string(),
+            size: file.size,
+            blob_sha,
+            synced_at: file.modified.map(unix_secs).unwrap_or_default(),
+            load_state: load_state as i32,
+            upstream,
+        })
+    }
+
+    /// Resolves a `hashtable_type` to a built-in or custom table, `None` if it names neither
+    fn table_target(&self, name: &str) -> Result<Option<TableTarget>, Status> {
+        if let Ok(hashtable_type) = HashtableType::parse(name) {
+            return Ok(Some(TableTarget::Builtin(hashtable_type)));
+        }
+        Ok(self
+            .engine
+            .table_algorithm(name)
+            .map_err(Status::internal)?
+            .map(TableTarget::Custom))
+    }
+
+    /// Rejects invalid names and names no snapshot exists for
+    fn require_snapshot(&self, name: &str) -> Result<(), Status> {
+        validate_name(name).map_err(Status::invalid_argument)?;
+        let snapshots = self.engine.list_snapshots().map_err(Status::internal)?;
+        if snapshots.iter().any(|snapshot| snapshot.name == name) {
+            Ok(())
+        } else {
+            Err(Status::not_found(format!("Snapshot '{}' not found", name)))
+        }
+    }
+
+    pub fn new(engine: HashEngine) -> Self {
+        ServiceHashLoader {
+            engine,
+            statistics: Arc::new(Mutex::new(Statistics::default())),
+            unknown_hashes: Arc::new(Mutex::new(UnknownHashes::default())),
+            wordlist_dir: None,
+            cache_manager: None,
+        }
+    }
+
+    /// Shares statistics, the unknown hash store and wordlists, but serves `engine`'s tables
+    pub fn with_engine(&self, engine: HashEngine) -> Self {
+        ServiceHashLoader {
+            engine,
+            ..self.clone()
+        }
+    }
+
+    pub fn with_wordlist_dir(mut self, wordlist_dir: Option<PathBuf>) -> Self {
+        self.wordlist_dir = wordlist_dir;
+        self
+    }
+
+    pub fn with_cache_manager(mut self, cache_manager: Arc<CacheManager>) -> Self {
+        self.cache_manager = Some(cache_manager);
+        self
+    }
+
+    /// Records missed lookups into `unknown_hashes`, saving it every minute
+    pub fn with_unknown_hashes(mut self, unknown_hashes: UnknownHashes) -> Self {
+        self.unknown_hashes = Arc::new(Mutex::new(unknown_hashes));
+        let loader = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(UNKNOWN_HASHES_SAVE_INTERVAL);
+            loop {
+                interval.tick().await;
+                loader.save_unknown_hashes();
+            }
+        });
+        self
+    }
+
+    pub fn save_unknown_hashes(&self) {
+        let result = self
+            .unknown_hashes
+            .lock()
+            .map_err(|_| "Failed to lock unknown hashes".to_string())
+            .and_then(|mut unknown_hashes| unknown_hashes.save());
+        if let Err(e) = result {
+            error!("Failed to save unknown hashes: {}", e);
+        }
+    }
+
+    /// Publishes whether the hashtables are loaded under [`TABLES_HEALTH_SERVICE`]
+    pub fn with_health_reporter(self, health_reporter: HealthReporter) -> Self {
+        let mut loading_state = self.engine.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let status = match *loading_state.borrow_and_update() {
+                    LoadingState::Loaded => ServingStatus::Serving,
+                    LoadingState::Loading | LoadingState::Unloaded => ServingStatus::NotServing,
+                };
+                health_reporter
+                    .set_service_status(TABLES_HEALTH_SERVICE, status)
+                    .await;
+                if loading_state.changed().await.is_err() {
+                    break;
+                }
+            }
+        });
+        self
+    }
+
+    #[cfg(feature = "http-gateway")]
+    pub fn is_loaded(&self) -> bool {
+        self.engine.is_loaded()
+    }
+
+    /// Syncs and loads the hashtables ahead of the first request
+    pub async fn preload(&self) {
+        info!("Preloading hashtables");
+        let result = if self.engine.uses_lookup_store() {
+            // Only the lookup store needs to be ready, the tables load when a scan needs them
+            match self.engine.ensure_lookups_ready(HashtableType::Game).await {
+                Ok(()) => self.engine.ensure_lookups_ready(HashtableType::Bin).await,
+                Err(e) => Err(e),
+            }
+        } else {
+            self.engine.ensure_loaded().await
+        };
+        if let Err(e) = result {
+            error!("Failed to preload hashtables: {}", e);
+        }
+    }
+
+    async fn ensure_loaded_status(&self) -> Result<(), Status> {
+        self.engine.ensure_loaded().await.map_err(load_error_status)
+    }
+
+    /// Makes point lookups in one table answerable, from the database when there is one
+    async fn ensure_lookups_ready_status(
+        &self,
+        hashtable_type: HashtableType,
+    ) -> Result<(), Status> {
+        self.engine
+            .ensure_lookups_ready(hashtable_type)
+            .await
+            .map_err(load_error_status)
+    }
+
+    /// Like `ensure_loaded_status`, but leaves the other table unloaded
+    async fn ensure_table_loaded_status(
+        &self,
+        hashtable_type: HashtableType,
+    ) -> Result<(), Status> {
+        self.engine
+            .ensure_table_loaded(hashtable_type)
+            .await
+            .map_err(load_error_status)
+    }
+
+    fn record_call(&self, method: &'static str) {
+        if let Ok(mut statistics) = self.statistics.lock() {
+            statistics.record_call(method);
+        }
+    }
+
+    fn record_lookup(&self, hashtable_type: &'static str, hash: u64, found: bool) {
+        if let Ok(mut statistics) = self.statistics.lock() {
+            if found {
+                statistics.record_hit();
+            } else {
+                statistics.record_miss(hashtable_type, hash);
+            }
+        }
+        if !found && let Ok(mut unknown_hashes) = self.unknown_hashes.lock() {
+            unknown_hashes.record(hashtable_type, hash);
+        }
+    }
+}
+
+fn conflict_policy_from_proto(policy: hash_service::ConflictPolicy) -> ConflictPolicy {
+    match policy {
+        hash_service::ConflictPolicy::KeepLast => ConflictPolicy::KeepLast,
+        hash_service::ConflictPolicy::KeepFirst => ConflictPolicy::KeepFirst,
+        hash_service::ConflictPolicy::PreferOfficial => ConflictPolicy::PreferOfficial,
+    }
+}
+
+fn conflict_policy_to_proto(policy: ConflictPolicy) -> hash_service::ConflictPolicy {
+    match policy {
+        ConflictPolicy::KeepLast => hash_service::ConflictPolicy::KeepLast,
+        ConflictPolicy::KeepFirst => hash_service::ConflictPolicy::KeepFirst,
+        ConflictPolicy::PreferOfficial => hash_service::ConflictPolicy::PreferOfficial,
+    }
 }
@@ -1,3 +1,12 @@
+mod cancel;
+mod error;
 mod hashtable;
+mod stats;
+mod unknown;
+mod v2;
+#[cfg(feature = "http-gateway")]
+pub use error::ERROR_CODE_HEADER;
 pub use hashtable::hash_service;
 pub use hashtable::*;
+pub use unknown::UnknownHashes;
+pub use v2::HashLoaderV2;
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+/// Upper bound on distinct missed hashes tracked; once reached only known ones are counted.
+const MAX_TRACKED_MISSES: usize = 100_000;
+pub const DEFAULT_TOP_MISSED: usize = 20;
+
+#[derive(Debug, Default)]
+pub struct Statistics {
+    pub hits: u64,
+    pub misses: u64,
+    missed: HashMap<(&'static str, u64), u64>,
+    pub rpc_calls: HashMap<&'static str, u64>,
+}
+
+impl Statistics {
+    pub fn record_call(&mut self, method: &'static str) {
+        *self.rpc_calls.entry(method).or_default() += 1;
+    }
+
+    pub fn record_hit(&mut self) {
+        self.hits += 1;
+    }
+
+    pub fn record_miss(&mut self, hashtable_type: &'static str, hash: u64) {
+        self.misses += 1;
+        if let Some(count) = self.missed.get_mut(&(hashtable_type, hash)) {
+            *count += 1;
+        } else if self.missed.len() < MAX_TRACKED_MISSES {
+            self.missed.insert((hashtable_type, hash), 1);
+        }
+    }
+
+    /// Most frequently missed `(table, hash, count)` triples, highest count first
+    pub fn most_missed(&self, limit: usize) -> Vec<(&'static str, u64, u64)> {
+        let mut missed: Vec<_> = self
+            .missed
+            .iter()
+            .map(|((hashtable_type, hash), count)| (*hashtable_type, *hash, *count))
+            .collect();
+        missed.sort_unstable_by(|a, b| b.2.cmp(&a.2).then(a.1.cmp(&b.1)));
+        missed.truncate(limit);
+        missed
+    }
+}
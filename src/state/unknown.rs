@@ -0,0 +1,144 @@
+//! Persistent record of hashes lookups failed to resolve, the community's list of hashes
+//! worth cracking. Kept in memory and flushed to a JSON file in the project data dir.
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths::project_dirs;
+
+const UNKNOWN_HASHES_FILE_NAME: &str = "unknown_hashes.json";
+/// Upper bound on distinct hashes stored; once reached only known ones are counted
+const MAX_UNKNOWN_HASHES: usize = 1_000_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnknownHash {
+    pub hash: u64,
+    /// "game", "bin" or "any" for lookups across tables
+    pub hashtable_type: String,
+    pub count: u64,
+    /// Unix seconds
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UnknownHashFile {
+    hashes: Vec<UnknownHash>,
+}
+
+#[derive(Debug, Default)]
+pub struct UnknownHashes {
+    entries: HashMap<(String, u64), UnknownHash>,
+    path: Option<PathBuf>,
+    /// Changed since the last save
+    dirty: bool,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+impl UnknownHashes {
+    pub fn default_path() -> Option<PathBuf> {
+        project_dirs().map(|dirs| dirs.data_dir().join(UNKNOWN_HASHES_FILE_NAME))
+    }
+
+    /// Loads the store at `path`; a missing file starts an empty one
+    pub fn open(path: PathBuf) -> Result<UnknownHashes, String> {
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str::<UnknownHashFile>(&text)
+                .map_err(|e| format!("Failed to parse {:?}: {}", path, e))?
+                .hashes
+                .into_iter()
+                .map(|entry| ((entry.hashtable_type.clone(), entry.hash), entry))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+        };
+        Ok(UnknownHashes {
+            entries,
+            path: Some(path),
+            dirty: false,
+        })
+    }
+
+    pub fn record(&mut self, hashtable_type: &str, hash: u64) {
+        let now = now();
+        if let Some(entry) = self.entries.get_mut(&(hashtable_type.to_string(), hash)) {
+            entry.count += 1;
+            entry.last_seen = now;
+        } else if self.entries.len() < MAX_UNKNOWN_HASHES {
+            self.entries.insert(
+                (hashtable_type.to_string(), hash),
+                UnknownHash {
+                    hash,
+                    hashtable_type: hashtable_type.to_string(),
+                    count: 1,
+                    first_seen: now,
+                    last_seen: now,
+                },
+            );
+        } else {
+            return;
+        }
+        self.dirty = true;
+    }
+
+    /// Hashes missed in a table, including ones missed in lookups across tables
+    pub fn hashes(&self, hashtable_type: &str) -> HashSet<u64> {
+        self.entries
+            .values()
+            .filter(|entry| entry.hashtable_type == hashtable_type || entry.hashtable_type == "any")
+            .map(|entry| entry.hash)
+            .collect()
+    }
+
+    /// Every entry of a table ("" for all), most frequently missed first
+    pub fn list(&self, hashtable_type: &str) -> Vec<&UnknownHash> {
+        let mut entries: Vec<&UnknownHash> = self
+            .entries
+            .values()
+            .filter(|entry| hashtable_type.is_empty() || entry.hashtable_type == hashtable_type)
+            .collect();
+        entries.sort_unstable_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.hashtable_type.cmp(&b.hashtable_type))
+                .then(a.hash.cmp(&b.hash))
+        });
+        entries
+    }
+
+    /// Writes the store if anything changed since the last save
+    pub fn save(&mut self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+        let file = UnknownHashFile {
+            hashes: self.entries.values().cloned().collect(),
+        };
+        write_atomic(path, &file)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+fn write_atomic(path: &Path, file: &UnknownHashFile) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    let text = serde_json::to_string(file)
+        .map_err(|e| format!("Failed to serialize unknown hashes: {}", e))?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, text)
+        .map_err(|e| format!("Failed to write {:?}: {}", tmp_path, e))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
@@ -0,0 +1,358 @@
+//! `hashservice.v2.HashLoader`: the typed API, served next to v1. Each call runs the v1
+//! handlers, so both versions share the engine, statistics and behaviour; this only converts
+//! between the two schemas and turns v1's `success = false` responses into error statuses.
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Extensions, Request, Response, Status};
+use tracing::{info, instrument};
+
+use hash_service_core::search::STREAM_CHUNK_SIZE;
+
+use super::ServiceHashLoader;
+use super::error::{error_status, load_error_code};
+use super::hash_service::hash_loader_server::HashLoader as HashLoaderV1;
+use super::hash_service::v2::hash_loader_server::HashLoader;
+use super::hash_service::v2::{
+    AddRequest, AddResponse, AddResult, FileProgress, GetStatusRequest, GetStatusResponse,
+    LoadRequest, LoadResult, LoadUpdate, LookupRequest, LookupResponse, SearchRequest,
+    SearchResponse, Table, TableRef, TableStatus, UnloadRequest, UnloadResponse, load_update,
+    search_request, table_ref,
+};
+use super::hash_service::{
+    self as v1, AddHashRequest, ErrorCode, GetStringsRequest, LoadHashesRequest, SearchEntry,
+    SearchGlobRequest, SearchPrefixRequest, SearchRegexRequest, UnloadHashesRequest,
+    load_hashes_update,
+};
+
+#[derive(Clone)]
+pub struct HashLoaderV2 {
+    loader: ServiceHashLoader,
+}
+
+impl HashLoaderV2 {
+    pub fn new(loader: ServiceHashLoader) -> Self {
+        HashLoaderV2 { loader }
+    }
+}
+
+/// `message` as a v1 request, keeping the metadata of `request` such as its deadline
+fn forward<T, U>(request: &Request<T>, message: U) -> Request<U> {
+    Request::from_parts(request.metadata().clone(), Extensions::new(), message)
+}
+
+/// v1 name of a built-in table
+fn builtin_name(table: i32) -> Result<&'static str, Status> {
+    match Table::try_from(table) {
+        Ok(Table::Game) => Ok("game"),
+        Ok(Table::Bin) => Ok("bin"),
+        _ => Err(error_status(ErrorCode::InvalidTable, "No table given")),
+    }
+}
+
+fn table_from_name(name: &str) -> Table {
+    match name {
+        "game" => Table::Game,
+        "bin" => Table::Bin,
+        _ => Table::Unspecified,
+    }
+}
+
+/// v1 name of a built-in or custom table
+fn table_name(table: Option<TableRef>) -> Result<String, Status> {
+    match table.and_then(|table| table.table) {
+        Some(table_ref::Table::Builtin(table)) => builtin_name(table).map(str::to_string),
+        Some(table_ref::Table::Custom(name)) => Ok(name),
+        None => Err(error_status(ErrorCode::InvalidTable, "No table given")),
+    }
+}
+
+/// Error status of a v1 response with `success = false`
+fn failure(error_code: i32, message: String) -> Status {
+    match ErrorCode::try_from(error_code) {
+        Ok(code) if code != ErrorCode::None => error_status(code, message),
+        _ => Status::internal(message),
+    }
+}
+
+fn file_progress(progress: v1::FileProgress) -> FileProgress {
+    FileProgress {
+        file_name: progress.file_name,
+        table: table_from_name(&progress.hashtable_type) as i32,
+        index: progress.index,
+        file_count: progress.file_count,
+        bytes_read: progress.bytes_read,
+        total_bytes: progress.total_bytes,
+        lines: progress.lines,
+        elapsed_ms: progress.elapsed_ms,
+        done: progress.done,
+    }
+}
+
+#[tonic::async_trait]
+impl HashLoader for HashLoaderV2 {
+    type SearchStream = ReceiverStream<Result<SearchResponse, Status>>;
+    type LoadStream = ReceiverStream<Result<LoadUpdate, Status>>;
+
+    #[instrument(skip_all)]
+    async fn lookup(
+        &self,
+        request: Request<LookupRequest>,
+    ) -> Result<Response<LookupResponse>, Status> {
+        let hashtable_type = table_name(request.get_ref().table.clone())?;
+        let message = GetStringsRequest {
+            hashes: request.get_ref().hashes.clone(),
+            hashtable_type,
+        };
+        let response = self
+            .loader
+            .get_strings(forward(&request, message))
+            .await?
+            .into_inner();
+        Ok(Response::new(LookupResponse {
+            results: response.results,
+        }))
+    }
+
+    #[instrument(skip_all)]
+    async fn add(&self, request: Request<AddRequest>) -> Result<Response<AddResponse>, Status> {
+        let req = request.get_ref();
+        let hashtable_type = table_name(req.table.clone())?;
+        info!(%hashtable_type, entries = req.entries.len(), "add called");
+
+        let mut results = Vec::with_capacity(req.entries.len());
+        for entry in &req.entries {
+            let message = AddHashRequest {
+                string: entry.value.clone(),
+                hashtable_type: hashtable_type.clone(),
+                hash: entry.hash,
+                validate: req.validate,
+                on_conflict: req.on_conflict,
+            };
+            let response = self
+                .loader
+                .add_hash(forward(&request, message))
+                .await?
+                .into_inner();
+            if !response.success {
+                return Err(failure(response.error_code, response.message));
+            }
+            results.push(AddResult {
+                hash: response.hash,
+                verified: response.verified,
+                existed: response.existed,
+                existing_value: response.existing_value,
+                replaced: response.replaced,
+            });
+        }
+        Ok(Response::new(AddResponse { results }))
+    }
+
+    #[instrument(skip_all)]
+    async fn search(
+        &self,
+        request: Request<SearchRequest>,
+    ) -> Result<Response<Self::SearchStream>, Status> {
+        let req = request.get_ref().clone();
+        let hashtable_type = builtin_name(req.table)?.to_string();
+        let (entries, next_page_token, truncated, timed_out) = match req.pattern {
+            Some(search_request::Pattern::Prefix(prefix)) => {
+                let message = SearchPrefixRequest {
+                    prefix,
+                    hashtable_type,
+                    limit: req.limit,
+                    page_token: req.page_token,
+                };
+                let response = self
+                    .loader
+                    .search_prefix(forward(&request, message))
+                    .await?
+                    .into_inner();
+                (response.entries, response.next_page_token, false, false)
+            }
+            Some(search_request::Pattern::Regex(pattern)) => {
+                let message = SearchRegexRequest {
+                    pattern,
+                    hashtable_type,
+                    limit: req.limit,
+                    case_insensitive: req.case_insensitive,
+                    timeout_ms: 0,
+                };
+                let response = self
+                    .loader
+                    .search_regex(forward(&request, message))
+                    .await?
+                    .into_inner();
+                (
+                    response.entries,
+                    String::new(),
+                    response.truncated,
+                    response.timed_out,
+                )
+            }
+            Some(search_request::Pattern::Glob(pattern)) => {
+                let message = SearchGlobRequest {
+                    pattern,
+                    hashtable_type,
+                    limit: req.limit,
+                    page_token: req.page_token,
+                    case_insensitive: req.case_insensitive,
+                };
+                let mut chunks = self
+                    .loader
+                    .search_glob(forward(&request, message))
+                    .await?
+                    .into_inner();
+                let mut entries = Vec::new();
+                let mut next_page_token = String::new();
+                while let Some(chunk) = chunks.next().await {
+                    let chunk = chunk?;
+                    entries.extend(chunk.entries);
+                    next_page_token = chunk.next_page_token;
+                }
+                (entries, next_page_token, false, false)
+            }
+            None => return Err(Status::invalid_argument("No pattern given")),
+        };
+
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let mut entries = entries.into_iter().peekable();
+            loop {
+                let chunk: Vec<SearchEntry> = entries.by_ref().take(STREAM_CHUNK_SIZE).collect();
+                let is_last = entries.peek().is_none();
+                let response = if is_last {
+                    SearchResponse {
+                        entries: chunk,
+                        next_page_token: next_page_token.clone(),
+                        truncated,
+                        timed_out,
+                    }
+                } else {
+                    SearchResponse {
+                        entries: chunk,
+                        ..SearchResponse::default()
+                    }
+                };
+                if tx.send(Ok(response)).await.is_err() || is_last {
+                    // Client went away or everything was sent
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    #[instrument(skip_all)]
+    async fn load(
+        &self,
+        request: Request<LoadRequest>,
+    ) -> Result<Response<Self::LoadStream>, Status> {
+        let req = request.get_ref();
+        let hashtable_types = req
+            .tables
+            .iter()
+            .map(|&table| builtin_name(table).map(str::to_string))
+            .collect::<Result<_, _>>()?;
+        let message = LoadHashesRequest {
+            resync: req.resync,
+            files: req.files.clone(),
+            hashtable_types,
+        };
+        let mut updates = self
+            .loader
+            .load_hashes_stream(forward(&request, message))
+            .await?
+            .into_inner();
+        let loader = self.loader.clone();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            while let Some(update) = updates.next().await {
+                let update = match update.map(|update| update.update) {
+                    Ok(Some(load_hashes_update::Update::File(progress))) => {
+                        Ok(load_update::Update::File(file_progress(progress)))
+                    }
+                    Ok(Some(load_hashes_update::Update::Result(result))) if result.success => {
+                        HashLoaderV1::get_status(&loader, Request::new(v1::GetStatusRequest {}))
+                            .await
+                            .map(|status| {
+                                let status = status.into_inner();
+                                load_update::Update::Result(LoadResult {
+                                    game_count: status.game_count,
+                                    bin_count: status.bin_count,
+                                    updated_files: result.updated_files,
+                                    added: result.added,
+                                    failed_files: result.failed_files,
+                                })
+                            })
+                    }
+                    Ok(Some(load_hashes_update::Update::Result(result))) => {
+                        Err(failure(result.error_code, result.message))
+                    }
+                    Ok(None) => continue,
+                    Err(status) => Err(status),
+                };
+                let update = update.map(|update| LoadUpdate {
+                    update: Some(update),
+                });
+                if tx.send(update).await.is_err() {
+                    // Dropping the v1 stream abandons the load, as it does for v1 clients
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    #[instrument(skip_all)]
+    async fn unload(
+        &self,
+        request: Request<UnloadRequest>,
+    ) -> Result<Response<UnloadResponse>, Status> {
+        self.loader
+            .unload_hashes(forward(&request, UnloadHashesRequest {}))
+            .await?;
+        Ok(Response::new(UnloadResponse {}))
+    }
+
+    #[instrument(skip_all)]
+    async fn get_status(
+        &self,
+        request: Request<GetStatusRequest>,
+    ) -> Result<Response<GetStatusResponse>, Status> {
+        let status =
+            HashLoaderV1::get_status(&self.loader, forward(&request, v1::GetStatusRequest {}))
+                .await?
+                .into_inner();
+        let last_error_code = if status.last_error.is_empty() {
+            ErrorCode::None
+        } else {
+            load_error_code(&status.last_error)
+        };
+        Ok(Response::new(GetStatusResponse {
+            state: status.loading_state,
+            product: status.product,
+            branch: status.branch,
+            patch: status.patch,
+            tables: vec![
+                TableStatus {
+                    table: Table::Game as i32,
+                    state: status.game_state,
+                    entries: status.game_count,
+                    memory_bytes: status.game_memory_bytes,
+                },
+                TableStatus {
+                    table: Table::Bin as i32,
+                    state: status.bin_state,
+                    entries: status.bin_count,
+                    memory_bytes: status.bin_memory_bytes,
+                },
+            ],
+            last_error_code: last_error_code as i32,
+            last_error: status.last_error,
+            failed_files: status.failed_files,
+        }))
+    }
+}
@@ -0,0 +1,153 @@
+//! Logging and tracing setup. Logs go to stdout, as text or JSON, and optionally to a daily
+//! rotated file under the project data dir, filtered by the configured log level.
+//!
+//! With the `otel` feature enabled and `OTEL_EXPORTER_OTLP_ENDPOINT` set, spans are
+//! additionally exported over OTLP/gRPC.
+use tracing::error;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
+
+#[cfg(feature = "otel")]
+use opentelemetry::trace::TracerProvider as _;
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::trace::SdkTracerProvider;
+
+use crate::config::LogFormat;
+use crate::paths::project_dirs;
+
+#[cfg(feature = "otel")]
+const SERVICE_NAME: &str = "ltk-hash-service";
+const LOG_FILE_PREFIX: &str = "ltk-hash-service";
+const MAX_LOG_FILES: usize = 7;
+
+/// Flushes pending log lines and spans when dropped
+pub struct TelemetryGuard {
+    _file_guard: Option<WorkerGuard>,
+    #[cfg(feature = "otel")]
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            error!("Failed to shut down tracer provider: {:?}", e);
+        }
+    }
+}
+
+pub fn init(log_level: &str, log_format: LogFormat, log_file: bool) -> TelemetryGuard {
+    let appender = if log_file {
+        file_appender().map(Some)
+    } else {
+        Ok(None)
+    };
+    let (file_layer, file_guard, file_error) = match appender {
+        Ok(Some(appender)) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer);
+            (Some(layer), Some(guard), None)
+        }
+        Ok(None) => (None, None, None),
+        Err(e) => (None, None, Some(e)),
+    };
+
+    let (text_layer, json_layer) = match log_format {
+        LogFormat::Text => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => (None, Some(tracing_subscriber::fmt::layer().json())),
+    };
+
+    let (filter, filter_error) = match EnvFilter::try_new(log_level) {
+        Ok(filter) => (filter, None),
+        Err(e) => (EnvFilter::new("info"), Some(e)),
+    };
+
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(text_layer)
+        .with(json_layer)
+        .with(file_layer);
+
+    #[cfg(feature = "otel")]
+    let guard = {
+        let (provider, otel_error) = match otlp_provider() {
+            Ok(provider) => (provider, None),
+            Err(e) => (None, Some(e)),
+        };
+        let layer = provider
+            .as_ref()
+            .map(|p| tracing_opentelemetry::layer().with_tracer(p.tracer(SERVICE_NAME)));
+        registry.with(layer).init();
+
+        if let Some(e) = otel_error {
+            error!("Failed to create OTLP exporter: {}", e);
+        }
+        TelemetryGuard {
+            _file_guard: file_guard,
+            provider,
+        }
+    };
+
+    #[cfg(not(feature = "otel"))]
+    let guard = {
+        registry.init();
+        TelemetryGuard {
+            _file_guard: file_guard,
+        }
+    };
+
+    if let Some(e) = filter_error {
+        error!("Invalid log level '{}', using info: {}", log_level, e);
+    }
+    if let Some(e) = file_error {
+        error!("File logging disabled: {}", e);
+    }
+    guard
+}
+
+fn file_appender() -> Result<RollingFileAppender, String> {
+    let log_dir = project_dirs()
+        .ok_or_else(|| "Failed to get project directories".to_string())?
+        .data_dir()
+        .join("logs");
+    // The appender prunes old files before creating the directory itself
+    std::fs::create_dir_all(&log_dir)
+        .map_err(|e| format!("Failed to create log directory {:?}: {}", log_dir, e))?;
+
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(&log_dir)
+        .map_err(|e| format!("Failed to create log file in {:?}: {}", log_dir, e))
+}
+
+#[cfg(feature = "otel")]
+fn otlp_provider() -> Result<Option<SdkTracerProvider>, String> {
+    // The exporter reads the endpoint, headers and timeout from the standard OTEL_* variables
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() {
+        return Ok(None);
+    }
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(
+        SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                opentelemetry_sdk::Resource::builder()
+                    .with_service_name(SERVICE_NAME)
+                    .build(),
+            )
+            .build(),
+    ))
+}
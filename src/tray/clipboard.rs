@@ -0,0 +1,86 @@
+//! Looks up the hash on the clipboard, the quickest way to resolve one hash without a client.
+use arboard::Clipboard;
+use tracing::{error, info};
+
+use crate::client::ClientSettings;
+use crate::hash_service::GetStringRequest;
+
+use super::notify::notify;
+
+/// Tables tried in order; game hashes are far more common on the clipboard
+const TABLES: [&str; 2] = ["game", "bin"];
+
+/// Reads a hash as `0x`-prefixed hex, decimal or bare hex. Digits alone count as decimal
+/// unless there are 8 or 16 of them, the width hashes are printed in.
+pub fn parse_hash(text: &str) -> Option<u64> {
+    let text = text.trim().trim_matches(|c| c == '"' || c == '\'');
+    if let Some(digits) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return u64::from_str_radix(digits, 16).ok();
+    }
+    let decimal = text.bytes().all(|b| b.is_ascii_digit());
+    if decimal && text.len() != 8 && text.len() != 16 {
+        text.parse().ok()
+    } else {
+        u64::from_str_radix(text, 16).ok()
+    }
+}
+
+/// Resolves the clipboard's hash, shows the result and copies the path back
+pub async fn lookup_clipboard(client: ClientSettings) {
+    let text = match Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+        Ok(text) => text,
+        Err(e) => {
+            notify(
+                "Hash lookup",
+                &format!("Failed to read the clipboard: {}", e),
+            );
+            return;
+        }
+    };
+    let Some(hash) = parse_hash(&text) else {
+        notify("Hash lookup", "The clipboard does not hold a hash");
+        return;
+    };
+    match lookup(&client, hash).await {
+        Ok(Some((table, value))) => {
+            info!(
+                hash = format_args!("{:016x}", hash),
+                table, "Resolved clipboard hash"
+            );
+            if let Err(e) = Clipboard::new().and_then(|mut clipboard| clipboard.set_text(&value)) {
+                error!("Failed to copy to the clipboard: {}", e);
+            }
+            notify(&format!("{:x} ({})", hash, table), &value);
+        }
+        Ok(None) => notify("Hash lookup", &format!("{:x} is not a known hash", hash)),
+        Err(e) => {
+            error!("Failed to look up clipboard hash: {}", e);
+            notify("Hash lookup failed", &e);
+        }
+    }
+}
+
+/// The first table that knows `hash`, and its value
+async fn lookup(
+    client: &ClientSettings,
+    hash: u64,
+) -> Result<Option<(&'static str, String)>, String> {
+    let mut client = client
+        .connect()
+        .await
+        .map_err(|e| format!("Failed to connect to gRPC server: {}", e))?;
+    for table in TABLES {
+        let response = client
+            .get_string(GetStringRequest {
+                hash,
+                hashtable_type: table.to_string(),
+            })
+            .await
+            .map_err(|e| e.message().to_string())?
+            .into_inner();
+        if response.found {
+            return Ok(Some((table, response.value)));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,76 @@
+//! Global hotkeys for quick search and clipboard lookup, reachable without the tray menu.
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use tracing::info;
+
+use super::notify::notify_error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    Search,
+    LookupClipboard,
+}
+
+/// Registered hotkeys; they are released when this is dropped
+pub struct Hotkeys {
+    manager: GlobalHotKeyManager,
+    actions: Vec<(HotKey, HotkeyAction)>,
+}
+
+impl Hotkeys {
+    /// Registers each configured hotkey, e.g. `ctrl+shift+h`. Blank ones are skipped;
+    /// ones that fail to parse or are taken by another program are reported and skipped.
+    pub fn register(bindings: &[(Option<&str>, HotkeyAction)]) -> Option<Hotkeys> {
+        let bindings: Vec<_> = bindings
+            .iter()
+            .filter_map(|(binding, action)| Some((binding.map(str::trim)?, *action)))
+            .filter(|(binding, _)| !binding.is_empty())
+            .collect();
+        if bindings.is_empty() {
+            return None;
+        }
+        let manager = match GlobalHotKeyManager::new() {
+            Ok(manager) => manager,
+            Err(e) => {
+                notify_error(&format!("Failed to set up global hotkeys: {}", e));
+                return None;
+            }
+        };
+        let mut actions = Vec::new();
+        for (binding, action) in bindings {
+            let hotkey: HotKey = match binding.parse() {
+                Ok(hotkey) => hotkey,
+                Err(e) => {
+                    notify_error(&format!("Invalid hotkey '{}': {}", binding, e));
+                    continue;
+                }
+            };
+            match manager.register(hotkey) {
+                Ok(()) => {
+                    info!(hotkey = binding, ?action, "Registered global hotkey");
+                    actions.push((hotkey, action));
+                }
+                Err(e) => notify_error(&format!("Failed to register hotkey '{}': {}", binding, e)),
+            }
+        }
+        Some(Hotkeys { manager, actions })
+    }
+
+    /// What a key press is bound to; releases and unknown keys map to nothing
+    pub fn action(&self, event: &GlobalHotKeyEvent) -> Option<HotkeyAction> {
+        if event.state() != HotKeyState::Pressed {
+            return None;
+        }
+        self.actions
+            .iter()
+            .find(|(hotkey, _)| hotkey.id() == event.id())
+            .map(|(_, action)| *action)
+    }
+}
+
+impl Drop for Hotkeys {
+    fn drop(&mut self) {
+        let hotkeys: Vec<_> = self.actions.iter().map(|(hotkey, _)| *hotkey).collect();
+        let _ = self.manager.unregister_all(&hotkeys);
+    }
+}
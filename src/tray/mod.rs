@@ -1,2 +1,13 @@
+mod clipboard;
+mod hotkey;
+mod notify;
+mod search;
+mod settings;
+mod status;
+#[allow(clippy::module_inception)]
 mod tray;
+mod update;
+mod window;
+pub use notify::notify;
+pub use settings::SettingsContext;
 pub use tray::*;
@@ -0,0 +1,23 @@
+//! Native desktop notifications. The service runs without a console, so this is how the
+//! tray gets a message in front of the user.
+use notify_rust::Notification;
+use tracing::error;
+
+const APP_NAME: &str = "Hash Service";
+
+pub fn notify(summary: &str, body: &str) {
+    if let Err(e) = Notification::new()
+        .appname(APP_NAME)
+        .summary(summary)
+        .body(body)
+        .show()
+    {
+        error!("Failed to show notification: {}", e);
+    }
+}
+
+/// Logs `message` and shows it, for failures the user would otherwise never see
+pub fn notify_error(message: &str) {
+    error!("{}", message);
+    notify("Hash Service error", message);
+}
@@ -0,0 +1,250 @@
+//! Always-on-top quick search: type a hash or part of a path and see matches as you type.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tracing::debug;
+use winit::dpi::LogicalSize;
+use winit::event::WindowEvent;
+use winit::event_loop::{ActiveEventLoop, EventLoopProxy};
+use winit::window::{WindowAttributes, WindowId, WindowLevel};
+
+use crate::client::ClientSettings;
+use crate::hash_service::{GetStringRequest, SearchRegexRequest};
+
+use super::UserEvent;
+use super::clipboard::parse_hash;
+use super::window::EguiWindow;
+
+const TABLES: [&str; 2] = ["game", "bin"];
+/// Shorter queries match too much of the tables to be useful
+const MIN_SUBSTRING_LEN: usize = 3;
+/// Matches fetched per table
+const RESULT_LIMIT: u32 = 50;
+/// Typing pause before a search is sent, so each keystroke does not scan the tables
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+#[derive(Debug)]
+pub struct SearchHit {
+    table: &'static str,
+    hash: u64,
+    value: String,
+}
+
+/// Results for one query, sent back to the event loop
+#[derive(Debug)]
+pub struct SearchResults {
+    query: String,
+    result: Result<Vec<SearchHit>, String>,
+    /// A table had more matches than were fetched
+    truncated: bool,
+}
+
+pub struct SearchWindow {
+    window: EguiWindow,
+    client: ClientSettings,
+    proxy: EventLoopProxy<UserEvent>,
+    query: String,
+    /// Query the shown results are for
+    results: Option<SearchResults>,
+    /// Bumped per query so searches for text that was typed over give up
+    generation: Arc<AtomicU64>,
+    focus_query: bool,
+}
+
+impl SearchWindow {
+    pub fn open(
+        event_loop: &ActiveEventLoop,
+        client: ClientSettings,
+        proxy: EventLoopProxy<UserEvent>,
+    ) -> Result<SearchWindow, String> {
+        let attributes = WindowAttributes::default()
+            .with_title("Hash Search")
+            .with_inner_size(LogicalSize::new(560.0, 420.0))
+            .with_window_level(WindowLevel::AlwaysOnTop);
+        Ok(SearchWindow {
+            window: EguiWindow::new(event_loop, attributes)?,
+            client,
+            proxy,
+            query: String::new(),
+            results: None,
+            generation: Arc::new(AtomicU64::new(0)),
+            focus_query: true,
+        })
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    pub fn focus(&self) {
+        self.window.focus();
+    }
+
+    /// Handles an event for this window; returns `false` once it should close
+    pub fn window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CloseRequested => false,
+            WindowEvent::RedrawRequested => self.redraw(),
+            _ => {
+                self.window.on_event(event);
+                true
+            }
+        }
+    }
+
+    /// Shows `results` unless the query changed since they were requested
+    pub fn show_results(&mut self, results: SearchResults) {
+        if results.query == self.query.trim() {
+            self.results = Some(results);
+            self.window.request_redraw();
+        }
+    }
+
+    fn redraw(&mut self) -> bool {
+        let mut changed = false;
+        let mut close = false;
+        let focus_query = std::mem::take(&mut self.focus_query);
+        self.window.draw(|ui| {
+            (changed, close) = search_ui(ui, &mut self.query, self.results.as_ref(), focus_query);
+        });
+        if changed {
+            self.search();
+        }
+        !close
+    }
+
+    fn search(&self) {
+        let query = self.query.trim().to_string();
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if parse_hash(&query).is_none() && query.chars().count() < MIN_SUBSTRING_LEN {
+            return;
+        }
+        let client = self.client.clone();
+        let proxy = self.proxy.clone();
+        let current = self.generation.clone();
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        handle.spawn(async move {
+            tokio::time::sleep(DEBOUNCE).await;
+            if current.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            let results = search(&client, query).await;
+            let _ = proxy.send_event(UserEvent::SearchResults(results));
+        });
+    }
+}
+
+/// Draws the window; returns whether the query changed and whether to close
+fn search_ui(
+    ui: &mut egui::Ui,
+    query: &mut String,
+    results: Option<&SearchResults>,
+    focus_query: bool,
+) -> (bool, bool) {
+    let mut changed = false;
+    let close = ui.input(|input| input.key_pressed(egui::Key::Escape));
+    egui::CentralPanel::default_margins().show(ui, |ui| {
+        let response = ui.add(
+            egui::TextEdit::singleline(query)
+                .hint_text("Hash or part of a path")
+                .desired_width(f32::INFINITY),
+        );
+        if focus_query {
+            response.request_focus();
+        }
+        changed = response.changed();
+
+        let Some(results) = results.filter(|results| results.query == query.trim()) else {
+            return;
+        };
+        match &results.result {
+            Err(e) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+            Ok(hits) if hits.is_empty() => {
+                ui.label("No matches");
+            }
+            Ok(hits) => {
+                ui.label(if results.truncated {
+                    format!("First {} matches, click one to copy it", hits.len())
+                } else {
+                    format!("{} matches, click one to copy it", hits.len())
+                });
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for hit in hits {
+                        let text = format!("{:016x}  {}  {}", hit.hash, hit.table, hit.value);
+                        let label = egui::Label::new(egui::RichText::new(text).monospace())
+                            .sense(egui::Sense::click());
+                        if ui.add(label).clicked() {
+                            ui.ctx().copy_text(hit.value.clone());
+                        }
+                    }
+                });
+            }
+        }
+    });
+    (changed, close)
+}
+
+/// The exact hash, if the query is one, then paths containing the query
+async fn search(client: &ClientSettings, query: String) -> SearchResults {
+    let mut truncated = false;
+    let result = async {
+        let mut client = client
+            .connect()
+            .await
+            .map_err(|e| format!("Failed to connect to gRPC server: {}", e))?;
+        let mut hits = Vec::new();
+        if let Some(hash) = parse_hash(&query) {
+            for table in TABLES {
+                let response = client
+                    .get_string(GetStringRequest {
+                        hash,
+                        hashtable_type: table.to_string(),
+                    })
+                    .await
+                    .map_err(|e| e.message().to_string())?
+                    .into_inner();
+                if response.found {
+                    hits.push(SearchHit {
+                        table,
+                        hash,
+                        value: response.value,
+                    });
+                }
+            }
+        }
+        if query.chars().count() >= MIN_SUBSTRING_LEN {
+            for table in TABLES {
+                let response = client
+                    .search_regex(SearchRegexRequest {
+                        pattern: regex::escape(&query),
+                        hashtable_type: table.to_string(),
+                        limit: RESULT_LIMIT,
+                        case_insensitive: true,
+                        timeout_ms: 0,
+                    })
+                    .await
+                    .map_err(|e| e.message().to_string())?
+                    .into_inner();
+                truncated |= response.truncated || response.timed_out;
+                hits.extend(response.entries.into_iter().map(|entry| SearchHit {
+                    table,
+                    hash: entry.hash,
+                    value: entry.value,
+                }));
+            }
+        }
+        debug!(%query, hits = hits.len(), "Quick search finished");
+        Ok::<_, String>(hits)
+    }
+    .await;
+    SearchResults {
+        query,
+        result,
+        truncated,
+    }
+}
@@ -0,0 +1,306 @@
+//! Settings window opened from the tray. It edits the config file; start at login and idle
+//! unloading apply right away, the port, cache dir and sources once the service restarts.
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tracing::{error, info};
+use winit::dpi::LogicalSize;
+use winit::event::WindowEvent;
+use winit::event_loop::ActiveEventLoop;
+use winit::window::{WindowAttributes, WindowId};
+
+use crate::admin::{self, ShutdownKind};
+use crate::autostart;
+use crate::config::{Config, split_list};
+
+use super::window::EguiWindow;
+
+/// What the settings window needs from the running service
+pub struct SettingsContext {
+    /// Config file settings are saved to; saving is disabled without one
+    pub config_path: Option<PathBuf>,
+    /// The config the service started with, to tell which changes need a restart
+    pub running: Config,
+    /// Idle unload timeout of the running service
+    pub idle_unload: watch::Sender<Option<Duration>>,
+    pub shutdown: watch::Sender<Option<ShutdownKind>>,
+}
+
+/// Settings as typed into the window
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SettingsForm {
+    port: String,
+    cache_dir: String,
+    products: String,
+    product: String,
+    branches: String,
+    branch: String,
+    preload: bool,
+    idle_unload_minutes: String,
+    autostart: bool,
+}
+
+impl SettingsForm {
+    fn from_config(config: &Config, autostart: bool) -> SettingsForm {
+        SettingsForm {
+            port: config.port.to_string(),
+            cache_dir: config
+                .cache_dir
+                .as_ref()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+            products: config.products.join(", "),
+            product: config.product.clone(),
+            branches: config.branches.join(", "),
+            branch: config.branch.clone(),
+            preload: config.preload,
+            idle_unload_minutes: config
+                .idle_unload_minutes
+                .map(|minutes| minutes.to_string())
+                .unwrap_or_default(),
+            autostart,
+        }
+    }
+
+    /// Writes the form into `config`, failing on values the service would not start with
+    fn apply(&self, config: &mut Config) -> Result<(), String> {
+        config.port = self
+            .port
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid port '{}': {}", self.port, e))?;
+        let cache_dir = self.cache_dir.trim();
+        config.cache_dir = (!cache_dir.is_empty()).then(|| PathBuf::from(cache_dir));
+        config.products = split_list(&self.products);
+        config.product = self.product.trim().to_string();
+        config.branches = split_list(&self.branches);
+        config.branch = self.branch.trim().to_string();
+        config.preload = self.preload;
+        let minutes = self.idle_unload_minutes.trim();
+        config.idle_unload_minutes = if minutes.is_empty() {
+            None
+        } else {
+            Some(
+                minutes
+                    .parse()
+                    .map_err(|e| format!("Invalid idle unload minutes '{}': {}", minutes, e))?,
+            )
+        };
+        // An explicit setting is re-applied on every launch and would undo the change
+        if config.autostart.is_some() {
+            config.autostart = Some(self.autostart);
+        }
+        config.hash_sources()?;
+        Ok(())
+    }
+}
+
+/// Whether `config` differs from `running` in settings only read at startup
+fn needs_restart(config: &Config, running: &Config) -> bool {
+    config.port != running.port
+        || config.cache_dir != running.cache_dir
+        || config.products != running.products
+        || config.product != running.product
+        || config.branches != running.branches
+        || config.branch != running.branch
+}
+
+enum FormAction {
+    Save,
+    Restart,
+    Close,
+}
+
+pub struct SettingsWindow {
+    window: EguiWindow,
+    form: SettingsForm,
+    /// The form as last loaded or saved
+    saved: SettingsForm,
+    /// Outcome of the last save
+    message: Option<String>,
+    /// A saved change only applies after a restart
+    restart_pending: bool,
+}
+
+impl SettingsWindow {
+    pub fn open(
+        event_loop: &ActiveEventLoop,
+        context: &SettingsContext,
+    ) -> Result<SettingsWindow, String> {
+        let config = read_config(context)?;
+        let form = SettingsForm::from_config(&config, autostart::is_enabled().unwrap_or(false));
+        let attributes = WindowAttributes::default()
+            .with_title("Hash Service Settings")
+            .with_inner_size(LogicalSize::new(440.0, 380.0))
+            .with_resizable(false);
+        Ok(SettingsWindow {
+            window: EguiWindow::new(event_loop, attributes)?,
+            saved: form.clone(),
+            form,
+            message: None,
+            restart_pending: false,
+        })
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    pub fn focus(&self) {
+        self.window.focus();
+    }
+
+    /// Handles an event for this window; returns `false` once it should close
+    pub fn window_event(&mut self, event: &WindowEvent, context: &SettingsContext) -> bool {
+        match event {
+            WindowEvent::CloseRequested => false,
+            WindowEvent::RedrawRequested => self.redraw(context),
+            _ => {
+                self.window.on_event(event);
+                true
+            }
+        }
+    }
+
+    fn redraw(&mut self, context: &SettingsContext) -> bool {
+        let mut action = None;
+        let can_save = context.config_path.is_some() && self.form != self.saved;
+        self.window.draw(|ui| {
+            action = form_ui(
+                ui,
+                &mut self.form,
+                self.message.as_deref(),
+                can_save,
+                self.restart_pending,
+            );
+        });
+        match action {
+            Some(FormAction::Save) => {
+                self.save(context);
+                self.window.request_redraw();
+            }
+            Some(FormAction::Restart) => {
+                info!("Restart requested from the settings window");
+                admin::request_shutdown(&context.shutdown, ShutdownKind::Restart);
+            }
+            Some(FormAction::Close) => return false,
+            None => {}
+        }
+        true
+    }
+
+    fn save(&mut self, context: &SettingsContext) {
+        self.message = Some(match self.try_save(context) {
+            Ok(message) => message.to_string(),
+            Err(e) => {
+                error!("Failed to save settings: {}", e);
+                e
+            }
+        });
+    }
+
+    fn try_save(&mut self, context: &SettingsContext) -> Result<&'static str, String> {
+        let path = context
+            .config_path
+            .as_deref()
+            .ok_or("No config file location available")?;
+        let mut config = read_config(context)?;
+        self.form.apply(&mut config)?;
+        config.save(path)?;
+        info!(?path, "Saved settings");
+
+        context.idle_unload.send_replace(config.idle_unload());
+        if self.form.autostart != self.saved.autostart {
+            autostart::set_enabled(self.form.autostart)?;
+        }
+        self.saved = self.form.clone();
+        self.restart_pending = needs_restart(&config, &context.running);
+        Ok(if self.restart_pending {
+            "Saved. The port, cache directory and sources apply after a restart."
+        } else {
+            "Saved."
+        })
+    }
+}
+
+/// The config file as saved, without environment or command line overrides
+fn read_config(context: &SettingsContext) -> Result<Config, String> {
+    match &context.config_path {
+        Some(path) if path.exists() => Config::load(Some(path)),
+        _ => Ok(Config::default()),
+    }
+}
+
+fn form_ui(
+    ui: &mut egui::Ui,
+    form: &mut SettingsForm,
+    message: Option<&str>,
+    can_save: bool,
+    restart_pending: bool,
+) -> Option<FormAction> {
+    let mut action = None;
+    egui::CentralPanel::default_margins().show(ui, |ui| {
+        egui::Grid::new("settings")
+            .num_columns(2)
+            .spacing([12.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("Port");
+                ui.text_edit_singleline(&mut form.port);
+                ui.end_row();
+
+                ui.label("Cache directory");
+                ui.add(egui::TextEdit::singleline(&mut form.cache_dir).hint_text("Default"));
+                ui.end_row();
+
+                ui.label("Products");
+                ui.add(egui::TextEdit::singleline(&mut form.products).hint_text("lol, tft"));
+                ui.end_row();
+
+                ui.label("Default product");
+                ui.text_edit_singleline(&mut form.product);
+                ui.end_row();
+
+                ui.label("Branches");
+                ui.add(egui::TextEdit::singleline(&mut form.branches).hint_text("live, pbe"));
+                ui.end_row();
+
+                ui.label("Default branch");
+                ui.text_edit_singleline(&mut form.branch);
+                ui.end_row();
+
+                ui.label("Unload after idle minutes");
+                ui.add(
+                    egui::TextEdit::singleline(&mut form.idle_unload_minutes).hint_text("Never"),
+                );
+                ui.end_row();
+
+                ui.label("");
+                ui.checkbox(&mut form.preload, "Load hashtables on startup");
+                ui.end_row();
+
+                ui.label("");
+                ui.checkbox(&mut form.autostart, "Start at login");
+                ui.end_row();
+            });
+        ui.separator();
+        if let Some(message) = message {
+            ui.label(message);
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(can_save, egui::Button::new("Save"))
+                .clicked()
+            {
+                action = Some(FormAction::Save);
+            }
+            if restart_pending && ui.button("Restart Now").clicked() {
+                action = Some(FormAction::Restart);
+            }
+            if ui.button("Close").clicked() {
+                action = Some(FormAction::Close);
+            }
+        });
+    });
+    action
+}
@@ -0,0 +1,289 @@
+//! Keeps the tray up to date with the service: polls `GetStatus` and forwards every
+//! response, or why there was none, to the event loop, polling faster while a load is
+//! running.
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tonic::transport::Channel;
+use tracing::debug;
+use winit::event_loop::EventLoopProxy;
+
+use crate::client::ClientSettings;
+use crate::hash_service::hash_loader_client::HashLoaderClient;
+use crate::hash_service::{
+    FileProgress, GetStatusRequest, GetStatusResponse, LoadingState, SourceFile,
+};
+
+use super::UserEvent;
+
+const LOADING_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Windows truncates longer tooltips
+const MAX_TOOLTIP_CHARS: usize = 127;
+
+/// Polls until the event loop is gone; notifying the returned handle polls right away
+pub fn spawn_status_monitor(
+    client: ClientSettings,
+    proxy: EventLoopProxy<UserEvent>,
+) -> Arc<Notify> {
+    let refresh = Arc::new(Notify::new());
+    let refreshed = refresh.clone();
+    tokio::spawn(async move {
+        let mut connection: Option<HashLoaderClient<Channel>> = None;
+        loop {
+            let status = match &mut connection {
+                Some(hash_loader) => hash_loader
+                    .get_status(GetStatusRequest {})
+                    .await
+                    .map(|response| response.into_inner())
+                    .map_err(|e| e.message().to_string()),
+                None => match client.connect().await {
+                    Ok(hash_loader) => {
+                        connection = Some(hash_loader);
+                        continue;
+                    }
+                    Err(e) => Err(e.to_string()),
+                },
+            };
+            if let Err(e) = &status {
+                debug!("Failed to get status: {}", e);
+                connection = None;
+            }
+            let interval = match &status {
+                Ok(status) if status.loading_state() == LoadingState::Loading => {
+                    LOADING_POLL_INTERVAL
+                }
+                _ => IDLE_POLL_INTERVAL,
+            };
+            if proxy.send_event(UserEvent::Status(status)).is_err() {
+                // The event loop is gone
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = refreshed.notified() => {}
+            }
+        }
+    });
+    refresh
+}
+
+/// Lines of the tray menu's info section: game and bin entry counts and memory use
+pub fn info_lines(status: &Result<GetStatusResponse, String>) -> [String; 3] {
+    match status {
+        Ok(status)
+            if matches!(
+                status.loading_state(),
+                LoadingState::Loaded | LoadingState::LoadedWithWarnings
+            ) =>
+        {
+            [
+                format!(
+                    "Game hashes: {} (~{})",
+                    status.game_count,
+                    megabytes(status.game_memory_bytes)
+                ),
+                format!(
+                    "Bin hashes: {} (~{})",
+                    status.bin_count,
+                    megabytes(status.bin_memory_bytes)
+                ),
+                format!("Memory: ~{}", megabytes(status.memory_bytes)),
+            ]
+        }
+        _ => [
+            "Game hashes: -".to_string(),
+            "Bin hashes: -".to_string(),
+            "Memory: -".to_string(),
+        ],
+    }
+}
+
+pub fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// What a glance at the tray icon tells: whether lookups will work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayState {
+    Unloaded,
+    Loading,
+    Loaded,
+    /// The service is unreachable or its last load failed
+    Error,
+}
+
+impl TrayState {
+    pub fn from_status(status: &Result<GetStatusResponse, String>) -> TrayState {
+        match status {
+            Err(_) => TrayState::Error,
+            Ok(status) => match status.loading_state() {
+                LoadingState::Unloaded if !status.last_error.is_empty() => TrayState::Error,
+                LoadingState::Unloaded => TrayState::Unloaded,
+                LoadingState::Loading => TrayState::Loading,
+                // Lookups work, only without the failed files
+                LoadingState::Loaded | LoadingState::LoadedWithWarnings => TrayState::Loaded,
+            },
+        }
+    }
+}
+
+/// What the service is doing and, while loading, how far it got; `file` is the latest
+/// progress of a load the tray started, which is more detailed than the polled status
+pub fn tooltip(status: &Result<GetStatusResponse, String>, file: Option<&FileProgress>) -> String {
+    match (status, file) {
+        (Ok(status), Some(file)) if status.loading_state() == LoadingState::Loading => {
+            file_tooltip(file)
+        }
+        _ => truncate_tooltip(tooltip_text(status)),
+    }
+}
+
+/// The file a load is reading and how long it has taken so far
+pub fn file_tooltip(file: &FileProgress) -> String {
+    let percent = if file.total_bytes == 0 {
+        0.0
+    } else {
+        file.bytes_read as f64 * 100.0 / file.total_bytes as f64
+    };
+    truncate_tooltip(format!(
+        "Hash Service: loading {} ({}/{} files, {:.0}%, {} lines in {:.1}s)",
+        file.file_name,
+        file.index + 1,
+        file.file_count,
+        percent,
+        file.lines,
+        file.elapsed_ms as f64 / 1000.0
+    ))
+}
+
+fn truncate_tooltip(mut tooltip: String) -> String {
+    if let Some((end, _)) = tooltip.char_indices().nth(MAX_TOOLTIP_CHARS) {
+        tooltip.truncate(end);
+    }
+    tooltip
+}
+
+fn tooltip_text(status: &Result<GetStatusResponse, String>) -> String {
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => return format!("Hash Service: unreachable ({})", e),
+    };
+    match status.loading_state() {
+        LoadingState::Unloaded if !status.last_error.is_empty() => {
+            format!("Hash Service: load failed ({})", status.last_error)
+        }
+        LoadingState::Unloaded => "Hash Service: not loaded".to_string(),
+        LoadingState::Loading => match &status.progress {
+            Some(progress) if progress.file_count > 0 => {
+                let percent = if progress.total_bytes == 0 {
+                    0.0
+                } else {
+                    progress.bytes_read as f64 * 100.0 / progress.total_bytes as f64
+                };
+                format!(
+                    "Hash Service: loading {} ({}/{} files, {:.0}%, {} entries)",
+                    progress.current_file,
+                    progress.files_done,
+                    progress.file_count,
+                    percent,
+                    progress.entries
+                )
+            }
+            _ => "Hash Service: syncing hashtables".to_string(),
+        },
+        LoadingState::Loaded => format!(
+            "Hash Service: {} game / {} bin hashes",
+            status.game_count, status.bin_count
+        ),
+        LoadingState::LoadedWithWarnings => format!(
+            "Hash Service: {} game / {} bin hashes, {} files failed",
+            status.game_count,
+            status.bin_count,
+            status.failed_files.len()
+        ),
+    }
+}
+
+/// Turns status changes into desktop notifications: a finished or failed load, updated
+/// hashtable files and losing the service
+#[derive(Debug, Default)]
+pub struct StatusNotices {
+    /// State of the last status, `None` before the first one or while unreachable
+    previous: Option<LoadingState>,
+    /// Synced files when no load was running, to tell which ones a sync updated
+    settled_files: Option<Vec<SourceFile>>,
+}
+
+impl StatusNotices {
+    /// Summary and body of the notification `status` calls for, if any
+    pub fn update(
+        &mut self,
+        status: &Result<GetStatusResponse, String>,
+    ) -> Option<(String, String)> {
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => {
+                return self
+                    .previous
+                    .take()
+                    .map(|_| ("Hash Service unreachable".to_string(), e.clone()));
+            }
+        };
+        let state = status.loading_state();
+        let was_loading = self.previous.replace(state) == Some(LoadingState::Loading);
+        if state == LoadingState::Loading {
+            return None;
+        }
+        let updated_files = self
+            .settled_files
+            .replace(status.files.clone())
+            .map(|settled| {
+                status
+                    .files
+                    .iter()
+                    .filter(|file| !settled.contains(file))
+                    .count()
+            });
+        if !was_loading {
+            return None;
+        }
+        match state {
+            LoadingState::Loaded => {
+                let mut body = format!(
+                    "{} game and {} bin hashes",
+                    status.game_count, status.bin_count
+                );
+                if let Some(updated_files) = updated_files.filter(|count| *count > 0) {
+                    body.push_str(&format!(", {} hashtable files updated", updated_files));
+                    if !status.patch.is_empty() {
+                        body.push_str(&format!(" (patch {})", status.patch));
+                    }
+                }
+                Some(("Hashtables loaded".to_string(), body))
+            }
+            LoadingState::LoadedWithWarnings => {
+                let failed: Vec<&str> = status
+                    .failed_files
+                    .iter()
+                    .map(|file| file.file_name.as_str())
+                    .collect();
+                Some((
+                    "Hashtables loaded with warnings".to_string(),
+                    format!(
+                        "{} game and {} bin hashes, without {}",
+                        status.game_count,
+                        status.bin_count,
+                        failed.join(", ")
+                    ),
+                ))
+            }
+            _ if !status.last_error.is_empty() => Some((
+                "Loading hashtables failed".to_string(),
+                status.last_error.clone(),
+            )),
+            _ => None,
+        }
+    }
+}
@@ -1,54 +1,269 @@
+use global_hotkey::GlobalHotKeyEvent;
 use tray_icon::{
-    TrayIcon, TrayIconBuilder,
-    menu::{Menu, MenuId, MenuItem},
+    TrayIcon, TrayIconBuilder, TrayIconEvent,
+    menu::{CheckMenuItem, IsMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu},
 };
 
+use std::sync::Arc;
+
+use tokio::sync::{Notify, watch};
+use tracing::{debug, error, info};
 use winit::application::ApplicationHandler;
+use winit::event_loop::{EventLoop, EventLoopProxy};
+
+use crate::admin::ShutdownKind;
+use crate::autostart;
 
-use crate::hash_service::hash_loader_client::HashLoaderClient;
-use crate::hash_service::{LoadHashesRequest, UnloadHashesRequest};
+use crate::client::ClientSettings;
+use crate::hash_service::load_hashes_update::Update;
+use crate::hash_service::{
+    CleanCacheRequest, FileProgress, GetStatusResponse, LoadHashesRequest, LoadingState,
+    UnloadHashesRequest,
+};
+
+use super::clipboard::lookup_clipboard;
+use super::hotkey::{HotkeyAction, Hotkeys};
+use super::notify::{notify, notify_error};
+use super::search::{SearchResults, SearchWindow};
+use super::settings::{SettingsContext, SettingsWindow};
+use super::status::{
+    StatusNotices, TrayState, file_tooltip, info_lines, megabytes, spawn_status_monitor, tooltip,
+};
+use super::update::check_for_update;
 
 #[derive(Debug)]
 pub enum UserEvent {
     TrayIconEvent(tray_icon::TrayIconEvent),
     MenuEvent(tray_icon::menu::MenuEvent),
+    /// The server was asked to stop through the admin API
+    Shutdown,
+    /// Latest status reported by the service, or why it could not be fetched
+    Status(Result<GetStatusResponse, String>),
+    /// A file of a load the tray started was read further
+    FileProgress(FileProgress),
+    /// Matches for a quick search query
+    SearchResults(SearchResults),
+    Hotkey(GlobalHotKeyEvent),
+}
+
+/// Runs the tray icon event loop on the current thread until it exits
+pub fn run(
+    client: ClientSettings,
+    settings: SettingsContext,
+    mut shutdown: watch::Receiver<Option<ShutdownKind>>,
+) {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build().unwrap();
+
+    let proxy = event_loop.create_proxy();
+    tokio::spawn(async move {
+        if shutdown.wait_for(Option::is_some).await.is_ok() {
+            let _ = proxy.send_event(UserEvent::Shutdown);
+        }
+    });
+    let status_refresh = spawn_status_monitor(client.clone(), event_loop.create_proxy());
+    let proxy = event_loop.create_proxy();
+    TrayIconEvent::set_event_handler(Some(move |event| {
+        let _ = proxy.send_event(UserEvent::TrayIconEvent(event));
+    }));
+    let proxy = event_loop.create_proxy();
+    GlobalHotKeyEvent::set_event_handler(Some(move |event| {
+        let _ = proxy.send_event(UserEvent::Hotkey(event));
+    }));
+    let proxy = event_loop.create_proxy();
+    MenuEvent::set_event_handler(Some(move |event| {
+        let _ = proxy.send_event(UserEvent::MenuEvent(event));
+    }));
+
+    if settings.running.update_check {
+        tokio::spawn(check_for_update(false));
+    }
+    let mut app = Application::new(client, settings, status_refresh, event_loop.create_proxy());
+
+    #[cfg(target_os = "linux")]
+    {
+        gtk::init().unwrap();
+        let _tray_icon = app.new_tray_icon();
+        gtk::main();
+    }
+
+    if let Err(err) = event_loop.run_app(&mut app) {
+        error!("TrayIcon Error: {err:?}");
+    }
+}
+
+const UNLOADED_ICON: &[u8] = include_bytes!("../../unloaded.png");
+const LOADING_ICON: &[u8] = include_bytes!("../../loading.png");
+const LOADED_ICON: &[u8] = include_bytes!("../../regular.png");
+const ERROR_ICON: &[u8] = include_bytes!("../../error.png");
+/// Side of the icon drawn when an embedded one fails to decode
+const FALLBACK_ICON_SIZE: u32 = 32;
+
+/// One icon per [`TrayState`]
+struct StateIcons {
+    unloaded: tray_icon::Icon,
+    loading: tray_icon::Icon,
+    loaded: tray_icon::Icon,
+    error: tray_icon::Icon,
+}
+
+impl StateIcons {
+    fn load() -> StateIcons {
+        StateIcons {
+            unloaded: load_icon(UNLOADED_ICON, [128, 128, 128]),
+            loading: load_icon(LOADING_ICON, [240, 170, 0]),
+            loaded: load_icon(LOADED_ICON, [40, 140, 220]),
+            error: load_icon(ERROR_ICON, [210, 50, 50]),
+        }
+    }
+
+    fn get(&self, state: TrayState) -> &tray_icon::Icon {
+        match state {
+            TrayState::Unloaded => &self.unloaded,
+            TrayState::Loading => &self.loading,
+            TrayState::Loaded => &self.loaded,
+            TrayState::Error => &self.error,
+        }
+    }
 }
 
 pub struct Application {
     tray_icon: Option<TrayIcon>,
+    client: ClientSettings,
+    proxy: EventLoopProxy<UserEvent>,
+    lookup_item: MenuItem,
+    search_item: MenuItem,
+    load_item: MenuItem,
+    sync_item: MenuItem,
+    /// Refreshes one table's files, leaving the other table alone
+    sync_table_menu: Submenu,
+    sync_game_item: MenuItem,
+    sync_bin_item: MenuItem,
+    unload_item: MenuItem,
+    clean_item: MenuItem,
+    settings_item: MenuItem,
+    update_item: MenuItem,
+    autostart_item: CheckMenuItem,
+    quit_item: MenuItem,
+    /// Disabled items showing entry counts and memory use
+    info_items: [MenuItem; 3],
+    /// Asks the status monitor for a fresh status
+    status_refresh: Arc<Notify>,
+    icons: StateIcons,
+    /// State the icon currently shows
+    state: TrayState,
+    notices: StatusNotices,
+    /// Latest file of a load the tray started, shown instead of the polled progress
+    file_progress: Option<FileProgress>,
+    settings: SettingsContext,
+    settings_window: Option<SettingsWindow>,
+    search_window: Option<SearchWindow>,
+    /// Kept alive so the hotkeys stay registered
+    hotkeys: Option<Hotkeys>,
 }
 
 impl Application {
-    pub fn new() -> Application {
-        Application { tray_icon: None }
+    pub fn new(
+        client: ClientSettings,
+        settings: SettingsContext,
+        status_refresh: Arc<Notify>,
+        proxy: EventLoopProxy<UserEvent>,
+    ) -> Application {
+        let autostart_enabled = autostart::is_enabled().unwrap_or_else(|e| {
+            error!("{}", e);
+            false
+        });
+        let hotkeys = Hotkeys::register(&[
+            (
+                settings.running.search_hotkey.as_deref(),
+                HotkeyAction::Search,
+            ),
+            (
+                settings.running.lookup_hotkey.as_deref(),
+                HotkeyAction::LookupClipboard,
+            ),
+        ]);
+        Application {
+            tray_icon: None,
+            client,
+            proxy,
+            info_items: info_lines(&Err(String::new()))
+                .map(|line| MenuItem::new(line, false, None)),
+            status_refresh,
+            icons: StateIcons::load(),
+            state: TrayState::Unloaded,
+            notices: StatusNotices::default(),
+            file_progress: None,
+            lookup_item: MenuItem::new("Look Up Clipboard Hash", true, None),
+            search_item: MenuItem::new("Quick Search", true, None),
+            // Enabled once the first status says what the service can do
+            load_item: MenuItem::new("Load Hashes", false, None),
+            sync_item: MenuItem::new("Sync Now", false, None),
+            sync_table_menu: Submenu::new("Sync Table", false),
+            sync_game_item: MenuItem::new("Game Hashes", true, None),
+            sync_bin_item: MenuItem::new("Bin Entries", true, None),
+            unload_item: MenuItem::new("Unload Hashes", false, None),
+            clean_item: MenuItem::new("Clean Cache", true, None),
+            settings_item: MenuItem::new("Settings...", true, None),
+            update_item: MenuItem::new("Check for Updates", true, None),
+            autostart_item: CheckMenuItem::new("Start at Login", true, autostart_enabled, None),
+            quit_item: MenuItem::new("Quit", true, None),
+            settings,
+            settings_window: None,
+            search_window: None,
+            hotkeys,
+        }
     }
 
-    pub fn new_tray_icon() -> TrayIcon {
-        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/regular.png");
-        let icon = load_icon(std::path::Path::new(path));
-
+    pub fn new_tray_icon(&self) -> TrayIcon {
         TrayIconBuilder::new()
-            .with_menu(Box::new(Self::new_tray_menu()))
+            .with_menu(Box::new(self.new_tray_menu()))
             .with_tooltip("Hash Service")
-            .with_icon(icon)
+            .with_icon(self.icons.get(self.state).clone())
             .with_title("Hash Service")
             .build()
             .unwrap()
     }
 
-    pub fn new_tray_menu() -> Menu {
+    pub fn new_tray_menu(&self) -> Menu {
         let menu = Menu::new();
-        let load = MenuItem::new("Load Hashes", true, None);
-        if let Err(err) = menu.append(&load) {
-            println!("{err:?}");
+        for item in [&self.sync_game_item, &self.sync_bin_item] {
+            if let Err(err) = self.sync_table_menu.append(item) {
+                error!("Failed to append menu item: {err:?}");
+            }
         }
-        let unload = MenuItem::new("Unload Hashes", true, None);
-        if let Err(err) = menu.append(&unload) {
-            println!("{err:?}");
+        for item in &self.info_items {
+            if let Err(err) = menu.append(item) {
+                error!("Failed to append menu item: {err:?}");
+            }
         }
-        let quit = MenuItem::new("Quit", true, None);
-        if let Err(err) = menu.append(&quit) {
-            println!("{err:?}");
+        if let Err(err) = menu.append(&PredefinedMenuItem::separator()) {
+            error!("Failed to append menu item: {err:?}");
+        }
+        let actions: [&dyn IsMenuItem; 7] = [
+            &self.lookup_item,
+            &self.search_item,
+            &self.load_item,
+            &self.sync_item,
+            &self.sync_table_menu,
+            &self.unload_item,
+            &self.clean_item,
+        ];
+        for item in actions {
+            if let Err(err) = menu.append(item) {
+                error!("Failed to append menu item: {err:?}");
+            }
+        }
+        if let Err(err) = menu.append(&self.settings_item) {
+            error!("Failed to append menu item: {err:?}");
+        }
+        if let Err(err) = menu.append(&self.update_item) {
+            error!("Failed to append menu item: {err:?}");
+        }
+        if let Err(err) = menu.append(&self.autostart_item) {
+            error!("Failed to append menu item: {err:?}");
+        }
+        if let Err(err) = menu.append(&self.quit_item) {
+            error!("Failed to append menu item: {err:?}");
         }
         menu
     }
@@ -60,51 +275,204 @@ impl Application {
         if let Ok(handle) = tokio::runtime::Handle::try_current() {
             handle.spawn(call_fn);
         } else {
-            eprintln!("No tokio runtime available");
+            error!("No tokio runtime available");
+        }
+    }
+
+    fn update_status(&mut self, status: &Result<GetStatusResponse, String>) {
+        if let Some((summary, body)) = self.notices.update(status) {
+            notify(&summary, &body);
+        }
+        for (item, line) in self.info_items.iter().zip(info_lines(status)) {
+            item.set_text(line);
+        }
+        // Only offer what the service will accept right now
+        let (load, sync, unload) = match status.as_ref().map(|status| status.loading_state()) {
+            Ok(LoadingState::Unloaded) => (true, true, false),
+            Ok(LoadingState::Loaded | LoadingState::LoadedWithWarnings) => (false, true, true),
+            Ok(LoadingState::Loading) | Err(_) => (false, false, false),
+        };
+        self.set_actions_enabled(load, sync, unload);
+        if !matches!(status, Ok(status) if status.loading_state() == LoadingState::Loading) {
+            self.file_progress = None;
+        }
+        let Some(tray_icon) = &self.tray_icon else {
+            return;
+        };
+        if let Err(err) = tray_icon.set_tooltip(Some(tooltip(status, self.file_progress.as_ref())))
+        {
+            error!("Failed to set tray tooltip: {err:?}");
+        }
+        let state = TrayState::from_status(status);
+        if state != self.state {
+            self.state = state;
+            if let Err(err) = tray_icon.set_icon(Some(self.icons.get(state).clone())) {
+                error!("Failed to set tray icon: {err:?}");
+            }
+        }
+    }
+
+    fn set_actions_enabled(&self, load: bool, sync: bool, unload: bool) {
+        self.load_item.set_enabled(load);
+        self.sync_item.set_enabled(sync);
+        self.sync_table_menu.set_enabled(sync);
+        self.unload_item.set_enabled(unload);
+    }
+
+    fn open_settings(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(window) = &self.settings_window {
+            window.focus();
+            return;
         }
+        match SettingsWindow::open(event_loop, &self.settings) {
+            Ok(window) => self.settings_window = Some(window),
+            Err(e) => {
+                error!("Failed to open settings: {}", e);
+                notify("Failed to open settings", &e);
+            }
+        }
+    }
+
+    fn toggle_search(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.search_window.take().is_none() {
+            self.show_search(event_loop);
+        }
+    }
+
+    fn show_search(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(window) = &self.search_window {
+            window.focus();
+            return;
+        }
+        match SearchWindow::open(event_loop, self.client.clone(), self.proxy.clone()) {
+            Ok(window) => self.search_window = Some(window),
+            Err(e) => {
+                error!("Failed to open quick search: {}", e);
+                notify("Failed to open quick search", &e);
+            }
+        }
+    }
+
+    fn toggle_autostart(&self) {
+        let enable = !autostart::is_enabled().unwrap_or(false);
+        match autostart::set_enabled(enable) {
+            Ok(()) => info!(enabled = enable, "Updated start at login"),
+            Err(e) => notify_error(&e),
+        }
+        // Reflect the registered state rather than the requested one
+        self.autostart_item
+            .set_checked(autostart::is_enabled().unwrap_or(false));
     }
 
-    fn call_load_hashes() {
-        Self::spawn_grpc_call(async {
-            match HashLoaderClient::connect("http://[::1]:50051").await {
+    /// Loads the hashtables as `request` asks, e.g. downloading every one again first, and
+    /// forwards the progress of each file to the tooltip
+    fn call_load_hashes(
+        client: ClientSettings,
+        proxy: EventLoopProxy<UserEvent>,
+        status_refresh: Arc<Notify>,
+        request: LoadHashesRequest,
+    ) {
+        Self::spawn_grpc_call(async move {
+            match client.connect().await {
                 Ok(mut client) => {
-                    let request = tonic::Request::new(LoadHashesRequest {});
-                    match client.load_hashes(request).await {
+                    let request = tonic::Request::new(request);
+                    match client.load_hashes_stream(request).await {
                         Ok(response) => {
-                            let inner = response.into_inner();
-                            if inner.success {
-                                println!("Loaded {} hashes successfully", inner.count);
-                            } else {
-                                eprintln!("Failed to load hashes: {}", inner.message);
+                            let mut stream = response.into_inner();
+                            loop {
+                                match stream.message().await {
+                                    Ok(Some(update)) => match update.update {
+                                        Some(Update::File(progress)) => {
+                                            let _ =
+                                                proxy.send_event(UserEvent::FileProgress(progress));
+                                        }
+                                        // The status monitor announces the result once the
+                                        // load finished
+                                        Some(Update::Result(result)) if result.success => {
+                                            info!(
+                                                count = result.count,
+                                                updated_files = result.updated_files,
+                                                added = result.added,
+                                                "Loaded hashes"
+                                            );
+                                        }
+                                        Some(Update::Result(result)) => {
+                                            error!("Failed to load hashes: {}", result.message);
+                                        }
+                                        None => {}
+                                    },
+                                    Ok(None) => break,
+                                    Err(e) => {
+                                        notify_error(&format!("Loading hashes failed: {}", e));
+                                        break;
+                                    }
+                                }
                             }
                         }
-                        Err(e) => eprintln!("gRPC error calling load_hashes: {}", e),
+                        Err(e) => {
+                            notify_error(&format!("gRPC error calling load_hashes_stream: {}", e))
+                        }
+                    }
+                }
+                Err(e) => notify_error(&format!("Failed to connect to gRPC server: {}", e)),
+            }
+            status_refresh.notify_one();
+        });
+    }
+
+    fn call_clean_cache(client: ClientSettings) {
+        Self::spawn_grpc_call(async move {
+            match client.connect().await {
+                Ok(mut client) => {
+                    let request = tonic::Request::new(CleanCacheRequest { dry_run: false });
+                    match client.clean_cache(request).await {
+                        Ok(response) => {
+                            let inner = response.into_inner();
+                            info!(
+                                removed = inner.removed.len(),
+                                bytes_freed = inner.bytes_freed,
+                                "Cleaned cache"
+                            );
+                            notify(
+                                "Cache cleaned",
+                                &format!(
+                                    "Removed {} files, freeing {}",
+                                    inner.removed.len(),
+                                    megabytes(inner.bytes_freed)
+                                ),
+                            );
+                        }
+                        Err(e) => notify_error(&format!("gRPC error calling clean_cache: {}", e)),
                     }
                 }
-                Err(e) => eprintln!("Failed to connect to gRPC server: {}", e),
+                Err(e) => notify_error(&format!("Failed to connect to gRPC server: {}", e)),
             }
         });
     }
 
-    fn call_unload_hashes() {
-        Self::spawn_grpc_call(async {
-            match HashLoaderClient::connect("http://[::1]:50051").await {
+    fn call_unload_hashes(client: ClientSettings, status_refresh: Arc<Notify>) {
+        Self::spawn_grpc_call(async move {
+            match client.connect().await {
                 Ok(mut client) => {
                     let request = tonic::Request::new(UnloadHashesRequest {});
                     match client.unload_hashes(request).await {
                         Ok(response) => {
                             let inner = response.into_inner();
                             if inner.success {
-                                println!("Unloaded hashes successfully");
+                                info!("Unloaded hashes successfully");
                             } else {
-                                eprintln!("Failed to unload hashes: {}", inner.message);
+                                notify_error(&format!(
+                                    "Failed to unload hashes: {}",
+                                    inner.message
+                                ));
                             }
                         }
-                        Err(e) => eprintln!("gRPC error calling unload_hashes: {}", e),
+                        Err(e) => notify_error(&format!("gRPC error calling unload_hashes: {}", e)),
                     }
                 }
-                Err(e) => eprintln!("Failed to connect to gRPC server: {}", e),
+                Err(e) => notify_error(&format!("Failed to connect to gRPC server: {}", e)),
             }
+            status_refresh.notify_one();
         });
     }
 }
@@ -115,9 +483,24 @@ impl ApplicationHandler<UserEvent> for Application {
     fn window_event(
         &mut self,
         _event_loop: &winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
-        _event: winit::event::WindowEvent,
+        window_id: winit::window::WindowId,
+        event: winit::event::WindowEvent,
     ) {
+        if let Some(window) = &mut self.search_window
+            && window.id() == window_id
+            && !window.window_event(&event)
+        {
+            self.search_window = None;
+        }
+        if let Some(window) = &mut self.settings_window
+            && window.id() == window_id
+            && !window.window_event(&event, &self.settings)
+        {
+            self.settings_window = None;
+            // The window may have changed it
+            self.autostart_item
+                .set_checked(autostart::is_enabled().unwrap_or(false));
+        }
     }
 
     fn new_events(
@@ -128,7 +511,7 @@ impl ApplicationHandler<UserEvent> for Application {
         if winit::event::StartCause::Init == cause {
             #[cfg(not(target_os = "linux"))]
             {
-                self.tray_icon = Some(Self::new_tray_icon());
+                self.tray_icon = Some(self.new_tray_icon());
             }
 
             #[cfg(target_os = "macos")]
@@ -141,32 +524,146 @@ impl ApplicationHandler<UserEvent> for Application {
         }
     }
 
-    fn user_event(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop, event: UserEvent) {
+    fn user_event(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, event: UserEvent) {
         match event {
-            UserEvent::MenuEvent(event) => match &event.id {
-                MenuId(id) if id == "1001" => {
-                    Self::call_load_hashes();
-                }
-                MenuId(id) if id == "1002" => {
-                    Self::call_unload_hashes();
-                }
-                _ => {
+            UserEvent::MenuEvent(event) => {
+                let id = &event.id;
+                if id == self.lookup_item.id() {
+                    Self::spawn_grpc_call(lookup_clipboard(self.client.clone()));
+                } else if id == self.load_item.id() {
+                    // Until the next status arrives, so the same action cannot be sent twice
+                    self.set_actions_enabled(false, false, false);
+                    Self::call_load_hashes(
+                        self.client.clone(),
+                        self.proxy.clone(),
+                        self.status_refresh.clone(),
+                        LoadHashesRequest::default(),
+                    );
+                } else if id == self.sync_item.id() {
+                    // Downloads every hashtable again and reloads
+                    self.set_actions_enabled(false, false, false);
+                    Self::call_load_hashes(
+                        self.client.clone(),
+                        self.proxy.clone(),
+                        self.status_refresh.clone(),
+                        LoadHashesRequest {
+                            resync: true,
+                            ..LoadHashesRequest::default()
+                        },
+                    );
+                } else if id == self.sync_game_item.id() || id == self.sync_bin_item.id() {
+                    // Downloads the table's files again and reloads only that table
+                    let table = if id == self.sync_game_item.id() {
+                        "game"
+                    } else {
+                        "bin"
+                    };
+                    self.set_actions_enabled(false, false, false);
+                    Self::call_load_hashes(
+                        self.client.clone(),
+                        self.proxy.clone(),
+                        self.status_refresh.clone(),
+                        LoadHashesRequest {
+                            resync: true,
+                            hashtable_types: vec![table.to_string()],
+                            ..LoadHashesRequest::default()
+                        },
+                    );
+                } else if id == self.unload_item.id() {
+                    self.set_actions_enabled(false, false, false);
+                    Self::call_unload_hashes(self.client.clone(), self.status_refresh.clone());
+                } else if id == self.clean_item.id() {
+                    Self::call_clean_cache(self.client.clone());
+                } else if id == self.search_item.id() {
+                    self.toggle_search(event_loop);
+                } else if id == self.update_item.id() {
+                    Self::spawn_grpc_call(check_for_update(true));
+                } else if id == self.settings_item.id() {
+                    self.open_settings(event_loop);
+                } else if id == self.autostart_item.id() {
+                    self.toggle_autostart();
+                } else if id == self.quit_item.id() {
                     std::process::exit(0);
+                } else {
+                    // Info lines are disabled and never send events; anything else is not ours
+                    debug!(?id, "Ignoring unknown menu item");
                 }
-            },
+            }
+            UserEvent::TrayIconEvent(TrayIconEvent::Click { .. }) => {
+                // The menu is opening; show current numbers rather than the last poll's
+                self.status_refresh.notify_one();
+            }
             UserEvent::TrayIconEvent(_event) => {}
+            UserEvent::Status(status) => self.update_status(&status),
+            UserEvent::FileProgress(progress) => {
+                if let Some(tray_icon) = &self.tray_icon
+                    && let Err(err) = tray_icon.set_tooltip(Some(file_tooltip(&progress)))
+                {
+                    error!("Failed to set tray tooltip: {err:?}");
+                }
+                self.file_progress = Some(progress);
+            }
+            UserEvent::Hotkey(event) => {
+                match self
+                    .hotkeys
+                    .as_ref()
+                    .and_then(|hotkeys| hotkeys.action(&event))
+                {
+                    Some(HotkeyAction::Search) => self.show_search(event_loop),
+                    Some(HotkeyAction::LookupClipboard) => {
+                        Self::spawn_grpc_call(lookup_clipboard(self.client.clone()));
+                    }
+                    None => {}
+                }
+            }
+            UserEvent::SearchResults(results) => {
+                if let Some(window) = &mut self.search_window {
+                    window.show_results(results);
+                }
+            }
+            UserEvent::Shutdown => {
+                info!("Closing tray after shutdown request");
+                self.settings_window = None;
+                self.search_window = None;
+                self.tray_icon = None;
+                event_loop.exit();
+            }
+        }
+    }
+}
+
+/// Decodes an embedded PNG, or draws a dot in `fallback_color` so the tray still shows
+/// something
+pub fn load_icon(png: &[u8], fallback_color: [u8; 3]) -> tray_icon::Icon {
+    match decode_icon(png) {
+        Ok(icon) => icon,
+        Err(e) => {
+            error!("Failed to decode tray icon: {}", e);
+            fallback_icon(fallback_color)
         }
     }
 }
 
-pub fn load_icon(path: &std::path::Path) -> tray_icon::Icon {
-    let (icon_rgba, icon_width, icon_height) = {
-        let image = image::open(path)
-            .expect("Failed to open icon path")
-            .into_rgba8();
-        let (width, height) = image.dimensions();
-        let rgba = image.into_raw();
-        (rgba, width, height)
-    };
-    tray_icon::Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
+fn decode_icon(png: &[u8]) -> Result<tray_icon::Icon, String> {
+    let image = image::load_from_memory_with_format(png, image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+    tray_icon::Icon::from_rgba(image.into_raw(), width, height).map_err(|e| e.to_string())
+}
+
+/// A filled circle with an anti-aliased edge
+fn fallback_icon(color: [u8; 3]) -> tray_icon::Icon {
+    let size = FALLBACK_ICON_SIZE;
+    let center = (size - 1) as f32 / 2.0;
+    let radius = size as f32 / 2.0 - 1.0;
+    let mut rgba = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let distance = (x as f32 - center).hypot(y as f32 - center);
+            let alpha = (radius - distance + 0.5).clamp(0.0, 1.0);
+            rgba.extend_from_slice(&[color[0], color[1], color[2], (alpha * 255.0) as u8]);
+        }
+    }
+    tray_icon::Icon::from_rgba(rgba, size, size).expect("Fallback icon size matches its pixels")
 }
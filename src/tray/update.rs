@@ -0,0 +1,64 @@
+//! Checks GitHub releases for a newer version than the one running.
+use serde::Deserialize;
+use tracing::info;
+
+use super::notify::{notify, notify_error};
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/TheMartynasXS/hash-service/releases/latest";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+}
+
+/// Numeric parts of `v1.2.3` or `1.2.3-beta`; anything after the numbers is ignored
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches(['v', 'V'])
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+async fn latest_release() -> Result<Release, String> {
+    let response = reqwest::Client::new()
+        .get(LATEST_RELEASE_URL)
+        .header("User-Agent", "Rust-Client")
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to check for updates: {}", e))?;
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to read the latest release: {}", e))
+}
+
+/// Notifies about a newer release. With `manual`, also reports being up to date and
+/// failures, which a check on startup keeps to the log.
+pub async fn check_for_update(manual: bool) {
+    let release = match latest_release().await {
+        Ok(release) => release,
+        Err(e) if manual => return notify_error(&e),
+        Err(e) => return info!("{}", e),
+    };
+    let current = env!("CARGO_PKG_VERSION");
+    if parse_version(&release.tag_name) > parse_version(current) {
+        info!(current, latest = %release.tag_name, "Update available");
+        notify(
+            &format!("Hash Service {} is available", release.tag_name),
+            &format!(
+                "You are running {}. Download it from {}",
+                current, release.html_url
+            ),
+        );
+    } else if manual {
+        notify(
+            "Hash Service is up to date",
+            &format!("{} is the latest version", current),
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! A winit window that egui draws into over OpenGL, shared by the tray's small windows.
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use glutin::config::ConfigTemplateBuilder;
+use glutin::context::PossiblyCurrentContext;
+use glutin::context::{ContextApi, ContextAttributesBuilder, NotCurrentGlContext};
+use glutin::display::{GetGlDisplay, GlDisplay};
+use glutin::surface::{GlSurface, Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface};
+use glutin_winit::{ApiPreference, DisplayBuilder};
+use tracing::error;
+use winit::event::WindowEvent;
+use winit::event_loop::ActiveEventLoop;
+use winit::raw_window_handle::HasWindowHandle;
+use winit::window::{Window, WindowAttributes, WindowId};
+
+pub struct EguiWindow {
+    egui_glow: egui_glow::EguiGlow,
+    gl_surface: Surface<WindowSurface>,
+    gl_context: PossiblyCurrentContext,
+    // Dropped last, the GL surface and context render into it
+    window: Window,
+}
+
+impl EguiWindow {
+    pub fn new(
+        event_loop: &ActiveEventLoop,
+        attributes: WindowAttributes,
+    ) -> Result<EguiWindow, String> {
+        let (window, gl_config) = DisplayBuilder::new()
+            .with_preference(ApiPreference::FallbackEgl)
+            .with_window_attributes(Some(attributes.clone()))
+            .build(event_loop, ConfigTemplateBuilder::new(), |mut configs| {
+                // glutin only calls the picker once it found at least one config
+                configs.next().expect("No OpenGL config available")
+            })
+            .map_err(|e| format!("Failed to create OpenGL display: {}", e))?;
+        let window = match window {
+            Some(window) => window,
+            None => glutin_winit::finalize_window(event_loop, attributes, &gl_config)
+                .map_err(|e| format!("Failed to create window: {}", e))?,
+        };
+        let window_handle = window
+            .window_handle()
+            .map_err(|e| format!("Failed to get window handle: {}", e))?
+            .as_raw();
+
+        let gl_display = gl_config.display();
+        let context_attributes = ContextAttributesBuilder::new().build(Some(window_handle));
+        // Some drivers only offer OpenGL ES
+        let fallback_attributes = ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::Gles(None))
+            .build(Some(window_handle));
+        // SAFETY: the context and surface are dropped before the window they render into
+        let not_current = unsafe {
+            gl_display
+                .create_context(&gl_config, &context_attributes)
+                .or_else(|_| gl_display.create_context(&gl_config, &fallback_attributes))
+        }
+        .map_err(|e| format!("Failed to create OpenGL context: {}", e))?;
+        let (width, height): (u32, u32) = window.inner_size().into();
+        let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+            window_handle,
+            NonZeroU32::new(width).unwrap_or(NonZeroU32::MIN),
+            NonZeroU32::new(height).unwrap_or(NonZeroU32::MIN),
+        );
+        // SAFETY: as above
+        let gl_surface =
+            unsafe { gl_display.create_window_surface(&gl_config, &surface_attributes) }
+                .map_err(|e| format!("Failed to create OpenGL surface: {}", e))?;
+        let gl_context = not_current
+            .make_current(&gl_surface)
+            .map_err(|e| format!("Failed to activate OpenGL context: {}", e))?;
+        if let Err(e) =
+            gl_surface.set_swap_interval(&gl_context, SwapInterval::Wait(NonZeroU32::MIN))
+        {
+            error!("Failed to enable vsync: {}", e);
+        }
+        // SAFETY: the function pointers come from the context that was just made current
+        let gl = unsafe {
+            glow::Context::from_loader_function_cstr(|name| gl_display.get_proc_address(name))
+        };
+        let egui_glow = egui_glow::EguiGlow::new(event_loop, Arc::new(gl), None, None, true);
+
+        window.request_redraw();
+        Ok(EguiWindow {
+            egui_glow,
+            gl_surface,
+            gl_context,
+            window,
+        })
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    pub fn focus(&self) {
+        self.window.focus_window();
+    }
+
+    pub fn request_redraw(&self) {
+        self.window.request_redraw();
+    }
+
+    /// Passes input to egui and keeps the surface sized to the window. Closing and
+    /// redrawing are left to the caller.
+    pub fn on_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::Resized(size) = event {
+            self.gl_surface.resize(
+                &self.gl_context,
+                NonZeroU32::new(size.width).unwrap_or(NonZeroU32::MIN),
+                NonZeroU32::new(size.height).unwrap_or(NonZeroU32::MIN),
+            );
+        }
+        if self.egui_glow.on_window_event(&self.window, event).repaint {
+            self.window.request_redraw();
+        }
+    }
+
+    /// Lays out a frame with `run_ui` and presents it
+    pub fn draw(&mut self, run_ui: impl FnMut(&mut egui::Ui)) {
+        self.egui_glow.run(&self.window, run_ui);
+
+        let size = self.window.inner_size();
+        let clear_color = self
+            .egui_glow
+            .egui_ctx
+            .global_style()
+            .visuals
+            .panel_fill
+            .to_normalized_gamma_f32();
+        self.egui_glow
+            .painter
+            .clear([size.width, size.height], clear_color);
+        self.egui_glow.paint(&self.window);
+        if let Err(e) = self.gl_surface.swap_buffers(&self.gl_context) {
+            error!("Failed to present window: {}", e);
+        }
+    }
+}
+
+impl Drop for EguiWindow {
+    fn drop(&mut self) {
+        // Frees the GL textures and buffers while the context still exists
+        self.egui_glow.destroy();
+    }
+}